@@ -0,0 +1,305 @@
+//! Checking the generated site for common accessibility problems: images without alt text,
+//! headings that skip a level, pages without a language, and theme text colors too faint to read
+//! against their background
+
+use crate::{
+    css::BASE_STYLESHEETS,
+    error::{Error, Result},
+    html,
+    links::list_pages,
+    site::Site,
+};
+use std::{collections::BTreeMap, fs};
+use tracing::{info, warn};
+
+/// An accessibility problem found on one or more pages or stylesheets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub message: String,
+    /// The pages or stylesheets with the problem, relative to the output directory
+    pub pages: Vec<String>,
+}
+
+/// Lowest contrast ratio WCAG allows between body text and its background
+const MIN_CONTRAST: f64 = 4.5;
+
+/// The stylesheet holding the theme's colors, relative to the output directory
+const THEME_STYLESHEET: &str = "theme/style.css";
+
+/// Audits every generated page and the theme's colors, logging each issue found as a warning
+/// along with where it was found
+pub fn audit(site: &Site) -> Result<Vec<Issue>> {
+    let output_dir = site.output_dir();
+    let mut issues: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for file in site.output_files()? {
+        if file.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let path = output_dir.join(&file);
+        let html = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        let page = file.to_string_lossy().replace('\\', "/");
+        for message in audit_page(&html) {
+            issues.entry(message).or_default().push(page.clone());
+        }
+    }
+    for (stylesheet, message) in audit_colors(site) {
+        issues.entry(message).or_default().push(stylesheet);
+    }
+
+    for (message, pages) in &issues {
+        warn!("Accessibility: {} on {}", message, list_pages(pages));
+    }
+    match issues.len() {
+        0 => info!("Found no accessibility issues"),
+        count => warn!("Found {} accessibility issues", count),
+    }
+    Ok(issues
+        .into_iter()
+        .map(|(message, pages)| Issue { message, pages })
+        .collect())
+}
+
+/// The accessibility problems of one page, each described once
+pub fn audit_page(html: &str) -> Vec<String> {
+    let mut issues = Vec::new();
+    let mut report = |issue: String| {
+        if !issues.contains(&issue) {
+            issues.push(issue);
+        }
+    };
+    let mut heading = None;
+    for tag in html::tags(html) {
+        if tag.starts_with('/') {
+            continue;
+        }
+        match html::tag_name(tag).as_str() {
+            "html" if html::attribute(tag, "lang").is_none_or(|lang| lang.trim().is_empty()) => {
+                report("the page doesn't say what language it is in".to_string())
+            }
+            "img" => {
+                // Empty alt text is fine for images marked as decoration
+                let decorative = html::attribute(tag, "role")
+                    .is_some_and(|role| role == "presentation" || role == "none")
+                    || html::attribute(tag, "aria-hidden").is_some_and(|hidden| hidden == "true");
+                let alt = html::attribute(tag, "alt").filter(|alt| !alt.trim().is_empty());
+                if alt.is_none() && !decorative {
+                    let src = html::attribute(tag, "src").unwrap_or_default();
+                    report(format!("the image {} has no alt text", src));
+                }
+            }
+            name if name.len() == 2 && name.starts_with('h') => {
+                let Some(level) = name[1..].parse::<u8>().ok().filter(|l| (1..=6).contains(l))
+                else {
+                    continue;
+                };
+                if let Some(previous) = heading.filter(|previous| level > previous + 1) {
+                    report(format!(
+                        "<h{}> follows <h{}>, skipping a level",
+                        level, previous
+                    ));
+                }
+                heading = Some(level);
+            }
+            _ => {}
+        }
+    }
+    issues
+}
+
+/// Rules of the theme's stylesheet and the site's own whose text color is too faint against its
+/// background, by stylesheet. Rules without a background of their own are read against the
+/// page's. Syntax highlighting is left to the highlighting theme chosen.
+fn audit_colors(site: &Site) -> Vec<(String, String)> {
+    let output_dir = site.output_dir();
+    let own = site
+        .config
+        .stylesheets
+        .iter()
+        .filter(|stylesheet| !stylesheet.contains("://"))
+        .map(String::as_str);
+    // The theme's stylesheet is among the base ones
+    let audited: Vec<&str> = std::iter::once(THEME_STYLESHEET).chain(own).collect();
+    let stylesheets: Vec<(&str, String)> = BASE_STYLESHEETS
+        .iter()
+        .copied()
+        .chain(audited.iter().copied().skip(1))
+        .filter_map(|stylesheet| {
+            let css = fs::read_to_string(output_dir.join(stylesheet)).ok()?;
+            Some((stylesheet, css))
+        })
+        .collect();
+
+    // Later rules for the page override earlier ones, as they do in the browser
+    let mut page_background = (255, 255, 255);
+    for (_, css) in &stylesheets {
+        for (selectors, declarations) in rules(css) {
+            let is_page = selectors
+                .split(',')
+                .any(|selector| ["html", "body", ":root"].contains(&selector.trim()));
+            if let Some(background) = is_page.then(|| background(&declarations)).flatten() {
+                page_background = background;
+            }
+        }
+    }
+
+    let mut issues = Vec::new();
+    for (stylesheet, css) in &stylesheets {
+        if !audited.contains(stylesheet) {
+            continue;
+        }
+        for (selectors, declarations) in rules(css) {
+            let Some(color) = declaration(&declarations, "color").and_then(parse_color) else {
+                continue;
+            };
+            // Text over a translucent color or an image can't be judged from the stylesheet
+            let background = match background(&declarations) {
+                Some(background) => background,
+                None if declaration(&declarations, "background").is_some()
+                    || declaration(&declarations, "background-color").is_some() =>
+                {
+                    continue
+                }
+                None => page_background,
+            };
+            let ratio = contrast(color, background);
+            if ratio < MIN_CONTRAST {
+                issues.push((
+                    stylesheet.to_string(),
+                    format!(
+                        "{} has text {} on {}, a contrast of {:.1}:1 where {}:1 is needed",
+                        selectors.split_whitespace().collect::<Vec<_>>().join(" "),
+                        hex(color),
+                        hex(background),
+                        ratio,
+                        MIN_CONTRAST
+                    ),
+                ));
+            }
+        }
+    }
+    issues
+}
+
+/// The selectors and declarations of each top-level rule of `css`. Rules inside `@media` and
+/// other at-rules only apply some of the time, so they are left out.
+fn rules(css: &str) -> Vec<(String, String)> {
+    let mut rules = Vec::new();
+    let mut rest = css;
+    while let Some(open) = rest.find('{') {
+        let selectors = strip_comments(&rest[..open]).trim().to_string();
+        rest = &rest[open + 1..];
+        let mut depth = 1;
+        let mut end = rest.len();
+        for (i, c) in rest.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                end = i;
+                break;
+            }
+        }
+        if !selectors.starts_with('@') {
+            rules.push((selectors, strip_comments(&rest[..end])));
+        }
+        rest = &rest[(end + 1).min(rest.len())..];
+    }
+    rules
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = rest[start..]
+            .find("*/")
+            .map_or("", |end| &rest[start + end + 2..]);
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The value of the last `property` in `declarations`
+fn declaration<'a>(declarations: &'a str, property: &str) -> Option<&'a str> {
+    declarations
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .filter(|(name, _)| name.trim().eq_ignore_ascii_case(property))
+        .map(|(_, value)| value.trim())
+        .next_back()
+}
+
+/// The background color `declarations` give, from `background-color` or the color in a
+/// `background` shorthand
+fn background(declarations: &str) -> Option<(u8, u8, u8)> {
+    if let Some(color) = declaration(declarations, "background-color").and_then(parse_color) {
+        return Some(color);
+    }
+    let shorthand = declaration(declarations, "background")?;
+    shorthand.split_whitespace().find_map(parse_color)
+}
+
+/// An opaque color written as a hex code, `rgb()`, or the names `white` or `black`. Anything
+/// else can't be told without the page, so it isn't audited.
+fn parse_color(value: &str) -> Option<(u8, u8, u8)> {
+    let value = value.trim().trim_end_matches("!important").trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        return match digits.len() {
+            3 => Some((digits[0] * 17, digits[1] * 17, digits[2] * 17)),
+            6 => Some((
+                digits[0] * 16 + digits[1],
+                digits[2] * 16 + digits[3],
+                digits[4] * 16 + digits[5],
+            )),
+            _ => None,
+        };
+    }
+    if let Some(channels) = value
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels: Vec<u8> = channels
+            .split([',', ' '])
+            .filter(|channel| !channel.is_empty())
+            .map(|channel| channel.parse().ok())
+            .collect::<Option<_>>()?;
+        return match channels[..] {
+            [r, g, b] => Some((r, g, b)),
+            _ => None,
+        };
+    }
+    match value.to_ascii_lowercase().as_str() {
+        "white" => Some((255, 255, 255)),
+        "black" => Some((0, 0, 0)),
+        _ => None,
+    }
+}
+
+/// The WCAG contrast ratio between two colors, from 1 to 21
+fn contrast(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Relative luminance of a color, as WCAG defines it
+fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    let channel = |c: u8| {
+        let c = c as f64 / 255.0;
+        match c <= 0.03928 {
+            true => c / 12.92,
+            false => ((c + 0.055) / 1.055).powf(2.4),
+        }
+    };
+    0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
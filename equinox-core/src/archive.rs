@@ -0,0 +1,61 @@
+use crate::{
+    content::{Post, PostList},
+    error::Result,
+    site::Site,
+    templates::{archive_year_path, ArchiveMonth, ArchiveYear, ListedPost, Renderer, ARCHIVE_PAGE},
+};
+use chrono::Datelike;
+use tracing::debug;
+
+/// Writes `archive.html`, listing every post grouped by year and month, and an
+/// `archive/<year>.html` page for each year
+pub fn write_archives(site: &Site, renderer: &Renderer, posts: &PostList) -> Result<()> {
+    let years = group_by_month(site, posts);
+
+    let html = renderer.render_archive(&site.config, ARCHIVE_PAGE, "Archive", &years)?;
+    write_page(site, ARCHIVE_PAGE, html)?;
+
+    for year in &years {
+        let path = archive_year_path(year.year);
+        let heading = format!("Archive: {}", year.year);
+        let html =
+            renderer.render_archive(&site.config, &path, &heading, std::slice::from_ref(year))?;
+        write_page(site, &path, html)?;
+    }
+    Ok(())
+}
+
+/// Groups consecutive posts sharing a year and month, keeping the order of `posts`
+fn group_by_month(site: &Site, posts: &[Post]) -> Vec<ArchiveYear> {
+    let mut years: Vec<ArchiveYear> = Vec::new();
+    for post in posts {
+        let date = post.date();
+        if years.last().is_none_or(|y| y.year != date.year()) {
+            years.push(ArchiveYear {
+                year: date.year(),
+                url: archive_year_path(date.year()),
+                months: Vec::new(),
+            });
+        }
+        let months = &mut years.last_mut().unwrap().months;
+        let name = date.format("%B").to_string();
+        if months.last().is_none_or(|m| m.name != name) {
+            months.push(ArchiveMonth {
+                name,
+                posts: Vec::new(),
+            });
+        }
+        months
+            .last_mut()
+            .unwrap()
+            .posts
+            .push(ListedPost::from_post(&site.config, post));
+    }
+    years
+}
+
+fn write_page(site: &Site, path: &str, html: String) -> Result<()> {
+    let path = site.write_page(path, html)?;
+    debug!("Wrote {}", path.display());
+    Ok(())
+}
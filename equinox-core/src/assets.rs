@@ -0,0 +1,106 @@
+use crate::{
+    atomic,
+    error::{Error, Result},
+    fingerprint,
+};
+use std::{fs, io, path::Path};
+use tracing::debug;
+
+/// Result of an asset sync
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SyncReport {
+    pub copied: usize,
+    pub removed: usize,
+}
+
+/// Mirrors `src` into `dst`: copies files that are new or changed (by size or modification
+/// time) and removes files from `dst` that no longer exist in `src`. Does nothing if `src` does
+/// not exist or both paths are the same directory.
+pub fn sync(src: &Path, dst: &Path) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    if !src.is_dir() {
+        return Ok(report);
+    }
+    if let (Ok(a), Ok(b)) = (src.canonicalize(), dst.canonicalize()) {
+        if a == b {
+            return Ok(report);
+        }
+    }
+
+    sync_dir(src, dst, &mut report).map_err(|e| Error::io("copy files into", dst, e))?;
+    Ok(report)
+}
+
+fn sync_dir(src: &Path, dst: &Path, report: &mut SyncReport) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+
+        if from.is_dir() {
+            sync_dir(&from, &to, report)?;
+        } else if is_stale(&from, &to)? {
+            atomic::copy(&from, &to)?;
+            debug!("Copied {} to {}", from.display(), to.display());
+            report.copied += 1;
+        }
+    }
+
+    for entry in fs::read_dir(dst)? {
+        let entry = entry?;
+        // Fingerprinted copies of files that are still there are kept too
+        let name = entry.file_name();
+        let original = fingerprint::original_name(&name.to_string_lossy());
+        if src.join(&name).exists() || original.is_some_and(|original| src.join(original).exists())
+        {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        debug!("Removed {}", path.display());
+        report.removed += 1;
+    }
+
+    Ok(())
+}
+
+fn is_stale(from: &Path, to: &Path) -> io::Result<bool> {
+    let Ok(existing) = fs::metadata(to) else {
+        return Ok(true);
+    };
+    let source = fs::metadata(from)?;
+    Ok(source.len() != existing.len() || source.modified()? > existing.modified()?)
+}
+
+/// MIME type to serve a file with, based on its extension
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" => "text/plain; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,97 @@
+//! Writing files so they are never seen half written
+//!
+//! Each file is written to a hidden temporary file next to it, then renamed over it, so a crash
+//! mid-write leaves the previous version in place rather than a truncated one. State that can't
+//! be regenerated, like posts and the logs under `.equinox`, is written with [`write_synced`],
+//! which also flushes it to disk so a power loss doesn't leave an empty file either.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tracing::warn;
+
+/// Keeps temporary names apart when two threads write the same file
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+/// Replaces the file at `path` with `contents`
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    write_with(path, |file| file.write_all(contents.as_ref()))
+}
+
+/// Replaces the file at `path` with `contents`, flushing the contents and then the rename to
+/// disk
+pub fn write_synced(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    replace(path, |file| file.write_all(contents.as_ref()), true)?;
+    // The new file is in place by now, so failing to make the rename durable isn't an error
+    if let Err(e) = sync_dir(path) {
+        warn!(
+            "Failed to flush the directory of {} to disk: {}",
+            path.display(),
+            e
+        );
+    }
+    Ok(())
+}
+
+/// Replaces the file at `to` with a copy of `from`
+pub fn copy(from: &Path, to: &Path) -> io::Result<()> {
+    let mut source = File::open(from)?;
+    write_with(to, |file| io::copy(&mut source, file).map(|_| ()))
+}
+
+/// Replaces the file at `path` with whatever `write` writes to the file it is given
+pub fn write_with(path: &Path, write: impl FnOnce(&mut File) -> io::Result<()>) -> io::Result<()> {
+    replace(path, write, false)
+}
+
+/// Writes the temporary file with `write`, flushing it to disk first if `sync` is set, and
+/// renames it over `path`
+fn replace(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+    sync: bool,
+) -> io::Result<()> {
+    let temp = temp_path(path);
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            write(&mut file)?;
+            match sync {
+                true => file.sync_all(),
+                false => Ok(()),
+            }
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Flushes the rename of `path` to disk by syncing the directory holding it
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+/// Windows has no way to open a directory to sync it, and makes renames durable itself
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// A hidden file beside `path`, which deploying and exporting skip
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let n = NEXT_TEMP.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}-{}.tmp", name, process::id(), n))
+}
@@ -0,0 +1,184 @@
+//! Working out which of a project's sources changed since it was last backed up, and where
+//! restored ones go. Sending them to the backup service is left to the caller, since it needs
+//! the network.
+
+use crate::{
+    atomic, cache,
+    error::{Error, Result},
+    redirects::REDIRECTS_FILE,
+    site::Site,
+    sri::PINS_FILE,
+    themes::THEMES_DIR,
+    versions::VERSIONS_DIR,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Record of what the last backup holds, relative to the project root
+pub const BACKUP_FILE: &str = ".equinox/backup.toml";
+
+/// Name of the copy of the record kept alongside the backup, read to restore it
+pub const MANIFEST_NAME: &str = "equinox-backup.toml";
+
+/// Every backed up file, relative to the project root, with a hash of its contents
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    files: BTreeMap<String, String>,
+}
+
+/// The files to send to the backup and the ones to remove from it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub upload: Vec<String>,
+    pub remove: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.upload.is_empty() && self.remove.is_empty()
+    }
+}
+
+impl Manifest {
+    /// The record of the last backup of the project at `root`, empty if it has never been
+    /// backed up
+    pub fn load(root: &Path) -> Manifest {
+        fs::read_to_string(root.join(BACKUP_FILE))
+            .ok()
+            .and_then(|contents| Manifest::parse(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = root.join(BACKUP_FILE);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        atomic::write_synced(&path, self.to_toml()?).map_err(|e| Error::io("write", &path, e))
+    }
+
+    /// Reads a manifest fetched from a backup. Paths that would land outside the project are
+    /// refused.
+    pub fn parse(contents: &str) -> Result<Manifest> {
+        let manifest: Manifest =
+            toml::from_str(contents).map_err(|e| Error::parse("backup manifest", None, e))?;
+        if let Some(path) = manifest.files.keys().find(|path| !is_relative(path)) {
+            return Err(Error::Invalid(format!(
+                "The backup holds {}, which is outside the project",
+                path
+            )));
+        }
+        Ok(manifest)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self)
+            .map_err(|e| Error::Invalid(format!("Failed to serialize the backup manifest: {}", e)))
+    }
+
+    /// The backed up files, relative to the project root
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Notes that `path` now holds `contents`
+    pub fn insert(&mut self, path: &str, contents: &[u8]) {
+        self.files.insert(path.to_string(), cache::hash([contents]));
+    }
+
+    /// What has to change in a backup holding `self` for it to hold `current`
+    pub fn changes(&self, current: &Manifest) -> Changes {
+        Changes {
+            upload: current
+                .files
+                .iter()
+                .filter(|(path, hash)| self.files.get(*path) != Some(hash))
+                .map(|(path, _)| path.clone())
+                .collect(),
+            remove: self
+                .files
+                .keys()
+                .filter(|path| !current.files.contains_key(*path))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// The project's sources as they are now: its settings, post sources and their earlier
+/// versions, assets, templates, themes, and the records of redirects and pinned hashes. The
+/// generated site can be built again from these, so it isn't backed up.
+pub fn scan(site: &Site) -> Result<Manifest> {
+    let mut manifest = Manifest::default();
+    for file in [Site::CONFIG_FILE, REDIRECTS_FILE, PINS_FILE] {
+        let path = site.root.join(file);
+        if path.is_file() {
+            let contents = fs::read(&path).map_err(|e| Error::io("read", &path, e))?;
+            manifest.insert(file, &contents);
+        }
+    }
+    let dirs = [
+        site.content_dir(),
+        site.assets_dir(),
+        site.templates_dir(),
+        site.root.join(THEMES_DIR),
+        site.root.join(VERSIONS_DIR),
+    ];
+    for dir in dirs {
+        let Ok(relative) = dir.strip_prefix(&site.root) else {
+            continue;
+        };
+        if dir.is_dir() {
+            collect(&site.root, relative, &mut manifest)?;
+        }
+    }
+    Ok(manifest)
+}
+
+/// Where the backed up file `path` goes in the project at `root`
+pub fn restored_path(root: &Path, path: &str) -> Result<PathBuf> {
+    match is_relative(path) {
+        true => Ok(root.join(path)),
+        false => Err(Error::Invalid(format!(
+            "The backup holds {}, which is outside the project",
+            path
+        ))),
+    }
+}
+
+/// Adds every file under `relative`, skipping hidden ones such as `.DS_Store`
+fn collect(root: &Path, relative: &Path, manifest: &mut Manifest) -> Result<()> {
+    let dir = root.join(relative);
+    for entry in fs::read_dir(&dir).map_err(|e| Error::io("read", &dir, e))? {
+        let entry = entry.map_err(|e| Error::io("read", &dir, e))?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        if path.is_dir() {
+            collect(root, &relative.join(&name), manifest)?;
+        } else {
+            let contents = fs::read(&path).map_err(|e| Error::io("read", &path, e))?;
+            let file = relative.join(&name).to_string_lossy().replace('\\', "/");
+            manifest.insert(&file, &contents);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` stays inside the directory it is relative to
+fn is_relative(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
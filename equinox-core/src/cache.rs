@@ -0,0 +1,106 @@
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// Location of the build cache, relative to the project root
+pub const CACHE_FILE: &str = ".equinox/build-cache.toml";
+
+/// Hashes of the inputs each post page was last rendered from, so builds can skip posts that
+/// haven't changed since
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildCache {
+    /// Fingerprint of the equinox version, site settings, and templates behind the cached pages
+    fingerprint: String,
+    /// Input hash of each post's page, by post id
+    posts: BTreeMap<String, String>,
+}
+
+impl BuildCache {
+    /// Loads the cache for the project at `root`. It starts out empty if it is missing,
+    /// unreadable, or was written with a different `fingerprint`, since every page would then
+    /// render differently.
+    pub fn load(root: &Path, fingerprint: &str) -> BuildCache {
+        let cached = fs::read_to_string(path(root))
+            .ok()
+            .and_then(|contents| toml::from_str::<BuildCache>(&contents).ok())
+            .filter(|cache| cache.fingerprint == fingerprint);
+        cached.unwrap_or_else(|| {
+            debug!("Build cache is missing or out of date, every post will be rendered");
+            BuildCache {
+                fingerprint: fingerprint.to_string(),
+                posts: BTreeMap::new(),
+            }
+        })
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = path(root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        let contents = toml::to_string(self)
+            .map_err(|e| Error::Invalid(format!("Failed to serialize the build cache: {}", e)))?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    /// Deletes the cache so the next build renders every page
+    pub fn clear(root: &Path) -> Result<()> {
+        let path = path(root);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(Error::io("remove", &path, e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether `post`'s page was last rendered from exactly its current inputs. `inputs`
+    /// describes whatever else the page shows, such as the titles of the posts it links to.
+    pub fn is_fresh(&self, post: &Post, inputs: &str) -> bool {
+        self.posts.get(&post.id) == Some(&post_hash(post, inputs))
+    }
+
+    /// Records that `post`'s page was rendered from its current inputs and `inputs`
+    pub fn insert(&mut self, post: &Post, inputs: &str) {
+        self.posts.insert(post.id.clone(), post_hash(post, inputs));
+    }
+
+    /// Forgets posts that no longer exist
+    pub fn retain(&mut self, posts: &[Post]) {
+        let ids: HashSet<&str> = posts.iter().map(|p| p.id.as_str()).collect();
+        self.posts.retain(|id, _| ids.contains(id.as_str()));
+    }
+}
+
+/// Hex-encoded SHA-256 of `parts`, each length-prefixed so their boundaries count
+pub fn hash<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn post_hash(post: &Post, inputs: &str) -> String {
+    let meta = toml::to_string(&post.meta).unwrap_or_default();
+    hash([meta.as_bytes(), post.body.as_bytes(), inputs.as_bytes()])
+}
+
+fn path(root: &Path) -> PathBuf {
+    root.join(CACHE_FILE)
+}
@@ -0,0 +1,385 @@
+use crate::{
+    atomic,
+    error::{Error, Result},
+    front_matter::{self, FrontMatter, FENCE},
+};
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, warn};
+
+/// Metadata stored alongside each post's Markdown source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostMeta {
+    pub title: String,
+    pub created: DateTime<FixedOffset>,
+    pub updated: DateTime<FixedOffset>,
+    /// Section of the site the post belongs to, e.g. `tech`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub category: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Name of the series the post is a part of, ordered among the other parts by date
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub series: String,
+    /// Publish date shown on the post and in listings, defaulting to `created`. Posts dated in
+    /// the future are treated as drafts until then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<FixedOffset>>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub author: String,
+    /// Overrides the file name of the generated page
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub slug: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// Preview image shown when the post is shared, relative to the output directory or an
+    /// absolute URL
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub image: String,
+    /// Audio file played on the post's page and enclosed in its feed entries, relative to the
+    /// output directory or an absolute URL
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub audio: String,
+    /// Where the post was first published, for posts syndicated from elsewhere; the post's own
+    /// page is its canonical URL otherwise
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub canonical_url: String,
+    /// Drafts are generated but left out of the index, listings, and feeds
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub draft: bool,
+    /// Show a table of contents linking to the post's headings
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub toc: bool,
+    /// Ask search engines not to index the post's page, and leave it out of the sitemap
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub noindex: bool,
+    /// Leave the post out when new posts are shared to Mastodon and Bluesky
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_crosspost: bool,
+    /// Leave the author's card off the end of the post
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_author_card: bool,
+}
+
+/// Posts newest first, as [`ContentStore::list`] returns them. The listing pages, feeds, and
+/// related and series lookups all rely on this order.
+pub type PostList = [Post];
+
+/// A post as stored in the site's content directory
+#[derive(Debug, Clone)]
+pub struct Post {
+    /// File stem of the source file, unique within the content store
+    pub id: String,
+    pub meta: PostMeta,
+    /// Markdown source of the post
+    pub body: String,
+}
+
+impl Post {
+    /// Creates a new, unsaved post whose id and slug are both `slug`
+    pub fn new(slug: String, title: String, body: String) -> Post {
+        let now = Local::now().fixed_offset();
+        Post {
+            id: slug.clone(),
+            meta: PostMeta {
+                title,
+                created: now,
+                updated: now,
+                slug,
+                ..PostMeta::default()
+            },
+            body,
+        }
+    }
+
+    /// Slug used in the post's URL, falling back to its id
+    pub fn slug(&self) -> &str {
+        if self.meta.slug.is_empty() {
+            &self.id
+        } else {
+            &self.meta.slug
+        }
+    }
+
+    /// Publish date of the post
+    pub fn date(&self) -> DateTime<FixedOffset> {
+        self.meta.date.unwrap_or(self.meta.created)
+    }
+
+    /// Whether the post is dated in the future, keeping it out of listings until then
+    pub fn is_scheduled(&self) -> bool {
+        self.date() > Local::now()
+    }
+
+    /// Whether the post belongs in the index, listings, and feeds
+    pub fn is_published(&self) -> bool {
+        !self.meta.draft && !self.is_scheduled()
+    }
+
+    fn parse(id: String, path: &Path, source: &str) -> Result<Post> {
+        let (meta, body) = front_matter::split(source)
+            .ok_or_else(|| Error::parse("post", Some(path), "missing its metadata block"))?;
+        let meta = toml::from_str(meta).map_err(|e| Error::parse("metadata", Some(path), e))?;
+
+        Ok(Post {
+            id,
+            meta,
+            body: body.to_string(),
+        })
+    }
+
+    fn serialize(&self) -> Result<String> {
+        let meta = toml::to_string(&self.meta).map_err(|e| {
+            Error::Invalid(format!(
+                "Failed to serialize metadata for {}: {}",
+                self.id, e
+            ))
+        })?;
+        Ok(format!(
+            "{fence}\n{meta}{fence}\n\n{body}",
+            fence = FENCE,
+            meta = meta,
+            body = self.body
+        ))
+    }
+}
+
+/// A post's fields as entered in an editor, before they are merged into a stored [`Post`]
+#[derive(Debug, Clone, Default)]
+pub struct PostEdit {
+    pub title: String,
+    pub category: String,
+    pub tags: Vec<String>,
+    /// Summary for search engines and link previews; an excerpt is used when empty
+    pub description: String,
+    /// Publish date, defaulting to when the post was created. Posts dated in the future are
+    /// generated but left out of listings until a build after that date.
+    pub date: Option<DateTime<FixedOffset>>,
+    /// Author picked in the editor, unless the front matter names one
+    pub author: String,
+    /// Author recorded when neither the editor nor the front matter names one
+    pub default_author: String,
+    /// Markdown content, optionally starting with a [`FrontMatter`] block
+    pub content: String,
+}
+
+/// Lowercases `text` and replaces everything but ASCII letters and digits with single dashes,
+/// producing a string safe to use in file names and URLs
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Slug of `title`, or `post` if it has no letters or digits
+fn base_slug(title: &str) -> String {
+    match slugify(title) {
+        slug if slug.is_empty() => "post".to_string(),
+        slug => slug,
+    }
+}
+
+/// Splits a comma separated list of tags, dropping empty entries and duplicates
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        if !tags
+            .iter()
+            .any(|existing| slugify(existing) == slugify(tag))
+        {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// What to do with a post whose page another stored post already has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collision {
+    /// Replace the other post with this one
+    Overwrite,
+    /// Give this post a suffixed slug so both are kept
+    KeepBoth,
+}
+
+/// Reads and writes post sources in a site's content directory
+pub struct ContentStore {
+    dir: PathBuf,
+}
+
+impl ContentStore {
+    pub fn new(dir: &Path) -> ContentStore {
+        ContentStore {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.md", id))
+    }
+
+    /// Lists all stored posts, newest first, as a [`PostList`] expects. Files that fail to parse
+    /// are skipped with a warning.
+    pub fn list(&self) -> Result<Vec<Post>> {
+        if !self.dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let entries = fs::read_dir(&self.dir).map_err(|e| Error::io("read", &self.dir, e))?;
+
+        let mut posts: Vec<Post> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "md"))
+            .filter_map(|p| {
+                let id = p.file_stem()?.to_string_lossy().into_owned();
+                match self.load(&id) {
+                    Ok(post) => Some(post),
+                    Err(e) => {
+                        warn!("Skipping {}: {}", p.display(), e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        posts.sort_by_key(|post| std::cmp::Reverse(post.date()));
+        Ok(posts)
+    }
+
+    /// Slug for a new post titled `title`, suffixed with `-2`, `-3`, ... if another post already
+    /// uses it
+    pub fn unique_slug(&self, title: &str) -> Result<String> {
+        let base = base_slug(title);
+
+        let posts = self.list()?;
+        let taken =
+            |slug: &str| self.path(slug).exists() || posts.iter().any(|post| post.slug() == slug);
+
+        let mut slug = base.clone();
+        let mut n = 2;
+        while taken(&slug) {
+            slug = format!("{}-{}", base, n);
+            n += 1;
+        }
+        Ok(slug)
+    }
+
+    /// The stored post, other than `post` itself, whose page `post` would be written over. A new
+    /// post without a slug in its front matter is checked under the slug of its title rather
+    /// than the suffixed one it was given.
+    pub fn colliding_post(&self, post: &Post) -> Result<Option<Post>> {
+        let wanted = self.wanted_slug(post);
+        Ok(self
+            .list()?
+            .into_iter()
+            .find(|other| other.id != post.id && (other.slug() == wanted || other.id == wanted)))
+    }
+
+    /// Settles a collision of `post` with `other`, as found by
+    /// [`colliding_post`](ContentStore::colliding_post), returning the post to save. Overwriting
+    /// removes `other`'s source and gives `post` its page; keeping both suffixes `post`'s slug.
+    pub fn resolve_collision(
+        &self,
+        mut post: Post,
+        other: &Post,
+        collision: Collision,
+    ) -> Result<Post> {
+        let wanted = self.wanted_slug(&post);
+        let is_new = !self.path(&post.id).exists();
+        match collision {
+            Collision::Overwrite if is_new && post.meta.slug.is_empty() => {
+                // Taking over the other post's id replaces its source when saved
+                post.id = other.id.clone();
+                if wanted != other.id {
+                    post.meta.slug = wanted;
+                }
+            }
+            Collision::Overwrite => {
+                let path = self.path(&other.id);
+                fs::remove_file(&path).map_err(|e| Error::io("remove", &path, e))?;
+                info!("Removed post {}, overwritten by {}", other.id, post.id);
+            }
+            // A new post's id was already suffixed when it was created
+            Collision::KeepBoth if is_new && post.meta.slug.is_empty() => {}
+            Collision::KeepBoth => post.meta.slug = self.unique_slug(&wanted)?,
+        }
+        Ok(post)
+    }
+
+    /// The slug `post` asks for: the one in its front matter, else its title's for a new post
+    fn wanted_slug(&self, post: &Post) -> String {
+        match post.meta.slug.is_empty() {
+            false => post.meta.slug.clone(),
+            true if !self.path(&post.id).exists() => base_slug(&post.meta.title),
+            true => post.id.clone(),
+        }
+    }
+
+    /// Applies `edit` to the stored post `id`, or to a new post with a fresh slug if `id` is
+    /// `None`. The result is not saved.
+    pub fn edit(&self, id: Option<&str>, edit: PostEdit) -> Result<Post> {
+        let (front_matter, body) = FrontMatter::parse(&edit.content)?;
+        let body = body.to_string();
+
+        let mut post = match id {
+            None => Post::new(self.unique_slug(&edit.title)?, edit.title, body),
+            Some(id) => {
+                let mut post = self.load(id)?;
+                post.meta.title = edit.title;
+                post.meta.updated = Local::now().fixed_offset();
+                post.body = body;
+                post
+            }
+        };
+        post.meta.category = edit.category;
+        post.meta.tags = edit.tags;
+        post.meta.description = edit.description;
+        post.meta.date = edit.date;
+        post.meta.author = edit.author;
+        front_matter.unwrap_or_default().apply(&mut post.meta)?;
+        if post.meta.author.is_empty() {
+            post.meta.author = edit.default_author;
+        }
+        Ok(post)
+    }
+
+    pub fn load(&self, id: &str) -> Result<Post> {
+        let path = self.path(id);
+        let source = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        Post::parse(id.to_string(), &path, &source)
+    }
+
+    /// The stored source of the post `id`, metadata and all
+    pub fn source(&self, id: &str) -> Result<String> {
+        let path = self.path(id);
+        fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))
+    }
+
+    /// Replaces the stored source of the post `id` with `source`, which must parse as a post
+    pub fn restore(&self, id: &str, source: &str) -> Result<Post> {
+        let path = self.path(id);
+        let post = Post::parse(id.to_string(), &path, source)?;
+        atomic::write_synced(&path, source).map_err(|e| Error::io("write", &path, e))?;
+        debug!("Restored post {} to {}", id, path.display());
+        Ok(post)
+    }
+
+    pub fn save(&self, post: &Post) -> Result<()> {
+        fs::create_dir_all(&self.dir).map_err(|e| Error::io("create", &self.dir, e))?;
+        let path = self.path(&post.id);
+        atomic::write_synced(&path, post.serialize()?).map_err(|e| Error::io("write", &path, e))?;
+        debug!("Saved post {} to {}", post.id, path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+//! Sharing new posts to social networks once they are online. Posting is left to the caller;
+//! this picks which posts to share and keeps track of which have been, separately for each
+//! network.
+
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+    markdown,
+    site::Site,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Length of the summary of posts without a description, as in the feeds
+const SUMMARY_LENGTH: usize = 280;
+
+/// A network posts are shared to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mastodon,
+    Bluesky,
+}
+
+impl Network {
+    pub fn name(self) -> &'static str {
+        match self {
+            Network::Mastodon => "Mastodon",
+            Network::Bluesky => "Bluesky",
+        }
+    }
+
+    /// Record of the posts already shared to the network, relative to the project root
+    pub fn log_file(self) -> &'static str {
+        match self {
+            Network::Mastodon => ".equinox/mastodon.toml",
+            Network::Bluesky => ".equinox/bluesky.toml",
+        }
+    }
+}
+
+/// Result of sharing new posts to a network
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShareReport {
+    pub shared: usize,
+    /// Posts already online before sharing was set up, recorded as shared without being posted
+    pub earlier: usize,
+}
+
+/// The posts shared to a network so far, and where
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SharedLog {
+    /// URL of each status or post sharing a post, by the ID of the post it shares
+    shared: BTreeMap<String, String>,
+}
+
+impl SharedLog {
+    /// Loads the log for `network` of the project at `root`, or `None` if nothing has been
+    /// shared to it yet
+    pub fn load(root: &Path, network: Network) -> Option<SharedLog> {
+        fs::read_to_string(path(root, network))
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+    }
+
+    pub fn save(&self, root: &Path, network: Network) -> Result<()> {
+        let path = path(root, network);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        let contents = toml::to_string(self)
+            .map_err(|e| Error::Invalid(format!("Failed to serialize the shared posts: {}", e)))?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    pub fn is_shared(&self, id: &str) -> bool {
+        self.shared.contains_key(id)
+    }
+
+    /// Records that the post `id` was shared at `url`, which is empty for posts that were
+    /// online before sharing was set up
+    pub fn record(&mut self, id: &str, url: &str) {
+        self.shared.insert(id.to_string(), url.to_string());
+    }
+}
+
+/// Starts the log for `network` if `site` doesn't have one, recording the posts already
+/// published as shared so setting up sharing doesn't post the whole archive at once. Returns
+/// how many were recorded.
+pub fn start(site: &Site, network: Network) -> Result<usize> {
+    if SharedLog::load(&site.root, network).is_some() {
+        return Ok(0);
+    }
+    let mut log = SharedLog::default();
+    let posts = unshared(site, &log)?;
+    for post in &posts {
+        log.record(&post.id, "");
+    }
+    log.save(&site.root, network)?;
+    Ok(posts.len())
+}
+
+/// Records the published ones among `posts`, just imported from elsewhere, as shared to
+/// `network` so an import doesn't post a whole archive. Before the log is started there is
+/// nothing to do, as starting it records them.
+pub fn record_imported(site: &Site, network: Network, posts: &[Post]) -> Result<()> {
+    let Some(mut log) = SharedLog::load(&site.root, network) else {
+        return Ok(());
+    };
+    for post in posts.iter().filter(|post| post.is_published()) {
+        log.record(&post.id, "");
+    }
+    log.save(&site.root, network)
+}
+
+/// Published posts of `site` that aren't in `log` and aren't marked `no_crosspost`, oldest
+/// first
+pub fn unshared(site: &Site, log: &SharedLog) -> Result<Vec<Post>> {
+    let mut posts: Vec<Post> = site
+        .store()
+        .list()?
+        .into_iter()
+        .filter(|post| post.is_published() && !post.meta.no_crosspost && !log.is_shared(&post.id))
+        .collect();
+    posts.sort_by_key(Post::date);
+    Ok(posts)
+}
+
+/// The post's description, or an excerpt of it if it has none
+pub fn summary(post: &Post) -> String {
+    match post.meta.description.as_str() {
+        "" => markdown::excerpt(&post.body, SUMMARY_LENGTH),
+        description => description.to_string(),
+    }
+}
+
+/// `text` cut to `max_chars`, ending in an ellipsis if anything was cut
+pub fn shorten(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut shortened: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    shortened.push('…');
+    shortened
+}
+
+fn path(root: &Path, network: Network) -> PathBuf {
+    root.join(network.log_file())
+}
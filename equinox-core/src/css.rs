@@ -0,0 +1,171 @@
+//! Bundling every stylesheet a page loads into one minified file
+
+use crate::{
+    atomic,
+    error::{Error, Result},
+    site::Site,
+    templates::root_for,
+};
+use std::fs;
+use tracing::{debug, warn};
+
+/// Path of the bundled stylesheet, relative to the output directory
+pub const BUNDLE: &str = "css/bundle.css";
+
+/// Stylesheets `base.html` links every page to, relative to the output directory, ahead of the
+/// site's own
+pub const BASE_STYLESHEETS: &[&str] = &[
+    "assets/barebones.css",
+    "theme/style.css",
+    "theme/highlight.css",
+];
+
+/// Writes [`BUNDLE`] from the base stylesheets and the site's own, in the order pages load them,
+/// meant to be called once the theme and assets have been written. Removes it again when the
+/// site doesn't bundle its stylesheets.
+pub fn write_bundle(site: &Site) -> Result<()> {
+    let path = site.output_dir().join(BUNDLE);
+    if !site.config.bundle_css {
+        // The directory only ever holds the bundle and its fingerprinted copies
+        if let Some(dir) = path.parent().filter(|dir| dir.is_dir()) {
+            fs::remove_dir_all(dir).map_err(|e| Error::io("remove", dir, e))?;
+        }
+        return Ok(());
+    }
+
+    let own = site
+        .config
+        .stylesheets
+        .iter()
+        .filter(|stylesheet| !stylesheet.contains("://"));
+    let mut bundle = String::new();
+    for stylesheet in BASE_STYLESHEETS
+        .iter()
+        .copied()
+        .chain(own.map(String::as_str))
+    {
+        let source = site.output_dir().join(stylesheet);
+        match fs::read_to_string(&source) {
+            Ok(css) => {
+                bundle.push_str(&minify(&rebase_urls(&css, stylesheet, BUNDLE)));
+                bundle.push('\n');
+            }
+            // Sites don't have to provide a barebones stylesheet of their own
+            Err(_) if BASE_STYLESHEETS.contains(&stylesheet) => {}
+            Err(e) => warn!("Left {} out of the stylesheet bundle: {}", stylesheet, e),
+        }
+    }
+
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == bundle) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+    }
+    atomic::write(&path, &bundle).map_err(|e| Error::io("write", &path, e))?;
+    debug!("Wrote {} ({} bytes)", path.display(), bundle.len());
+    Ok(())
+}
+
+/// Shrinks a stylesheet without changing what it means: comments are removed, whitespace
+/// collapses, and it is dropped entirely around braces, semicolons, commas, and after colons.
+/// Strings are kept exactly as written.
+pub fn minify(css: &str) -> String {
+    let mut minified = String::with_capacity(css.len());
+    let mut rest = css;
+    let mut pending_space = false;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("/*") {
+            rest = rest.find("*/").map_or("", |end| &rest[end + "*/".len()..]);
+            pending_space = true;
+            continue;
+        }
+        if c.is_whitespace() {
+            pending_space = true;
+            rest = rest.trim_start();
+            continue;
+        }
+        // Nothing needs a space before these, or after them and colons
+        let tight = ['{', '}', ';', ',', '>'];
+        if pending_space
+            && !minified.is_empty()
+            && !tight.contains(&c)
+            && !minified.ends_with(tight)
+            && !minified.ends_with(':')
+        {
+            minified.push(' ');
+        }
+        pending_space = false;
+        if c == '"' || c == '\'' {
+            let end = string_end(rest, c);
+            minified.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        // The last declaration of a block needs no semicolon
+        if c == '}' && minified.ends_with(';') {
+            minified.pop();
+        }
+        minified.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    minified
+}
+
+/// Length of the string at the start of `css`, quoted with `quote`, including both quotes
+fn string_end(css: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in css.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == quote => return i + 1,
+            _ => {}
+        }
+    }
+    css.len()
+}
+
+/// `css` read from `from` with its relative `url()`s pointed where they led from there when
+/// it is loaded from `to` instead, both relative to the output directory
+pub fn rebase_urls(css: &str, from: &str, to: &str) -> String {
+    let root = root_for(to);
+    let dir = from.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let mut rebased = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("url(") {
+        let start = start + "url(".len();
+        rebased.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(')').unwrap_or(rest.len());
+        let value = rest[..end].trim();
+        let quote = value
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .map(String::from)
+            .unwrap_or_default();
+        let url = value.trim_matches(['"', '\'']);
+        let is_relative =
+            !(url.is_empty() || url.contains(':') || url.starts_with('/') || url.starts_with('#'));
+        match is_relative {
+            true => {
+                let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+                for part in url.split('/') {
+                    match part {
+                        "." => {}
+                        ".." => {
+                            parts.pop();
+                        }
+                        part => parts.push(part),
+                    }
+                }
+                rebased.push_str(&format!("{}{}{}{}", quote, root, parts.join("/"), quote));
+            }
+            false => rebased.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+    rebased.push_str(rest);
+    rebased
+}
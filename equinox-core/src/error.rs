@@ -0,0 +1,151 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Everything that can go wrong while managing, generating, or publishing a site
+#[derive(Debug)]
+pub enum Error {
+    /// A file or directory couldn't be read, written, created, or removed
+    Io {
+        /// What was being done, e.g. `read` or `create`
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A settings file, stored post, or front matter block isn't valid TOML or has the wrong
+    /// shape
+    Parse {
+        /// What was being parsed, e.g. `front matter`
+        what: String,
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// A built-in, theme, or site template failed to load
+    Template {
+        /// Where the templates came from, e.g. `theme 'dark'`
+        origin: String,
+        path: Option<PathBuf>,
+        message: String,
+    },
+    /// A page failed to render from its template
+    Render { template: String, message: String },
+    /// Uploading the site to a deploy target failed
+    Deploy {
+        /// Name of the target, e.g. `SFTP`
+        target: String,
+        message: String,
+    },
+    /// A request equinox can't carry out, e.g. an unknown theme or an invalid date
+    Invalid(String),
+}
+
+/// Result type used throughout equinox
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn io(action: &'static str, path: impl AsRef<Path>, source: io::Error) -> Error {
+        Error::Io {
+            action,
+            path: path.as_ref().to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn parse(what: &str, path: Option<&Path>, message: impl fmt::Display) -> Error {
+        Error::Parse {
+            what: what.to_string(),
+            path: path.map(Path::to_path_buf),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn deploy(target: &str, message: impl fmt::Display) -> Error {
+        Error::Deploy {
+            target: target.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Short name for the kind of error, e.g. for a dialog's title
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io { .. } => "File error",
+            Error::Parse { .. } => "Invalid file",
+            Error::Template { .. } => "Template error",
+            Error::Render { .. } => "Render error",
+            Error::Deploy { .. } => "Publish error",
+            Error::Invalid(_) => "Invalid input",
+        }
+    }
+
+    /// The file or directory the error concerns, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Io { path, .. } => Some(path),
+            Error::Parse { path, .. } | Error::Template { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// What the user can do about the error, if there is anything obvious
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            Error::Io { source, .. } => match source.kind() {
+                io::ErrorKind::NotFound => {
+                    Some("Check that the path exists and the project hasn't been moved.")
+                }
+                io::ErrorKind::PermissionDenied => {
+                    Some("Check that you have permission to read and write this location.")
+                }
+                _ => None,
+            },
+            Error::Parse { .. } => Some("Fix the TOML syntax in the file and try again."),
+            Error::Template { .. } => {
+                Some("Check the template's Tera syntax; removing the template restores the built-in one.")
+            }
+            Error::Render { .. } => {
+                Some("Check that the template only uses variables equinox provides for the page.")
+            }
+            Error::Deploy { .. } => {
+                Some("Check the publish settings and your network connection.")
+            }
+            Error::Invalid(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io {
+                action,
+                path,
+                source,
+            } => write!(f, "Failed to {} {}: {}", action, path.display(), source),
+            Error::Parse {
+                what,
+                path: Some(path),
+                message,
+            } => write!(f, "Invalid {} in {}: {}", what, path.display(), message),
+            Error::Parse { what, message, .. } => write!(f, "Invalid {}: {}", what, message),
+            Error::Template {
+                origin, message, ..
+            } => write!(f, "Failed to load {}: {}", origin, message),
+            Error::Render { template, message } => {
+                write!(f, "Failed to render {}: {}", template, message)
+            }
+            Error::Deploy { target, message } => write!(f, "{}: {}", target, message),
+            Error::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
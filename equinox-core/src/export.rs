@@ -0,0 +1,231 @@
+//! Packing a freshly built copy of a site into one archive for manual uploads or backups, and
+//! the output directory into a zip archive for hosts that take a whole site at once
+
+use crate::{
+    atomic,
+    error::{Error, Result},
+    site::Site,
+};
+use flate2::{
+    write::{DeflateEncoder, GzEncoder},
+    Compression, Crc,
+};
+use std::{fs, io::Write, path::Path, process};
+use tracing::{info, warn};
+
+/// Extension of the archives written by [`export_site`]
+pub const EXTENSION: &str = "tar.gz";
+
+/// Renders every page of `site` into a temporary directory and packs the result into a gzipped
+/// tarball at `archive`, returning the number of files packed. The site's own output directory
+/// and build cache are left alone.
+pub fn export_site(site: &Site, archive: &Path) -> Result<usize> {
+    let dir = std::env::temp_dir().join(format!("equinox-export-{}", process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| Error::io("remove", &dir, e))?;
+    }
+    let result = build_into(site, &dir).and_then(|build| pack(&build, archive));
+    if let Err(e) = fs::remove_dir_all(&dir) {
+        warn!("Failed to remove {}: {}", dir.display(), e);
+    }
+    let count = result?;
+    info!("Exported {} files to {}", count, archive.display());
+    Ok(count)
+}
+
+/// A copy of `site` built from scratch into `dir`
+fn build_into(site: &Site, dir: &Path) -> Result<Site> {
+    let mut build = site.clone();
+    build.set_output_dir(dir);
+    let renderer = build.renderer()?;
+    let posts = build.store().list()?;
+    for post in &posts {
+        build.write_post(&renderer, post, &posts)?;
+    }
+    // Moves are recorded by the site's own builds, so the export leaves the project as it was
+    build.write_listings_with(&renderer, &posts, false)?;
+    build.write_static_files()?;
+    Ok(build)
+}
+
+/// Writes the output files of `build` into a gzipped tarball at `archive`
+fn pack(build: &Site, archive: &Path) -> Result<usize> {
+    let files = build.output_files()?;
+    let output_dir = build.output_dir();
+    atomic::write_with(archive, |file| {
+        let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for relative in &files {
+            tar.append_path_with_name(output_dir.join(relative), relative)?;
+        }
+        tar.into_inner()?.finish().map(|_| ())
+    })
+    .map_err(|e| Error::io("write", archive, e))?;
+    Ok(files.len())
+}
+
+/// The site's output directory packed into a zip archive, as hosts such as Netlify take it
+pub fn zip_output(site: &Site) -> Result<Vec<u8>> {
+    let output_dir = site.output_dir();
+    let mut zip = Zip::default();
+    for relative in site.output_files()? {
+        let path = output_dir.join(&relative);
+        let contents = fs::read(&path).map_err(|e| Error::io("read", &path, e))?;
+        let name = relative.to_string_lossy().replace('\\', "/");
+        zip.add(&name, &contents)?;
+    }
+    zip.finish()
+}
+
+/// A zip archive written in memory, each file deflated. Archives needing zip64, with more than
+/// 65535 files or 4 GiB, are refused.
+#[derive(Default)]
+struct Zip {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    entries: usize,
+}
+
+impl Zip {
+    /// Version of the format needed to read the archive: 2.0, for deflate
+    const VERSION: u16 = 20;
+    /// Marks the names as UTF-8
+    const FLAGS: u16 = 1 << 11;
+    const DEFLATE: u16 = 8;
+    /// Midnight on 1 January 1980, the earliest time the format has; hosts don't look at it
+    const TIME: u16 = 0;
+    const DATE: u16 = (1 << 5) | 1;
+
+    fn add(&mut self, name: &str, contents: &[u8]) -> Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        let compressed = encoder
+            .write_all(contents)
+            .and_then(|()| encoder.finish())
+            .map_err(|e| Error::Invalid(format!("Failed to compress {}: {}", name, e)))?;
+        let mut crc = Crc::new();
+        crc.update(contents);
+
+        let too_big = |_| Error::Invalid(format!("{} is too big for a zip archive", name));
+        let compressed_size = u32::try_from(compressed.len()).map_err(too_big)?;
+        let size = u32::try_from(contents.len()).map_err(too_big)?;
+        let name_length = u16::try_from(name.len()).map_err(too_big)?;
+        let offset = u32::try_from(self.data.len()).map_err(too_big)?;
+        let header = |out: &mut Vec<u8>| {
+            out.extend(Zip::VERSION.to_le_bytes());
+            out.extend(Zip::FLAGS.to_le_bytes());
+            out.extend(Zip::DEFLATE.to_le_bytes());
+            out.extend(Zip::TIME.to_le_bytes());
+            out.extend(Zip::DATE.to_le_bytes());
+            out.extend(crc.sum().to_le_bytes());
+            out.extend(compressed_size.to_le_bytes());
+            out.extend(size.to_le_bytes());
+            out.extend(name_length.to_le_bytes());
+            // No extra field
+            out.extend(0u16.to_le_bytes());
+        };
+
+        self.data.extend(0x04034b50u32.to_le_bytes());
+        header(&mut self.data);
+        self.data.extend(name.as_bytes());
+        self.data.extend(&compressed);
+
+        let directory = &mut self.central_directory;
+        directory.extend(0x02014b50u32.to_le_bytes());
+        // Made by the same version it needs
+        directory.extend(Zip::VERSION.to_le_bytes());
+        header(directory);
+        // No comment, on the first disk, with no attributes
+        directory.extend([0; 10]);
+        directory.extend(offset.to_le_bytes());
+        directory.extend(name.as_bytes());
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        let too_big = |_| Error::Invalid("The site is too big for a zip archive".to_string());
+        let entries = u16::try_from(self.entries).map_err(too_big)?;
+        let offset = u32::try_from(self.data.len()).map_err(too_big)?;
+        let size = u32::try_from(self.central_directory.len()).map_err(too_big)?;
+        self.data.append(&mut self.central_directory);
+        self.data.extend(0x06054b50u32.to_le_bytes());
+        // Everything is on the first disk
+        self.data.extend([0; 4]);
+        self.data.extend(entries.to_le_bytes());
+        self.data.extend(entries.to_le_bytes());
+        self.data.extend(size.to_le_bytes());
+        self.data.extend(offset.to_le_bytes());
+        // No comment
+        self.data.extend(0u16.to_le_bytes());
+        Ok(self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([data[at], data[at + 1]])
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn zip_lists_every_file_in_its_directory() {
+        let mut zip = Zip::default();
+        zip.add("index.html", b"<p>Hello</p>").unwrap();
+        zip.add("posts/a.html", b"<p>A</p>").unwrap();
+        let data = zip.finish().unwrap();
+
+        // The end of central directory record is the last 22 bytes, without a comment
+        let end = data.len() - 22;
+        assert_eq!(u32_at(&data, end), 0x06054b50);
+        assert_eq!(u16_at(&data, end + 10), 2);
+        let directory = u32_at(&data, end + 16) as usize;
+        assert_eq!(directory + u32_at(&data, end + 12) as usize, end);
+
+        let mut names = Vec::new();
+        let mut at = directory;
+        while at < end {
+            assert_eq!(u32_at(&data, at), 0x02014b50);
+            let name_length = u16_at(&data, at + 28) as usize;
+            names.push(String::from_utf8(data[at + 46..at + 46 + name_length].to_vec()).unwrap());
+            at += 46 + name_length;
+        }
+        assert_eq!(names, ["index.html", "posts/a.html"]);
+    }
+
+    #[test]
+    fn zip_entries_inflate_to_their_contents() {
+        let contents = b"<p>Hello</p>".repeat(100);
+        let mut zip = Zip::default();
+        zip.add("index.html", &contents).unwrap();
+        let data = zip.finish().unwrap();
+
+        assert_eq!(u32_at(&data, 0), 0x04034b50);
+        let mut crc = Crc::new();
+        crc.update(&contents);
+        assert_eq!(u32_at(&data, 14), crc.sum());
+        let compressed_size = u32_at(&data, 18) as usize;
+        assert_eq!(u32_at(&data, 22) as usize, contents.len());
+        let start = 30 + u16_at(&data, 26) as usize;
+
+        let mut inflated = Vec::new();
+        DeflateDecoder::new(&data[start..start + compressed_size])
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, contents);
+    }
+
+    #[test]
+    fn zip_refuses_names_too_long_to_record() {
+        let mut zip = Zip::default();
+        let name = "a".repeat(u16::MAX as usize + 1);
+        assert!(zip.add(&name, b"").is_err());
+        assert_eq!(zip.entries, 0);
+    }
+}
@@ -0,0 +1,306 @@
+use crate::{
+    atomic,
+    content::{Post, PostList},
+    error::{Error, Result},
+    html::{self, escape},
+    links, markdown,
+    site::Site,
+};
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use serde::Serialize;
+use std::fs;
+use tracing::debug;
+
+/// Maximum number of posts included in each feed
+const FEED_LENGTH: usize = 20;
+
+/// Length of the plain-text summary included for each entry
+const SUMMARY_LENGTH: usize = 280;
+
+/// Version URL every JSON Feed starts with
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// Top level of a JSON Feed document
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    home_page_url: String,
+    feed_url: String,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<String>,
+    summary: String,
+    date_published: String,
+    date_modified: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<JsonFeedAuthor>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<JsonFeedAttachment>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAuthor {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct JsonFeedAttachment {
+    url: String,
+    mime_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_in_bytes: Option<u64>,
+}
+
+/// The audio file attached to a post, as podcast apps expect to find it in a feed
+struct Enclosure {
+    url: String,
+    mime_type: &'static str,
+    /// Size in bytes, unknown for files hosted elsewhere
+    length: Option<u64>,
+}
+
+impl Enclosure {
+    /// The enclosure of `post`, or `None` if it has no audio attached
+    fn of(site: &Site, post: &Post) -> Option<Enclosure> {
+        let audio = &post.meta.audio;
+        if audio.is_empty() {
+            return None;
+        }
+        let length = match audio.contains("://") {
+            true => None,
+            false => fs::metadata(site.source_of(audio)).ok().map(|m| m.len()),
+        };
+        Some(Enclosure {
+            url: site.url_for(audio),
+            mime_type: audio_mime_type(audio),
+            length,
+        })
+    }
+}
+
+/// Regenerates `feed.xml` (RSS 2.0), `atom.xml` (Atom 1.0), and `feed.json` (JSON Feed 1.1) in
+/// the output directory
+pub fn write_feeds(site: &Site, posts: &PostList) -> Result<()> {
+    let posts = &posts[..posts.len().min(FEED_LENGTH)];
+    let output_dir = site.output_dir();
+
+    for (name, contents) in [
+        ("feed.xml", rss(site, posts)),
+        ("atom.xml", atom(site, posts)),
+        ("feed.json", json(site, posts)?),
+    ] {
+        let path = output_dir.join(name);
+        atomic::write(&path, contents).map_err(|e| Error::io("write", &path, e))?;
+        debug!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn rss(site: &Site, posts: &[Post]) -> String {
+    let config = &site.config;
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape(&site.name())));
+    xml.push_str(&format!("  <link>{}</link>\n", escape(&config.base_url)));
+    xml.push_str(&format!(
+        "  <description>{}</description>\n",
+        escape(&site.name())
+    ));
+    xml.push_str(&format!(
+        "  <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\" />\n",
+        escape(&site.url_for("feed.xml"))
+    ));
+    xml.push_str(&format!(
+        "  <lastBuildDate>{}</lastBuildDate>\n",
+        last_updated(posts).to_rfc2822()
+    ));
+
+    for post in posts {
+        let link = escape(&site.url_for(&site.config.post_url(post)));
+        xml.push_str("  <item>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape(&post.meta.title)
+        ));
+        xml.push_str(&format!("    <link>{}</link>\n", link));
+        xml.push_str(&format!("    <guid isPermaLink=\"true\">{}</guid>\n", link));
+        xml.push_str(&format!(
+            "    <pubDate>{}</pubDate>\n",
+            post.date().to_rfc2822()
+        ));
+        xml.push_str(&format!(
+            "    <description>{}</description>\n",
+            escape(&markdown::excerpt(&post.body, SUMMARY_LENGTH))
+        ));
+        if let Some(enclosure) = Enclosure::of(site, post) {
+            // RSS requires a length, and 0 is what feeds use when it isn't known
+            xml.push_str(&format!(
+                "    <enclosure url=\"{}\" length=\"{}\" type=\"{}\" />\n",
+                escape(&enclosure.url),
+                enclosure.length.unwrap_or(0),
+                enclosure.mime_type
+            ));
+        }
+        xml.push_str("  </item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn atom(site: &Site, posts: &[Post]) -> String {
+    let config = &site.config;
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape(&site.name())));
+    xml.push_str(&format!("  <id>{}</id>\n", escape(&config.base_url)));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" />\n",
+        escape(&config.base_url)
+    ));
+    xml.push_str(&format!(
+        "  <link href=\"{}\" rel=\"self\" />\n",
+        escape(&site.url_for("atom.xml"))
+    ));
+    xml.push_str(&format!(
+        "  <updated>{}</updated>\n",
+        last_updated(posts).to_rfc3339()
+    ));
+
+    for post in posts {
+        let link = escape(&site.url_for(&site.config.post_url(post)));
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape(&post.meta.title)
+        ));
+        xml.push_str(&format!("    <id>{}</id>\n", link));
+        xml.push_str(&format!("    <link href=\"{}\" />\n", link));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            post.date().to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            post.meta.updated.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            escape(&markdown::excerpt(&post.body, SUMMARY_LENGTH))
+        ));
+        if let Some(enclosure) = Enclosure::of(site, post) {
+            let length = enclosure
+                .length
+                .map(|length| format!(" length=\"{}\"", length))
+                .unwrap_or_default();
+            xml.push_str(&format!(
+                "    <link rel=\"enclosure\" href=\"{}\" type=\"{}\"{} />\n",
+                escape(&enclosure.url),
+                enclosure.mime_type,
+                length
+            ));
+        }
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Each item carries its rendered content if the site asks for full content, otherwise its
+/// summary as plain text
+fn json(site: &Site, posts: &[Post]) -> Result<String> {
+    let config = &site.config;
+    let items = posts
+        .iter()
+        .map(|post| {
+            let url = site.url_for(&site.config.post_url(post));
+            let summary = markdown::excerpt(&post.body, SUMMARY_LENGTH);
+            // Rendered as for the post's page, with links that work from a feed reader and
+            // without the scripts and frames shortcodes bring, which readers won't run
+            let content_html = config.feed_full_content.then(|| {
+                let page = config.post_path(post);
+                let content = html::sanitize(&site.render_content(post, posts));
+                links::absolute_links(site, &page, &content)
+            });
+            JsonFeedItem {
+                id: url.clone(),
+                url,
+                title: post.meta.title.clone(),
+                content_text: content_html.is_none().then(|| summary.clone()),
+                content_html,
+                summary,
+                date_published: post.date().to_rfc3339_opts(SecondsFormat::Secs, false),
+                date_modified: post
+                    .meta
+                    .updated
+                    .to_rfc3339_opts(SecondsFormat::Secs, false),
+                authors: match post.meta.author.is_empty() {
+                    true => Vec::new(),
+                    false => vec![JsonFeedAuthor {
+                        name: post.meta.author.clone(),
+                    }],
+                },
+                tags: post.meta.tags.clone(),
+                attachments: Enclosure::of(site, post)
+                    .map(|enclosure| JsonFeedAttachment {
+                        url: enclosure.url,
+                        mime_type: enclosure.mime_type,
+                        size_in_bytes: enclosure.length,
+                    })
+                    .into_iter()
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let feed = JsonFeed {
+        version: JSON_FEED_VERSION,
+        title: site.name(),
+        home_page_url: config.base_url.clone(),
+        feed_url: site.url_for("feed.json"),
+        items,
+    };
+    serde_json::to_string_pretty(&feed)
+        .map_err(|e| Error::Invalid(format!("Failed to serialize feed.json: {}", e)))
+}
+
+/// MIME type of the audio file at `path`, guessed from its extension
+fn audio_mime_type(path: &str) -> &'static str {
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "m4a" | "mp4" => "audio/mp4",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "aac" => "audio/aac",
+        "webm" => "audio/webm",
+        _ => "audio/mpeg",
+    }
+}
+
+fn last_updated(posts: &[Post]) -> DateTime<FixedOffset> {
+    posts
+        .iter()
+        .map(|post| post.meta.updated)
+        .max()
+        .unwrap_or_else(|| Local::now().fixed_offset())
+}
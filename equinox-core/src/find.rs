@@ -0,0 +1,72 @@
+//! Finding and replacing text in the content editor
+
+/// Byte ranges of every non-overlapping occurrence of `query` in `text`, in order. Without
+/// `case_sensitive`, letters match regardless of case.
+pub fn matches(text: &str, query: &str, case_sensitive: bool) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+    if query.is_empty() {
+        return found;
+    }
+
+    let mut from = 0;
+    while from < text.len() {
+        let Some((start, end)) = find_from(text, from, query, case_sensitive) else {
+            break;
+        };
+        found.push((start, end));
+        from = end;
+    }
+    found
+}
+
+/// Replaces every occurrence of `query` in `text` with `replacement`, returning the new text
+/// and the number of replacements
+pub fn replace_all(
+    text: &str,
+    query: &str,
+    replacement: &str,
+    case_sensitive: bool,
+) -> (String, usize) {
+    let found = matches(text, query, case_sensitive);
+    let mut replaced = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, end) in &found {
+        replaced.push_str(&text[last..*start]);
+        replaced.push_str(replacement);
+        last = *end;
+    }
+    replaced.push_str(&text[last..]);
+    (replaced, found.len())
+}
+
+/// First occurrence of `query` starting at or after byte `from`
+fn find_from(text: &str, from: usize, query: &str, case_sensitive: bool) -> Option<(usize, usize)> {
+    if case_sensitive {
+        return text[from..]
+            .find(query)
+            .map(|i| (from + i, from + i + query.len()));
+    }
+
+    // Compare character by character so offsets stay valid when case changes a character's
+    // length in bytes
+    let starts = text[from..].char_indices().map(|(i, _)| from + i);
+    for start in starts {
+        let mut candidate = text[start..].char_indices();
+        let mut end = start;
+        let matched = query.chars().all(|q| match candidate.next() {
+            Some((i, c)) if same_letter(c, q) => {
+                end = start + i + c.len_utf8();
+                true
+            }
+            _ => false,
+        });
+        if matched {
+            return Some((start, end));
+        }
+    }
+    None
+}
+
+fn same_letter(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
@@ -0,0 +1,225 @@
+//! Copies of stylesheets, scripts, images, and fonts named after a hash of their contents, linked
+//! from every page in place of the originals. A changed file gets a new name, so hosts can tell
+//! browsers to cache them forever.
+
+use crate::{
+    atomic, cache,
+    error::{Error, Result},
+    links::resolve,
+    site::Site,
+};
+use std::{collections::BTreeMap, fs, path::Path};
+use tracing::{debug, info};
+
+/// Directories of the output directory whose files are fingerprinted
+const DIRS: &[&str] = &["assets", "theme", "resized", "css"];
+
+/// Extensions of the files fingerprinted
+const EXTENSIONS: &[&str] = &[
+    "css", "js", "png", "jpg", "jpeg", "gif", "webp", "avif", "svg", "ico", "woff", "woff2",
+];
+
+/// Hex digits of the contents hash put in fingerprinted names
+const HASH_LENGTH: usize = 10;
+
+/// The original name of a fingerprinted `name`, e.g. `style.css` for `style.0123456789.css`
+pub fn original_name(name: &str) -> Option<String> {
+    let (rest, extension) = name.rsplit_once('.')?;
+    let (stem, hash) = rest.rsplit_once('.')?;
+    let is_hash = hash.len() == HASH_LENGTH
+        && hash
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+    (is_hash && !stem.is_empty()).then(|| format!("{}.{}", stem, extension))
+}
+
+/// Whether the file at `path` is a fingerprinted copy, which never changes once written
+pub fn is_fingerprinted(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| original_name(&name.to_string_lossy()))
+        .is_some_and(|original| path.with_file_name(original).is_file())
+}
+
+/// Writes a fingerprinted copy of every asset in the output directory and points every page's
+/// links at them, meant to be called once everything else has been written. Pages skipped by an
+/// incremental build are fixed up too, and copies of assets that have since changed are removed.
+/// All copies are removed again when the site turns fingerprinting off.
+pub fn write_fingerprints(site: &Site) -> Result<()> {
+    let output_dir = site.output_dir();
+    let mut names = BTreeMap::new();
+    if site.config.fingerprint_assets {
+        for dir in DIRS {
+            collect_assets(&output_dir, Path::new(dir), &mut names)?;
+        }
+    }
+    for dir in DIRS {
+        remove_stale(&output_dir.join(dir), &output_dir, &names)?;
+    }
+
+    let mut rewritten = 0;
+    for file in site.output_files()? {
+        if file.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let path = output_dir.join(&file);
+        let html = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        let page = file.to_string_lossy().replace('\\', "/");
+        let relinked = relink(site, &page, &html, &names);
+        if relinked != html {
+            atomic::write(&path, relinked).map_err(|e| Error::io("write", &path, e))?;
+            rewritten += 1;
+        }
+    }
+    if rewritten > 0 {
+        info!("Linked {} pages to fingerprinted assets", rewritten);
+    }
+    Ok(())
+}
+
+/// Adds the fingerprinted name of every asset under `dir` of `output_dir` to `names`, by the
+/// asset's path, writing the copies that don't exist yet
+fn collect_assets(
+    output_dir: &Path,
+    dir: &Path,
+    names: &mut BTreeMap<String, String>,
+) -> Result<()> {
+    let full = output_dir.join(dir);
+    let Ok(entries) = fs::read_dir(&full) else {
+        return Ok(());
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if path.is_dir() {
+            collect_assets(output_dir, &dir.join(&name), names)?;
+            continue;
+        }
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if !EXTENSIONS.contains(&extension.as_str()) || is_fingerprinted(&path) {
+            continue;
+        }
+        let contents = fs::read(&path).map_err(|e| Error::io("read", &path, e))?;
+        let hash = cache::hash([contents.as_slice()]);
+        let (stem, extension) = name.rsplit_once('.').unwrap_or((&name, ""));
+        let copy = path.with_file_name(format!("{}.{}.{}", stem, &hash[..HASH_LENGTH], extension));
+        if !copy.is_file() {
+            atomic::write(&copy, &contents).map_err(|e| Error::io("write", &copy, e))?;
+            debug!("Fingerprinted {} as {}", path.display(), copy.display());
+        }
+        let relative = |path: &Path| {
+            path.strip_prefix(output_dir)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        };
+        names.insert(relative(&path), relative(&copy));
+    }
+    Ok(())
+}
+
+/// Removes the fingerprinted copies under `dir` that aren't among the current `names`
+fn remove_stale(dir: &Path, output_dir: &Path, names: &BTreeMap<String, String>) -> Result<()> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            remove_stale(&path, output_dir, names)?;
+            continue;
+        }
+        let relative = path
+            .strip_prefix(output_dir)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if is_fingerprinted(&path) && !names.values().any(|name| *name == relative) {
+            fs::remove_file(&path).map_err(|e| Error::io("remove", &path, e))?;
+            debug!("Removed stale copy {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// `html` of the page at `page` with every `href`, `src`, and `srcset` leading to an asset, or
+/// to an older copy of one, pointed at its current fingerprinted copy, or back at the asset
+/// itself if it has none
+fn relink(site: &Site, page: &str, html: &str, names: &BTreeMap<String, String>) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some((start, end, is_srcset)) = next_value(rest) {
+        output.push_str(&rest[..start]);
+        let value = &rest[start..end];
+        if is_srcset {
+            let candidates: Vec<String> = value
+                .split(',')
+                .map(|candidate| {
+                    let candidate = candidate.trim();
+                    let (url, width) = candidate.split_once(' ').unwrap_or((candidate, ""));
+                    let url = relink_url(site, page, url, names);
+                    match width {
+                        "" => url,
+                        width => format!("{} {}", url, width),
+                    }
+                })
+                .collect();
+            output.push_str(&candidates.join(", "));
+        } else {
+            output.push_str(&relink_url(site, page, value, names));
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Where the next `href`, `src`, or `srcset` value in `html` starts and ends, and whether it is
+/// a `srcset`
+fn next_value(html: &str) -> Option<(usize, usize, bool)> {
+    let (start, is_srcset) = [
+        (" href=\"", false),
+        (" src=\"", false),
+        (" srcset=\"", true),
+    ]
+    .iter()
+    .filter_map(|(attribute, is_srcset)| {
+        html.find(attribute)
+            .map(|i| (i + attribute.len(), *is_srcset))
+    })
+    .min()?;
+    let end = html[start..].find('"').map_or(html.len(), |i| start + i);
+    Some((start, end, is_srcset))
+}
+
+/// `url`, linked from the page at `page`, with its file name swapped for that of the current
+/// copy of the asset it leads to
+fn relink_url(site: &Site, page: &str, url: &str, names: &BTreeMap<String, String>) -> String {
+    let (path, suffix) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+    let Some(target) = resolve(site, page, path) else {
+        return url.to_string();
+    };
+    let (dir, name) = target.rsplit_once('/').unwrap_or(("", &target));
+    let original = match original_name(name) {
+        Some(original) if dir.is_empty() => original,
+        Some(original) => format!("{}/{}", dir, original),
+        None => target.clone(),
+    };
+    if !DIRS
+        .iter()
+        .any(|d| original.starts_with(&format!("{}/", d)))
+    {
+        return url.to_string();
+    }
+    let current = names.get(&original).unwrap_or(&original);
+    let file_name = current
+        .rsplit_once('/')
+        .map_or(current.as_str(), |(_, name)| name);
+    match path.rsplit_once('/') {
+        Some((prefix, _)) => format!("{}/{}{}", prefix, file_name, suffix),
+        None => format!("{}{}", file_name, suffix),
+    }
+}
@@ -0,0 +1,121 @@
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use std::collections::HashMap;
+
+/// Numbers the footnote references in `events` in the order they are first made and moves the
+/// footnotes they refer to into a list at the end, each linking back to where it was first
+/// referenced. Footnotes that are never referred to are dropped.
+pub fn move_to_end(events: Vec<Event<'_>>) -> Vec<Event<'_>> {
+    let mut body = Vec::with_capacity(events.len());
+    let mut definitions: HashMap<String, Vec<Event>> = HashMap::new();
+    let mut definition: Option<(String, Vec<Event>)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                definition = Some((label.to_lowercase(), Vec::new()));
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                if let Some((label, events)) = definition.take() {
+                    definitions.entry(label).or_insert(events);
+                }
+            }
+            event => match &mut definition {
+                Some((_, events)) => events.push(event),
+                None => body.push(event),
+            },
+        }
+    }
+
+    let mut references = References::default();
+    let mut body = references.number(body, &definitions);
+    // Footnotes can refer to further footnotes, which are numbered as they are reached
+    let mut notes = Vec::new();
+    while let Some(label) = references.order.get(notes.len()).cloned() {
+        let events = definitions.remove(&label).unwrap_or_default();
+        notes.push(references.number(events, &definitions));
+    }
+    if notes.is_empty() {
+        return body;
+    }
+
+    body.push(Event::Html(
+        "<div class=\"footnotes\">\n<hr />\n<ol>\n".into(),
+    ));
+    for (i, mut events) in notes.into_iter().enumerate() {
+        let n = i + 1;
+        let backref = Event::InlineHtml(
+            format!(
+                " <a href=\"#fnref-{}\" class=\"footnote-backref\">&#8617;</a>",
+                n
+            )
+            .into(),
+        );
+        // Inside the last paragraph, so the link doesn't sit on a line of its own
+        match events
+            .iter()
+            .rposition(|event| matches!(event, Event::End(TagEnd::Paragraph)))
+        {
+            Some(end) => events.insert(end, backref),
+            None => events.push(backref),
+        }
+        body.push(Event::Html(format!("<li id=\"fn-{}\">\n", n).into()));
+        body.extend(events);
+        body.push(Event::Html("</li>\n".into()));
+    }
+    body.push(Event::Html("</ol>\n</div>\n".into()));
+    body
+}
+
+/// Footnote labels in the order they were first referred to, and how often each was
+#[derive(Default)]
+struct References {
+    order: Vec<String>,
+    counts: HashMap<String, usize>,
+}
+
+impl References {
+    /// Replaces each footnote reference in `events` with a link to its numbered footnote, or
+    /// with the text as written if `definitions` has no such footnote
+    fn number<'a>(
+        &mut self,
+        events: Vec<Event<'a>>,
+        definitions: &HashMap<String, Vec<Event>>,
+    ) -> Vec<Event<'a>> {
+        events
+            .into_iter()
+            .map(|event| match event {
+                Event::FootnoteReference(label) => self.reference(&label, definitions),
+                event => event,
+            })
+            .collect()
+    }
+
+    fn reference<'a>(
+        &mut self,
+        label: &str,
+        definitions: &HashMap<String, Vec<Event>>,
+    ) -> Event<'a> {
+        let key = label.to_lowercase();
+        let n = match self.order.iter().position(|seen| *seen == key) {
+            Some(i) => i + 1,
+            None if definitions.contains_key(&key) => {
+                self.order.push(key.clone());
+                self.order.len()
+            }
+            None => return Event::Text(CowStr::from(format!("[^{}]", label))),
+        };
+        let count = self.counts.entry(key).or_insert(0);
+        *count += 1;
+        // Only the first reference is linked back to, but every one needs a distinct id
+        let id = match *count {
+            1 => format!("fnref-{}", n),
+            count => format!("fnref-{}-{}", n, count),
+        };
+        Event::InlineHtml(
+            format!(
+                "<sup class=\"footnote-ref\" id=\"{}\"><a href=\"#fn-{}\">{}</a></sup>",
+                id, n, n
+            )
+            .into(),
+        )
+    }
+}
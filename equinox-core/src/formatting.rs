@@ -0,0 +1,202 @@
+//! Markdown formatting commands for the editor's toolbar and shortcuts
+
+/// A formatting command applied to the selection in the content editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Bold,
+    Italic,
+    /// Inline code, or a fenced block if the selection spans several lines
+    Code,
+    Link,
+    /// Toggles a `## ` heading on the selected lines
+    Heading,
+    /// Toggles a `- ` bullet on each selected line
+    List,
+}
+
+impl Format {
+    /// Parses the command names used by the editor: `bold`, `italic`, `code`, `link`,
+    /// `heading`, and `list`
+    pub fn from_name(name: &str) -> Option<Format> {
+        Some(match name {
+            "bold" => Format::Bold,
+            "italic" => Format::Italic,
+            "code" => Format::Code,
+            "link" => Format::Link,
+            "heading" => Format::Heading,
+            "list" => Format::List,
+            _ => return None,
+        })
+    }
+}
+
+/// Text after a formatting command, with the byte range to select afterwards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Formatted {
+    pub text: String,
+    pub selection_start: usize,
+    pub selection_end: usize,
+}
+
+/// Applies `format` to the bytes `start..end` of `text` (in either order). Wrapping formats
+/// are removed again if the selection is already wrapped; with nothing selected, the markers
+/// are inserted with the cursor between them.
+pub fn apply(text: &str, start: usize, end: usize, format: Format) -> Formatted {
+    let (start, end) = (start.min(end), start.max(end));
+    let (start, end) = (floor_boundary(text, start), floor_boundary(text, end));
+    match format {
+        Format::Bold => wrap(text, start, end, "**", "**"),
+        Format::Italic => wrap(text, start, end, "*", "*"),
+        Format::Code if text[start..end].contains('\n') => {
+            let before = if start == 0 || text[..start].ends_with('\n') {
+                "```\n"
+            } else {
+                "\n```\n"
+            };
+            let after = if text[start..end].ends_with('\n') {
+                "```\n"
+            } else {
+                "\n```\n"
+            };
+            wrap(text, start, end, before, after)
+        }
+        Format::Code => wrap(text, start, end, "`", "`"),
+        Format::Link => link(text, start, end),
+        Format::Heading => prefix_lines(text, start, end, "## ", |line| {
+            let rest = line.trim_start_matches('#');
+            (rest.len() < line.len())
+                .then(|| rest.strip_prefix(' '))
+                .flatten()
+                .map(|rest| line.len() - rest.len())
+        }),
+        Format::List => prefix_lines(text, start, end, "- ", |line| {
+            ["- ", "* ", "+ "]
+                .iter()
+                .any(|bullet| line.starts_with(bullet))
+                .then_some(2)
+        }),
+    }
+}
+
+/// Surrounds `start..end` with `before` and `after`, or removes them if they already surround it
+fn wrap(text: &str, start: usize, end: usize, before: &str, after: &str) -> Formatted {
+    let selected = &text[start..end];
+    if text[..start].ends_with(before)
+        && text[end..].starts_with(after)
+        && !is_other_emphasis(&text[..start], before)
+    {
+        let outer_start = start - before.len();
+        return Formatted {
+            text: format!(
+                "{}{}{}",
+                &text[..outer_start],
+                selected,
+                &text[end + after.len()..]
+            ),
+            selection_start: outer_start,
+            selection_end: outer_start + selected.len(),
+        };
+    }
+    if selected.len() >= before.len() + after.len()
+        && selected.starts_with(before)
+        && selected.ends_with(after)
+        && !is_other_emphasis(
+            &selected[..selected.len() - selected.trim_start_matches('*').len()],
+            before,
+        )
+    {
+        let inner = &selected[before.len()..selected.len() - after.len()];
+        return Formatted {
+            text: format!("{}{}{}", &text[..start], inner, &text[end..]),
+            selection_start: start,
+            selection_end: start + inner.len(),
+        };
+    }
+
+    Formatted {
+        text: format!(
+            "{}{}{}{}{}",
+            &text[..start],
+            before,
+            selected,
+            after,
+            &text[end..]
+        ),
+        selection_start: start + before.len(),
+        selection_end: start + before.len() + selected.len(),
+    }
+}
+
+/// Whether a run of asterisks ending `before_text` is bold rather than italic, so an italic
+/// marker shouldn't be removed from it: an even number of asterisks is only bold.
+fn is_other_emphasis(before_text: &str, marker: &str) -> bool {
+    let run = before_text.len() - before_text.trim_end_matches('*').len();
+    marker == "*" && run.is_multiple_of(2)
+}
+
+/// Turns the selection into a link's text and selects a placeholder URL to type over
+fn link(text: &str, start: usize, end: usize) -> Formatted {
+    let selected = &text[start..end];
+    let label = if selected.is_empty() {
+        "text"
+    } else {
+        selected
+    };
+    let url_start = start + label.len() + 3;
+    Formatted {
+        text: format!("{}[{}](url){}", &text[..start], label, &text[end..]),
+        selection_start: url_start,
+        selection_end: url_start + "url".len(),
+    }
+}
+
+/// Adds `prefix` to every line touched by `start..end`, or if `existing` finds a marker of
+/// that kind on every one of them, removes those markers instead. `existing` returns the length
+/// of the marker at the start of a line.
+fn prefix_lines(
+    text: &str,
+    start: usize,
+    end: usize,
+    prefix: &str,
+    existing: impl Fn(&str) -> Option<usize>,
+) -> Formatted {
+    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    // A selection ending just after a newline doesn't include the next line
+    let last = if end > start && text[..end].ends_with('\n') {
+        end - 1
+    } else {
+        end
+    };
+    let line_end = text[last..].find('\n').map_or(text.len(), |i| last + i);
+
+    let lines: Vec<&str> = text[line_start..line_end].split('\n').collect();
+    let remove = lines.iter().all(|line| existing(line).is_some());
+    let replaced: Vec<String> = lines
+        .iter()
+        .map(|line| match remove {
+            true => line[existing(line).unwrap_or(0)..].to_string(),
+            false if line.is_empty() && lines.len() > 1 => String::new(),
+            false => format!("{}{}", prefix, line),
+        })
+        .collect();
+    let replaced = replaced.join("\n");
+
+    // With nothing selected, the cursor moves to the end of the line instead
+    let selection_start = match start == end {
+        true => line_start + replaced.len(),
+        false => line_start,
+    };
+    Formatted {
+        text: format!("{}{}{}", &text[..line_start], replaced, &text[line_end..]),
+        selection_start,
+        selection_end: line_start + replaced.len(),
+    }
+}
+
+fn floor_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
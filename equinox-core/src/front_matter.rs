@@ -0,0 +1,249 @@
+use crate::{
+    content::PostMeta,
+    error::{Error, Result},
+};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// Delimits a TOML block at the top of a post, both in the editor and in stored sources
+pub const FENCE: &str = "+++";
+
+/// Splits `source` into its leading `+++` delimited block and the remaining body. Returns
+/// `None` if the source does not start with a block.
+pub fn split(source: &str) -> Option<(&str, &str)> {
+    let rest = source.trim_start().strip_prefix(FENCE)?;
+    let rest = rest.strip_prefix('\r').unwrap_or(rest).strip_prefix('\n')?;
+    let end = rest.find(&format!("\n{}", FENCE))?;
+    let block = &rest[..end];
+    let body = &rest[end + 1 + FENCE.len()..];
+    Some((block, body.trim_start_matches(['\r', '\n'])))
+}
+
+/// Metadata written by hand at the top of the content box, e.g.
+///
+/// ```toml
+/// +++
+/// date = 2024-05-01
+/// image = "assets/images/cover.png"
+/// audio = "assets/audio/episode-1.mp3"
+/// canonical_url = "https://dev.to/james/original-post"
+/// series = "Writing a static site generator"
+/// draft = true
+/// toc = true
+/// noindex = true
+/// no_crosspost = true
+/// no_author_card = true
+/// +++
+/// ```
+///
+/// Every field is optional; fields that are present override the editor's own fields.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Publish date, either a TOML date/datetime or an RFC 3339 string
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<toml::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub series: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub draft: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toc: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub noindex: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_crosspost: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_author_card: Option<bool>,
+}
+
+impl FrontMatter {
+    /// Parses the front matter at the top of `content`, returning it along with the rest of the
+    /// content. Content without front matter is returned unchanged.
+    pub fn parse(content: &str) -> Result<(Option<FrontMatter>, &str)> {
+        match split(content) {
+            Some((block, body)) => {
+                let front_matter =
+                    toml::from_str(block).map_err(|e| Error::parse("front matter", None, e))?;
+                Ok((Some(front_matter), body))
+            }
+            None => Ok((None, content)),
+        }
+    }
+
+    /// Front matter for the fields of `meta` that have no dedicated editor field, so they can
+    /// be shown and edited at the top of the content box
+    pub fn from_meta(meta: &PostMeta) -> FrontMatter {
+        let non_empty = |s: &String| (!s.is_empty()).then(|| s.clone());
+        FrontMatter {
+            slug: non_empty(&meta.slug),
+            image: non_empty(&meta.image),
+            audio: non_empty(&meta.audio),
+            canonical_url: non_empty(&meta.canonical_url),
+            series: non_empty(&meta.series),
+            draft: meta.draft.then_some(true),
+            toc: meta.toc.then_some(true),
+            noindex: meta.noindex.then_some(true),
+            no_crosspost: meta.no_crosspost.then_some(true),
+            no_author_card: meta.no_author_card.then_some(true),
+            ..FrontMatter::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.date.is_none()
+            && self.author.is_none()
+            && self.slug.is_none()
+            && self.description.is_none()
+            && self.image.is_none()
+            && self.audio.is_none()
+            && self.canonical_url.is_none()
+            && self.category.is_none()
+            && self.tags.is_none()
+            && self.series.is_none()
+            && self.draft.is_none()
+            && self.toc.is_none()
+            && self.noindex.is_none()
+            && self.no_crosspost.is_none()
+            && self.no_author_card.is_none()
+    }
+
+    /// Overrides fields of `meta` with those present in the front matter. Fields without a
+    /// dedicated editor field only come from the front matter, so removing them from the block
+    /// clears them.
+    pub fn apply(self, meta: &mut PostMeta) -> Result<()> {
+        meta.slug.clear();
+        meta.image.clear();
+        meta.audio.clear();
+        meta.canonical_url.clear();
+        meta.series.clear();
+        meta.draft = false;
+        meta.toc = false;
+        meta.noindex = false;
+        meta.no_crosspost = false;
+        meta.no_author_card = false;
+
+        if let Some(date) = self.date {
+            let text = match date {
+                toml::Value::String(s) => s,
+                toml::Value::Datetime(d) => d.to_string(),
+                other => {
+                    return Err(Error::Invalid(format!(
+                        "Invalid date in front matter: {}",
+                        other
+                    )))
+                }
+            };
+            meta.date = Some(
+                parse_date(&text)
+                    .ok_or_else(|| Error::Invalid(format!("Invalid date: {}", text)))?,
+            );
+        }
+        if let Some(title) = self.title {
+            meta.title = title;
+        }
+        if let Some(author) = self.author {
+            meta.author = author;
+        }
+        if let Some(slug) = self.slug {
+            meta.slug = slug;
+        }
+        if let Some(description) = self.description {
+            meta.description = description;
+        }
+        if let Some(image) = self.image {
+            meta.image = image;
+        }
+        if let Some(audio) = self.audio {
+            meta.audio = audio;
+        }
+        if let Some(canonical_url) = self.canonical_url {
+            meta.canonical_url = canonical_url;
+        }
+        if let Some(category) = self.category {
+            meta.category = category;
+        }
+        if let Some(tags) = self.tags {
+            meta.tags = tags;
+        }
+        if let Some(series) = self.series {
+            meta.series = series;
+        }
+        if let Some(draft) = self.draft {
+            meta.draft = draft;
+        }
+        if let Some(toc) = self.toc {
+            meta.toc = toc;
+        }
+        if let Some(noindex) = self.noindex {
+            meta.noindex = noindex;
+        }
+        if let Some(no_crosspost) = self.no_crosspost {
+            meta.no_crosspost = no_crosspost;
+        }
+        if let Some(no_author_card) = self.no_author_card {
+            meta.no_author_card = no_author_card;
+        }
+        Ok(())
+    }
+
+    /// Formats the front matter as a `+++` block, or an empty string if it has no fields
+    pub fn to_block(&self) -> Result<String> {
+        if self.is_empty() {
+            return Ok(String::new());
+        }
+        let toml = toml::to_string(self)
+            .map_err(|e| Error::Invalid(format!("Failed to serialize front matter: {}", e)))?;
+        Ok(format!(
+            "{fence}\n{toml}{fence}\n\n",
+            fence = FENCE,
+            toml = toml
+        ))
+    }
+}
+
+/// Parses an RFC 3339 datetime, a local `YYYY-MM-DD HH:MM[:SS]` datetime, or a local date
+pub fn parse_date(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+        return Some(date);
+    }
+
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|date| date.fixed_offset())
+}
@@ -0,0 +1,43 @@
+use crate::html::escape;
+use std::sync::LazyLock;
+use syntect::{
+    html::{ClassStyle, ClassedHTMLGenerator},
+    parsing::SyntaxSet,
+    util::LinesWithEndings,
+};
+
+/// Grammars for every language syntect ships with
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+/// Prefixes the scope classes so they can't collide with the site's own, matching the
+/// `highlight.css` bundled with each theme
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+/// The language named by a fence's info string, e.g. `rust` in `rust,ignore`
+pub fn language(info: &str) -> &str {
+    info.split(|c: char| c.is_whitespace() || c == ',')
+        .next()
+        .unwrap_or_default()
+}
+
+/// Renders a fenced code block, with each token wrapped in a `<span>` classed by its scope.
+/// Languages syntect doesn't know are rendered as plain code.
+pub fn code_block(code: &str, language: &str) -> String {
+    let highlighted = highlight(code, language).unwrap_or_else(|| escape(code));
+    format!(
+        "<pre class=\"hl-code\"><code class=\"language-{}\">{}</code></pre>\n",
+        escape(language),
+        highlighted
+    )
+}
+
+fn highlight(code: &str, language: &str) -> Option<String> {
+    let syntax = SYNTAXES.find_syntax_by_token(language)?;
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+    Some(generator.finalize())
+}
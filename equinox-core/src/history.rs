@@ -0,0 +1,111 @@
+//! Undo and redo for the content editor
+
+use std::time::{Duration, Instant};
+
+/// Edits closer together than this are undone as one
+const BURST: Duration = Duration::from_millis(1000);
+
+/// Number of undo steps kept
+const MAX_STEPS: usize = 200;
+
+/// The editor's text and cursor at one point in its history
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub text: String,
+    /// Byte offset of the cursor
+    pub cursor: usize,
+}
+
+/// Undo and redo stacks for one document. Typing is grouped into bursts separated by pauses,
+/// while programmatic changes such as formatting or inserting an image are always steps of
+/// their own.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    current: Snapshot,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// When the current burst of typing last changed, if one is in progress
+    burst: Option<Instant>,
+}
+
+impl History {
+    pub fn new(text: &str) -> History {
+        History {
+            current: Snapshot {
+                text: text.to_string(),
+                cursor: 0,
+            },
+            undo: Vec::new(),
+            redo: Vec::new(),
+            burst: None,
+        }
+    }
+
+    /// Forgets every step, e.g. when another post is opened
+    pub fn reset(&mut self, text: &str) {
+        *self = History::new(text);
+    }
+
+    /// Records typing that left the editor with `text`, merging it into the current burst if
+    /// the last change was recent
+    pub fn record(&mut self, text: &str, cursor: usize) {
+        if text == self.current.text {
+            return;
+        }
+        let now = Instant::now();
+        let in_burst = self
+            .burst
+            .is_some_and(|last| now.duration_since(last) < BURST);
+        self.change(text, cursor, !in_burst);
+        self.burst = Some(now);
+    }
+
+    /// Records a change made by the editor itself as a separate step
+    pub fn checkpoint(&mut self, text: &str, cursor: usize) {
+        if text == self.current.text {
+            return;
+        }
+        self.change(text, cursor, true);
+        self.burst = None;
+    }
+
+    /// Steps back, returning the state to show
+    pub fn undo(&mut self) -> Option<&Snapshot> {
+        let previous = self.undo.pop()?;
+        self.redo
+            .push(std::mem::replace(&mut self.current, previous));
+        self.burst = None;
+        Some(&self.current)
+    }
+
+    /// Steps forward again after an undo, returning the state to show
+    pub fn redo(&mut self) -> Option<&Snapshot> {
+        let next = self.redo.pop()?;
+        self.undo.push(std::mem::replace(&mut self.current, next));
+        self.burst = None;
+        Some(&self.current)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    fn change(&mut self, text: &str, cursor: usize, new_step: bool) {
+        let snapshot = Snapshot {
+            text: text.to_string(),
+            cursor,
+        };
+        let previous = std::mem::replace(&mut self.current, snapshot);
+        if new_step {
+            self.undo.push(previous);
+            if self.undo.len() > MAX_STEPS {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::LazyLock;
+
+/// Allowlist applied to rendered post content
+static SANITIZER: LazyLock<ammonia::Builder<'static>> = LazyLock::new(|| {
+    let mut builder = ammonia::Builder::default();
+    // Keep `language-*` classes on fenced code blocks and the syntax highlighting classes
+    builder.add_tag_attributes("code", &["class"]);
+    builder.add_tag_attributes("pre", &["class"]);
+    builder.add_tag_attributes("span", &["class"]);
+    // Keep the anchor ids given to headings for the table of contents
+    for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+        builder.add_tag_attributes(heading, &["id"]);
+    }
+    // Keep the numbered footnotes and the links between them and their references
+    builder.add_tag_attributes("sup", &["class", "id"]);
+    builder.add_tag_attributes("li", &["id"]);
+    builder.add_tag_attributes("a", &["class"]);
+    builder.add_tag_attributes("div", &["class"]);
+    builder
+});
+
+/// Escapes text for use in HTML or XML element content and attribute values
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The first `<div>` with the class `class`, tags included, matching nested divs to find where
+/// it ends
+pub fn div_with_class<'a>(html: &'a str, class: &str) -> Option<&'a str> {
+    let start = html.find(&format!("<div class=\"{}\"", class))?;
+    let mut depth = 0;
+    let mut pos = start;
+    while let Some(offset) = html[pos..]
+        .find("<div")
+        .into_iter()
+        .chain(html[pos..].find("</div>"))
+        .min()
+    {
+        pos += offset;
+        if html[pos..].starts_with("</div>") {
+            depth -= 1;
+            pos += "</div>".len();
+            if depth == 0 {
+                return Some(&html[start..pos]);
+            }
+        } else {
+            depth += 1;
+            pos += "<div".len();
+        }
+    }
+    None
+}
+
+/// The value of the attribute `name` of `tag`, the text between a tag's `<` and `>`, which may
+/// be quoted either way or not at all
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(i) = rest.find('=') {
+        let key = rest[..i]
+            .rsplit(|c: char| c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+        let after = rest[i + 1..].trim_start();
+        let (value, remaining) = match after.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value = &after[1..];
+                let end = value.find(quote).unwrap_or(value.len());
+                (&value[..end], &value[(end + 1).min(value.len())..])
+            }
+            _ => {
+                let end = after
+                    .find(|c: char| c.is_whitespace() || c == '/')
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        if key.eq_ignore_ascii_case(name) {
+            return Some(value.replace("&amp;", "&"));
+        }
+        rest = remaining;
+    }
+    None
+}
+
+/// The tags of `html`, each the text between its `<` and `>`, leaving out comments, doctypes,
+/// and anything that only looks like a tag inside a script or stylesheet
+pub fn tags(html: &str) -> Tags<'_> {
+    Tags { rest: html }
+}
+
+/// Iterator over the tags of a page, see [`tags`]
+pub struct Tags<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tags<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            let start = self.rest.find('<')?;
+            let rest = &self.rest[start + 1..];
+            if let Some(comment) = rest.strip_prefix("!--") {
+                self.rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+                continue;
+            }
+            let end = tag_end(rest);
+            let tag = &rest[..end];
+            self.rest = &rest[(end + 1).min(rest.len())..];
+            if tag.starts_with(['!', '?']) {
+                continue;
+            }
+            let name = tag_name(tag);
+            if (name == "script" || name == "style") && !tag.starts_with('/') {
+                let close = format!("</{}", name);
+                let body_end = self
+                    .rest
+                    .to_ascii_lowercase()
+                    .find(&close)
+                    .unwrap_or(self.rest.len());
+                self.rest = &self.rest[body_end..];
+            }
+            return Some(tag);
+        }
+    }
+}
+
+/// The lowercased name of the element `tag` starts or ends
+pub fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('/')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// `html` with a `style` attribute given to each element `styles` names, as `(element, style)`
+/// pairs, for mail clients that ignore stylesheets. Elements with a style of their own keep it.
+pub fn inline_styles(html: &str, styles: &[(&str, &str)]) -> String {
+    let mut styled = String::with_capacity(html.len() * 2);
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        styled.push_str(&rest[..=start]);
+        rest = &rest[start + 1..];
+        let end = tag_end(rest);
+        let tag = &rest[..end];
+        let name = tag_name(tag);
+        let style = styles.iter().find(|(element, _)| *element == name);
+        match style {
+            Some((_, style)) if !tag.starts_with('/') && attribute(tag, "style").is_none() => {
+                let (tag, closing) = match tag.strip_suffix('/') {
+                    Some(tag) => (tag.trim_end(), " /"),
+                    None => (tag, ""),
+                };
+                styled.push_str(&format!("{} style=\"{}\"{}", tag, escape(style), closing));
+            }
+            _ => styled.push_str(tag),
+        }
+        rest = &rest[end..];
+    }
+    styled.push_str(rest);
+    styled
+}
+
+/// Where the tag at the start of `html`, just after its `<`, ends, skipping any `>` in quoted
+/// attribute values
+fn tag_end(html: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in html.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return i,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+/// Elements whose contents are kept exactly as written when minifying
+const PRESERVED_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Shrinks a page without changing how it displays: comments are removed and runs of whitespace
+/// collapse to one space, except inside `<pre>`, `<textarea>`, `<script>`, and `<style>`
+pub fn minify(html: &str) -> String {
+    let mut minified = String::with_capacity(html.len());
+    let mut rest = html.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("<!--") {
+            rest = rest
+                .find("-->")
+                .map_or("", |end| &rest[end + "-->".len()..]);
+        } else if let Some(element) = preserved_element(rest) {
+            let close = format!("</{}", element);
+            let end = rest.to_ascii_lowercase().find(&close).unwrap_or(rest.len());
+            minified.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else if c.is_whitespace() {
+            // A removed comment can leave two runs of whitespace side by side
+            if !minified.ends_with(' ') {
+                minified.push(' ');
+            }
+            rest = rest.trim_start();
+        } else {
+            minified.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    minified.truncate(minified.trim_end().len());
+    minified
+}
+
+/// The preserved element `html` starts with the opening tag of, if any
+fn preserved_element(html: &str) -> Option<&'static str> {
+    let tag = html.strip_prefix('<')?;
+    PRESERVED_ELEMENTS.iter().copied().find(|element| {
+        tag.get(..element.len())
+            .is_some_and(|name| name.eq_ignore_ascii_case(element))
+            && tag[element.len()..].starts_with(|c: char| c == '>' || c.is_whitespace())
+    })
+}
+
+/// Strips any tags, attributes, and URL schemes outside the allowlist, e.g. `<script>`,
+/// `onclick`, and `javascript:` links
+pub fn sanitize(html: &str) -> String {
+    SANITIZER.clean(html).to_string()
+}
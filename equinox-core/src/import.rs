@@ -0,0 +1,177 @@
+//! Turning Markdown or text files written elsewhere into new posts
+
+use crate::{
+    content::{Post, PostEdit, PostMeta},
+    crosspost::{self, Network},
+    error::{Error, Result},
+    front_matter::FrontMatter,
+    site::Site,
+    webhooks,
+};
+use chrono::{DateTime, Local};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+/// Extensions of the files that can be imported
+pub const EXTENSIONS: &[&str] = &["md", "markdown", "txt"];
+
+/// Outcome of importing several posts at once
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    /// The stored posts, in the order they were imported
+    pub posts: Vec<Post>,
+    /// Files or entries that couldn't be imported, by name, and why
+    pub failed: Vec<(String, Error)>,
+}
+
+impl ImportReport {
+    /// Records the published posts imported as already announced and shared, as they were
+    /// online elsewhere before
+    pub(crate) fn record_announced(&self, site: &Site) -> Result<()> {
+        webhooks::record_imported(site, &self.posts)?;
+        crosspost::record_imported(site, Network::Mastodon, &self.posts)?;
+        crosspost::record_imported(site, Network::Bluesky, &self.posts)
+    }
+}
+
+/// Stores every Markdown and text file under `dir` as a new post of `site`, keeping going past
+/// files that fail. Posts without a date in their front matter are dated when their file was
+/// last modified; `default_author` is recorded on those that don't name an author.
+pub fn import_folder(site: &Site, dir: &Path, default_author: &str) -> Result<ImportReport> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.sort();
+
+    let store = site.store();
+    let mut import = ImportReport::default();
+    for path in files {
+        let result = read(&path).and_then(|mut edit| {
+            if edit.date.is_none() {
+                edit.date = fs::metadata(&path)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+                    .map(|time| DateTime::<Local>::from(time).fixed_offset());
+            }
+            edit.default_author = default_author.to_string();
+            let post = store.edit(None, edit)?;
+            site.save_post(&post)?;
+            Ok(post)
+        });
+        match result {
+            Ok(post) => import.posts.push(post),
+            Err(e) => {
+                warn!("Skipped {}: {}", path.display(), e);
+                import.failed.push((path.display().to_string(), e));
+            }
+        }
+    }
+    import.record_announced(site)?;
+    info!(
+        "Imported {} posts from {}",
+        import.posts.len(),
+        dir.display()
+    );
+    Ok(import)
+}
+
+/// Adds the importable files under `dir` to `files`, skipping hidden files and folders
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::io("read", dir, e))?;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if is_importable(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_importable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// Reads a `.md` or `.txt` file into the fields of a new post
+pub fn read(path: &Path) -> Result<PostEdit> {
+    if !is_importable(path) {
+        return Err(Error::Invalid(format!(
+            "Only Markdown and text files can be imported, not {}",
+            path.display()
+        )));
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| Error::io("read", path, e))?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    parse(&text, &name).map_err(|e| match e {
+        Error::Parse { what, message, .. } => Error::parse(&what, Some(path), message),
+        e => e,
+    })
+}
+
+/// Splits `text` into the fields of a new post. Front matter fills in the fields it names and
+/// those without an editor field are kept at the top of the content. Without a title in the
+/// front matter, a leading `# Heading` becomes the title, or else `file_name`.
+pub fn parse(text: &str, file_name: &str) -> Result<PostEdit> {
+    let (front_matter, body) = FrontMatter::parse(text)?;
+    let mut meta = PostMeta::default();
+    if let Some(front_matter) = front_matter {
+        front_matter.apply(&mut meta)?;
+    }
+
+    let mut body = body.trim_start_matches(['\r', '\n']);
+    if meta.title.is_empty() {
+        match title_heading(body) {
+            Some((title, rest)) => {
+                meta.title = title.to_string();
+                body = rest.trim_start_matches(['\r', '\n']);
+            }
+            None => meta.title = title_from_file_name(file_name),
+        }
+    }
+
+    let block = FrontMatter::from_meta(&meta).to_block()?;
+    Ok(PostEdit {
+        title: meta.title,
+        category: meta.category,
+        tags: meta.tags,
+        description: meta.description,
+        date: meta.date,
+        author: meta.author,
+        content: format!("{}{}", block, body),
+        ..PostEdit::default()
+    })
+}
+
+/// The text of a level one ATX heading on the first line, and what follows it
+fn title_heading(body: &str) -> Option<(&str, &str)> {
+    let (line, rest) = body.split_once('\n').unwrap_or((body, ""));
+    let title = line
+        .trim_end()
+        .strip_prefix("# ")?
+        .trim()
+        .trim_end_matches('#')
+        .trim_end();
+    (!title.is_empty()).then_some((title, rest))
+}
+
+/// `my_first-post` becomes `My first post`
+fn title_from_file_name(file_name: &str) -> String {
+    let words = file_name.replace(['-', '_'], " ");
+    let mut chars = words.trim().chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
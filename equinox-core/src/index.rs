@@ -0,0 +1,23 @@
+use crate::{
+    content::PostList,
+    error::Result,
+    pagination,
+    site::Site,
+    templates::{ListedPost, Renderer},
+};
+use std::path::PathBuf;
+
+/// Rewrites `index.html` in the output directory with all posts, newest first, continuing on
+/// `page/<n>.html` once there are more than the site's `posts_per_page`. Returns the path of
+/// the first page.
+pub fn write_index(site: &Site, renderer: &Renderer, posts: &PostList) -> Result<PathBuf> {
+    let listed: Vec<ListedPost> = posts.iter().map(|post| site.listed_post(post)).collect();
+    pagination::write_pages(
+        site,
+        "index.html",
+        &listed,
+        site.config.posts_per_page,
+        |path, posts, pagination| renderer.render_index(&site.config, path, posts, pagination),
+    )?;
+    Ok(site.output_dir().join("index.html"))
+}
@@ -0,0 +1,82 @@
+//! Static site generation for equinox
+//!
+//! A [`Site`] is a directory holding an `equinox.toml`, Markdown post sources, optional template
+//! overrides, and assets. Posts are [`Post`]s kept in the site's [`ContentStore`] and turned
+//! into pages by a [`Renderer`]:
+//!
+//! ```no_run
+//! use equinox_core::{PostEdit, Site};
+//! use std::path::Path;
+//!
+//! let site = Site::open_or_create(Path::new("my-blog"))?;
+//! let post = site.store().edit(
+//!     None,
+//!     PostEdit {
+//!         title: "Hello".to_string(),
+//!         content: "First *post*.".to_string(),
+//!         ..PostEdit::default()
+//!     },
+//! )?;
+//! let page = site.publish(&post)?;
+//! println!("Wrote {}", page.display());
+//! # Ok::<(), equinox_core::Error>(())
+//! ```
+
+pub mod accessibility;
+pub mod archive;
+pub mod assets;
+pub mod atomic;
+pub mod backup;
+pub mod cache;
+pub mod content;
+pub mod crosspost;
+pub mod css;
+pub mod error;
+pub mod export;
+pub mod feed;
+pub mod find;
+pub mod fingerprint;
+pub mod footnotes;
+pub mod formatting;
+pub mod front_matter;
+pub mod highlight;
+pub mod history;
+pub mod html;
+pub mod import;
+pub mod index;
+pub mod links;
+pub mod markdown;
+pub mod newsletter;
+pub mod not_found;
+pub mod pagination;
+pub mod permalink;
+pub mod preview;
+pub mod pwa;
+pub mod redirects;
+pub mod related;
+pub mod responsive;
+pub mod robots;
+pub mod search;
+pub mod series;
+pub mod serve;
+pub mod shortcodes;
+pub mod site;
+pub mod sitemap;
+pub mod spelling;
+pub mod sri;
+pub mod structured_data;
+pub mod taxonomy;
+pub mod templates;
+pub mod themes;
+pub mod toc;
+pub mod typography;
+pub mod validate;
+pub mod versions;
+pub mod webhooks;
+pub mod webmention;
+pub mod wordpress;
+
+pub use content::{ContentStore, Post, PostEdit, PostMeta};
+pub use error::{Error, Result};
+pub use site::{NavLink, Section, Site, SiteConfig};
+pub use templates::Renderer;
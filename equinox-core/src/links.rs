@@ -0,0 +1,250 @@
+//! Finding the links in the generated site that lead nowhere. Pages linking to files the site
+//! doesn't have are found here; checking links to other sites is left to the caller, since it
+//! needs the network. Links in content read away from the site are made absolute here too.
+
+use crate::{
+    error::{Error, Result},
+    html,
+    site::Site,
+};
+use std::{collections::BTreeMap, fs};
+use tracing::{info, warn};
+
+/// A page or file the site doesn't have that its generated pages link to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// Where the link leads, relative to the output directory
+    pub target: String,
+    /// The pages linking to it, relative to the output directory
+    pub pages: Vec<String>,
+}
+
+/// Pages listed in a warning before the rest are only counted
+const PAGES_LISTED: usize = 3;
+
+/// Every page or file the site doesn't have that its generated pages link to, each logged as a
+/// warning
+pub fn check_internal(site: &Site) -> Result<Vec<BrokenLink>> {
+    let output_dir = site.output_dir();
+    let mut broken: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (page, html) in pages(site)? {
+        for url in links(&html) {
+            let Some(target) = resolve(site, &page, url.split(['?', '#']).next().unwrap_or(""))
+            else {
+                continue;
+            };
+            let target = percent_decode(&target);
+            let path = output_dir.join(&target);
+            let exists = match target.is_empty() || target.ends_with('/') {
+                true => path.join("index.html").is_file(),
+                false => path.is_file() || path.join("index.html").is_file(),
+            };
+            let pages = broken.entry(target).or_default();
+            if !exists && !pages.contains(&page) {
+                pages.push(page.clone());
+            }
+        }
+    }
+    broken.retain(|_, pages| !pages.is_empty());
+
+    for (target, pages) in &broken {
+        warn!(
+            "{} doesn't exist but is linked from {}",
+            target,
+            list_pages(pages)
+        );
+    }
+    match broken.len() {
+        0 => info!("Found no broken links"),
+        count => warn!("Found {} broken links", count),
+    }
+    Ok(broken
+        .into_iter()
+        .map(|(target, pages)| BrokenLink { target, pages })
+        .collect())
+}
+
+/// The first few of `pages`, counting the rest
+pub(crate) fn list_pages(pages: &[String]) -> String {
+    let mut listed = pages[..pages.len().min(PAGES_LISTED)].join(", ");
+    if pages.len() > PAGES_LISTED {
+        listed.push_str(&format!(" and {} more", pages.len() - PAGES_LISTED));
+    }
+    listed
+}
+
+/// Links on the site's generated pages to other sites, with the pages linking to each
+pub fn external_links(site: &Site) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut external: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (page, html) in pages(site)? {
+        for url in links(&html) {
+            let is_web = url.starts_with("https://") || url.starts_with("http://");
+            if !is_web || resolve(site, &page, &url).is_some() {
+                continue;
+            }
+            // Whichever part of the page a fragment leads to, the page is the same
+            let url = url
+                .split('#')
+                .next()
+                .unwrap_or_default()
+                .replace("&amp;", "&");
+            let pages = external.entry(url).or_default();
+            if !pages.contains(&page) {
+                pages.push(page.clone());
+            }
+        }
+    }
+    Ok(external)
+}
+
+/// `html`, the content of the page at `page`, with every link and source into the site made
+/// absolute
+pub(crate) fn absolute_links(site: &Site, page: &str, html: &str) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = [" href=\"", " src=\""]
+        .iter()
+        .filter_map(|attribute| rest.find(attribute).map(|i| i + attribute.len()))
+        .min()
+    {
+        let end = rest[start..].find('"').map_or(rest.len(), |i| start + i);
+        output.push_str(&rest[..start]);
+        let link = rest[start..end].replace("&amp;", "&");
+        output.push_str(&html::escape(&absolute(site, page, &link)));
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// `link` from the page at `page` as an absolute URL, unless it leads to another site or only to
+/// a fragment of the page
+pub(crate) fn absolute(site: &Site, page: &str, link: &str) -> String {
+    let split = link.find(['?', '#']).unwrap_or(link.len());
+    let (path, suffix) = link.split_at(split);
+    match resolve(site, page, path) {
+        Some(path) => format!("{}{}", site.url_for(&path), suffix),
+        // A path elsewhere on the site's host, such as `/` above a base URL of `/blog/`
+        None if path.starts_with('/') && !path.starts_with("//") => {
+            let base = site.url_for("");
+            match base.split_once("://") {
+                Some((scheme, rest)) => {
+                    let host = rest.find('/').map_or(rest, |i| &rest[..i]);
+                    format!("{}://{}{}", scheme, host, link)
+                }
+                None => link.to_string(),
+            }
+        }
+        None => link.to_string(),
+    }
+}
+
+/// `path`, linked from the page at `page`, relative to the output directory, if it leads into
+/// the site
+pub(crate) fn resolve(site: &Site, page: &str, path: &str) -> Option<String> {
+    let base = site.url_for("");
+    let relative = if let Some(path) = path.strip_prefix(&base).filter(|_| base.contains("://")) {
+        path.to_string()
+    } else if path.contains("://") || has_scheme(path) || path.is_empty() {
+        return None;
+    } else if path.starts_with('/') {
+        // The path of the site's address on its host, `/` without a base URL
+        let site_path = base
+            .split_once("://")
+            .map_or(base.as_str(), |(_, rest)| {
+                rest.find('/').map_or("/", |i| &rest[i..])
+            })
+            .to_string();
+        path.strip_prefix(&site_path)?.to_string()
+    } else {
+        let mut parts: Vec<&str> = page.split('/').collect();
+        parts.pop();
+        for part in path.split('/') {
+            match part {
+                "." => {}
+                ".." => {
+                    parts.pop()?;
+                }
+                part => parts.push(part),
+            }
+        }
+        parts.join("/")
+    };
+    Some(relative)
+}
+
+/// Whether `url` starts with a scheme such as `mailto:` or `data:`
+fn has_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    })
+}
+
+/// Every generated page, relative to the output directory, with its contents
+fn pages(site: &Site) -> Result<Vec<(String, String)>> {
+    let output_dir = site.output_dir();
+    let mut pages = Vec::new();
+    for file in site.output_files()? {
+        if file.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let path = output_dir.join(&file);
+        let html = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        pages.push((file.to_string_lossy().replace('\\', "/"), html));
+    }
+    Ok(pages)
+}
+
+/// The `href`, `src`, and `srcset` URLs of every tag in `html`. Hints to connect to hosts ahead
+/// of time aren't links to anything on them, so they are left out.
+fn links(html: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    for tag in html::tags(html) {
+        let is_hint = html::attribute(tag, "rel").is_some_and(|rel| {
+            rel.split_whitespace()
+                .any(|rel| rel == "preconnect" || rel == "dns-prefetch")
+        });
+        if is_hint {
+            continue;
+        }
+        links.extend(html::attribute(tag, "href"));
+        links.extend(html::attribute(tag, "src"));
+        if let Some(srcset) = html::attribute(tag, "srcset") {
+            links.extend(
+                srcset
+                    .split(',')
+                    .filter_map(|candidate| candidate.split_whitespace().next())
+                    .map(str::to_string),
+            );
+        }
+    }
+    links.retain(|link| !link.is_empty() && !link.starts_with('#'));
+    links
+}
+
+/// `path` with its `%XX` escapes decoded
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,205 @@
+use crate::{
+    footnotes, highlight,
+    html::{escape, sanitize},
+    toc,
+    typography::Typography,
+};
+use pulldown_cmark::{html, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+/// Switches for how post content is turned into HTML, set per site
+#[derive(Debug, Clone, Default)]
+pub struct MarkdownOptions {
+    /// Keep raw HTML written in posts (after sanitizing) instead of escaping it
+    pub raw_html: bool,
+    /// Curly quotes, em dashes, and ellipses in place of their ASCII stand-ins
+    pub smart_typography: bool,
+    /// Math between `$` and `$$` delimiters, emitted as KaTeX-ready markup
+    pub math: bool,
+    /// Levels each heading is moved down by, stopping at `<h6>`
+    ///
+    /// ```
+    /// use equinox_core::markdown::{to_html, MarkdownOptions};
+    ///
+    /// let options = MarkdownOptions {
+    ///     heading_offset: 2,
+    ///     ..MarkdownOptions::default()
+    /// };
+    /// assert_eq!(to_html("# Intro", &options), "<h3 id=\"intro\">Intro</h3>\n");
+    /// assert!(to_html("##### Aside", &options).starts_with("<h6"));
+    /// ```
+    pub heading_offset: u8,
+    /// Linked table of contents above posts with enough headings, set per post
+    pub table_of_contents: bool,
+}
+
+/// Converts Markdown post content into a sanitized HTML fragment
+///
+/// Raw HTML in the content is escaped and shown as text unless `raw_html` is set, in which case
+/// it is kept as long as it passes the sanitizer's allowlist.
+pub fn to_html(content: &str, options: &MarkdownOptions) -> String {
+    let mut extensions = Options::empty();
+    extensions.insert(Options::ENABLE_TABLES);
+    extensions.insert(Options::ENABLE_STRIKETHROUGH);
+    extensions.insert(Options::ENABLE_FOOTNOTES);
+    if options.math {
+        extensions.insert(Options::ENABLE_MATH);
+    }
+
+    let mut typography = options.smart_typography.then(Typography::default);
+    // Language and text of the fenced code block being collected for highlighting
+    let mut code_block: Option<(String, String)> = None;
+    let mut events: Vec<Event> = Parser::new_ext(content, extensions)
+        .map(|event| match &mut typography {
+            Some(typography) => typography.apply(event),
+            None => event,
+        })
+        .map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) if !options.raw_html => Event::Text(html),
+            Event::InlineMath(math) => Event::InlineHtml(
+                format!(
+                    "<span class=\"math math-inline\">\\({}\\)</span>",
+                    escape(&math)
+                )
+                .into(),
+            ),
+            Event::DisplayMath(math) => Event::InlineHtml(
+                format!(
+                    "<span class=\"math math-display\">\\[{}\\]</span>",
+                    escape(&math)
+                )
+                .into(),
+            ),
+            Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            }) => Event::Start(Tag::Heading {
+                level: offset_heading(level, options.heading_offset),
+                id,
+                classes,
+                attrs,
+            }),
+            Event::End(TagEnd::Heading(level)) => Event::End(TagEnd::Heading(offset_heading(
+                level,
+                options.heading_offset,
+            ))),
+            event => event,
+        })
+        .filter_map(|event| match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if !highlight::language(&info).is_empty() =>
+            {
+                code_block = Some((highlight::language(&info).to_string(), String::new()));
+                None
+            }
+            Event::Text(text) if code_block.is_some() => {
+                code_block.as_mut().unwrap().1.push_str(&text);
+                None
+            }
+            Event::End(TagEnd::CodeBlock) if code_block.is_some() => {
+                let (language, code) = code_block.take().unwrap();
+                Some(Event::Html(highlight::code_block(&code, &language).into()))
+            }
+            event => Some(event),
+        })
+        .collect();
+
+    let headings = toc::assign_ids(&mut events);
+    let events = footnotes::move_to_end(events);
+    let mut output = String::with_capacity(content.len() * 3 / 2);
+    html::push_html(&mut output, events.into_iter());
+    let output = sanitize(&output);
+
+    if options.table_of_contents && headings.len() >= toc::MIN_HEADINGS {
+        toc::to_html(&headings) + &output
+    } else {
+        output
+    }
+}
+
+/// `level` moved down by `offset` levels, stopping at `<h6>`
+fn offset_heading(level: HeadingLevel, offset: u8) -> HeadingLevel {
+    HeadingLevel::try_from((level as usize + offset as usize).min(6)).unwrap_or(HeadingLevel::H6)
+}
+
+/// Reading speed behind the estimated reading time, in words per minute
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// Length of a post's text, not counting Markdown syntax
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextStats {
+    pub words: usize,
+    pub characters: usize,
+}
+
+impl TextStats {
+    /// Estimated minutes to read the text, rounded up
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+}
+
+/// Counts the words and characters in the text of `content`
+pub fn text_stats(content: &str) -> TextStats {
+    let mut stats = TextStats::default();
+    for event in Parser::new_ext(content, Options::ENABLE_FOOTNOTES) {
+        match event {
+            Event::Text(t) | Event::Code(t) => {
+                stats.words += t.split_whitespace().count();
+                stats.characters += t.chars().count();
+            }
+            Event::SoftBreak | Event::HardBreak => stats.characters += 1,
+            _ => {}
+        }
+    }
+    stats
+}
+
+/// All the text of `content`, without its Markdown syntax
+pub fn plain_text(content: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(content, Options::ENABLE_FOOTNOTES) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Plain-text excerpt of the first paragraph, cut at a word boundary after `max_chars`
+pub fn excerpt(content: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(content, Options::ENABLE_FOOTNOTES) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(TagEnd::Paragraph) if !text.trim().is_empty() => break,
+            _ => {}
+        }
+    }
+
+    let text = text.trim();
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(' ') {
+        Some(i) => &cut[..i],
+        None => &cut,
+    };
+    format!("{}…", cut.trim_end_matches([',', '.', ';', ':']))
+}
+
+/// Destinations of the links in `content`, in order
+pub fn links(content: &str) -> Vec<String> {
+    Parser::new_ext(content, Options::ENABLE_FOOTNOTES)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url.to_string()),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,223 @@
+//! Posts as email newsletters: HTML with every style inlined and every link absolute, as mail
+//! clients drop stylesheets and have no page to resolve links against, and a plain-text
+//! alternative
+
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+    html::{self, escape},
+    links, permalink, shortcodes,
+    site::Site,
+    templates::format_date,
+};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use std::path::{Path, PathBuf};
+
+/// Fonts every mail client has one of
+const FONTS: &str = "-apple-system, 'Segoe UI', Helvetica, Arial, sans-serif";
+
+/// Styles given to each element of a post's content, the same whatever the site's theme. Mail
+/// clients only agree on a small subset of CSS, drop dark backgrounds or invert them on their
+/// own, and lay out none of a theme's page, so newsletters keep to light, plain declarations
+/// every client shows alike rather than copying the theme's stylesheet.
+const EMAIL_SAFE_STYLES: &[(&str, &str)] = &[
+    (
+        "h1",
+        "margin: 32px 0 12px; font-size: 26px; line-height: 1.3;",
+    ),
+    (
+        "h2",
+        "margin: 28px 0 12px; font-size: 22px; line-height: 1.3;",
+    ),
+    (
+        "h3",
+        "margin: 24px 0 8px; font-size: 18px; line-height: 1.3;",
+    ),
+    ("h4", "margin: 20px 0 8px; font-size: 16px;"),
+    ("p", "margin: 0 0 16px;"),
+    ("a", "color: #1a5fb4; text-decoration: underline;"),
+    ("img", "max-width: 100%; height: auto; border: 0;"),
+    ("ul", "margin: 0 0 16px; padding-left: 24px;"),
+    ("ol", "margin: 0 0 16px; padding-left: 24px;"),
+    ("li", "margin: 0 0 4px;"),
+    (
+        "blockquote",
+        "margin: 0 0 16px; padding: 0 0 0 16px; border-left: 4px solid #d0d0d0; color: #555555;",
+    ),
+    (
+        "pre",
+        "margin: 0 0 16px; padding: 12px; background: #f4f4f4; border-radius: 4px; \
+         overflow-x: auto; font-size: 14px; line-height: 1.4;",
+    ),
+    (
+        "code",
+        "font-family: Menlo, Consolas, 'Courier New', monospace;",
+    ),
+    ("table", "margin: 0 0 16px; border-collapse: collapse;"),
+    (
+        "th",
+        "padding: 6px 10px; border: 1px solid #d0d0d0; text-align: left;",
+    ),
+    ("td", "padding: 6px 10px; border: 1px solid #d0d0d0;"),
+    (
+        "hr",
+        "margin: 24px 0; border: 0; border-top: 1px solid #d0d0d0;",
+    ),
+];
+
+/// A post ready to send by email
+#[derive(Debug, Clone)]
+pub struct Newsletter {
+    /// A whole HTML document, styled inline
+    pub html: String,
+    /// The same content as plain text, for mail clients that don't show HTML
+    pub text: String,
+}
+
+impl Newsletter {
+    /// Writes the HTML to `path` and the plain text next to it with a `.txt` extension,
+    /// returning the path of the text
+    pub fn save(&self, path: &Path) -> Result<PathBuf> {
+        atomic::write(path, &self.html).map_err(|e| Error::io("write", path, e))?;
+        let text = path.with_extension("txt");
+        atomic::write(&text, &self.text).map_err(|e| Error::io("write", &text, e))?;
+        Ok(text)
+    }
+}
+
+/// Renders `post` as a newsletter linking to its page on the site, styled alike whatever the
+/// site's theme. The site needs a base URL, as links and images have to reach it from someone's
+/// inbox.
+pub fn render(site: &Site, post: &Post) -> Result<Newsletter> {
+    if !site.config.base_url.contains("://") {
+        return Err(Error::Invalid(
+            "A newsletter needs the site's base URL so its links and images work from an inbox"
+                .to_string(),
+        ));
+    }
+    let posts = site.store().list()?;
+    let page = site.config.post_path(post);
+    let url = site.url_for(&site.config.post_url(post));
+
+    // Mail clients won't run the scripts or frames shortcodes bring
+    let content = html::sanitize(&site.render_content(post, &posts));
+    let content = links::absolute_links(site, &page, &content);
+    let content = html::inline_styles(&content, EMAIL_SAFE_STYLES);
+
+    let mut byline = format_date(post);
+    if !post.meta.author.is_empty() {
+        byline = format!("{} · {}", escape(&post.meta.author), byline);
+    }
+    let html = format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{title}</title>
+</head>
+<body style=\"margin: 0; padding: 0; background: #ffffff;\">
+<table role=\"presentation\" width=\"100%\" cellpadding=\"0\" cellspacing=\"0\" border=\"0\">
+<tr><td align=\"center\">
+<table role=\"presentation\" width=\"600\" cellpadding=\"0\" cellspacing=\"0\" border=\"0\" \
+         style=\"width: 100%; max-width: 600px;\">
+<tr><td style=\"padding: 24px; font-family: {fonts}; font-size: 16px; line-height: 1.6; \
+         color: #222222;\">
+<h1 style=\"margin: 0 0 8px; font-size: 30px; line-height: 1.2;\">{title}</h1>
+<p style=\"margin: 0 0 24px; font-size: 14px; color: #666666;\">{byline} · \
+         <a href=\"{url}\" style=\"color: #666666;\">Read it on the web</a></p>
+{content}
+</td></tr>
+</table>
+</td></tr>
+</table>
+</body>
+</html>
+",
+        title = escape(&post.meta.title),
+        fonts = FONTS,
+        byline = byline,
+        url = escape(&url),
+        content = content,
+    );
+
+    let text = format!(
+        "{}\n{}\n\n{}\n\nRead it on the web: {}\n",
+        post.meta.title,
+        "=".repeat(post.meta.title.chars().count()),
+        plain_text(site, &posts, &page, &shortcodes::strip(site, &post.body)),
+        url
+    );
+    Ok(Newsletter { html, text })
+}
+
+/// Markdown `content` of the page at `page` as plain text, with the address of each link after
+/// its text
+fn plain_text(site: &Site, posts: &[Post], page: &str, content: &str) -> String {
+    let mut text = String::new();
+    let mut links: Vec<String> = Vec::new();
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    for event in Parser::new_ext(
+        content,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    ) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            Event::Rule => text.push_str("----\n\n"),
+            Event::Start(Tag::List(start)) => lists.push(start),
+            Event::End(TagEnd::List(_)) => {
+                lists.pop();
+                if lists.is_empty() {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                if !text.ends_with('\n') && !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&"  ".repeat(lists.len().saturating_sub(1)));
+                match lists.last_mut() {
+                    Some(Some(n)) => {
+                        text.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => text.push_str("- "),
+                }
+            }
+            Event::End(TagEnd::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                // Links in content are written as if from the content directory
+                let anchor = format!("a href=\"{}\"", escape(&dest_url));
+                let anchor = permalink::relink(&site.config.permalink, posts, page, &anchor);
+                let link = html::attribute(&anchor, "href").unwrap_or_default();
+                links.push(links::absolute(site, page, &link));
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                if let Some(link) = links.pop() {
+                    // Only addresses that lead somewhere from an inbox are worth spelling out
+                    let reachable = link.contains("://") || link.starts_with("mailto:");
+                    if reachable && !text.ends_with(&link) {
+                        text.push_str(&format!(" ({})", link));
+                    }
+                }
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::CodeBlock)
+                if lists.is_empty() =>
+            {
+                text.truncate(text.trim_end().len());
+                text.push_str("\n\n");
+            }
+            Event::End(TagEnd::TableCell) => text.push_str(" | "),
+            Event::End(TagEnd::TableHead | TagEnd::TableRow) => {
+                text.truncate(text.trim_end_matches([' ', '|']).len());
+                text.push('\n');
+            }
+            Event::End(TagEnd::Table) => text.push('\n'),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
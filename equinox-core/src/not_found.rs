@@ -0,0 +1,17 @@
+//! The page hosts serve in place of any page that doesn't exist
+
+use crate::{error::Result, markdown, site::Site, templates::Renderer};
+use tracing::debug;
+
+/// Path of the not-found page, relative to the output directory, where GitHub Pages, Netlify,
+/// and most other hosts look for it
+pub const NOT_FOUND_PAGE: &str = "404.html";
+
+/// Writes `404.html` with the site's [`not_found`](crate::site::SiteConfig::not_found) message
+pub fn write_not_found(site: &Site, renderer: &Renderer) -> Result<()> {
+    let content = markdown::to_html(&site.config.not_found, &site.config.markdown_options());
+    let html = renderer.render_not_found(&site.config, NOT_FOUND_PAGE, &content)?;
+    let path = site.write_page(NOT_FOUND_PAGE, html)?;
+    debug!("Wrote {}", path.display());
+    Ok(())
+}
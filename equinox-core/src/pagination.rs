@@ -0,0 +1,77 @@
+use crate::{
+    error::{Error, Result},
+    {site::Site, templates::ListedPost},
+};
+use serde::Serialize;
+use std::fs;
+use tracing::{debug, info};
+
+/// Where a listing page sits among the pages its posts are split across
+#[derive(Debug, Clone, Serialize)]
+pub struct Pagination {
+    /// Page number, starting at 1
+    pub current: usize,
+    pub total: usize,
+    /// Path of the listing's first page, relative to the output directory
+    pub first: String,
+    /// Paths of the neighbouring pages, relative to the output directory
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+/// Path of page `number` of the listing whose first page is `first`, e.g. `tech/page/2.html`
+/// for `tech.html` and `page/2.html` for `index.html`
+pub fn page_path(first: &str, number: usize) -> String {
+    if number <= 1 {
+        return first.to_string();
+    }
+    match first.strip_suffix(".html").unwrap_or(first) {
+        "index" => format!("page/{}.html", number),
+        stem => format!("{}/page/{}.html", stem, number),
+    }
+}
+
+/// Splits `posts` into pages of `per_page` (all on one page if it is 0), renders each with
+/// `render`, and writes them starting at `first`. Pages left over from when the listing was
+/// longer are removed.
+pub fn write_pages(
+    site: &Site,
+    first: &str,
+    posts: &[ListedPost],
+    per_page: usize,
+    render: impl Fn(&str, &[ListedPost], &Pagination) -> Result<String>,
+) -> Result<()> {
+    let chunks: Vec<&[ListedPost]> = match per_page {
+        0 => vec![posts],
+        _ if posts.is_empty() => vec![posts],
+        n => posts.chunks(n).collect(),
+    };
+    let total = chunks.len();
+
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let number = i + 1;
+        let pagination = Pagination {
+            current: number,
+            total,
+            first: first.to_string(),
+            prev: (number > 1).then(|| page_path(first, number - 1)),
+            next: (number < total).then(|| page_path(first, number + 1)),
+        };
+        let path = page_path(first, number);
+        let html = render(&path, chunk, &pagination)?;
+        let path = site.write_page(&path, html)?;
+        debug!("Wrote {}", path.display());
+    }
+
+    let mut number = total + 1;
+    loop {
+        let stale = site.output_dir().join(page_path(first, number));
+        if !stale.is_file() {
+            break;
+        }
+        fs::remove_file(&stale).map_err(|e| Error::io("remove", &stale, e))?;
+        info!("Removed stale page {}", stale.display());
+        number += 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,178 @@
+//! Where posts' pages are written, set by the site's permalink format, e.g. `posts/:slug.html`,
+//! `:year/:month/:slug.html`, or `posts/:slug/`
+//!
+//! A format ending in `/` writes each post to an `index.html` in its own directory, linked to
+//! by the directory's URL. Any other format without `.html` at the end has it added.
+
+use crate::{
+    content::{slugify, Post},
+    templates::root_for,
+};
+
+/// Format of sites that don't choose one
+pub const DEFAULT: &str = "posts/:slug.html";
+
+/// Placeholders a format can use, replaced with the post's slug, its publish date's year,
+/// month, and day, and its slugified category
+pub const PLACEHOLDERS: &[&str] = &[":slug", ":year", ":month", ":day", ":category"];
+
+/// Why `format` can't be used, if it can't
+pub fn validate(format: &str) -> Result<(), String> {
+    if !format.contains(":slug") {
+        return Err(format!(
+            "permalink {} needs a :slug, or every post would share one page",
+            format
+        ));
+    }
+    let segments = format.trim_start_matches('/').split('/');
+    if segments
+        .clone()
+        .any(|segment| segment == ".." || segment == ".")
+    {
+        return Err(format!(
+            "permalink {} can't leave the output directory",
+            format
+        ));
+    }
+    for segment in segments {
+        let mut rest = segment;
+        while let Some(start) = rest.find(':') {
+            rest = &rest[start..];
+            match PLACEHOLDERS.iter().find(|p| rest.starts_with(*p)) {
+                Some(placeholder) => rest = &rest[placeholder.len()..],
+                None => {
+                    return Err(format!(
+                        "permalink {} has an unknown placeholder {}, expected one of {}",
+                        format,
+                        rest,
+                        PLACEHOLDERS.join(", ")
+                    ))
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Path of `post`'s page relative to the output directory under `format`
+pub fn page_path(format: &str, post: &Post) -> String {
+    let date = post.date();
+    let category = slugify(&post.meta.category);
+    let path = format
+        .trim_start_matches('/')
+        .replace(":slug", post.slug())
+        .replace(":year", &date.format("%Y").to_string())
+        .replace(":month", &date.format("%m").to_string())
+        .replace(":day", &date.format("%d").to_string())
+        .replace(":category", &category);
+    // A post without a category leaves its directory out rather than an empty one
+    let (dirs, file) = path.rsplit_once('/').unwrap_or(("", &path));
+    let mut path: String = dirs
+        .split('/')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| format!("{}/", dir))
+        .collect();
+    path.push_str(file);
+    if path.ends_with('/') || path.is_empty() {
+        format!("{}index.html", path)
+    } else if path.ends_with(".html") {
+        path
+    } else {
+        format!("{}.html", path)
+    }
+}
+
+/// Link to the page at `path`, relative to the output directory: its directory for an
+/// `index.html`, so directory-style permalinks are linked without the file name
+pub fn url(path: &str) -> &str {
+    match path.strip_suffix("index.html") {
+        Some(dir) if dir.is_empty() || dir.ends_with('/') => dir,
+        _ => path,
+    }
+}
+
+/// Directory relative to the output directory that holds only post pages under `format`, if
+/// the format starts with one, e.g. `posts` for `posts/:year/:slug.html`
+pub fn posts_dir(format: &str) -> Option<&str> {
+    let format = format.trim_start_matches('/');
+    let fixed = &format[..format.find(':')?];
+    let dir = fixed.rsplit_once('/')?.0;
+    (!dir.is_empty()).then_some(dir)
+}
+
+/// Directory post content is written from: relative links in a post are read as if its page
+/// were in it, as every post's page was under [`DEFAULT`], so they survive format changes
+pub const CONTENT_DIR: &str = "posts";
+
+/// Link from a post's content to `path`, relative to the output directory
+pub fn content_link(path: &str) -> String {
+    format!("{}{}", root_for(&format!("{}/", CONTENT_DIR)), path)
+}
+
+/// `html` rendered from post content, fixed up for its page at `page` under `format`: relative
+/// links and sources are pointed where they led from [`CONTENT_DIR`], and links to another of
+/// `posts` at its page under [`DEFAULT`] go to its page under `format` instead
+pub fn relink(format: &str, posts: &[Post], page: &str, html: &str) -> String {
+    if format == DEFAULT {
+        return html.to_string();
+    }
+    let root = root_for(page);
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some((start, end)) = next_link(rest) {
+        output.push_str(&rest[..start]);
+        let link = &rest[start..end];
+        match resolve(link) {
+            Some((target, suffix)) => {
+                let target = target
+                    .strip_prefix(CONTENT_DIR)
+                    .and_then(|file| file.strip_prefix('/')?.strip_suffix(".html"))
+                    .and_then(|slug| posts.iter().find(|post| post.slug() == slug))
+                    .map_or(target.clone(), |post| {
+                        url(&page_path(format, post)).to_string()
+                    });
+                let relinked = format!("{}{}{}", root, target, suffix);
+                // An empty link would lead back to the page itself
+                output.push_str(if relinked.is_empty() { "./" } else { &relinked });
+            }
+            None => output.push_str(link),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Where the next `href` or `src` attribute value in `html` starts and ends
+fn next_link(html: &str) -> Option<(usize, usize)> {
+    let start = [" href=\"", " src=\""]
+        .iter()
+        .filter_map(|attribute| html.find(attribute).map(|i| i + attribute.len()))
+        .min()?;
+    let end = html[start..].find('"').map_or(html.len(), |i| start + i);
+    Some((start, end))
+}
+
+/// `link` resolved from a page in [`CONTENT_DIR`] to a path relative to the output directory,
+/// with its query and fragment split off, or `None` if it isn't relative or leaves the output
+/// directory
+fn resolve(link: &str) -> Option<(String, &str)> {
+    let scheme = link
+        .find(':')
+        .is_some_and(|colon| !link[..colon].contains(['/', '?', '#']));
+    if link.is_empty() || scheme || link.starts_with(['/', '#', '?']) {
+        return None;
+    }
+    let (path, suffix) = link.split_at(link.find(['?', '#']).unwrap_or(link.len()));
+    let mut parts = vec![CONTENT_DIR];
+    for part in path.split('/') {
+        match part {
+            "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some((parts.join("/"), suffix))
+}
@@ -0,0 +1,79 @@
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+/// A block-level element of a post, as shown in the editor's preview pane
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockKind {
+    Heading(u8),
+    Paragraph,
+    ListItem,
+    Quote,
+    Code,
+    Rule,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub text: String,
+}
+
+/// Flattens Markdown content into styled text blocks. Inline formatting is dropped; this is an
+/// approximation of the generated page, not a faithful rendering of it.
+pub fn blocks(content: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut quote_depth = 0;
+    let mut item_depth = 0;
+
+    for event in Parser::new(content) {
+        match event {
+            Event::Start(Tag::BlockQuote(_)) => quote_depth += 1,
+            Event::End(TagEnd::BlockQuote(_)) => quote_depth -= 1,
+            Event::Start(Tag::Item) => {
+                if !text.is_empty() {
+                    flush(&mut blocks, BlockKind::ListItem, &mut text);
+                }
+                item_depth += 1;
+            }
+            Event::End(TagEnd::Item) => {
+                flush(&mut blocks, BlockKind::ListItem, &mut text);
+                item_depth -= 1;
+            }
+            Event::End(TagEnd::Paragraph) => {
+                let kind = if item_depth > 0 {
+                    BlockKind::ListItem
+                } else if quote_depth > 0 {
+                    BlockKind::Quote
+                } else {
+                    BlockKind::Paragraph
+                };
+                flush(&mut blocks, kind, &mut text);
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                flush(&mut blocks, BlockKind::Heading(level as u8), &mut text)
+            }
+            Event::End(TagEnd::CodeBlock) => flush(&mut blocks, BlockKind::Code, &mut text),
+            Event::Rule => blocks.push(Block {
+                kind: BlockKind::Rule,
+                text: String::new(),
+            }),
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak => text.push(' '),
+            Event::HardBreak => text.push('\n'),
+            _ => {}
+        }
+    }
+
+    blocks
+}
+
+fn flush(blocks: &mut Vec<Block>, kind: BlockKind, text: &mut String) {
+    let trimmed = text.trim_end();
+    if !trimmed.is_empty() {
+        blocks.push(Block {
+            kind,
+            text: trimmed.to_string(),
+        });
+    }
+    text.clear();
+}
@@ -0,0 +1,181 @@
+//! A web app manifest and service worker, so readers can install the site and read it offline
+
+use crate::{
+    atomic, cache,
+    error::{Error, Result},
+    fingerprint, permalink,
+    site::Site,
+};
+use serde_json::json;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+
+/// Path of the manifest, relative to the output directory
+pub const MANIFEST: &str = "manifest.webmanifest";
+
+/// Path of the service worker, relative to the output directory. It sits at the root so it can
+/// serve every page.
+pub const SERVICE_WORKER: &str = "sw.js";
+
+/// Largest file the service worker caches when it is installed; bigger ones are only fetched
+/// when they are needed
+const MAX_PRECACHED_SIZE: u64 = 1024 * 1024;
+
+/// Writes the manifest and a service worker precaching the output directory's files, meant to
+/// be called once everything else has been written. A site that turned the app off gets a
+/// worker that removes itself instead, so readers who installed it see the live site again.
+pub fn write_pwa(site: &Site) -> Result<()> {
+    let output_dir = site.output_dir();
+    let worker_path = output_dir.join(SERVICE_WORKER);
+    if !site.config.pwa.enabled {
+        if worker_path.is_file() {
+            atomic::write(&worker_path, REMOVE_WORKER)
+                .map_err(|e| Error::io("write", &worker_path, e))?;
+            info!("Replaced the service worker with one that removes itself");
+        }
+        return Ok(());
+    }
+
+    let manifest = manifest(site);
+    let path = output_dir.join(MANIFEST);
+    atomic::write(&path, manifest).map_err(|e| Error::io("write", &path, e))?;
+
+    let output_files = site.output_files()?;
+    // Pages link to the fingerprinted copies of assets rather than the assets themselves
+    let superseded: HashSet<PathBuf> = output_files
+        .iter()
+        .filter(|file| fingerprint::is_fingerprinted(&output_dir.join(file)))
+        .filter_map(|file| {
+            let name = fingerprint::original_name(&file.file_name()?.to_string_lossy())?;
+            Some(file.with_file_name(name))
+        })
+        .collect();
+    let mut files = Vec::new();
+    let mut version = Vec::new();
+    for file in output_files {
+        let relative = file.to_string_lossy().replace('\\', "/");
+        // Responsive copies duplicate the images that are cached
+        if relative == SERVICE_WORKER
+            || relative.starts_with("resized/")
+            || superseded.contains(&file)
+        {
+            continue;
+        }
+        let Ok(metadata) = fs::metadata(output_dir.join(&file)) else {
+            continue;
+        };
+        if metadata.len() > MAX_PRECACHED_SIZE {
+            continue;
+        }
+        // A changed file changes the cache's name, so the new worker replaces the old copies
+        let Ok(contents) = fs::read(output_dir.join(&file)) else {
+            continue;
+        };
+        version.push(format!(
+            "{} {}",
+            relative,
+            cache::hash([contents.as_slice()])
+        ));
+        files.push(match permalink::url(&relative) {
+            "" => "./".to_string(),
+            url => url.to_string(),
+        });
+    }
+    let version = cache::hash(version.iter().map(String::as_bytes));
+    let worker = WORKER
+        .replace("{version}", &version[..16])
+        .replace("{files}", &json!(files).to_string());
+    atomic::write(&worker_path, worker).map_err(|e| Error::io("write", &worker_path, e))?;
+    debug!(
+        "Wrote {} precaching {} files",
+        worker_path.display(),
+        files.len()
+    );
+    Ok(())
+}
+
+/// The manifest describing the site as an app, starting at the front page
+fn manifest(site: &Site) -> String {
+    let config = &site.config;
+    let title = match config.title.as_str() {
+        "" => "Blog",
+        title => title,
+    };
+    let short_name = match config.pwa.short_name.as_str() {
+        "" => title,
+        short_name => short_name,
+    };
+    let mut manifest = json!({
+        "name": title,
+        "short_name": short_name,
+        "start_url": "./",
+        "scope": "./",
+        "display": "standalone",
+        "theme_color": config.pwa.theme_color,
+        "background_color": config.pwa.background_color,
+    });
+    let icon = &config.pwa.icon;
+    let icon_path = site.output_dir().join(icon);
+    if !icon.is_empty() && icon_path.is_file() {
+        let sizes = match image::image_dimensions(&icon_path) {
+            Ok((width, height)) => format!("{}x{}", width, height),
+            Err(_) => "any".to_string(),
+        };
+        manifest["icons"] = json!([{ "src": icon, "sizes": sizes, "type": icon_type(icon) }]);
+    }
+    manifest.to_string()
+}
+
+fn icon_type(icon: &str) -> &'static str {
+    let extension = Path::new(icon)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "svg" => "image/svg+xml",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        _ => "image/png",
+    }
+}
+
+/// Service worker that caches `{files}` when installed and serves them whenever the network
+/// can't, falling back to the not-found page for pages it never saw
+const WORKER: &str = r#"const CACHE = "equinox-{version}";
+const FILES = {files};
+
+self.addEventListener("install", (event) => {
+    event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(FILES)).then(() => self.skipWaiting()));
+});
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(caches.keys()
+        .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+        .then(() => self.clients.claim()));
+});
+
+// Pages are fetched fresh while online and read from the cache otherwise
+self.addEventListener("fetch", (event) => {
+    if (event.request.method !== "GET") {
+        return;
+    }
+    event.respondWith(fetch(event.request).catch(() =>
+        caches.match(event.request, { ignoreSearch: true }).then((cached) =>
+            cached || (event.request.mode === "navigate" ? caches.match("404.html") : Response.error()))));
+});
+"#;
+
+/// Service worker that deletes everything the app cached and unregisters itself
+const REMOVE_WORKER: &str = r#"self.addEventListener("install", () => self.skipWaiting());
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(caches.keys()
+        .then((keys) => Promise.all(keys.filter((key) => key.startsWith("equinox-")).map((key) => caches.delete(key))))
+        .then(() => self.registration.unregister()));
+});
+"#;
@@ -0,0 +1,172 @@
+//! Redirects from the pages posts used to have to the ones they have now, so links to a post
+//! keep working after its slug, date, or category or the site's permalink format changes
+
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+    html::escape,
+    permalink,
+    site::Site,
+    templates::root_for,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+
+/// Record of the pages posts have had, relative to the project root
+pub const REDIRECTS_FILE: &str = ".equinox/redirects.toml";
+
+/// Rules file read by Netlify and Cloudflare Pages, relative to the output directory
+pub const NETLIFY_FILE: &str = "_redirects";
+
+/// How the old pages of moved posts send readers on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectFormat {
+    /// A page at each old path that refreshes to the new one, which works on any host
+    #[default]
+    Html,
+    /// Permanent redirects listed in a `_redirects` file
+    Netlify,
+}
+
+/// The page each post had when the site was last built, and the posts that pages it no longer
+/// has lead to
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RedirectLog {
+    /// Path of each post's page relative to the output directory, by post id
+    pages: BTreeMap<String, String>,
+    /// Id of the post each old page now leads to, by the page's path
+    moved: BTreeMap<String, String>,
+}
+
+impl RedirectLog {
+    /// Loads the log of the project at `root`, empty if it has never been built
+    pub fn load(root: &Path) -> RedirectLog {
+        fs::read_to_string(path(root))
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = path(root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        let contents = toml::to_string(self)
+            .map_err(|e| Error::Invalid(format!("Failed to serialize the redirects: {}", e)))?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    /// Notes where each of `posts`' pages is now, recording a redirect from wherever one was
+    /// before. Redirects for posts that are gone, or from pages another post now has, are
+    /// dropped.
+    pub fn update(&mut self, site: &Site, posts: &[Post]) {
+        let pages: BTreeMap<String, String> = posts
+            .iter()
+            .map(|post| (post.id.clone(), site.config.post_path(post)))
+            .collect();
+        for (id, page) in &pages {
+            if let Some(old) = self.pages.get(id).filter(|old| *old != page) {
+                info!("Post {} moved from {} to {}", id, old, page);
+                self.moved.insert(old.clone(), id.clone());
+            }
+        }
+        let current: HashSet<&String> = pages.values().collect();
+        self.moved
+            .retain(|old, id| pages.contains_key(id) && !current.contains(old));
+        self.pages = pages;
+    }
+
+    /// Old pages that have redirect pages written at them, relative to the output directory
+    pub fn stubs(&self) -> impl Iterator<Item = &str> {
+        self.moved.keys().map(String::as_str)
+    }
+}
+
+/// Writes a redirect for every page a post has moved away from, in the site's
+/// [`RedirectFormat`]. Where `posts`' pages are is recorded in the project if `record` is set,
+/// and otherwise only taken into account, as for an export.
+pub fn write_redirects(site: &Site, posts: &[Post], record: bool) -> Result<()> {
+    let mut log = RedirectLog::load(&site.root);
+    log.update(site, posts);
+    if record {
+        log.save(&site.root)?;
+    }
+
+    let targets: BTreeMap<&str, &Post> = log
+        .moved
+        .iter()
+        .filter_map(|(old, id)| Some((old.as_str(), posts.iter().find(|p| p.id == *id)?)))
+        .collect();
+    match site.config.redirects {
+        RedirectFormat::Html => {
+            for (old, post) in &targets {
+                let link = format!("{}{}", root_for(old), site.config.post_url(post));
+                let canonical = site.url_for(&site.config.post_url(post));
+                site.write_page(old, stub(&post.meta.title, &link, &canonical))?;
+            }
+        }
+        RedirectFormat::Netlify => {
+            let rules: String = targets
+                .iter()
+                .map(|(old, post)| {
+                    format!(
+                        "{} {} 301\n",
+                        site_path(site, permalink::url(old)),
+                        site_path(site, &site.config.post_url(post))
+                    )
+                })
+                .collect();
+            let path = site.output_dir().join(NETLIFY_FILE);
+            atomic::write(&path, rules).map_err(|e| Error::io("write", &path, e))?;
+        }
+    }
+    debug!("Wrote redirects for {} moved pages", targets.len());
+    Ok(())
+}
+
+/// A page that sends readers straight on to `link`, the page of the post titled `title`
+fn stub(title: &str, link: &str, canonical: &str) -> String {
+    let link = escape(link);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8" />
+    <title>{title}</title>
+    <link rel="canonical" href="{canonical}" />
+    <meta name="robots" content="noindex" />
+    <meta http-equiv="refresh" content="0; url={link}" />
+</head>
+<body>
+    <p>This post has moved to <a href="{link}">{link}</a>.</p>
+</body>
+</html>
+"#,
+        title = escape(title),
+        canonical = escape(canonical),
+        link = link,
+    )
+}
+
+/// `path`, relative to the output directory, as a path from the root of the site's host
+fn site_path(site: &Site, path: &str) -> String {
+    let url = site.url_for(path);
+    match url.split_once("://") {
+        Some((_, rest)) => rest
+            .find('/')
+            .map_or("/".to_string(), |i| rest[i..].to_string()),
+        None => url,
+    }
+}
+
+fn path(root: &Path) -> PathBuf {
+    root.join(REDIRECTS_FILE)
+}
@@ -0,0 +1,38 @@
+//! Picking the posts linked under a post as related reading
+
+use crate::content::{slugify, Post, PostList};
+use std::collections::HashSet;
+
+/// Up to `limit` published posts other than `post` that share its tags or category, closest
+/// first. Each shared tag counts twice as much as sharing the category, and ties go to the
+/// newer post.
+pub fn related_posts<'a>(post: &Post, posts: &'a PostList, limit: usize) -> Vec<&'a Post> {
+    let tags: HashSet<String> = post.meta.tags.iter().map(|tag| slugify(tag)).collect();
+    let category = slugify(&post.meta.category);
+
+    let mut scored: Vec<(usize, &Post)> = posts
+        .iter()
+        .filter(|other| other.id != post.id && other.is_published())
+        .filter_map(|other| {
+            let shared = other
+                .meta
+                .tags
+                .iter()
+                .map(|tag| slugify(tag))
+                .collect::<HashSet<_>>()
+                .intersection(&tags)
+                .count();
+            let same_category = !category.is_empty() && slugify(&other.meta.category) == category;
+            let score = 2 * shared + usize::from(same_category);
+            (score > 0).then_some((score, other))
+        })
+        .collect();
+
+    // Stable, so equal scores keep the newest first order of `posts`
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, other)| other)
+        .collect()
+}
@@ -0,0 +1,412 @@
+//! Resized and converted copies of the images in posts, and the `srcset` attributes and
+//! `<picture>` sources that let browsers pick the smallest one they can show
+
+use crate::{
+    atomic,
+    error::{Error, Result},
+    site::Site,
+    templates::root_for,
+};
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        jpeg::JpegEncoder,
+        png::{CompressionType, PngEncoder},
+        webp::WebPEncoder,
+    },
+    imageops::FilterType,
+    DynamicImage, ImageFormat,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Cursor, Write},
+    path::{Component, Path, PathBuf},
+};
+use tracing::{debug, warn};
+
+/// Directory the resized and converted images are written to, relative to the output
+/// directory. It is kept apart from `assets/`, which is mirrored from the site's assets and
+/// would lose them.
+pub const RESIZED_DIR: &str = "resized";
+
+/// How wide images are shown on the page, so browsers know which size they need before the
+/// stylesheet loads
+const SIZES: &str = "(max-width: 800px) 100vw, 800px";
+
+/// Width in pixels of the thumbnails of cover images shown on listing pages, twice the size
+/// they are shown at so they stay sharp on high density screens
+const THUMBNAIL_WIDTH: u32 = 320;
+
+/// Encoder speed for AVIF, from 1 (smallest files) to 10 (fastest), traded so builds stay quick
+const AVIF_SPEED: u8 = 8;
+
+/// A smaller format post images are converted to, offered ahead of the original to browsers
+/// that support it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvertedFormat {
+    /// Lossless WebP
+    Webp,
+    /// AVIF at the site's image quality
+    Avif,
+}
+
+impl ConvertedFormat {
+    fn format(self) -> ImageFormat {
+        match self {
+            ConvertedFormat::Webp => ImageFormat::WebP,
+            ConvertedFormat::Avif => ImageFormat::Avif,
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            ConvertedFormat::Webp => "image/webp",
+            ConvertedFormat::Avif => "image/avif",
+        }
+    }
+}
+
+/// Adds `width` and `height` attributes to the `<img>` tags in `html`, the content of the page
+/// at `page`, and offers copies of each image at every one of
+/// [`SiteConfig::image_widths`](crate::SiteConfig::image_widths) narrower than it through
+/// `srcset`. Copies in each of [`SiteConfig::image_formats`](crate::SiteConfig::image_formats)
+/// that come out smaller than the original are offered through a `<picture>` around the image.
+/// Images that are remote, missing, or not PNG or JPEG files are left as they are.
+pub fn rewrite_images(site: &Site, page: &str, html: &str) -> String {
+    if site.config.image_widths.is_empty() && site.config.image_formats.is_empty() {
+        return html.to_string();
+    }
+
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<img ") {
+        let Some(end) = tag_end(&rest[start..]).map(|end| start + end + 1) else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        let tag = &rest[start..end];
+        match src(tag).and_then(|src| resolve(page, src)) {
+            Some(path) => output.push_str(&responsive_tag(site, page, tag, &path)),
+            None => output.push_str(tag),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Re-encodes an imported image more tightly, returning `contents` unchanged if that doesn't
+/// make it smaller. PNGs are recompressed losslessly and JPEGs at `quality`, unless it is 100.
+pub fn recompress(contents: &[u8], quality: u8) -> Vec<u8> {
+    let recompressed = image::guess_format(contents)
+        .ok()
+        .filter(|format| match format {
+            ImageFormat::Png => true,
+            ImageFormat::Jpeg => quality < 100,
+            _ => false,
+        })
+        .and_then(|format| {
+            let image = image::load_from_memory_with_format(contents, format).ok()?;
+            let mut recompressed = Vec::new();
+            encode(&image, format, quality, Cursor::new(&mut recompressed)).ok()?;
+            Some(recompressed)
+        });
+    match recompressed {
+        Some(recompressed) if recompressed.len() < contents.len() => recompressed,
+        _ => contents.to_vec(),
+    }
+}
+
+/// Path relative to the output directory of a thumbnail of the cover image `image`, written if
+/// it is missing or out of date, or `None` if the image is remote or not a PNG or JPEG file.
+/// Images no wider than a thumbnail are used as they are.
+pub fn thumbnail(site: &Site, image: &str) -> Option<String> {
+    if image.is_empty() || image.contains("://") {
+        return None;
+    }
+    let image = image.trim_start_matches("./");
+    let source = site.source_of(image);
+    let format = ImageFormat::from_path(&source)
+        .ok()
+        .filter(|format| matches!(format, ImageFormat::Png | ImageFormat::Jpeg))?;
+    let (width, _) = image::image_dimensions(&source).ok()?;
+    if width <= THUMBNAIL_WIDTH {
+        return Some(image.to_string());
+    }
+
+    let thumbnail = Variant {
+        path: variant_path(image, Some(THUMBNAIL_WIDTH), extension(image)),
+        format,
+        width: Some(THUMBNAIL_WIDTH),
+    };
+    match write_variants(site, &source, [&thumbnail].into_iter()) {
+        Ok(()) => Some(thumbnail.path),
+        Err(e) => {
+            warn!("Failed to make a thumbnail of {}: {}", source.display(), e);
+            None
+        }
+    }
+}
+
+/// A copy of an image, at a path relative to the output directory
+struct Variant {
+    path: String,
+    format: ImageFormat,
+    /// Width it is scaled down to, or `None` for the original size
+    width: Option<u32>,
+}
+
+/// `tag` with the attributes that size it and offer the copies of `image`, given relative to
+/// the output directory, wrapped in a `<picture>` if any converted copies are offered
+fn responsive_tag(site: &Site, page: &str, tag: &str, image: &str) -> String {
+    let source = site.source_of(image);
+    let Some(format) = ImageFormat::from_path(&source)
+        .ok()
+        .filter(|format| matches!(format, ImageFormat::Png | ImageFormat::Jpeg))
+    else {
+        return tag.to_string();
+    };
+    let (width, height) = match image::image_dimensions(&source) {
+        Ok(dimensions) => dimensions,
+        Err(e) => {
+            debug!("Not resizing {}: {}", source.display(), e);
+            return tag.to_string();
+        }
+    };
+
+    // Each format's copies, narrowest first, ending with one at the original size
+    let widths = site.config.image_widths.iter().filter(|&&w| w < width);
+    let variants = |format: ImageFormat, extension: &str| -> Vec<Variant> {
+        widths
+            .clone()
+            .map(|&w| Some(w))
+            .chain([None])
+            .map(|width| Variant {
+                path: variant_path(image, width, extension),
+                format,
+                width,
+            })
+            .collect()
+    };
+    let mut resized = variants(format, extension(image));
+    // The original is linked as it is rather than copied
+    resized.pop();
+    let converted: Vec<(ConvertedFormat, Vec<Variant>)> = site
+        .config
+        .image_formats
+        .iter()
+        .map(|&converted| {
+            let format = converted.format();
+            (converted, variants(format, format.extensions_str()[0]))
+        })
+        .collect();
+
+    let all = resized
+        .iter()
+        .chain(converted.iter().flat_map(|(_, variants)| variants));
+    if let Err(e) = write_variants(site, &source, all) {
+        warn!("Failed to resize {}: {}", source.display(), e);
+        return tag.to_string();
+    }
+
+    let root = root_for(page);
+    let srcset = |candidates: &[(&str, u32)]| -> String {
+        candidates
+            .iter()
+            .map(|(path, width)| format!("{}{} {}w", root, path, width))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    // Each original-format candidate with the file it is read from, to compare copies against
+    let output_dir = site.output_dir();
+    let mut originals: Vec<(&str, u32, PathBuf)> = resized
+        .iter()
+        .map(|variant| {
+            let path = output_dir.join(&variant.path);
+            (variant.path.as_str(), variant.width.unwrap_or(width), path)
+        })
+        .collect();
+    originals.push((image, width, source.clone()));
+
+    let tag = tag.trim_end_matches('>').trim_end_matches('/').trim_end();
+    let mut tag = format!("{} width=\"{}\" height=\"{}\"", tag, width, height);
+    if originals.len() > 1 {
+        let candidates: Vec<(&str, u32)> = originals.iter().map(|(p, w, _)| (*p, *w)).collect();
+        tag.push_str(&format!(
+            " srcset=\"{}\" sizes=\"{}\"",
+            srcset(&candidates),
+            SIZES
+        ));
+    }
+    tag.push('>');
+
+    let size = |path: &Path| fs::metadata(path).map_or(u64::MAX, |m| m.len());
+    let mut sources = String::new();
+    for (converted, variants) in &converted {
+        // A copy is only worth offering if it is smaller than the original format at its width
+        let candidates: Vec<(&str, u32)> = variants
+            .iter()
+            .zip(&originals)
+            .filter(|(variant, (_, _, original))| {
+                size(&output_dir.join(&variant.path)) < size(original)
+            })
+            .map(|(variant, (_, width, _))| (variant.path.as_str(), *width))
+            .collect();
+        if candidates.is_empty() {
+            continue;
+        }
+        sources.push_str(&format!(
+            "<source type=\"{}\" srcset=\"{}\" sizes=\"{}\">",
+            converted.mime_type(),
+            srcset(&candidates),
+            SIZES
+        ));
+    }
+    match sources.is_empty() {
+        true => tag,
+        false => format!("<picture>{}{}</picture>", sources, tag),
+    }
+}
+
+/// Removes every resized and converted image, to be written again by the pages that still use
+/// them
+pub fn clear(site: &Site) -> Result<()> {
+    let dir = site.output_dir().join(RESIZED_DIR);
+    match fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(Error::io("remove", &dir, e)),
+        _ => Ok(()),
+    }
+}
+
+/// Writes each of `variants` of `source` that is missing or older than `source`, decoding the
+/// source only if any are
+fn write_variants<'a>(
+    site: &Site,
+    source: &Path,
+    variants: impl Iterator<Item = &'a Variant>,
+) -> Result<()> {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let original = modified(source);
+    let output_dir = site.output_dir();
+    let stale: Vec<&Variant> = variants
+        .filter(
+            |variant| match (modified(&output_dir.join(&variant.path)), original) {
+                (Some(copy), Some(original)) => copy < original,
+                _ => true,
+            },
+        )
+        .collect();
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    let image = image::open(source)
+        .map_err(|e| Error::Invalid(format!("Failed to read {}: {}", source.display(), e)))?;
+    for variant in stale {
+        let copy = match variant.width {
+            Some(width) => {
+                let height =
+                    u64::from(image.height()) * u64::from(width) / u64::from(image.width());
+                image.resize(width, height.max(1) as u32, FilterType::Lanczos3)
+            }
+            None => image.clone(),
+        };
+        let dest = output_dir.join(&variant.path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        atomic::write_with(&dest, |file| {
+            encode(&copy, variant.format, site.config.image_quality, file)
+        })
+        .map_err(|e| Error::io("write", &dest, e))?;
+        debug!("Wrote {} from {}", dest.display(), source.display());
+    }
+    Ok(())
+}
+
+/// Encodes `image` as `format` into `writer`, as small as the format allows at `quality`
+fn encode(
+    image: &DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+    writer: impl Write,
+) -> io::Result<()> {
+    let quality = quality.clamp(1, 100);
+    let result = match format {
+        ImageFormat::Jpeg => {
+            image.write_with_encoder(JpegEncoder::new_with_quality(writer, quality))
+        }
+        ImageFormat::Png => image.write_with_encoder(PngEncoder::new_with_quality(
+            writer,
+            CompressionType::Best,
+            Default::default(),
+        )),
+        ImageFormat::WebP => image.write_with_encoder(WebPEncoder::new_lossless(writer)),
+        ImageFormat::Avif => image.write_with_encoder(AvifEncoder::new_with_speed_quality(
+            writer, AVIF_SPEED, quality,
+        )),
+        format => return Err(io::Error::other(format!("Can't write {:?} images", format))),
+    };
+    result.map_err(io::Error::other)
+}
+
+/// Offset of the `>` closing the tag `html` starts with, skipping any in quoted attribute
+/// values, since alt text may contain one
+fn tag_end(html: &str) -> Option<usize> {
+    let mut quoted = false;
+    html.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        '>' if !quoted => Some(i),
+        _ => None,
+    })
+}
+
+/// The value of the `src` attribute of `tag`
+fn src(tag: &str) -> Option<&str> {
+    let start = tag.find(" src=\"")? + " src=\"".len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+/// Path relative to the output directory of the local image linked as `src` from the page at
+/// `page`, or `None` for remote images and links outside the output directory
+fn resolve(page: &str, src: &str) -> Option<String> {
+    if src.contains("://") || src.starts_with('/') || src.starts_with("data:") {
+        return None;
+    }
+    let mut parts: Vec<&str> = page.split('/').collect();
+    parts.pop();
+    for component in Path::new(src).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+fn extension(image: &str) -> &str {
+    image
+        .rsplit_once('.')
+        .map_or("", |(_, extension)| extension)
+}
+
+/// Path of the copy of `image` at `width` with `extension`, relative to the output directory,
+/// e.g. `resized/assets/images/cover-480w.webp` for `assets/images/cover.png`
+fn variant_path(image: &str, width: Option<u32>, extension: &str) -> String {
+    let stem = image.rsplit_once('.').map_or(image, |(stem, _)| stem);
+    match width {
+        Some(width) => format!("{}/{}-{}w.{}", RESIZED_DIR, stem, width, extension),
+        None => format!("{}/{}.{}", RESIZED_DIR, stem, extension),
+    }
+}
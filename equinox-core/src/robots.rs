@@ -0,0 +1,37 @@
+use crate::{
+    atomic,
+    error::{Error, Result},
+    site::Site,
+};
+use tracing::debug;
+
+/// Regenerates `robots.txt` in the output directory from the site's [`RobotsConfig`], pointing
+/// crawlers at the sitemap once the site has a base URL
+///
+/// Posts marked `noindex` aren't disallowed here: crawlers have to be let in to read the robots
+/// meta tag on their pages that keeps them out of search results.
+///
+/// [`RobotsConfig`]: crate::site::RobotsConfig
+pub fn write_robots(site: &Site) -> Result<()> {
+    let robots = &site.config.robots;
+    let mut txt = String::new();
+    for agent in &robots.blocked_agents {
+        txt.push_str(&format!("User-agent: {}\nDisallow: /\n\n", agent.trim()));
+    }
+    txt.push_str("User-agent: *\n");
+    if robots.disallow.is_empty() {
+        // An empty rule allows everything, as a group needs at least one
+        txt.push_str("Disallow:\n");
+    }
+    for path in &robots.disallow {
+        txt.push_str(&format!("Disallow: {}\n", path.trim()));
+    }
+    if !site.config.base_url.is_empty() {
+        txt.push_str(&format!("\nSitemap: {}\n", site.url_for("sitemap.xml")));
+    }
+
+    let path = site.output_dir().join("robots.txt");
+    atomic::write(&path, txt).map_err(|e| Error::io("write", &path, e))?;
+    debug!("Wrote {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,70 @@
+//! The search page and the index its script searches in the browser
+
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+    markdown,
+    site::Site,
+    templates::{ListedPost, Renderer},
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use tracing::debug;
+
+/// Path of the search page, relative to the output directory
+pub const SEARCH_PAGE: &str = "search.html";
+
+/// Path of the index the search page loads, relative to the output directory
+pub const SEARCH_INDEX: &str = "search.json";
+
+/// A post as listed in the search index
+#[derive(Debug, Serialize)]
+struct IndexedPost {
+    #[serde(flatten)]
+    listed: ListedPost,
+    tags: Vec<String>,
+    /// Every distinct word of the title, tags, and text, lowercased and separated by spaces
+    words: String,
+}
+
+/// Writes `search.json`, indexing the words of every post in `posts`, and the `search.html`
+/// page that searches it
+pub fn write_search(site: &Site, renderer: &Renderer, posts: &[Post]) -> Result<()> {
+    let index: Vec<IndexedPost> = posts
+        .iter()
+        .map(|post| IndexedPost {
+            listed: ListedPost::from_post(&site.config, post),
+            tags: post.meta.tags.clone(),
+            words: words(post),
+        })
+        .collect();
+    let json = serde_json::to_string(&index)
+        .map_err(|e| Error::Invalid(format!("Failed to serialize {}: {}", SEARCH_INDEX, e)))?;
+    let path = site.output_dir().join(SEARCH_INDEX);
+    atomic::write(&path, json).map_err(|e| Error::io("write", &path, e))?;
+    debug!("Wrote {} with {} posts", path.display(), index.len());
+
+    let html = renderer.render_search(&site.config, SEARCH_PAGE)?;
+    let path = site.write_page(SEARCH_PAGE, html)?;
+    debug!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// The distinct words of `post`, in the order they first appear
+fn words(post: &Post) -> String {
+    let text = format!(
+        "{} {} {} {}",
+        post.meta.title,
+        post.meta.category,
+        post.meta.tags.join(" "),
+        markdown::plain_text(&post.body)
+    );
+    let mut seen = HashSet::new();
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .filter(|word| seen.insert(word.clone()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,76 @@
+//! Posts grouped into a series, read in order
+
+use crate::{
+    content::{slugify, Post, PostList},
+    error::Result,
+    pagination,
+    site::{Site, SiteConfig},
+    taxonomy::group_by,
+    templates::{series_page_path, ListedPost, Renderer, SeriesNav, SeriesPart},
+};
+
+/// The parts of `post`'s series, oldest first: every published post in it, plus `post` itself
+/// if it is a draft. Empty if `post` isn't part of a series.
+pub fn series_parts<'a>(post: &Post, posts: &'a PostList) -> Vec<&'a Post> {
+    if post.meta.series.is_empty() {
+        return Vec::new();
+    }
+    let series = slugify(&post.meta.series);
+    posts
+        .iter()
+        .rev()
+        .filter(|other| other.id == post.id || other.is_published())
+        .filter(|other| slugify(&other.meta.series) == series)
+        .collect()
+}
+
+/// The series box of `post`, or `None` if it isn't part of a series among `posts`
+pub fn series_nav(site: &SiteConfig, post: &Post, posts: &[Post]) -> Option<SeriesNav> {
+    let parts = series_parts(post, posts);
+    let index = parts.iter().position(|part| part.id == post.id)?;
+    Some(SeriesNav {
+        name: post.meta.series.clone(),
+        url: series_page_path(&post.meta.series),
+        part: index + 1,
+        parts: parts
+            .iter()
+            .map(|part| SeriesPart {
+                title: part.meta.title.clone(),
+                url: site.post_url(part),
+                current: part.id == post.id,
+            })
+            .collect(),
+        previous: index
+            .checked_sub(1)
+            .map(|previous| ListedPost::from_post(site, parts[previous])),
+        next: parts
+            .get(index + 1)
+            .map(|next| ListedPost::from_post(site, next)),
+    })
+}
+
+/// Writes `series/<series>.html` for every series in `posts`, listing its parts oldest first
+pub fn write_series_pages(site: &Site, renderer: &Renderer, posts: &PostList) -> Result<()> {
+    let groups = group_by(site, posts, |post| {
+        if post.meta.series.is_empty() {
+            Vec::new()
+        } else {
+            vec![post.meta.series.clone()]
+        }
+    });
+
+    for (name, listed) in groups.into_values() {
+        let parts: Vec<ListedPost> = listed.into_iter().rev().collect();
+        // A series reads as one list, however many posts the other listings show per page
+        pagination::write_pages(
+            site,
+            &series_page_path(&name),
+            &parts,
+            0,
+            |path, posts, pagination| {
+                renderer.render_listing(&site.config, path, &name, posts, pagination)
+            },
+        )?;
+    }
+    Ok(())
+}
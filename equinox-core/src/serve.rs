@@ -0,0 +1,134 @@
+use crate::{
+    assets::content_type,
+    error::{Error, Result},
+};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+use tiny_http::{Header, Request, Response, Server};
+use tracing::{info, warn};
+
+/// A local HTTP server for previewing the output directory, so relative links, stylesheets, and
+/// the navbar behave as they will once deployed. Stops when dropped.
+pub struct PreviewServer {
+    root: PathBuf,
+    url: String,
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PreviewServer {
+    /// Starts serving `root` on a free port on localhost
+    pub fn start(root: &Path) -> Result<PreviewServer> {
+        let server = Server::http("127.0.0.1:0")
+            .map_err(|e| Error::Invalid(format!("Failed to start preview server: {}", e)))?;
+        let server = Arc::new(server);
+        let addr = server.server_addr().to_ip().ok_or_else(|| {
+            Error::Invalid("Preview server is not listening on an IP address".to_string())
+        })?;
+
+        let thread = thread::spawn({
+            let server = server.clone();
+            let root = root.to_path_buf();
+            move || {
+                for request in server.incoming_requests() {
+                    if let Err(e) = respond(&root, request) {
+                        warn!("Preview server: {}", e);
+                    }
+                }
+            }
+        });
+
+        info!("Serving {} at http://{}/", root.display(), addr);
+        Ok(PreviewServer {
+            root: root.to_path_buf(),
+            url: format!("http://{}/", addr),
+            server,
+            thread: Some(thread),
+        })
+    }
+
+    /// Directory being served
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// URL of the site's front page
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for PreviewServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn respond(root: &Path, request: Request) -> std::io::Result<()> {
+    let Some(path) = resolve(root, request.url()) else {
+        return request.respond(Response::from_string("Bad request").with_status_code(400));
+    };
+
+    match fs::read(&path) {
+        Ok(body) => {
+            let header = Header::from_bytes("Content-Type", content_type(&path))
+                .expect("content types are valid header values");
+            request.respond(Response::from_data(body).with_header(header))
+        }
+        Err(_) => {
+            let body = fs::read(root.join("404.html")).unwrap_or_else(|_| b"Not found".to_vec());
+            let header = Header::from_bytes("Content-Type", "text/html; charset=utf-8")
+                .expect("content types are valid header values");
+            request.respond(
+                Response::from_data(body)
+                    .with_header(header)
+                    .with_status_code(404),
+            )
+        }
+    }
+}
+
+/// Maps a request URL onto a file under `root`, serving `index.html` for directories. Returns
+/// `None` for paths that would escape `root`.
+fn resolve(root: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let path = percent_decode(path)?;
+
+    let mut resolved = root.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(part) => resolved.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+
+    if resolved.is_dir() {
+        resolved.push("index.html");
+    }
+    Some(resolved)
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
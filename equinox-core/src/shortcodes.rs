@@ -0,0 +1,507 @@
+//! Shortcodes written on a line of their own in a post, such as `{{gallery "photos/tokyo"}}` or
+//! `{{alert kind="warning" text="Back up first"}}`, expanded into markup Markdown can't express
+//!
+//! Shortcodes are swapped for placeholders before the post is rendered and expanded into the
+//! rendered HTML afterwards, so they work whether or not the site allows raw HTML. Each name is
+//! looked up in the site's [`Registry`], which starts with the built-in shortcodes and can be
+//! given more with [`Registry::register`].
+
+use crate::{html::escape, markdown, site::Site, templates::root_for};
+use std::{collections::HashMap, fmt, fs, path::Path, sync::Arc};
+use tracing::warn;
+
+/// File extensions of the images a gallery shows
+const GALLERY_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "avif", "svg"];
+
+/// Kinds of alert, each styled by an `alert-<kind>` class
+const ALERT_KINDS: &[&str] = &["note", "tip", "important", "warning", "caution"];
+
+/// Swaps a clicked video placeholder for the player, so nothing is loaded from the video site
+/// until a reader asks for it
+const VIDEO_SCRIPT: &str = r#"<script>
+document.addEventListener("click", function (event) {
+    var link = event.target.closest(".video-embed a");
+    if (!link) return;
+    event.preventDefault();
+    var iframe = document.createElement("iframe");
+    iframe.src = link.parentNode.dataset.src;
+    iframe.title = link.title;
+    iframe.allow = "autoplay; fullscreen; picture-in-picture";
+    iframe.allowFullscreen = true;
+    link.parentNode.replaceChildren(iframe);
+});
+</script>
+"#;
+
+/// A kind of shortcode, expanding each use of its name into markup
+pub trait Shortcode: Send + Sync {
+    /// The markup a use of the shortcode with `args` expands into, or why it can't be expanded
+    fn render(&self, context: &Context, args: &Args) -> Result<String, String>;
+
+    /// Paths of the files a use with `args` is made from besides the post, so its page is
+    /// rebuilt when they change
+    fn inputs(&self, _site: &Site, _args: &Args) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Markup added once to the end of the content of a page using the shortcode, such as a
+    /// script its markup needs
+    fn page_end(&self) -> &'static str {
+        ""
+    }
+}
+
+/// Where a shortcode is being expanded
+pub struct Context<'a> {
+    pub site: &'a Site,
+    /// Path of the page relative to the output directory
+    pub page: &'a str,
+    /// Position of this use among the shortcodes on the page, counting from 0
+    pub n: usize,
+}
+
+/// The arguments a shortcode was written with, as `"value"` or `name=value`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    positional: Vec<String>,
+    named: Vec<(String, String)>,
+}
+
+impl Args {
+    /// The argument `name`, or failing that the `position`th unnamed argument
+    pub fn get(&self, name: &str, position: usize) -> Option<&str> {
+        self.named(name)
+            .or_else(|| self.positional.get(position).map(String::as_str))
+    }
+
+    /// The argument `name`, only if it was given by name
+    pub fn named(&self, name: &str) -> Option<&str> {
+        self.named
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Like [`get`](Args::get), for arguments the shortcode can't do without
+    pub fn require(&self, name: &str, position: usize) -> Result<&str, String> {
+        self.get(name, position)
+            .ok_or_else(|| format!("missing {}", name))
+    }
+
+    /// Parses the arguments written after a shortcode's name
+    fn parse(text: &str) -> Result<Args, String> {
+        let mut args = Args::default();
+        let mut rest = text.trim_start();
+        while !rest.is_empty() {
+            let name_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '"');
+            let name = match name_end {
+                Some(end) if rest[end..].starts_with('=') && end > 0 => {
+                    let name = rest[..end].to_string();
+                    rest = &rest[end + 1..];
+                    Some(name)
+                }
+                _ => None,
+            };
+            let (value, after) = match rest.strip_prefix('"') {
+                Some(quoted) => quoted_value(quoted)?,
+                None if name.is_some() => {
+                    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    (rest[..end].to_string(), &rest[end..])
+                }
+                None => return Err(format!("unquoted argument {}", rest)),
+            };
+            match name {
+                Some(name) => args.named.push((name, value)),
+                None => args.positional.push(value),
+            }
+            rest = after.trim_start();
+        }
+        Ok(args)
+    }
+}
+
+/// The value of the quoted argument `text` starts with, after its opening quote, and the text
+/// after its closing quote. `\"` and `\\` stand for a quote and a backslash.
+fn quoted_value(text: &str) -> Result<(String, &str), String> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &text[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, escaped)) => value.push(escaped),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err("unterminated quote".to_string())
+}
+
+/// The shortcodes a site's posts can use, by name
+#[derive(Clone)]
+pub struct Registry {
+    shortcodes: HashMap<String, Arc<dyn Shortcode>>,
+}
+
+impl Registry {
+    /// A registry without any shortcodes, not even the built-in ones
+    pub fn empty() -> Registry {
+        Registry {
+            shortcodes: HashMap::new(),
+        }
+    }
+
+    /// Makes `{{name ...}}` expand with `shortcode`, replacing any shortcode of that name
+    pub fn register(&mut self, name: &str, shortcode: impl Shortcode + 'static) {
+        self.shortcodes
+            .insert(name.to_string(), Arc::new(shortcode));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Shortcode> {
+        self.shortcodes
+            .get(name)
+            .map(|shortcode| shortcode.as_ref())
+    }
+}
+
+/// The built-in `gallery`, `youtube`, `vimeo`, and `alert` shortcodes
+impl Default for Registry {
+    fn default() -> Registry {
+        let mut registry = Registry::empty();
+        registry.register("gallery", Gallery);
+        registry.register("youtube", YouTube);
+        registry.register("vimeo", Vimeo);
+        registry.register("alert", Alert);
+        registry
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.shortcodes.keys().collect();
+        names.sort();
+        f.debug_tuple("Registry").field(&names).finish()
+    }
+}
+
+/// A use of a registered shortcode found in a post
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    name: String,
+    /// The arguments, or why they couldn't be read
+    args: Result<Args, String>,
+    /// The line as written, shown instead of markup the shortcode can't expand
+    line: String,
+}
+
+impl Invocation {
+    /// The use of a shortcode in `registry` written as `line`, if it is one
+    fn parse(registry: &Registry, line: &str) -> Option<Invocation> {
+        let inner = line.trim().strip_prefix("{{")?.strip_suffix("}}")?.trim();
+        let (name, args) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+        registry.get(name)?;
+        Some(Invocation {
+            name: name.to_string(),
+            args: Args::parse(args),
+            line: line.trim().to_string(),
+        })
+    }
+}
+
+/// `body` with each shortcode line replaced by a placeholder, and the shortcodes it replaced
+pub fn extract(site: &Site, body: &str) -> (String, Vec<Invocation>) {
+    let mut invocations = Vec::new();
+    let mut in_code_block = false;
+    let lines: Vec<String> = body
+        .lines()
+        .map(|line| {
+            // Shortcodes shown in code blocks are left as they are written
+            if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+                in_code_block = !in_code_block;
+            }
+            match Invocation::parse(&site.shortcodes, line).filter(|_| !in_code_block) {
+                Some(invocation) => {
+                    invocations.push(invocation);
+                    placeholder(invocations.len() - 1)
+                }
+                None => line.to_string(),
+            }
+        })
+        .collect();
+    (lines.join("\n"), invocations)
+}
+
+/// `body` without its shortcode lines, for where their markup can't go
+pub fn strip(site: &Site, body: &str) -> String {
+    let (body, invocations) = extract(site, body);
+    let placeholders: Vec<String> = (0..invocations.len()).map(placeholder).collect();
+    body.lines()
+        .filter(|line| !placeholders.iter().any(|placeholder| placeholder == line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Expands the placeholders [`extract`] left in `html`, the rendered content of the page at
+/// `page`, into the markup of their `invocations`
+pub fn expand(site: &Site, page: &str, html: &str, invocations: &[Invocation]) -> String {
+    let mut html = html.to_string();
+    let mut page_ends: Vec<&'static str> = Vec::new();
+    // Last first, so `equinoxshortcode1` isn't found inside `equinoxshortcode10`
+    for (n, invocation) in invocations.iter().enumerate().rev() {
+        let Some(shortcode) = site.shortcodes.get(&invocation.name) else {
+            continue;
+        };
+        let context = Context { site, page, n };
+        let markup = invocation
+            .args
+            .as_ref()
+            .map_err(Clone::clone)
+            .and_then(|args| shortcode.render(&context, args));
+        let markup = match markup {
+            Ok(markup) => {
+                let page_end = shortcode.page_end();
+                if !page_end.is_empty() && !page_ends.contains(&page_end) {
+                    page_ends.push(page_end);
+                }
+                markup
+            }
+            Err(reason) => {
+                warn!("Can't expand {} on {}: {}", invocation.line, page, reason);
+                format!("<p>{}</p>", escape(&invocation.line))
+            }
+        };
+        // Placeholders in tight lists aren't wrapped in a paragraph
+        html = html
+            .replace(&format!("<p>{}</p>", placeholder(n)), &markup)
+            .replace(&placeholder(n), &markup);
+    }
+    for page_end in page_ends.into_iter().rev() {
+        html.push_str(page_end);
+    }
+    html
+}
+
+/// Paths of the files the shortcodes in `body` are made from, so a page can be rebuilt when
+/// they change
+pub fn inputs(site: &Site, body: &str) -> Vec<String> {
+    extract(site, body)
+        .1
+        .iter()
+        .flat_map(
+            |invocation| match (site.shortcodes.get(&invocation.name), &invocation.args) {
+                (Some(shortcode), Ok(args)) => shortcode.inputs(site, args),
+                _ => Vec::new(),
+            },
+        )
+        .collect()
+}
+
+/// `{{gallery "photos/tokyo"}}`: a grid of thumbnails of the images in a directory of the
+/// assets, each linking to the full image. The links of each gallery on a page share a
+/// `data-lightbox` group, which lightbox scripts use to step through them.
+struct Gallery;
+
+impl Shortcode for Gallery {
+    fn render(&self, context: &Context, args: &Args) -> Result<String, String> {
+        let root = root_for(context.page);
+        let mut html = String::from("<div class=\"gallery\">\n");
+        for path in gallery_paths(context.site, args.require("dir", 0)?) {
+            let alt = Path::new(&path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().replace(['-', '_'], " "))
+                .unwrap_or_default();
+            html.push_str(&format!(
+                "<a class=\"gallery-item\" href=\"{root}{path}\" data-lightbox=\"gallery-{n}\">\
+                 <img src=\"{root}{path}\" alt=\"{alt}\" loading=\"lazy\" /></a>\n",
+                root = root,
+                path = escape(&path),
+                n = context.n + 1,
+                alt = escape(&alt),
+            ));
+        }
+        html.push_str("</div>\n");
+        Ok(html)
+    }
+
+    fn inputs(&self, site: &Site, args: &Args) -> Vec<String> {
+        args.get("dir", 0)
+            .map(|dir| gallery_paths(site, dir))
+            .unwrap_or_default()
+    }
+}
+
+/// Paths relative to the output directory of the images in the assets directory `dir`, by
+/// file name
+fn gallery_paths(site: &Site, dir: &str) -> Vec<String> {
+    let dir = dir.trim_matches('/');
+    let dir = dir.strip_prefix("assets/").unwrap_or(dir);
+    if Path::new(dir)
+        .components()
+        .any(|c| !matches!(c, std::path::Component::Normal(_)))
+    {
+        warn!("Gallery directory {} is outside the assets directory", dir);
+        return Vec::new();
+    }
+
+    let path = site.assets_dir().join(dir);
+    let entries = match fs::read_dir(&path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Failed to read gallery directory {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with('.'))
+        .filter(|name| {
+            Path::new(name).extension().is_some_and(|ext| {
+                GALLERY_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+            })
+        })
+        .collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| format!("assets/{}/{}", dir, name))
+        .collect()
+}
+
+/// `{{youtube "dQw4w9WgXcQ"}}`: a YouTube video, by id or URL
+struct YouTube;
+
+impl Shortcode for YouTube {
+    fn render(&self, _context: &Context, args: &Args) -> Result<String, String> {
+        let video = args.require("id", 0)?;
+        let id = youtube_id(video).ok_or_else(|| format!("{} isn't a YouTube video", video))?;
+        Ok(video_embed(
+            &format!("https://www.youtube-nocookie.com/embed/{}?autoplay=1", id),
+            &format!("https://www.youtube.com/watch?v={}", id),
+            Some(&format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", id)),
+            "Play video on YouTube",
+        ))
+    }
+
+    fn page_end(&self) -> &'static str {
+        VIDEO_SCRIPT
+    }
+}
+
+/// `{{vimeo "76979871"}}`: a Vimeo video, by id or URL
+struct Vimeo;
+
+impl Shortcode for Vimeo {
+    fn render(&self, _context: &Context, args: &Args) -> Result<String, String> {
+        let video = args.require("id", 0)?;
+        let id = vimeo_id(video).ok_or_else(|| format!("{} isn't a Vimeo video", video))?;
+        Ok(video_embed(
+            &format!("https://player.vimeo.com/video/{}?autoplay=1&dnt=1", id),
+            &format!("https://vimeo.com/{}", id),
+            None,
+            "Play video on Vimeo",
+        ))
+    }
+
+    fn page_end(&self) -> &'static str {
+        VIDEO_SCRIPT
+    }
+}
+
+/// A placeholder for the player at `player`, linking to the video's page at `page` until it is
+/// clicked, showing `thumbnail` if there is one
+fn video_embed(player: &str, page: &str, thumbnail: Option<&str>, title: &str) -> String {
+    let thumbnail = thumbnail
+        .map(|src| format!("<img src=\"{}\" alt=\"\" loading=\"lazy\" />", escape(src)))
+        .unwrap_or_default();
+    format!(
+        "<div class=\"video-embed\" data-src=\"{player}\">\
+         <a href=\"{page}\" title=\"{title}\">{thumbnail}\
+         <span class=\"video-embed-play\">&#9654;</span></a></div>\n",
+        player = escape(player),
+        page = escape(page),
+        title = escape(title),
+        thumbnail = thumbnail,
+    )
+}
+
+/// The id of the YouTube video `video`, given as an id or a watch, share, embed, or shorts URL
+fn youtube_id(video: &str) -> Option<String> {
+    let id = match video.split_once("://") {
+        None => video,
+        Some((_, url)) => {
+            let url = url.trim_start_matches("www.").trim_start_matches("m.");
+            if let Some(query) = url.strip_prefix("youtube.com/watch?") {
+                query.split('&').find_map(|pair| pair.strip_prefix("v="))?
+            } else {
+                [
+                    "youtu.be/",
+                    "youtube.com/embed/",
+                    "youtube.com/shorts/",
+                    "youtube-nocookie.com/embed/",
+                ]
+                .iter()
+                .find_map(|prefix| url.strip_prefix(prefix))?
+                .split(['?', '&', '/', '#'])
+                .next()?
+            }
+        }
+    };
+    let valid = id.len() == 11
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| id.to_string())
+}
+
+/// The id of the Vimeo video `video`, given as an id or a video or player URL
+fn vimeo_id(video: &str) -> Option<String> {
+    let id = match video.split_once("://") {
+        None => video,
+        Some((_, url)) => {
+            let url = url.trim_start_matches("www.");
+            ["vimeo.com/", "player.vimeo.com/video/"]
+                .iter()
+                .find_map(|prefix| url.strip_prefix(prefix))?
+                .split(['?', '&', '/', '#'])
+                .next()?
+        }
+    };
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_digit());
+    valid.then(|| id.to_string())
+}
+
+/// `{{alert kind="warning" text="Back up first"}}`: a callout box around Markdown text. The
+/// kind is one of [`ALERT_KINDS`], `note` if not given, and titles the box unless it has a
+/// `title`.
+struct Alert;
+
+impl Shortcode for Alert {
+    fn render(&self, context: &Context, args: &Args) -> Result<String, String> {
+        let text = args.require("text", 0)?;
+        let kind = args.named("kind").unwrap_or("note").to_lowercase();
+        if !ALERT_KINDS.contains(&kind.as_str()) {
+            return Err(format!(
+                "unknown kind {}, expected one of {}",
+                kind,
+                ALERT_KINDS.join(", ")
+            ));
+        }
+        let title = match args.named("title") {
+            Some(title) => title.to_string(),
+            None => kind[..1].to_uppercase() + &kind[1..],
+        };
+        Ok(format!(
+            "<div class=\"alert alert-{kind}\">\n<p class=\"alert-title\">{title}</p>\n{text}</div>\n",
+            kind = kind,
+            title = escape(&title),
+            text = markdown::to_html(text, &context.site.config.markdown_options()),
+        ))
+    }
+}
+
+/// Text standing in for the `n`th shortcode, which Markdown renders as a paragraph unchanged
+fn placeholder(n: usize) -> String {
+    format!("equinoxshortcode{}", n)
+}
@@ -0,0 +1,1428 @@
+use crate::{
+    accessibility, archive, assets, atomic,
+    cache::{self, BuildCache},
+    content::{slugify, ContentStore, Post, PostList},
+    css,
+    error::{Error, Result},
+    feed, fingerprint, html, index, links,
+    markdown::{self, MarkdownOptions},
+    not_found, permalink, pwa,
+    redirects::{self, RedirectFormat, RedirectLog},
+    related,
+    responsive::{self, ConvertedFormat},
+    robots, search, series, shortcodes, sitemap, sri, taxonomy,
+    templates::{
+        archive_year_path, author_page_path, category_page_path, root_for, series_page_path,
+        tag_page_path, ListedPost, PostLinks, Renderer, ARCHIVE_PAGE,
+    },
+    themes::{self, Theme},
+    validate, versions,
+};
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info, warn};
+
+/// Site settings stored in `equinox.toml` at the root of a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteConfig {
+    /// Site title shown in page titles
+    pub title: String,
+    /// Public URL the site is served from, e.g. `https://example.com/`
+    pub base_url: String,
+    /// Where each post's page goes, e.g. `posts/:slug.html`, `:year/:month/:slug.html`, or
+    /// `posts/:slug/` for a directory per post. See [`permalink`] for the placeholders.
+    pub permalink: String,
+    /// Directory generated pages are written to, relative to the project root
+    pub output_dir: PathBuf,
+    /// Name of the theme used to render pages: a built-in theme or a directory under `themes/`
+    pub theme: String,
+    /// Keep raw HTML written in posts (after sanitizing) instead of escaping it
+    pub raw_html: bool,
+    /// Convert straight quotes, `--`, and `...` in posts to curly quotes, em dashes, and ellipses
+    pub smart_typography: bool,
+    /// Render `$...$` and `$$...$$` in posts as math, typeset in the browser by KaTeX
+    pub math: bool,
+    /// Levels each heading in posts is moved down by, so `#` becomes `<h3>` at 2 and the page's
+    /// own `<h1>` and `<h2>` stay above a post's headings
+    pub heading_offset: u8,
+    /// Show the estimated reading time under each post's title
+    pub show_reading_time: bool,
+    /// End each post by an author in the authors table with a card showing their picture, bio,
+    /// and links, unless the post sets `no_author_card`
+    pub author_cards: bool,
+    /// Put each post's whole rendered content in `feed.json` rather than only a summary
+    pub feed_full_content: bool,
+    /// Strip comments and collapse whitespace in generated pages to make them smaller
+    pub minify_html: bool,
+    /// The site's own stylesheets linked on every page after the theme's, relative to the
+    /// output directory (e.g. `assets/custom.css`) or absolute URLs
+    pub stylesheets: Vec<String>,
+    /// Load the theme's stylesheets and the site's own from one minified file
+    pub bundle_css: bool,
+    /// Pin the hashes of scripts and stylesheets loaded from other sites, so browsers refuse
+    /// them if they change. They are fetched and pinned when the site is built from the command
+    /// line or deployed.
+    pub subresource_integrity: bool,
+    /// Warn after every build about links to pages and files the site doesn't have
+    pub check_links: bool,
+    /// Also request every page the site links to on other sites, warning about those that can't
+    /// be reached. Checked when the site is rebuilt from the editor or built from the command
+    /// line.
+    pub check_external_links: bool,
+    /// Warn after every build about generated pages with invalid HTML structure
+    pub validate_html: bool,
+    /// Warn after every build, and before deploying, about images without alt text, skipped
+    /// heading levels, pages without a language, and theme colors too faint to read
+    pub audit_accessibility: bool,
+    /// Committing to the project's Git repository whenever a post or the site is published
+    pub auto_commit: AutoCommitConfig,
+    /// Link pages to copies of the site's stylesheets, scripts, images, and fonts named after
+    /// their contents, so hosts can let browsers cache them for good
+    pub fingerprint_assets: bool,
+    /// HTML added to the end of `<head>` on every page, e.g. analytics tags or verification meta
+    pub head_html: String,
+    /// HTML added just before `</body>` on every page, e.g. custom scripts
+    pub body_end_html: String,
+    /// Analytics service every page reports visits to
+    pub analytics: AnalyticsConfig,
+    /// Comment widget shown at the bottom of every post
+    pub comments: CommentsConfig,
+    /// Where other sites send webmentions about the site's pages, e.g. a webmention.io
+    /// endpoint, advertised in every page's head
+    pub webmention_endpoint: String,
+    /// Notify the sites posts link to with webmentions after each deploy
+    pub send_webmentions: bool,
+    /// URLs sent a JSON POST describing each post after it is published, e.g. to start a CI
+    /// build, purge a cache, or post to a chat
+    pub webhooks: Vec<String>,
+    /// What `robots.txt` asks of crawlers
+    pub robots: RobotsConfig,
+    /// Markdown shown on the page hosts serve for missing pages
+    pub not_found: String,
+    /// How links to the pages posts had before being renamed are sent to their new ones
+    pub redirects: RedirectFormat,
+    /// Installing the site as an app that can be read offline
+    pub pwa: PwaConfig,
+    /// Posts shown on each page of the index and category and tag listings before they continue
+    /// on another page, or 0 to list every post on one page
+    pub posts_per_page: usize,
+    /// Posts sharing tags or a category linked at the bottom of each post, or 0 for none
+    pub related_posts: usize,
+    /// Widths in pixels of the resized copies made of each PNG and JPEG image in a post, offered
+    /// to browsers so small screens can load a smaller one. Empty to link only the original.
+    pub image_widths: Vec<u32>,
+    /// Formats each PNG and JPEG image in a post is also converted to, in order of preference.
+    /// Browsers that support one load it instead wherever it comes out smaller.
+    pub image_formats: Vec<ConvertedFormat>,
+    /// Quality from 1 to 100 that imported JPEGs are recompressed at and AVIF copies are
+    /// encoded at. At 100 imported JPEGs are kept as they are.
+    pub image_quality: u8,
+    /// Directory of stylesheets, images, and other static files, relative to the project root.
+    /// Its contents are mirrored into `assets/` in the output directory on every build.
+    pub assets_dir: PathBuf,
+    /// Where the generated site is deployed to
+    #[serde(skip_serializing_if = "DeployConfig::is_empty")]
+    pub deploy: DeployConfig,
+    /// Where the project's sources are backed up to after every change, so it can be restored
+    /// on another machine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<BackupTarget>,
+    /// Mastodon account each new post is shared from after the deploy that puts it online
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mastodon: Option<MastodonConfig>,
+    /// Bluesky account each new post is shared from after the deploy that puts it online
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bluesky: Option<BlueskyConfig>,
+    /// People posts are credited to, each configured as an `[[authors]]` table. Posts by one of
+    /// them link to a page listing everything they wrote.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<Author>,
+    /// Links shown in the navigation bar of every page, in order
+    pub nav: Vec<NavLink>,
+}
+
+/// Deployment targets, each configured under `[deploy.<name>]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeployConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sftp: Option<SftpTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<GitTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub s3: Option<S3Target>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub netlify: Option<NetlifyTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rsync: Option<RsyncTarget>,
+}
+
+impl DeployConfig {
+    pub fn is_empty(&self) -> bool {
+        self.sftp.is_none()
+            && self.git.is_none()
+            && self.s3.is_none()
+            && self.netlify.is_none()
+            && self.rsync.is_none()
+    }
+}
+
+/// An analytics service that doesn't track readers across sites, configured under `[analytics]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyticsConfig {
+    pub provider: AnalyticsProvider,
+    /// The site's code on GoatCounter (or the URL of a self-hosted counter), or its domain on
+    /// Plausible
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyticsProvider {
+    #[default]
+    None,
+    GoatCounter,
+    Plausible,
+}
+
+impl AnalyticsProvider {
+    /// Every provider, in the order the preferences list them
+    pub const ALL: [AnalyticsProvider; 3] = [
+        AnalyticsProvider::None,
+        AnalyticsProvider::GoatCounter,
+        AnalyticsProvider::Plausible,
+    ];
+}
+
+impl AnalyticsConfig {
+    /// The script tag added to every page, or `None` if analytics are off or have no code
+    pub fn snippet(&self) -> Option<String> {
+        let code = self.code.trim();
+        if code.is_empty() {
+            return None;
+        }
+        match self.provider {
+            AnalyticsProvider::None => None,
+            AnalyticsProvider::GoatCounter => {
+                let endpoint = match code.contains("://") {
+                    true => format!("{}/count", code.trim_end_matches('/')),
+                    false => format!("https://{}.goatcounter.com/count", code),
+                };
+                Some(format!(
+                    "<script data-goatcounter=\"{}\" async src=\"https://gc.zgo.at/count.js\"></script>",
+                    html::escape(&endpoint)
+                ))
+            }
+            AnalyticsProvider::Plausible => Some(format!(
+                "<script defer data-domain=\"{}\" src=\"https://plausible.io/js/script.js\"></script>",
+                html::escape(code)
+            )),
+        }
+    }
+}
+
+/// The web app manifest and offline service worker, configured under `[pwa]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PwaConfig {
+    pub enabled: bool,
+    /// Name shown under the installed app's icon, the site title if empty
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub short_name: String,
+    /// The app's icon, relative to the output directory
+    pub icon: String,
+    /// Colour of the browser's toolbar and of the app's title bar, as a CSS colour
+    pub theme_color: String,
+    /// Colour of the screen shown while the app starts
+    pub background_color: String,
+}
+
+impl Default for PwaConfig {
+    fn default() -> Self {
+        PwaConfig {
+            enabled: false,
+            short_name: String::new(),
+            icon: "assets/favicon.png".to_string(),
+            theme_color: "#ffffff".to_string(),
+            background_color: "#ffffff".to_string(),
+        }
+    }
+}
+
+/// Paths and crawlers `robots.txt` turns away, configured under `[robots]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RobotsConfig {
+    /// Paths every crawler is asked to stay out of, e.g. `/drafts/`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disallow: Vec<String>,
+    /// User agents asked to stay out of the whole site, e.g. `GPTBot`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub blocked_agents: Vec<String>,
+}
+
+/// What is committed to the project's Git repository each time a post or the site is
+/// published, configured under `[auto_commit]`. The project becomes a repository the first
+/// time if it isn't one yet.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoCommitConfig {
+    /// The post sources and the site's assets
+    pub content: bool,
+    /// The generated site in the output directory
+    pub output: bool,
+}
+
+impl AutoCommitConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.content || self.output
+    }
+}
+
+/// Comments kept in a GitHub repository's discussions or issues, configured under `[comments]`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommentsConfig {
+    pub provider: CommentsProvider,
+    /// The repository comments are kept in, as `owner/name`
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub repo: String,
+    /// Discussion category of giscus threads, or label of utterances issues
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub category: String,
+    /// Ids giscus needs for the repository and category, shown on <https://giscus.app>
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub repo_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub category_id: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentsProvider {
+    #[default]
+    None,
+    Giscus,
+    Utterances,
+}
+
+impl CommentsProvider {
+    /// Every provider, in the order the preferences list them
+    pub const ALL: [CommentsProvider; 3] = [
+        CommentsProvider::None,
+        CommentsProvider::Giscus,
+        CommentsProvider::Utterances,
+    ];
+}
+
+impl CommentsConfig {
+    /// The script tag loading the widget, or `None` if comments are off or not fully
+    /// configured. Each post's thread is found by the path of its page.
+    pub fn snippet(&self) -> Option<String> {
+        let repo = self.repo.trim();
+        if repo.is_empty() {
+            return None;
+        }
+        match self.provider {
+            CommentsProvider::None => None,
+            CommentsProvider::Giscus => {
+                if self.repo_id.is_empty()
+                    || self.category.is_empty()
+                    || self.category_id.is_empty()
+                {
+                    return None;
+                }
+                Some(format!(
+                    "<script src=\"https://giscus.app/client.js\" data-repo=\"{}\" data-repo-id=\"{}\" \
+                     data-category=\"{}\" data-category-id=\"{}\" data-mapping=\"pathname\" \
+                     data-reactions-enabled=\"1\" data-input-position=\"bottom\" \
+                     data-theme=\"preferred_color_scheme\" data-loading=\"lazy\" \
+                     crossorigin=\"anonymous\" async></script>",
+                    html::escape(repo),
+                    html::escape(self.repo_id.trim()),
+                    html::escape(self.category.trim()),
+                    html::escape(self.category_id.trim())
+                ))
+            }
+            CommentsProvider::Utterances => {
+                let label = match self.category.trim() {
+                    "" => String::new(),
+                    label => format!(" label=\"{}\"", html::escape(label)),
+                };
+                Some(format!(
+                    "<script src=\"https://utteranc.es/client.js\" repo=\"{}\" \
+                     issue-term=\"pathname\"{} theme=\"preferred-color-scheme\" \
+                     crossorigin=\"anonymous\" async></script>",
+                    html::escape(repo),
+                    label
+                ))
+            }
+        }
+    }
+}
+
+/// A branch of a git repository the output directory is committed to and pushed, e.g. the
+/// `gh-pages` branch GitHub Pages serves from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GitTarget {
+    /// Name of a remote in the project's repository, or a repository URL
+    pub remote: String,
+    #[serde(default = "GitTarget::default_branch")]
+    pub branch: String,
+}
+
+impl GitTarget {
+    pub fn default_branch() -> String {
+        "gh-pages".to_string()
+    }
+}
+
+/// An S3 bucket serving the site, optionally behind a CloudFront distribution. Credentials come
+/// from the usual AWS environment variables or `~/.aws/credentials`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct S3Target {
+    pub bucket: String,
+    pub region: String,
+    /// Key prefix the output directory is uploaded under, e.g. `blog/`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
+    /// Profile in `~/.aws/credentials` to use when no credentials are set in the environment
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub profile: String,
+    /// Endpoint of an S3-compatible service to use instead of AWS, e.g. `http://localhost:9000`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub endpoint: String,
+    /// Delete objects under the prefix that are no longer in the output directory
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub delete_removed: bool,
+    /// CloudFront distribution to invalidate the changed paths of after uploading
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub cloudfront_distribution: String,
+}
+
+/// A site on Netlify, replaced by each deploy. The access token is kept in the OS keyring, or
+/// given in `NETLIFY_AUTH_TOKEN`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetlifyTarget {
+    /// The site's API ID or its domain, e.g. `my-blog.netlify.app`
+    pub site_id: String,
+}
+
+/// A Mastodon account, configured under `[mastodon]`. Its access token, which needs the
+/// `write:statuses` scope, is kept in the OS keyring or given in `MASTODON_ACCESS_TOKEN`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MastodonConfig {
+    /// Address of the account's server, e.g. `https://mastodon.social`
+    pub instance: String,
+    #[serde(default)]
+    pub visibility: MastodonVisibility,
+}
+
+/// Who sees the statuses sharing new posts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MastodonVisibility {
+    #[default]
+    Public,
+    /// Public, but kept out of the public timelines
+    Unlisted,
+    /// Followers only
+    Private,
+}
+
+impl MastodonVisibility {
+    /// The name Mastodon's API gives it
+    pub fn name(self) -> &'static str {
+        match self {
+            MastodonVisibility::Public => "public",
+            MastodonVisibility::Unlisted => "unlisted",
+            MastodonVisibility::Private => "private",
+        }
+    }
+}
+
+/// A Bluesky account, configured under `[bluesky]`. Posts are shared with an app password,
+/// made in Bluesky's settings and kept in the OS keyring or given in `BLUESKY_APP_PASSWORD`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlueskyConfig {
+    /// The account's handle, e.g. `me.bsky.social`
+    pub handle: String,
+    /// Server hosting the account, for accounts not on Bluesky's own
+    #[serde(default = "BlueskyConfig::default_service")]
+    pub service: String,
+}
+
+impl BlueskyConfig {
+    pub fn default_service() -> String {
+        "https://bsky.social".to_string()
+    }
+}
+
+/// A service the project's post sources, assets, templates, and settings are backed up to,
+/// configured under `[backup]` with `service` naming which one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "service", rename_all = "lowercase")]
+pub enum BackupTarget {
+    /// An S3 bucket, with credentials found as for deploying to S3
+    S3(S3Backup),
+    /// A folder on a WebDAV server such as Nextcloud. The password is kept in the OS keyring,
+    /// or given in `EQUINOX_BACKUP_SECRET`.
+    WebDav(WebDavBackup),
+    /// A folder in Dropbox. The token is kept in the OS keyring, or given in
+    /// `EQUINOX_BACKUP_SECRET`.
+    Dropbox(DropboxBackup),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct S3Backup {
+    pub bucket: String,
+    pub region: String,
+    /// Key prefix the project is backed up under, e.g. `backups/blog/`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
+    /// Profile in `~/.aws/credentials` to use when no credentials are set in the environment
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub profile: String,
+    /// Endpoint of an S3-compatible service to use instead of AWS, e.g. `http://localhost:9000`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub endpoint: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebDavBackup {
+    /// URL of the folder the project is backed up into, e.g.
+    /// `https://cloud.example.com/remote.php/dav/files/me/blog`
+    pub url: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DropboxBackup {
+    /// Folder the project is backed up into, e.g. `/Backups/blog`
+    pub folder: String,
+    /// Key of the Dropbox app the keyring holds a refresh token for. Without one, the keyring
+    /// holds an access token, which Dropbox only lets live a few hours.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub app_key: String,
+}
+
+/// A directory on a server reached over SFTP. The password, if any, is kept in the OS keyring
+/// rather than in `equinox.toml`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SftpTarget {
+    pub host: String,
+    #[serde(default = "SftpTarget::default_port")]
+    pub port: u16,
+    pub username: String,
+    /// Directory on the server the output directory is uploaded into
+    pub remote_dir: String,
+}
+
+impl SftpTarget {
+    pub fn default_port() -> u16 {
+        22
+    }
+}
+
+/// A directory on a server synced with `rsync` over SSH, sending only what changed. Needs
+/// `rsync` installed on both ends and an SSH key the server accepts, as there is no way to
+/// give a password.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RsyncTarget {
+    pub host: String,
+    #[serde(default = "SftpTarget::default_port")]
+    pub port: u16,
+    /// Account to sign in as, or empty for the one SSH picks
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub username: String,
+    /// Directory on the server the output directory is synced into
+    pub remote_dir: String,
+    /// Delete files in the remote directory that are no longer in the output directory
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub delete_removed: bool,
+}
+
+/// An entry in the site's navigation bar
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NavLink {
+    pub label: String,
+    /// Page path relative to the output directory (e.g. `tech.html`), or an absolute URL
+    pub url: String,
+    /// Pushed to the right-hand end of the bar
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub right: bool,
+}
+
+impl NavLink {
+    pub fn new(label: &str, url: &str, right: bool) -> NavLink {
+        NavLink {
+            label: label.to_string(),
+            url: url.to_string(),
+            right,
+        }
+    }
+
+    /// Whether the URL is used as written rather than relative to the output directory
+    pub fn is_absolute(&self) -> bool {
+        self.url.contains("://") || self.url.starts_with("mailto:") || self.url.starts_with('/')
+    }
+}
+
+/// An entry in the site's authors table, matched against the author each post names
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Author {
+    pub name: String,
+    /// A line or two shown with each of the author's posts and on their page
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub bio: String,
+    /// Picture of the author, relative to the output directory (e.g. `assets/me.jpg`) or an
+    /// absolute URL
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub avatar: String,
+    /// The author's own website or profile
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+    /// The author's profiles elsewhere, shown on their card, each configured as an
+    /// `[[authors.links]]` table
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<ProfileLink>,
+}
+
+/// A link to one of an author's profiles, e.g. on Mastodon or GitHub
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileLink {
+    pub label: String,
+    pub url: String,
+}
+
+/// A section of the site linked from the navigation bar, whose page lists one category's posts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// The navigation link's label, e.g. `Technical`
+    pub label: String,
+    /// The category listed on the linked page, e.g. `tech` for `tech.html`
+    pub category: String,
+}
+
+impl SiteConfig {
+    /// The sections of the site: left-hand navigation links to a top level page other than the
+    /// front page and the archive, each taken to list the category its page is named after
+    pub fn sections(&self) -> Vec<Section> {
+        self.nav
+            .iter()
+            .filter(|link| !link.right && !link.is_absolute())
+            .filter_map(|link| {
+                let page = link.url.trim_start_matches("./");
+                let stem = page.strip_suffix(".html")?;
+                // Categories named like the site's own pages are listed under a prefix
+                let category = stem
+                    .strip_prefix("category-")
+                    .filter(|category| category_page_path(category) == page)
+                    .unwrap_or(stem);
+                let listed = !page.contains('/') && page != "index.html" && page != ARCHIVE_PAGE;
+                (listed && category_page_path(category) == page).then(|| Section {
+                    label: link.label.clone(),
+                    category: category.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// The entry of the authors table for the author `name`, ignoring case and punctuation
+    pub fn author(&self, name: &str) -> Option<&Author> {
+        let slug = slugify(name);
+        self.authors
+            .iter()
+            .find(|author| !slug.is_empty() && slugify(&author.name) == slug)
+    }
+
+    /// How post content is rendered on this site
+    pub fn markdown_options(&self) -> MarkdownOptions {
+        MarkdownOptions {
+            raw_html: self.raw_html,
+            smart_typography: self.smart_typography,
+            math: self.math,
+            heading_offset: self.heading_offset,
+            table_of_contents: false,
+        }
+    }
+
+    /// Path of `post`'s page relative to the output directory
+    pub fn post_path(&self, post: &Post) -> String {
+        permalink::page_path(&self.permalink, post)
+    }
+
+    /// Link to `post`'s page relative to the output directory, which leaves out the
+    /// `index.html` of directory-style permalinks
+    pub fn post_url(&self, post: &Post) -> String {
+        permalink::url(&self.post_path(post)).to_string()
+    }
+
+    /// Relative link from `post`'s page to `path`, given relative to the output directory
+    pub fn link_from(&self, post: &Post, path: &str) -> String {
+        format!("{}{}", root_for(&self.post_path(post)), path)
+    }
+
+    /// Absolute URL of a page given its path relative to the output directory. URLs that are
+    /// already absolute are returned unchanged.
+    pub fn url_for(&self, path: &str) -> String {
+        if path.contains("://") {
+            return path.to_string();
+        }
+        let path = path.trim_start_matches("./").trim_start_matches('/');
+        format!("{}/{}", self.base_url.trim_end_matches('/'), path)
+    }
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            title: String::new(),
+            base_url: String::new(),
+            permalink: permalink::DEFAULT.to_string(),
+            output_dir: PathBuf::from("."),
+            theme: "default".to_string(),
+            raw_html: false,
+            smart_typography: false,
+            math: false,
+            heading_offset: 2,
+            show_reading_time: false,
+            author_cards: true,
+            feed_full_content: false,
+            minify_html: false,
+            stylesheets: Vec::new(),
+            bundle_css: false,
+            subresource_integrity: false,
+            check_links: true,
+            check_external_links: false,
+            validate_html: true,
+            audit_accessibility: true,
+            auto_commit: AutoCommitConfig::default(),
+            fingerprint_assets: false,
+            head_html: String::new(),
+            body_end_html: String::new(),
+            analytics: AnalyticsConfig::default(),
+            comments: CommentsConfig::default(),
+            webmention_endpoint: String::new(),
+            send_webmentions: false,
+            webhooks: Vec::new(),
+            robots: RobotsConfig::default(),
+            redirects: RedirectFormat::default(),
+            pwa: PwaConfig::default(),
+            not_found: "The page you were looking for doesn't exist. It may have moved, or the \
+                link that led here may be mistyped."
+                .to_string(),
+            posts_per_page: 10,
+            related_posts: 3,
+            image_widths: vec![480, 960, 1440],
+            image_formats: vec![ConvertedFormat::Webp],
+            image_quality: 80,
+            assets_dir: PathBuf::from("assets"),
+            deploy: DeployConfig::default(),
+            backup: None,
+            mastodon: None,
+            bluesky: None,
+            authors: Vec::new(),
+            nav: vec![
+                NavLink::new("Home", "index.html", false),
+                NavLink::new("Technical", "tech.html", false),
+                NavLink::new("Anime", "anime.html", false),
+                NavLink::new("Life", "life.html", false),
+                NavLink::new("Archive", "archive.html", false),
+                NavLink::new("Resume", "resume.html", true),
+                NavLink::new("Contact", "contact.html", true),
+            ],
+        }
+    }
+}
+
+/// A site project: a directory containing `equinox.toml`, post sources, templates, and assets
+///
+/// Publishing a post through [`Site::publish`] stores its source, renders its page, and
+/// regenerates every page that lists posts.
+#[derive(Debug, Clone)]
+pub struct Site {
+    pub root: PathBuf,
+    pub config: SiteConfig,
+    /// Shortcodes the site's posts can use, the built-in ones unless more are registered
+    pub shortcodes: shortcodes::Registry,
+}
+
+impl Site {
+    pub const CONFIG_FILE: &'static str = "equinox.toml";
+
+    /// Directories inside the project that hold equinox's own files rather than site content
+    pub const RESERVED_DIRS: &'static [&'static str] =
+        &["content", "templates", themes::THEMES_DIR];
+
+    pub fn exists(root: &Path) -> bool {
+        root.join(Site::CONFIG_FILE).is_file()
+    }
+
+    pub fn open(root: &Path) -> Result<Site> {
+        let path = root.join(Site::CONFIG_FILE);
+        let contents = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        let config: SiteConfig =
+            toml::from_str(&contents).map_err(|e| Error::parse("settings", Some(&path), e))?;
+        permalink::validate(&config.permalink)
+            .map_err(|e| Error::parse("settings", Some(&path), e))?;
+
+        Ok(Site {
+            root: root.to_path_buf(),
+            config,
+            shortcodes: shortcodes::Registry::default(),
+        })
+    }
+
+    /// Creates a new project in `root`, writing a default `equinox.toml` and the content directory
+    pub fn create(root: &Path) -> Result<Site> {
+        let title = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let site = Site {
+            root: root.to_path_buf(),
+            config: SiteConfig {
+                title,
+                ..SiteConfig::default()
+            },
+            shortcodes: shortcodes::Registry::default(),
+        };
+
+        let content_dir = site.content_dir();
+        fs::create_dir_all(&content_dir).map_err(|e| Error::io("create", &content_dir, e))?;
+        site.save()?;
+        Ok(site)
+    }
+
+    /// Opens the project in `root`, creating one if the directory does not have an `equinox.toml`
+    pub fn open_or_create(root: &Path) -> Result<Site> {
+        if Site::exists(root) {
+            Site::open(root)
+        } else {
+            Site::create(root)
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = self.root.join(Site::CONFIG_FILE);
+        let contents = toml::to_string_pretty(&self.config).map_err(|e| {
+            Error::Invalid(format!("Failed to serialize {}: {}", path.display(), e))
+        })?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    pub fn name(&self) -> String {
+        if !self.config.title.is_empty() {
+            return self.config.title.clone();
+        }
+        self.root
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| self.root.display().to_string())
+    }
+
+    pub fn output_dir(&self) -> PathBuf {
+        if self.config.output_dir == Path::new(".") {
+            return self.root.clone();
+        }
+        self.root.join(&self.config.output_dir)
+    }
+
+    /// Sets the output directory, stored relative to the project root when it is inside it
+    pub fn set_output_dir(&mut self, dir: &Path) {
+        self.config.output_dir = match dir.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => dir.to_path_buf(),
+        };
+    }
+
+    /// Absolute URL of a page given its path relative to the output directory
+    pub fn url_for(&self, path: &str) -> String {
+        self.config.url_for(path)
+    }
+
+    pub fn content_dir(&self) -> PathBuf {
+        self.root.join("content")
+    }
+
+    pub fn templates_dir(&self) -> PathBuf {
+        self.root.join("templates")
+    }
+
+    pub fn assets_dir(&self) -> PathBuf {
+        self.root.join(&self.config.assets_dir)
+    }
+
+    /// Directory imported images are copied to, published as `assets/images/`
+    pub fn images_dir(&self) -> PathBuf {
+        self.assets_dir().join("images")
+    }
+
+    /// Directory imported audio files are copied to, published as `assets/audio/`
+    pub fn audio_dir(&self) -> PathBuf {
+        self.assets_dir().join("audio")
+    }
+
+    /// Where the file at `path`, relative to the output directory, is read from. Assets come
+    /// from the site's assets directory, since they may not have been synced into the output.
+    pub fn source_of(&self, path: &str) -> PathBuf {
+        match path.strip_prefix("assets/") {
+            Some(asset) => self.assets_dir().join(asset),
+            None => self.output_dir().join(path),
+        }
+    }
+
+    /// Copies the image at `src` into the images directory, recompressed if that makes it
+    /// smaller, returning its path relative to the output directory. A different file with the
+    /// same name gets a `-2`, `-3`, ... suffix; the same file imported twice is only stored once.
+    pub fn import_image(&self, src: &Path) -> Result<String> {
+        let contents = fs::read(src).map_err(|e| Error::io("read", src, e))?;
+        let contents = responsive::recompress(&contents, self.config.image_quality);
+        self.import_asset(src, contents, "images")
+    }
+
+    /// Copies the audio file at `src` into the audio directory like
+    /// [`import_image`](Site::import_image), returning its path relative to the output directory
+    pub fn import_audio(&self, src: &Path) -> Result<String> {
+        let contents = fs::read(src).map_err(|e| Error::io("read", src, e))?;
+        self.import_asset(src, contents, "audio")
+    }
+
+    /// Stores `contents`, read from `src`, in the `dir` directory of the assets under a name
+    /// no other file there has
+    fn import_asset(&self, src: &Path, contents: Vec<u8>, dir: &str) -> Result<String> {
+        let dir_path = self.assets_dir().join(dir);
+        fs::create_dir_all(&dir_path).map_err(|e| Error::io("create", &dir_path, e))?;
+
+        let stem = src
+            .file_stem()
+            .map(|s| slugify(&s.to_string_lossy()))
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "file".to_string());
+        let extension = src
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let mut n = 1;
+        let name = loop {
+            let name = match n {
+                1 => format!("{}.{}", stem, extension),
+                n => format!("{}-{}.{}", stem, n, extension),
+            };
+            let dest = dir_path.join(&name);
+            match fs::read(&dest) {
+                Ok(existing) if existing == contents => break name,
+                Ok(_) => n += 1,
+                Err(_) => {
+                    atomic::write(&dest, &contents).map_err(|e| Error::io("write", &dest, e))?;
+                    info!("Imported {} as {}", src.display(), dest.display());
+                    break name;
+                }
+            }
+        };
+
+        Ok(format!("assets/{}/{}", dir, name))
+    }
+
+    /// Mirrors the assets directory into `assets/` in the output directory
+    pub fn sync_assets(&self) -> Result<assets::SyncReport> {
+        let report = assets::sync(&self.assets_dir(), &self.output_dir().join("assets"))?;
+        if report.copied > 0 || report.removed > 0 {
+            info!(
+                "Synced assets: {} copied, {} removed",
+                report.copied, report.removed
+            );
+        }
+        Ok(report)
+    }
+
+    /// Paths of every generated file to deploy, relative to the output directory. Hidden files
+    /// are skipped, as are the project's own files when the output directory is the project root.
+    pub fn output_files(&self) -> Result<Vec<PathBuf>> {
+        let output_dir = self.output_dir();
+        let mut files = Vec::new();
+        collect_output_files(
+            &output_dir,
+            Path::new(""),
+            output_dir == self.root,
+            &mut files,
+        )
+        .map_err(|e| Error::io("read", &output_dir, e))?;
+        files.sort();
+        Ok(files)
+    }
+
+    /// Content store holding the site's post sources
+    pub fn store(&self) -> ContentStore {
+        ContentStore::new(&self.content_dir())
+    }
+
+    /// The configured theme
+    pub fn theme(&self) -> Result<Theme> {
+        Theme::load(&self.root, &self.config.theme)
+    }
+
+    /// Mirrors the assets and writes the theme's files into the output, followed by everything
+    /// made from the finished output: the stylesheet bundle, fingerprinted copies of assets,
+    /// integrity hashes, and the offline app. Called once every page has been written.
+    pub fn write_static_files(&self) -> Result<()> {
+        self.sync_assets()?;
+        self.write_theme_files()?;
+        css::write_bundle(self)?;
+        fingerprint::write_fingerprints(self)?;
+        sri::write_integrity(self)?;
+        pwa::write_pwa(self)
+    }
+
+    /// Writes the configured theme's stylesheet and other static files into the output
+    pub fn write_theme_files(&self) -> Result<()> {
+        self.theme()?
+            .write_files(&self.output_dir().join(themes::OUTPUT_DIR))
+    }
+
+    /// Renderer using the configured theme overridden by the site's `templates/` directory
+    pub fn renderer(&self) -> Result<Renderer> {
+        Renderer::new(&self.theme()?, Some(&self.templates_dir()))
+    }
+
+    /// Renders `post` and writes its page, returning the path written. `posts` are every stored
+    /// post, newest first, to pick its related posts from.
+    pub fn write_post(&self, renderer: &Renderer, post: &Post, posts: &[Post]) -> Result<PathBuf> {
+        let htmldoc = self.render_post(renderer, post, posts)?;
+        let path = self.write_page(&self.config.post_path(post), htmldoc)?;
+        debug!("Wrote {} for post {}", path.display(), post.id);
+        Ok(path)
+    }
+
+    /// Writes the rendered page `html` to `path` in the output directory, minified if the site
+    /// asks for it, and returns where it was written
+    pub fn write_page(&self, path: &str, html: String) -> Result<PathBuf> {
+        let path = self.output_dir().join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        let html = match self.config.minify_html {
+            true => html::minify(&html),
+            false => html,
+        };
+        atomic::write(&path, html).map_err(|e| Error::io("write", &path, e))?;
+        Ok(path)
+    }
+
+    /// The `data-entry` element of `post`'s page, holding its title, byline, and content,
+    /// rendered without writing anything
+    pub fn entry_html(&self, post: &Post) -> Result<String> {
+        // Related posts are linked below the entry, so there is no need to look for them
+        let page = self.render_post(&self.renderer()?, post, &[])?;
+        html::div_with_class(&page, "data-entry")
+            .map(str::to_string)
+            .ok_or_else(|| {
+                Error::Invalid("The post template has no data-entry element".to_string())
+            })
+    }
+
+    /// `post` as shown on the index and listing pages, with a thumbnail of its cover image
+    pub fn listed_post(&self, post: &Post) -> ListedPost {
+        ListedPost {
+            thumbnail: responsive::thumbnail(self, &post.meta.image),
+            ..ListedPost::from_post(&self.config, post)
+        }
+    }
+
+    /// Up to [`SiteConfig::related_posts`] of `posts` linked under `post` as related reading
+    pub fn related<'a>(&self, post: &Post, posts: &'a [Post]) -> Vec<&'a Post> {
+        related::related_posts(post, posts, self.config.related_posts)
+    }
+
+    /// Every one of `posts` that `post`'s page links to, as related reading or as another part
+    /// of its series
+    pub fn linked_posts<'a>(&self, post: &Post, posts: &'a [Post]) -> Vec<&'a Post> {
+        let mut linked = series::series_parts(post, posts);
+        linked.extend(self.related(post, posts));
+        linked
+    }
+
+    /// What `post`'s page shows besides the post itself, for the build cache: the title and
+    /// date of each post it links to and the files its shortcodes are made from
+    fn page_inputs(&self, post: &Post, posts: &[Post]) -> String {
+        let mut inputs = String::new();
+        for other in self.linked_posts(post, posts) {
+            inputs.push_str(&format!(
+                "{}\n{}\n{}\n",
+                self.config.post_url(other),
+                other.meta.title,
+                other.date()
+            ));
+        }
+        for input in shortcodes::inputs(self, &post.body) {
+            inputs.push_str(&format!("{}\n", input));
+        }
+        inputs
+    }
+
+    /// The full page of `post`, linking to its related `posts` and the other parts of its series
+    fn render_post(&self, renderer: &Renderer, post: &Post, posts: &[Post]) -> Result<String> {
+        let links = PostLinks {
+            related: self
+                .related(post, posts)
+                .into_iter()
+                .map(|other| ListedPost::from_post(&self.config, other))
+                .collect(),
+            series: series::series_nav(&self.config, post, posts),
+        };
+        let page = self.config.post_path(post);
+        let content = self.render_content(post, posts);
+        let content = responsive::rewrite_images(self, &page, &content);
+        renderer.render_post(&self.config, post, &links, &content)
+    }
+
+    /// The content of `post` as HTML for its page, with its shortcodes expanded and its links
+    /// pointed where they lead from there among `posts`
+    pub(crate) fn render_content(&self, post: &Post, posts: &[Post]) -> String {
+        let page = self.config.post_path(post);
+        let (body, shortcodes) = shortcodes::extract(self, &post.body);
+        let content = markdown::to_html(
+            &body,
+            &MarkdownOptions {
+                table_of_contents: post.meta.toc,
+                ..self.config.markdown_options()
+            },
+        );
+        let content = permalink::relink(&self.config.permalink, posts, &page, &content);
+        shortcodes::expand(self, &page, &content, &shortcodes)
+    }
+
+    /// Regenerates the index, category, tag, series, and author pages, search index, and feeds
+    /// from `posts`, along with redirects from the pages any of them moved away from. Drafts are
+    /// left out of everything but the redirects.
+    pub fn write_listings(&self, renderer: &Renderer, posts: &PostList) -> Result<()> {
+        self.write_listings_with(renderer, posts, true)
+    }
+
+    /// Like [`write_listings`](Site::write_listings), recording where the pages of `posts` are
+    /// for later redirects only if `record` is set
+    pub(crate) fn write_listings_with(
+        &self,
+        renderer: &Renderer,
+        posts: &[Post],
+        record: bool,
+    ) -> Result<()> {
+        redirects::write_redirects(self, posts, record)?;
+        // Drafts keep their own page but stay out of everything that links to it
+        let posts: Vec<Post> = posts.iter().filter(|p| p.is_published()).cloned().collect();
+        index::write_index(self, renderer, &posts)?;
+        taxonomy::write_category_pages(self, renderer, &posts)?;
+        taxonomy::write_tag_pages(self, renderer, &posts)?;
+        archive::write_archives(self, renderer, &posts)?;
+        series::write_series_pages(self, renderer, &posts)?;
+        taxonomy::write_author_pages(self, renderer, &posts)?;
+        search::write_search(self, renderer, &posts)?;
+        not_found::write_not_found(self, renderer)?;
+        feed::write_feeds(self, &posts)?;
+        sitemap::write_sitemap(self, &posts)?;
+        robots::write_robots(self)
+    }
+
+    /// Re-renders every stored post whose source, settings, or templates changed since the last
+    /// build, then all listings, and warns about any problems in the output: links that lead
+    /// nowhere, invalid HTML, and accessibility issues. Returns the paths of the post pages
+    /// written.
+    pub fn build(&self) -> Result<Vec<PathBuf>> {
+        let pages = self.render_changed()?;
+        self.check_output()?;
+        Ok(pages)
+    }
+
+    fn render_changed(&self) -> Result<Vec<PathBuf>> {
+        info!(
+            "Building {} into {}",
+            self.root.display(),
+            self.output_dir().display()
+        );
+        let renderer = self.renderer()?;
+        let posts = self.store().list()?;
+        let mut cache = BuildCache::load(&self.root, &self.fingerprint(&renderer)?);
+
+        let mut pages = Vec::new();
+        for post in &posts {
+            let page = self.output_dir().join(self.config.post_path(post));
+            let inputs = self.page_inputs(post, &posts);
+            if cache.is_fresh(post, &inputs) && page.is_file() {
+                debug!("Skipped unchanged post {}", post.id);
+                continue;
+            }
+            pages.push(self.write_post(&renderer, post, &posts)?);
+            cache.insert(post, &inputs);
+        }
+        cache.retain(&posts);
+
+        self.write_listings(&renderer, &posts)?;
+        self.write_static_files()?;
+        cache.save(&self.root)?;
+        info!(
+            "Rendered {} of {} posts and every listing",
+            pages.len(),
+            posts.len()
+        );
+        Ok(pages)
+    }
+
+    /// Identifies everything besides a post's own source that its page depends on
+    fn fingerprint(&self, renderer: &Renderer) -> Result<String> {
+        let config = toml::to_string(&self.config)
+            .map_err(|e| Error::Invalid(format!("Failed to serialize the site settings: {}", e)))?;
+        Ok(cache::hash([
+            env!("CARGO_PKG_VERSION").as_bytes(),
+            config.as_bytes(),
+            renderer.fingerprint().as_bytes(),
+        ]))
+    }
+
+    /// Re-renders every page like [`Site::build`], ignoring the build cache, then removes pages
+    /// left behind by posts, tags, series, authors, and archive years that no longer exist.
+    /// Resized images are written afresh, so copies of removed or replaced images go too.
+    /// Returns the post pages written and the stale pages removed.
+    pub fn rebuild(&self) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+        info!("Clearing the build cache for a full rebuild");
+        BuildCache::clear(&self.root)?;
+        responsive::clear(self)?;
+        let pages = self.render_changed()?;
+        let posts = self.store().list()?;
+        let published: Vec<&Post> = posts.iter().filter(|p| p.is_published()).collect();
+
+        let mut removed = Vec::new();
+        let mut expected: HashSet<String> =
+            posts.iter().map(|p| self.config.post_path(p)).collect();
+        if self.config.redirects == RedirectFormat::Html {
+            expected.extend(RedirectLog::load(&self.root).stubs().map(str::to_string));
+        }
+        // Formats without a directory of their own share the output directory with every
+        // other page, so only pages left in the default one can be told apart there
+        let mut dirs = vec![permalink::CONTENT_DIR];
+        dirs.extend(permalink::posts_dir(&self.config.permalink));
+        dirs.dedup();
+        for dir in dirs {
+            self.remove_stale_posts(&self.output_dir().join(dir), &expected, &mut removed)?;
+        }
+        let expected: HashSet<String> = published
+            .iter()
+            .flat_map(|p| p.meta.tags.iter().map(|tag| tag_page_path(tag)))
+            .collect();
+        self.remove_stale_pages("tags", &expected, &mut removed)?;
+        let expected: HashSet<String> = published
+            .iter()
+            .filter(|p| !p.meta.series.is_empty())
+            .map(|p| series_page_path(&p.meta.series))
+            .collect();
+        self.remove_stale_pages("series", &expected, &mut removed)?;
+        let expected: HashSet<String> = published
+            .iter()
+            .filter_map(|p| self.config.author(&p.meta.author))
+            .map(|author| author_page_path(&author.name))
+            .collect();
+        self.remove_stale_pages("authors", &expected, &mut removed)?;
+        let expected: HashSet<String> = published
+            .iter()
+            .map(|p| archive_year_path(p.date().year()))
+            .collect();
+        self.remove_stale_pages("archive", &expected, &mut removed)?;
+        // Links to the pages just removed are broken now too
+        self.check_output()?;
+        Ok((pages, removed))
+    }
+
+    /// Warns about broken links, invalid HTML, and accessibility problems in the finished output,
+    /// as the site asks
+    fn check_output(&self) -> Result<()> {
+        if self.config.check_links {
+            links::check_internal(self)?;
+        }
+        if self.config.validate_html {
+            validate::check_output(self)?;
+        }
+        if self.config.audit_accessibility {
+            accessibility::audit(self)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the pages anywhere under `dir` that aren't `expected` post pages, along with the
+    /// directories they leave empty
+    fn remove_stale_posts(
+        &self,
+        dir: &Path,
+        expected: &HashSet<String>,
+        removed: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return Ok(());
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                self.remove_stale_posts(&path, expected, removed)?;
+                if fs::read_dir(&path).is_ok_and(|mut entries| entries.next().is_none()) {
+                    fs::remove_dir(&path).map_err(|e| Error::io("remove", &path, e))?;
+                }
+                continue;
+            }
+            let relative = path
+                .strip_prefix(self.output_dir())
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if path.extension().is_none_or(|ext| ext != "html") || expected.contains(&relative) {
+                continue;
+            }
+            fs::remove_file(&path).map_err(|e| Error::io("remove", &path, e))?;
+            info!("Removed stale page {}", path.display());
+            removed.push(path);
+        }
+        Ok(())
+    }
+
+    /// Removes the pages directly inside `dir` of the output directory that aren't `expected`,
+    /// along with the further pages of any such listing
+    fn remove_stale_pages(
+        &self,
+        dir: &str,
+        expected: &HashSet<String>,
+        removed: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let dir = self.output_dir().join(dir);
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Ok(());
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            // A listing's further pages live in a directory named after its first page
+            let page = if path.join("page").is_dir() {
+                path.with_extension("html")
+            } else if path.is_file() && path.extension().is_some_and(|ext| ext == "html") {
+                path.clone()
+            } else {
+                continue;
+            };
+            let relative = page
+                .strip_prefix(self.output_dir())
+                .unwrap_or(&page)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if expected.contains(&relative) {
+                continue;
+            }
+            let result = match path.is_dir() {
+                true => fs::remove_dir_all(&path),
+                false => fs::remove_file(&path),
+            };
+            result.map_err(|e| Error::io("remove", &path, e))?;
+            info!("Removed stale page {}", path.display());
+            removed.push(path);
+        }
+        Ok(())
+    }
+
+    /// Stores `post`, writes its page, and regenerates the listings, returning the page's path
+    pub fn publish(&self, post: &Post) -> Result<PathBuf> {
+        self.save_post(post)?;
+        self.generate(post)
+    }
+
+    /// Stores `post` and keeps its source as a new version. The post is saved either way, so a
+    /// version that can't be kept is only warned about.
+    pub fn save_post(&self, post: &Post) -> Result<()> {
+        let store = self.store();
+        store.save(post)?;
+        if let Err(e) = store
+            .source(&post.id)
+            .and_then(|source| versions::snapshot(&self.root, &post.id, &source))
+        {
+            warn!("Failed to keep a version of {}: {}", post.id, e);
+        }
+        Ok(())
+    }
+
+    /// Puts the post `id` back as it was in `version`, keeping its current source as a version
+    /// first so the restore can itself be undone
+    pub fn restore_version(&self, id: &str, version: &str) -> Result<Post> {
+        let store = self.store();
+        let source = versions::load(&self.root, id, version)?;
+        versions::snapshot(&self.root, id, &store.source(id)?)?;
+        let post = store.restore(id, &source)?;
+        versions::snapshot(&self.root, id, &source)?;
+        info!("Restored {} to its version from {}", id, version);
+        Ok(post)
+    }
+
+    /// Writes the page of an already stored `post` and regenerates the listings, returning the
+    /// page's path
+    pub fn generate(&self, post: &Post) -> Result<PathBuf> {
+        let store = self.store();
+        let renderer = self.renderer()?;
+        let posts = store.list()?;
+        let path = self.write_post(&renderer, post, &posts)?;
+        let mut cache = BuildCache::load(&self.root, &self.fingerprint(&renderer)?);
+        cache.insert(post, &self.page_inputs(post, &posts));
+        cache.save(&self.root)?;
+        self.write_listings(&renderer, &posts)?;
+        self.write_static_files()?;
+        info!("Generated {} and updated the listings", path.display());
+        Ok(path)
+    }
+}
+
+fn collect_output_files(
+    dir: &Path,
+    relative: &Path,
+    is_project_root: bool,
+    files: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        if name_str.starts_with('.') {
+            continue;
+        }
+        if is_project_root
+            && (name_str == Site::CONFIG_FILE || Site::RESERVED_DIRS.contains(&name_str.as_ref()))
+        {
+            continue;
+        }
+
+        let path = entry.path();
+        if path.is_dir() {
+            collect_output_files(&path, &relative.join(&name), false, files)?;
+        } else {
+            files.push(relative.join(&name));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_headings_sit_below_the_page_title() {
+        let root = std::env::temp_dir().join(format!("equinox-headings-{}", std::process::id()));
+        let site = Site::create(&root).unwrap();
+        let post = Post::new("x".to_string(), "X".to_string(), "# x".to_string());
+        let entry = site.entry_html(&post).unwrap();
+        assert!(entry.contains("<h3 id=\"x\">x</h3>"), "{entry}");
+        assert!(!entry.contains("<h1"), "{entry}");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
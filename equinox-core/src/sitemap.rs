@@ -0,0 +1,74 @@
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+    html::escape,
+    site::Site,
+    templates::{
+        archive_year_path, author_page_path, category_page_path, series_page_path, tag_page_path,
+        ARCHIVE_PAGE,
+    },
+};
+use chrono::{DateTime, Datelike, FixedOffset, SecondsFormat};
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// Regenerates `sitemap.xml` in the output directory, listing the front page, every post that
+/// isn't syndicated from elsewhere or marked `noindex`, and every category, tag, series, and
+/// archive page with the time its newest post was last updated
+///
+/// Sitemaps need absolute URLs, so nothing is written until the site has a base URL.
+pub fn write_sitemap(site: &Site, posts: &[Post]) -> Result<()> {
+    if site.config.base_url.is_empty() {
+        return Ok(());
+    }
+
+    let mut pages: BTreeMap<String, DateTime<FixedOffset>> = BTreeMap::new();
+    let mut touch = |path: String, updated: DateTime<FixedOffset>| {
+        let lastmod = pages.entry(path).or_insert(updated);
+        *lastmod = (*lastmod).max(updated);
+    };
+
+    for post in posts {
+        let updated = post.meta.updated;
+        touch(String::new(), updated);
+        // Search engines are pointed at the original of syndicated posts instead, and kept away
+        // from posts that asked not to be indexed
+        if post.meta.canonical_url.is_empty() && !post.meta.noindex {
+            touch(site.config.post_url(post), updated);
+        }
+        touch(ARCHIVE_PAGE.to_string(), updated);
+        touch(archive_year_path(post.date().year()), updated);
+        if !post.meta.category.is_empty() {
+            touch(category_page_path(&post.meta.category), updated);
+        }
+        for tag in &post.meta.tags {
+            touch(tag_page_path(tag), updated);
+        }
+        if !post.meta.series.is_empty() {
+            touch(series_page_path(&post.meta.series), updated);
+        }
+        if let Some(author) = site.config.author(&post.meta.author) {
+            touch(author_page_path(&author.name), updated);
+        }
+    }
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for (path, lastmod) in &pages {
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}</loc>\n", escape(&site.url_for(path))));
+        xml.push_str(&format!(
+            "    <lastmod>{}</lastmod>\n",
+            lastmod.to_rfc3339_opts(SecondsFormat::Secs, false)
+        ));
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+
+    let path = site.output_dir().join("sitemap.xml");
+    atomic::write(&path, xml).map_err(|e| Error::io("write", &path, e))?;
+    debug!("Wrote {} with {} pages", path.display(), pages.len());
+    Ok(())
+}
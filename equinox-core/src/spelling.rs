@@ -0,0 +1,232 @@
+//! Spell checking of post content against Hunspell dictionaries
+
+use crate::{
+    error::{Error, Result},
+    front_matter,
+};
+use pulldown_cmark::{Event, LinkType, Parser, Tag, TagEnd};
+use spellbook::Dictionary;
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use tracing::{debug, info};
+
+/// Words accepted on one site in addition to the dictionary, one per line, in the site root
+pub const SITE_DICTIONARY: &str = "dictionary.txt";
+
+/// Where Hunspell dictionaries are installed on common systems
+pub const SYSTEM_DICTIONARY_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+    "/Library/Spelling",
+];
+
+/// Most suggestions offered for one word
+const MAX_SUGGESTIONS: usize = 6;
+
+/// A Hunspell dictionary together with the words added for the open site
+pub struct SpellChecker {
+    dictionary: Dictionary,
+    site_words: HashSet<String>,
+    /// Root of the site `site_words` were read from
+    site_root: Option<PathBuf>,
+}
+
+impl SpellChecker {
+    /// Loads `<language>.aff` and `<language>.dic`, e.g. `en_US`, from the first of `dirs` that
+    /// has both
+    pub fn load(language: &str, dirs: &[PathBuf]) -> Result<SpellChecker> {
+        for dir in dirs {
+            let aff = dir.join(format!("{}.aff", language));
+            let dic = dir.join(format!("{}.dic", language));
+            if !aff.is_file() || !dic.is_file() {
+                continue;
+            }
+
+            let aff_text = fs::read_to_string(&aff).map_err(|e| Error::io("read", &aff, e))?;
+            let dic_text = fs::read_to_string(&dic).map_err(|e| Error::io("read", &dic, e))?;
+            let dictionary = Dictionary::new(&aff_text, &dic_text)
+                .map_err(|e| Error::parse("dictionary", Some(&aff), e))?;
+            info!("Loaded the {} dictionary from {}", language, dir.display());
+            return Ok(SpellChecker {
+                dictionary,
+                site_words: HashSet::new(),
+                site_root: None,
+            });
+        }
+
+        Err(Error::Invalid(format!(
+            "No Hunspell dictionary for {} was found; install one or copy {}.aff and {}.dic \
+             into one of: {}",
+            language,
+            language,
+            language,
+            dirs.iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )))
+    }
+
+    /// Reads the site dictionary of the site at `root`, unless it is already loaded. With no
+    /// site, only the main dictionary is used.
+    pub fn use_site(&mut self, root: Option<&Path>) {
+        if self.site_root.as_deref() == root {
+            return;
+        }
+        self.site_root = root.map(Path::to_path_buf);
+        self.site_words = root
+            .and_then(|root| fs::read_to_string(root.join(SITE_DICTIONARY)).ok())
+            .map(|text| {
+                text.lines()
+                    .map(str::trim)
+                    .filter(|word| !word.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        debug!(
+            "Using {} words from the site dictionary",
+            self.site_words.len()
+        );
+    }
+
+    /// Accepts `word` from now on, saving it in the dictionary of the site last passed to
+    /// [`use_site`](SpellChecker::use_site)
+    pub fn add_to_site(&mut self, word: &str) -> Result<()> {
+        let root = self
+            .site_root
+            .as_ref()
+            .ok_or_else(|| Error::Invalid("Open a site to add words to its dictionary".into()))?;
+        let path = root.join(SITE_DICTIONARY);
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| Error::io("open", &path, e))?;
+        writeln!(file, "{}", word).map_err(|e| Error::io("write", &path, e))?;
+        self.site_words.insert(word.to_string());
+        Ok(())
+    }
+
+    pub fn is_correct(&self, word: &str) -> bool {
+        let word = normalize(word);
+        self.site_words.contains(word.as_ref()) || self.dictionary.check(&word)
+    }
+
+    /// Likely corrections of `word`, best first
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let mut suggestions = Vec::new();
+        self.dictionary.suggest(&normalize(word), &mut suggestions);
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+
+    /// Byte ranges of the misspelled words in the prose of `content`, an editor's text with
+    /// optional front matter. Code, URLs, and HTML are not checked.
+    pub fn misspellings(&self, content: &str) -> Vec<Range<usize>> {
+        words(content)
+            .into_iter()
+            .filter(|range| !self.is_correct(&content[range.clone()]))
+            .collect()
+    }
+}
+
+/// Uses the straight apostrophe dictionaries are written with
+fn normalize(word: &str) -> std::borrow::Cow<'_, str> {
+    if word.contains('’') {
+        word.replace('’', "'").into()
+    } else {
+        word.into()
+    }
+}
+
+/// Byte ranges of the words in the prose of `content`
+fn words(content: &str) -> Vec<Range<usize>> {
+    let body_start = front_matter::split(content).map_or(0, |(_, body)| content.len() - body.len());
+    let body = &content[body_start..];
+
+    let mut found = Vec::new();
+    let mut in_code = false;
+    let mut in_autolink = false;
+    for (event, range) in Parser::new(body).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink | LinkType::Email,
+                ..
+            }) => in_autolink = true,
+            Event::End(TagEnd::Link) => in_autolink = false,
+            Event::Text(_) if !in_code && !in_autolink => {
+                let start = body_start + range.start;
+                found.extend(
+                    words_in(&body[range])
+                        .into_iter()
+                        .map(|word| start + word.start..start + word.end),
+                );
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Words in a run of plain text, skipping anything that looks like a URL, an email address, or
+/// a number
+fn words_in(text: &str) -> Vec<Range<usize>> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    for chunk in text.split_inclusive(char::is_whitespace) {
+        if !(chunk.contains("://") || chunk.contains('@') || chunk.starts_with("www.")) {
+            found.extend(
+                word_runs(chunk)
+                    .into_iter()
+                    .map(|word| start + word.start..start + word.end),
+            );
+        }
+        start += chunk.len();
+    }
+    found
+}
+
+/// Runs of letters in `chunk`, including apostrophes between letters
+fn word_runs(chunk: &str) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    let mut has_digit = false;
+    let mut chars = chunk.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next_is_letter = chars.peek().is_some_and(|(_, next)| next.is_alphabetic());
+        let in_word =
+            c.is_alphanumeric() || (start.is_some() && is_apostrophe(c) && next_is_letter);
+        match (in_word, start) {
+            (true, None) => {
+                start = Some(i);
+                has_digit = c.is_numeric();
+            }
+            (true, Some(_)) => has_digit |= c.is_numeric(),
+            (false, Some(s)) => {
+                if !has_digit {
+                    runs.push(s..i);
+                }
+                start = None;
+            }
+            (false, None) => {}
+        }
+    }
+    if let (Some(s), false) = (start, has_digit) {
+        runs.push(s..chunk.len());
+    }
+    runs
+}
+
+fn is_apostrophe(c: char) -> bool {
+    c == '\'' || c == '’'
+}
@@ -0,0 +1,226 @@
+//! Subresource integrity: pinning the hashes of the scripts and stylesheets pages load from
+//! other sites, so browsers refuse them if they ever change. Fetching them is left to the
+//! caller; this finds them, keeps the pinned hashes, and adds them to the pages.
+
+use crate::{
+    atomic,
+    error::{Error, Result},
+    html,
+    site::Site,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha384};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// Record of the pinned hashes, relative to the project root
+pub const PINS_FILE: &str = ".equinox/integrity.toml";
+
+/// Hosts whose responses differ from browser to browser, so no one hash fits them all. Google
+/// Fonts picks the font formats in its stylesheets by user agent.
+const VARYING_HOSTS: &[&str] = &["fonts.googleapis.com"];
+
+/// The `integrity` value of a resource with `contents`
+pub fn integrity(contents: &[u8]) -> String {
+    format!("sha384-{}", base64(&Sha384::digest(contents)))
+}
+
+/// The hash pinned for each external resource, by URL
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Pins {
+    hashes: BTreeMap<String, String>,
+}
+
+impl Pins {
+    /// Loads the pins of the project at `root`, empty if nothing has been pinned yet
+    pub fn load(root: &Path) -> Pins {
+        fs::read_to_string(path(root))
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = path(root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        let contents = toml::to_string(self).map_err(|e| {
+            Error::Invalid(format!("Failed to serialize the integrity hashes: {}", e))
+        })?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    pub fn get(&self, url: &str) -> Option<&str> {
+        self.hashes.get(url).map(String::as_str)
+    }
+
+    /// Pins `url` to `integrity`, replacing any hash it had
+    pub fn set(&mut self, url: &str, integrity: String) {
+        self.hashes.insert(url.to_string(), integrity);
+    }
+}
+
+/// External scripts and stylesheets linked from the site's generated pages, which all need to
+/// be pinned
+pub fn resources(site: &Site) -> Result<BTreeSet<String>> {
+    let mut resources = BTreeSet::new();
+    for path in pages(site)? {
+        let html = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        resources.extend(external_tags(&html).into_iter().map(|tag| tag.url));
+    }
+    Ok(resources)
+}
+
+/// Gives every external script and stylesheet in the site's generated pages its pinned hash,
+/// along with the `crossorigin` attribute browsers need to check it. Meant to be called once
+/// every page has been written.
+pub fn write_integrity(site: &Site) -> Result<()> {
+    if !site.config.subresource_integrity {
+        return Ok(());
+    }
+    let pins = Pins::load(&site.root);
+    let mut rewritten = 0;
+    for path in pages(site)? {
+        let html = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        let mut output = String::with_capacity(html.len());
+        let mut copied = 0;
+        for tag in external_tags(&html) {
+            let attributes = &html[tag.start..tag.end];
+            let Some(integrity) = pins.get(&tag.url) else {
+                continue;
+            };
+            // Pages left alone by an incremental build may have been given an older hash
+            if html::attribute(attributes, "integrity").as_deref() == Some(integrity) {
+                continue;
+            }
+            output.push_str(&html[copied..tag.start]);
+            output.push_str(&without_attribute(attributes, "integrity"));
+            output.push_str(&format!(" integrity=\"{}\"", integrity));
+            if !tag.has_crossorigin {
+                output.push_str(" crossorigin=\"anonymous\"");
+            }
+            copied = tag.end;
+        }
+        if copied > 0 {
+            output.push_str(&html[copied..]);
+            atomic::write(&path, output).map_err(|e| Error::io("write", &path, e))?;
+            rewritten += 1;
+        }
+    }
+    if rewritten > 0 {
+        info!("Added integrity hashes to {} pages", rewritten);
+    }
+    Ok(())
+}
+
+/// Every generated page
+fn pages(site: &Site) -> Result<Vec<PathBuf>> {
+    let output_dir = site.output_dir();
+    Ok(site
+        .output_files()?
+        .into_iter()
+        .filter(|file| file.extension().is_some_and(|ext| ext == "html"))
+        .map(|file| output_dir.join(file))
+        .collect())
+}
+
+/// A `<script>` or stylesheet `<link>` loading `url` from another site
+struct ExternalTag {
+    url: String,
+    /// Where the tag's name and attributes start and end, just after its `<` and before its
+    /// `>` or `/>`
+    start: usize,
+    end: usize,
+    has_crossorigin: bool,
+}
+
+fn external_tags(html: &str) -> Vec<ExternalTag> {
+    let lower = html.to_ascii_lowercase();
+    let mut tags = Vec::new();
+    let mut rest = 0;
+    while let Some(start) = lower[rest..].find('<').map(|i| rest + i + 1) {
+        let Some(close) = lower[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        rest = close;
+        let tag = &html[start..close];
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        let url = if name.eq_ignore_ascii_case("script") {
+            html::attribute(tag, "src")
+        } else if name.eq_ignore_ascii_case("link")
+            && html::attribute(tag, "rel").is_some_and(|rel| {
+                rel.split_whitespace()
+                    .any(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+            })
+        {
+            html::attribute(tag, "href")
+        } else {
+            None
+        };
+        let Some(url) = url.filter(|url| url.starts_with("https://")) else {
+            continue;
+        };
+        let host = url["https://".len()..]
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        if VARYING_HOSTS.contains(&host) {
+            continue;
+        }
+        tags.push(ExternalTag {
+            url,
+            start,
+            end: start + tag.trim_end_matches('/').trim_end().len(),
+            has_crossorigin: tag
+                .split([' ', '\t', '\n', '='])
+                .any(|word| word.eq_ignore_ascii_case("crossorigin")),
+        });
+    }
+    tags
+}
+
+/// `attributes` without the quoted attribute `name`
+fn without_attribute(attributes: &str, name: &str) -> String {
+    let lower = attributes.to_ascii_lowercase();
+    let Some(start) = lower.find(&format!(" {}=", name)) else {
+        return attributes.to_string();
+    };
+    let value = start + name.len() + 2;
+    let end = match attributes[value..].chars().next() {
+        Some(quote @ ('"' | '\'')) => attributes[value + 1..]
+            .find(quote)
+            .map_or(attributes.len(), |i| value + 1 + i + 1),
+        _ => attributes[value..]
+            .find(char::is_whitespace)
+            .map_or(attributes.len(), |i| value + i),
+    };
+    format!("{}{}", &attributes[..start], &attributes[end..])
+}
+
+/// Standard base64 with padding
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+fn path(root: &Path) -> PathBuf {
+    root.join(PINS_FILE)
+}
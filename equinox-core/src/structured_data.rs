@@ -0,0 +1,47 @@
+//! schema.org metadata embedded in pages as JSON-LD, which search engines use to show posts
+//! with their dates, author, and image
+
+use crate::{content::Post, site::SiteConfig};
+use chrono::SecondsFormat;
+use serde_json::{json, Map, Value};
+
+/// A `BlogPosting` describing `post`, whose page's canonical URL is `url`, ready to go in a
+/// `<script type="application/ld+json">` element
+pub fn blog_posting(site: &SiteConfig, post: &Post, description: &str, url: &str) -> String {
+    let mut data = Map::new();
+    data.insert("@context".into(), json!("https://schema.org"));
+    data.insert("@type".into(), json!("BlogPosting"));
+    data.insert("headline".into(), json!(post.meta.title));
+    data.insert("url".into(), json!(url));
+    data.insert("mainEntityOfPage".into(), json!(url));
+    let date = |date: chrono::DateTime<_>| date.to_rfc3339_opts(SecondsFormat::Secs, false);
+    data.insert("datePublished".into(), json!(date(post.date())));
+    data.insert("dateModified".into(), json!(date(post.meta.updated)));
+    if !description.is_empty() {
+        data.insert("description".into(), json!(description));
+    }
+    if !post.meta.author.is_empty() {
+        // Authors in the site's table are named as spelled there, and linked to their website
+        let entry = site.author(&post.meta.author);
+        let name = entry.map_or(post.meta.author.as_str(), |author| author.name.as_str());
+        let mut author = json!({ "@type": "Person", "name": name });
+        if let Some(entry) = entry.filter(|author| !author.url.is_empty()) {
+            author["url"] = json!(entry.url);
+        }
+        data.insert("author".into(), author);
+    }
+    if !post.meta.image.is_empty() {
+        data.insert("image".into(), json!(site.url_for(&post.meta.image)));
+    }
+    if !post.meta.tags.is_empty() {
+        data.insert("keywords".into(), json!(post.meta.tags));
+    }
+    if !site.title.is_empty() {
+        data.insert(
+            "publisher".into(),
+            json!({ "@type": "Organization", "name": site.title }),
+        );
+    }
+    // A `</script>` in any of the text would end the element early
+    Value::Object(data).to_string().replace("</", "<\\/")
+}
@@ -0,0 +1,101 @@
+use crate::{
+    content::{slugify, Post, PostList},
+    error::Result,
+    pagination,
+    site::Site,
+    templates::{
+        author_page_path, category_page_path, tag_page_path, AuthorProfile, ListedPost, Renderer,
+    },
+};
+use std::collections::BTreeMap;
+
+/// Writes `tags/<tag>.html` (and any further pages) for every tag used by `posts`
+pub fn write_tag_pages(site: &Site, renderer: &Renderer, posts: &PostList) -> Result<()> {
+    let groups = group_by(site, posts, |post| post.meta.tags.clone());
+
+    for (name, listed) in groups.values() {
+        let heading = format!("#{}", name);
+        pagination::write_pages(
+            site,
+            &tag_page_path(name),
+            listed,
+            site.config.posts_per_page,
+            |path, posts, pagination| {
+                renderer.render_listing(&site.config, path, &heading, posts, pagination)
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes `<category>.html` (and any further pages) for every category used by `posts`
+pub fn write_category_pages(site: &Site, renderer: &Renderer, posts: &PostList) -> Result<()> {
+    let groups = group_by(site, posts, |post| {
+        if post.meta.category.is_empty() {
+            Vec::new()
+        } else {
+            vec![post.meta.category.clone()]
+        }
+    });
+
+    for (name, listed) in groups.values() {
+        pagination::write_pages(
+            site,
+            &category_page_path(name),
+            listed,
+            site.config.posts_per_page,
+            |path, posts, pagination| {
+                renderer.render_listing(&site.config, path, name, posts, pagination)
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes `authors/<author>.html` (and any further pages) for every author in the site's authors
+/// table who wrote any of `posts`
+pub fn write_author_pages(site: &Site, renderer: &Renderer, posts: &PostList) -> Result<()> {
+    let groups = group_by(site, posts, |post| {
+        match site.config.author(&post.meta.author) {
+            Some(author) => vec![author.name.clone()],
+            None => Vec::new(),
+        }
+    });
+
+    for author in &site.config.authors {
+        let Some((_, listed)) = groups.get(&slugify(&author.name)) else {
+            continue;
+        };
+        pagination::write_pages(
+            site,
+            &author_page_path(&author.name),
+            listed,
+            site.config.posts_per_page,
+            |path, posts, pagination| {
+                let profile = AuthorProfile::new(author, path);
+                renderer.render_author(&site.config, path, &profile, posts, pagination)
+            },
+        )?;
+    }
+    Ok(())
+}
+
+/// Groups posts by the slug of each key returned by `keys`, keeping the first spelling seen as
+/// the display name
+pub(crate) fn group_by(
+    site: &Site,
+    posts: &[Post],
+    keys: impl Fn(&Post) -> Vec<String>,
+) -> BTreeMap<String, (String, Vec<ListedPost>)> {
+    let mut groups: BTreeMap<String, (String, Vec<ListedPost>)> = BTreeMap::new();
+    for post in posts {
+        for key in keys(post) {
+            groups
+                .entry(slugify(&key))
+                .or_insert_with(|| (key, Vec::new()))
+                .1
+                .push(site.listed_post(post));
+        }
+    }
+    groups
+}
@@ -0,0 +1,522 @@
+use crate::{
+    cache,
+    content::{slugify, Post},
+    error::{Error, Result},
+    html, markdown,
+    not_found::NOT_FOUND_PAGE,
+    pagination::Pagination,
+    permalink,
+    search::SEARCH_PAGE,
+    site::{Author, ProfileLink, SiteConfig},
+    structured_data,
+    themes::Theme,
+};
+use serde::Serialize;
+use std::{fs, path::Path};
+use tera::{Context, Tera};
+
+/// Built-in templates, used for any template the site does not override
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    ("base.html", include_str!("../templates/base.html")),
+    ("post.html", include_str!("../templates/post.html")),
+    (
+        "post-anime.html",
+        include_str!("../templates/post-anime.html"),
+    ),
+    ("index.html", include_str!("../templates/index.html")),
+    ("listing.html", include_str!("../templates/listing.html")),
+    ("archive.html", include_str!("../templates/archive.html")),
+    ("search.html", include_str!("../templates/search.html")),
+    ("404.html", include_str!("../templates/404.html")),
+];
+
+/// Length of the excerpt shown for each post on listing pages
+const EXCERPT_LENGTH: usize = 200;
+
+/// Length of the excerpt used as a post's meta description when it has none of its own, about
+/// what search engines show
+const DESCRIPTION_LENGTH: usize = 160;
+
+/// A post as shown on listing pages such as the index
+#[derive(Debug, Clone, Serialize)]
+pub struct ListedPost {
+    pub title: String,
+    pub date: String,
+    pub excerpt: String,
+    /// Link to the post, relative to the output directory
+    pub url: String,
+    /// Small copy of the post's cover image, relative to the output directory, on the listings
+    /// that show one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+impl ListedPost {
+    pub fn from_post(site: &SiteConfig, post: &Post) -> ListedPost {
+        ListedPost {
+            title: post.meta.title.clone(),
+            date: format_date(post),
+            excerpt: markdown::excerpt(&post.body, EXCERPT_LENGTH),
+            url: site.post_url(post),
+            thumbnail: None,
+        }
+    }
+}
+
+/// A year of posts on the archive pages, newest month first
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveYear {
+    pub year: i32,
+    /// Link to the year's archive page, relative to the output directory
+    pub url: String,
+    pub months: Vec<ArchiveMonth>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveMonth {
+    /// Full month name, e.g. `May`
+    pub name: String,
+    pub posts: Vec<ListedPost>,
+}
+
+/// A tag as linked from a post header
+#[derive(Debug, Clone, Serialize)]
+pub struct TagLink {
+    pub name: String,
+    /// Link to the tag's listing page, relative to the output directory
+    pub url: String,
+}
+
+impl TagLink {
+    pub fn new(name: &str) -> TagLink {
+        TagLink {
+            name: name.to_string(),
+            url: tag_page_path(name),
+        }
+    }
+}
+
+/// An author from the site's authors table, as shown on a post or their own page
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorProfile {
+    pub name: String,
+    pub bio: String,
+    /// Picture of the author, already relative to the page being rendered
+    pub avatar: Option<String>,
+    /// The author's own website or profile
+    pub website: Option<String>,
+    pub links: Vec<ProfileLink>,
+    /// Link to the page listing the author's posts, relative to the output directory
+    pub url: String,
+}
+
+impl AuthorProfile {
+    /// `author` as shown on the page at `path`
+    pub fn new(author: &Author, path: &str) -> AuthorProfile {
+        let avatar = &author.avatar;
+        AuthorProfile {
+            name: author.name.clone(),
+            bio: author.bio.clone(),
+            avatar: (!avatar.is_empty()).then(|| match avatar.contains("://") {
+                true => avatar.clone(),
+                false => format!("{}{}", root_for(path), avatar.trim_start_matches('/')),
+            }),
+            website: (!author.url.is_empty()).then(|| author.url.clone()),
+            links: author.links.clone(),
+            url: author_page_path(&author.name),
+        }
+    }
+}
+
+/// Other posts linked from a post's page, besides those on its category and tag pages
+#[derive(Debug, Clone, Default)]
+pub struct PostLinks {
+    pub related: Vec<ListedPost>,
+    pub series: Option<SeriesNav>,
+}
+
+/// The series box and previous and next links of a post that is part of a series
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesNav {
+    pub name: String,
+    /// Link to the series' landing page, relative to the output directory
+    pub url: String,
+    /// Number of the post among the parts, starting at 1
+    pub part: usize,
+    /// Every part, oldest first
+    pub parts: Vec<SeriesPart>,
+    pub previous: Option<ListedPost>,
+    pub next: Option<ListedPost>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesPart {
+    pub title: String,
+    /// Link to the part, relative to the output directory
+    pub url: String,
+    /// Whether this part is the post being shown
+    pub current: bool,
+}
+
+/// A navigation bar entry as rendered on a particular page
+#[derive(Debug, Clone, Serialize)]
+pub struct NavItem {
+    pub label: String,
+    /// Link to the target, already relative to the page being rendered
+    pub url: String,
+    /// Whether the link points at the page being rendered or the section it belongs to
+    pub active: bool,
+    pub right: bool,
+}
+
+/// Navigation bar entries for the page at `path`. A link is active if it targets `path` or any
+/// of `sections`, e.g. the category page of a post.
+pub fn nav_items(site: &SiteConfig, path: &str, sections: &[&str]) -> Vec<NavItem> {
+    nav_items_from(site, &root_for(path), path, sections)
+}
+
+/// Navigation bar entries like [`nav_items`], linked through `root` instead of relative to the
+/// page
+fn nav_items_from(site: &SiteConfig, root: &str, path: &str, sections: &[&str]) -> Vec<NavItem> {
+    site.nav
+        .iter()
+        .map(|link| {
+            let target = link.url.trim_start_matches("./");
+            NavItem {
+                label: link.label.clone(),
+                url: if link.is_absolute() {
+                    link.url.clone()
+                } else {
+                    format!("{}{}", root, target)
+                },
+                active: !link.is_absolute() && (target == path || sections.contains(&target)),
+                right: link.right,
+            }
+        })
+        .collect()
+}
+
+/// Path of a tag's listing page, relative to the output directory
+pub fn tag_page_path(tag: &str) -> String {
+    format!("tags/{}.html", slugify(tag))
+}
+
+/// Path of the archive page listing every post, relative to the output directory
+pub const ARCHIVE_PAGE: &str = "archive.html";
+
+/// Path of the archive page for `year`, relative to the output directory
+pub fn archive_year_path(year: i32) -> String {
+    format!("archive/{}.html", year)
+}
+
+/// Path of a series' landing page, relative to the output directory
+pub fn series_page_path(series: &str) -> String {
+    format!("series/{}.html", slugify(series))
+}
+
+/// Path of the page listing an author's posts, relative to the output directory
+pub fn author_page_path(author: &str) -> String {
+    format!("authors/{}.html", slugify(author))
+}
+
+/// Path of a category's listing page, relative to the output directory: `<category>.html`, or
+/// `category-<category>.html` for a category named like one of the site's own pages, which it
+/// would replace otherwise
+pub fn category_page_path(category: &str) -> String {
+    let path = format!("{}.html", slugify(category));
+    match ["index.html", ARCHIVE_PAGE, SEARCH_PAGE, NOT_FOUND_PAGE].contains(&path.as_str()) {
+        true => format!("category-{}", path),
+        false => path,
+    }
+}
+
+pub(crate) fn format_date(post: &Post) -> String {
+    post.date().format("%B %-d, %Y").to_string()
+}
+
+/// Prefix that leads from a page at `path` back to the output directory, e.g. `../` for
+/// `tags/rust.html`, so templates can build relative links that work from any depth
+pub fn root_for(path: &str) -> String {
+    "../".repeat(path.matches('/').count())
+}
+
+/// Renders pages from Tera templates
+pub struct Renderer {
+    tera: Tera,
+    /// Hash of every template source loaded, in loading order
+    fingerprint: String,
+}
+
+impl Renderer {
+    /// Loads the built-in templates, then `theme`'s templates, then any `.html` files in
+    /// `templates_dir` on top of them so the page skeleton can be changed without recompiling
+    pub fn new(theme: &Theme, templates_dir: Option<&Path>) -> Result<Renderer> {
+        let mut tera = Tera::default();
+        // Tera's default escaper also escapes `/`, which mangles every relative link
+        tera.set_escape_fn(html::escape);
+        let mut sources: Vec<(String, String)> = Vec::new();
+
+        tera.add_raw_templates(DEFAULT_TEMPLATES.iter().copied())
+            .map_err(|e| Error::Template {
+                origin: "built-in templates".to_string(),
+                path: None,
+                message: error_chain(&e),
+            })?;
+        sources.extend(
+            DEFAULT_TEMPLATES
+                .iter()
+                .map(|(name, source)| (name.to_string(), source.to_string())),
+        );
+
+        let theme_templates = theme.templates()?;
+        tera.add_raw_templates(theme_templates.clone())
+            .map_err(|e| Error::Template {
+                origin: format!("theme '{}'", theme.name()),
+                path: None,
+                message: error_chain(&e),
+            })?;
+        sources.extend(theme_templates);
+
+        if let Some(dir) = templates_dir.filter(|dir| dir.is_dir()) {
+            let entries = fs::read_dir(dir).map_err(|e| Error::io("read", dir, e))?;
+
+            let mut overrides = Vec::new();
+            for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+                if !path.is_file() || path.extension().is_none_or(|ext| ext != "html") {
+                    continue;
+                }
+                let source = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                overrides.push((name, source));
+            }
+            overrides.sort();
+
+            tera.add_raw_templates(overrides.clone())
+                .map_err(|e| Error::Template {
+                    origin: format!("templates from {}", dir.display()),
+                    path: Some(dir.to_path_buf()),
+                    message: error_chain(&e),
+                })?;
+            sources.extend(overrides);
+        }
+
+        let fingerprint = cache::hash(
+            sources
+                .iter()
+                .flat_map(|(name, source)| [name.as_bytes(), source.as_bytes()]),
+        );
+        Ok(Renderer { tera, fingerprint })
+    }
+
+    /// Changes whenever any template the renderer loaded does
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    pub fn render_post(
+        &self,
+        site: &SiteConfig,
+        post: &Post,
+        links: &PostLinks,
+        content_html: &str,
+    ) -> Result<String> {
+        let tags: Vec<TagLink> = post.meta.tags.iter().map(|tag| TagLink::new(tag)).collect();
+        let category = (!post.meta.category.is_empty()).then(|| TagLink {
+            name: post.meta.category.clone(),
+            url: category_page_path(&post.meta.category),
+        });
+
+        let path = site.post_path(post);
+        let mut context = page_context(site, &path);
+        if let Some(category) = &category {
+            context.insert("nav", &nav_items(site, &path, &[category.url.as_str()]));
+        }
+        context.insert("title", &post.meta.title);
+        context.insert("date", &format_date(post));
+        context.insert("author", &post.meta.author);
+        let author = site
+            .author(&post.meta.author)
+            .map(|author| AuthorProfile::new(author, &path));
+        let card = site.author_cards && !post.meta.no_author_card;
+        context.insert("author_card", &author.as_ref().filter(|_| card));
+        context.insert("author_profile", &author);
+        context.insert(
+            "reading_time",
+            &markdown::text_stats(&post.body).reading_minutes(),
+        );
+        let description = match post.meta.description.as_str() {
+            "" => markdown::excerpt(&post.body, DESCRIPTION_LENGTH),
+            description => description.to_string(),
+        };
+        context.insert("description", &description);
+        let image = (!post.meta.image.is_empty()).then(|| site.url_for(&post.meta.image));
+        context.insert("image", &image);
+        let cover = (!post.meta.image.is_empty()).then(|| match post.meta.image.contains("://") {
+            true => post.meta.image.clone(),
+            false => site.link_from(post, &post.meta.image),
+        });
+        context.insert("cover", &cover);
+        let audio = (!post.meta.audio.is_empty()).then(|| match post.meta.audio.contains("://") {
+            true => post.meta.audio.clone(),
+            false => site.link_from(post, &post.meta.audio),
+        });
+        context.insert("audio", &audio);
+        context.insert("comments", &site.comments.snippet());
+        let canonical_url = match post.meta.canonical_url.as_str() {
+            "" => site.url_for(&site.post_url(post)),
+            canonical_url => canonical_url.to_string(),
+        };
+        context.insert("canonical_url", &canonical_url);
+        context.insert(
+            "structured_data",
+            &structured_data::blog_posting(site, post, &description, &canonical_url),
+        );
+        context.insert("noindex", &post.meta.noindex);
+        context.insert("content", content_html);
+        context.insert("tags", &tags);
+        context.insert("category", &category);
+        context.insert("related", &links.related);
+        context.insert("series", &links.series);
+        self.render(&self.post_template(&post.meta.category), &context)
+    }
+
+    /// `post-<category>.html` if a theme or the site provides one for `category`, otherwise
+    /// `post.html`
+    fn post_template(&self, category: &str) -> String {
+        let name = format!("post-{}.html", slugify(category));
+        match !category.is_empty() && self.tera.get_template_names().any(|n| n == name) {
+            true => name,
+            false => "post.html".to_string(),
+        }
+    }
+
+    /// Renders a page of the front page listing at `path`, showing `posts` in the order given
+    pub fn render_index(
+        &self,
+        site: &SiteConfig,
+        path: &str,
+        posts: &[ListedPost],
+        pagination: &Pagination,
+    ) -> Result<String> {
+        let mut context = listing_context(site, path, pagination);
+        context.insert("posts", posts);
+        self.render("index.html", &context)
+    }
+
+    /// Renders a page at `path` of a tag or category listing, showing `posts` under `heading`
+    pub fn render_listing(
+        &self,
+        site: &SiteConfig,
+        path: &str,
+        heading: &str,
+        posts: &[ListedPost],
+        pagination: &Pagination,
+    ) -> Result<String> {
+        let mut context = listing_context(site, path, pagination);
+        context.insert("heading", heading);
+        context.insert("posts", posts);
+        self.render("listing.html", &context)
+    }
+
+    /// Renders a page at `path` of an author's listing, showing `posts` under their profile
+    pub fn render_author(
+        &self,
+        site: &SiteConfig,
+        path: &str,
+        author: &AuthorProfile,
+        posts: &[ListedPost],
+        pagination: &Pagination,
+    ) -> Result<String> {
+        let mut context = listing_context(site, path, pagination);
+        context.insert("heading", &author.name);
+        context.insert("author_profile", author);
+        context.insert("posts", posts);
+        self.render("listing.html", &context)
+    }
+
+    /// Renders an archive page at `path` listing `years` under `heading`
+    pub fn render_archive(
+        &self,
+        site: &SiteConfig,
+        path: &str,
+        heading: &str,
+        years: &[ArchiveYear],
+    ) -> Result<String> {
+        let mut context = page_context(site, path);
+        // Year pages belong to the archive section
+        context.insert("nav", &nav_items(site, path, &[ARCHIVE_PAGE]));
+        context.insert("heading", heading);
+        context.insert("years", years);
+        self.render("archive.html", &context)
+    }
+
+    /// Renders the search page at `path`, whose script loads the search index
+    pub fn render_search(&self, site: &SiteConfig, path: &str) -> Result<String> {
+        self.render("search.html", &page_context(site, path))
+    }
+
+    /// Renders the not-found page at `path`, showing `content_html`. Hosts serve it in place of
+    /// any missing page, so its links go through the site's address rather than relative to it.
+    pub fn render_not_found(
+        &self,
+        site: &SiteConfig,
+        path: &str,
+        content_html: &str,
+    ) -> Result<String> {
+        let root = match site.base_url.is_empty() {
+            true => "/".to_string(),
+            false => site.url_for(""),
+        };
+        let mut context = page_context(site, path);
+        context.insert("root", &root);
+        context.insert("nav", &nav_items_from(site, &root, path, &[]));
+        context.insert("noindex", &true);
+        context.insert("content", content_html);
+        self.render("404.html", &context)
+    }
+
+    fn render(&self, name: &str, context: &Context) -> Result<String> {
+        self.tera.render(name, context).map_err(|e| Error::Render {
+            template: name.to_string(),
+            message: error_chain(&e),
+        })
+    }
+}
+
+/// Variables shared by every page: the site config, the relative path to the output root, the
+/// page's absolute and canonical URLs, whether search engines should skip it, the navigation
+/// bar, and the analytics script
+fn page_context(site: &SiteConfig, path: &str) -> Context {
+    let mut context = Context::new();
+    context.insert("site", site);
+    context.insert("root", &root_for(path));
+    // Pages in a directory of their own, like the front page, are known by its address
+    let url = site.url_for(permalink::url(path));
+    context.insert("page_url", &url);
+    context.insert("canonical_url", &url);
+    context.insert("noindex", &false);
+    context.insert("nav", &nav_items(site, path, &[]));
+    context.insert("analytics", &site.analytics.snippet());
+    context
+}
+
+/// Variables for a page of a listing, whose later pages belong to the same nav section as the
+/// first
+fn listing_context(site: &SiteConfig, path: &str, pagination: &Pagination) -> Context {
+    let mut context = page_context(site, path);
+    context.insert("nav", &nav_items(site, path, &[pagination.first.as_str()]));
+    context.insert("pagination", pagination);
+    context
+}
+
+/// Tera nests the useful part of an error (e.g. the offending variable) in its source chain
+fn error_chain(error: &tera::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(e) = source {
+        message.push_str(": ");
+        message.push_str(&e.to_string());
+        source = e.source();
+    }
+    message
+}
@@ -0,0 +1,190 @@
+use crate::{
+    assets, atomic,
+    error::{Error, Result},
+    fingerprint,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// A theme built into equinox: template overrides plus the files served from `theme/`
+struct BuiltinTheme {
+    name: &'static str,
+    templates: &'static [(&'static str, &'static str)],
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+const BUILTIN_THEMES: &[BuiltinTheme] = &[
+    BuiltinTheme {
+        name: "default",
+        templates: &[],
+        files: &[
+            ("style.css", include_bytes!("../themes/default/style.css")),
+            (
+                "highlight.css",
+                include_bytes!("../themes/default/highlight.css"),
+            ),
+        ],
+    },
+    BuiltinTheme {
+        name: "dark",
+        templates: &[],
+        files: &[
+            ("style.css", include_bytes!("../themes/dark/style.css")),
+            (
+                "highlight.css",
+                include_bytes!("../themes/dark/highlight.css"),
+            ),
+        ],
+    },
+    BuiltinTheme {
+        name: "minimal",
+        templates: &[("base.html", include_str!("../themes/minimal/base.html"))],
+        files: &[
+            ("style.css", include_bytes!("../themes/minimal/style.css")),
+            (
+                "highlight.css",
+                include_bytes!("../themes/minimal/highlight.css"),
+            ),
+        ],
+    },
+];
+
+/// Directory inside a project holding user-provided themes, one subdirectory per theme
+pub const THEMES_DIR: &str = "themes";
+
+/// Directory in the output that a theme's static files are written to
+pub const OUTPUT_DIR: &str = "theme";
+
+enum Source {
+    Builtin(&'static BuiltinTheme),
+    /// A theme directory: `.html` templates at the top level and static files in `static/`
+    Dir(PathBuf),
+}
+
+/// The look of a generated site: templates layered over the built-in ones, and a stylesheet
+/// (plus any other static files) copied to `theme/` in the output directory
+pub struct Theme {
+    name: String,
+    source: Source,
+}
+
+impl Theme {
+    /// Loads the theme called `name`, preferring a directory of that name under the project's
+    /// `themes/` directory over a built-in theme
+    pub fn load(site_root: &Path, name: &str) -> Result<Theme> {
+        let dir = site_root.join(THEMES_DIR).join(name);
+        let source = if dir.is_dir() {
+            Source::Dir(dir)
+        } else if let Some(builtin) = BUILTIN_THEMES.iter().find(|t| t.name == name) {
+            Source::Builtin(builtin)
+        } else {
+            return Err(Error::Invalid(format!(
+                "Unknown theme '{}': expected a built-in theme ({}) or {}",
+                name,
+                builtin_names().join(", "),
+                dir.display()
+            )));
+        };
+
+        Ok(Theme {
+            name: name.to_string(),
+            source,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Templates the theme overrides, as (name, source) pairs
+    pub fn templates(&self) -> Result<Vec<(String, String)>> {
+        match &self.source {
+            Source::Builtin(theme) => Ok(theme
+                .templates
+                .iter()
+                .map(|(name, source)| (name.to_string(), source.to_string()))
+                .collect()),
+            Source::Dir(dir) => {
+                let entries = fs::read_dir(dir).map_err(|e| Error::io("read", dir, e))?;
+
+                let mut templates = Vec::new();
+                for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+                    if !path.is_file() || path.extension().is_none_or(|ext| ext != "html") {
+                        continue;
+                    }
+                    let source =
+                        fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+                    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                    templates.push((name, source));
+                }
+                Ok(templates)
+            }
+        }
+    }
+
+    /// Writes the theme's static files to `dst`, removing any left over from another theme
+    pub fn write_files(&self, dst: &Path) -> Result<()> {
+        match &self.source {
+            Source::Builtin(theme) => write_builtin_files(theme, dst),
+            Source::Dir(dir) => assets::sync(&dir.join("static"), dst).map(|_| ()),
+        }
+    }
+}
+
+/// Names of every theme available to a project: built-in themes followed by the project's own
+pub fn available(site_root: &Path) -> Vec<String> {
+    let mut names: Vec<String> = builtin_names().iter().map(|s| s.to_string()).collect();
+
+    if let Ok(entries) = fs::read_dir(site_root.join(THEMES_DIR)) {
+        let mut custom: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| !name.starts_with('.') && !names.contains(name))
+            .collect();
+        custom.sort();
+        names.extend(custom);
+    }
+    names
+}
+
+fn builtin_names() -> Vec<&'static str> {
+    BUILTIN_THEMES.iter().map(|t| t.name).collect()
+}
+
+fn write_builtin_files(theme: &BuiltinTheme, dst: &Path) -> Result<()> {
+    fs::create_dir_all(dst).map_err(|e| Error::io("create", dst, e))?;
+
+    for (name, contents) in theme.files {
+        let path = dst.join(name);
+        if fs::read(&path).is_ok_and(|existing| existing == *contents) {
+            continue;
+        }
+        atomic::write(&path, contents).map_err(|e| Error::io("write", &path, e))?;
+        debug!("Wrote {}", path.display());
+    }
+
+    let entries = fs::read_dir(dst).map_err(|e| Error::io("read", dst, e))?;
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        let name = path.file_name().unwrap().to_string_lossy();
+        // Fingerprinted copies of the theme's files are kept along with them
+        let original = fingerprint::original_name(&name);
+        if theme
+            .files
+            .iter()
+            .any(|(file, _)| *file == name || original.as_deref() == Some(*file))
+        {
+            continue;
+        }
+        let removed = if path.is_dir() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.map_err(|e| Error::io("remove", &path, e))?;
+    }
+    Ok(())
+}
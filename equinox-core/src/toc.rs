@@ -0,0 +1,94 @@
+use crate::{content::slugify, html::escape};
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+/// Fewest headings a post needs before a table of contents is worth showing
+pub const MIN_HEADINGS: usize = 3;
+
+/// A heading found in a post, linked to from the table of contents
+#[derive(Debug, Clone)]
+pub struct Heading {
+    /// 1 for `<h1>`, 2 for `<h2>`, and so on, after the site's `heading_offset` has moved it
+    /// down from the number of `#`s
+    pub level: usize,
+    pub id: String,
+    pub text: String,
+}
+
+/// Gives every heading in `events` an anchor id derived from its text, suffixed with a number
+/// where two headings share the same text, and returns the headings in order
+pub fn assign_ids(events: &mut [Event]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut i = 0;
+    while i < events.len() {
+        let Event::Start(Tag::Heading { level, .. }) = &events[i] else {
+            i += 1;
+            continue;
+        };
+        let level = *level as usize;
+
+        let mut text = String::new();
+        let mut end = i + 1;
+        while end < events.len() && !matches!(events[end], Event::End(TagEnd::Heading(_))) {
+            if let Event::Text(t) | Event::Code(t) = &events[end] {
+                text.push_str(t);
+            }
+            end += 1;
+        }
+
+        let base = match slugify(&text) {
+            slug if slug.is_empty() => "section".to_string(),
+            slug => slug,
+        };
+        let mut id = base.clone();
+        let mut n = 2;
+        while headings.iter().any(|h: &Heading| h.id == id) {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+
+        if let Event::Start(Tag::Heading { id: anchor, .. }) = &mut events[i] {
+            *anchor = Some(CowStr::from(id.clone()));
+        }
+        headings.push(Heading { level, id, text });
+        i = end;
+    }
+    headings
+}
+
+/// Nested list of links to `headings`, indented relative to the highest-level heading
+pub fn to_html(headings: &[Heading]) -> String {
+    let base = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let mut html = String::from("<nav class=\"toc\">\n<ul>\n");
+    let mut depth = 0;
+
+    for (i, heading) in headings.iter().enumerate() {
+        let level = heading.level - base;
+        if i > 0 {
+            if level > depth {
+                html.push_str("\n<ul>\n");
+                for _ in depth + 1..level {
+                    html.push_str("<li>\n<ul>\n");
+                }
+            } else {
+                html.push_str("</li>\n");
+                for _ in level..depth {
+                    html.push_str("</ul>\n</li>\n");
+                }
+            }
+        }
+        // The first heading starts the list even if a higher-level one comes later
+        depth = if i == 0 { 0 } else { level };
+        html.push_str(&format!(
+            "<li><a href=\"#{}\">{}</a>",
+            heading.id,
+            escape(&heading.text)
+        ));
+    }
+
+    html.push_str("</li>\n");
+    for _ in 0..depth {
+        html.push_str("</ul>\n</li>\n");
+    }
+    html.push_str("</ul>\n</nav>\n");
+    html
+}
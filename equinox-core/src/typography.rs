@@ -0,0 +1,107 @@
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+
+/// Replaces straight quotes with curly ones, `--` with em dashes, and `...` with ellipses in the
+/// text of a Markdown event stream, leaving code and raw HTML alone
+#[derive(Debug, Default)]
+pub struct Typography {
+    /// Nesting depth of code blocks the current event is inside
+    code_depth: usize,
+    /// Last character of text seen in the current block, which decides whether a quote opens
+    /// or closes
+    previous: Option<char>,
+}
+
+impl Typography {
+    pub fn apply<'a>(&mut self, event: Event<'a>) -> Event<'a> {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.code_depth += 1;
+                event
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                self.code_depth = self.code_depth.saturating_sub(1);
+                self.previous = None;
+                event
+            }
+            Event::Start(_) | Event::End(_) if self.code_depth == 0 => {
+                if is_block_boundary(&event) {
+                    self.previous = None;
+                }
+                event
+            }
+            Event::Text(text) if self.code_depth == 0 => {
+                let smart = smarten(&text, self.previous);
+                self.previous = text.chars().last().or(self.previous);
+                Event::Text(CowStr::from(smart))
+            }
+            Event::Code(ref code) => {
+                self.previous = code.chars().last().or(self.previous);
+                event
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                self.previous = Some(' ');
+                event
+            }
+            event => event,
+        }
+    }
+}
+
+fn is_block_boundary(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::Start(
+            Tag::Paragraph | Tag::Heading { .. } | Tag::Item | Tag::BlockQuote(_) | Tag::TableCell
+        )
+    )
+}
+
+/// Applies the replacements to `text`, given the character that came before it
+pub fn smarten(text: &str, previous: Option<char>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut previous = previous;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let replacement = match c {
+            '.' if text_continues(&mut chars, "..") => '…',
+            '-' if chars.peek() == Some(&'-') => {
+                chars.next();
+                // `---` is also an em dash rather than an em dash and a hyphen
+                if chars.peek() == Some(&'-') {
+                    chars.next();
+                }
+                '—'
+            }
+            '"' if opens_quote(previous) => '“',
+            '"' => '”',
+            '\'' if previous.is_some_and(char::is_alphanumeric) => '’',
+            '\'' if opens_quote(previous) => '‘',
+            '\'' => '’',
+            c => c,
+        };
+        output.push(replacement);
+        previous = Some(c);
+    }
+    output
+}
+
+/// Whether a quote after `previous` starts a quotation rather than ending one
+fn opens_quote(previous: Option<char>) -> bool {
+    match previous {
+        None => true,
+        Some(c) => c.is_whitespace() || "([{—–-/“‘".contains(c),
+    }
+}
+
+/// Consumes `rest` from `chars` if they come next
+fn text_continues(chars: &mut std::iter::Peekable<std::str::Chars>, rest: &str) -> bool {
+    let ahead = chars.clone();
+    if ahead.take(rest.len()).eq(rest.chars()) {
+        for _ in 0..rest.len() {
+            chars.next();
+        }
+        return true;
+    }
+    false
+}
@@ -0,0 +1,194 @@
+//! Checking the structure of the generated pages: elements that don't belong where they are,
+//! tags that are never closed or close the wrong element, and ids given twice. Browsers quietly
+//! repair all of these, each in its own way, so pages can look right in one and broken in
+//! another.
+
+use crate::{
+    error::{Error, Result},
+    html,
+    links::list_pages,
+    site::Site,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+};
+use tracing::{info, warn};
+
+/// Something wrong with the structure of one or more generated pages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub message: String,
+    /// The pages with the problem, relative to the output directory
+    pub pages: Vec<String>,
+}
+
+/// Elements that have no end tag
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose end tag may be left out, closed by whatever comes next
+const OPTIONAL_END: &[&str] = &[
+    "html", "head", "body", "p", "li", "dt", "dd", "option", "optgroup", "tr", "td", "th", "thead",
+    "tbody", "tfoot", "colgroup", "caption", "rt", "rp",
+];
+
+/// Elements that belong in `<head>`
+const HEAD_CONTENT: &[&str] = &[
+    "title", "meta", "link", "style", "script", "base", "noscript", "template",
+];
+
+/// Elements whose start tag closes an open `<p>`
+const CLOSES_PARAGRAPH: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Checks every generated page, logging each problem found as a warning along with the pages
+/// that have it
+pub fn check_output(site: &Site) -> Result<Vec<Problem>> {
+    let output_dir = site.output_dir();
+    let mut problems: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for file in site.output_files()? {
+        if file.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+        let path = output_dir.join(&file);
+        let html = fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))?;
+        let page = file.to_string_lossy().replace('\\', "/");
+        for message in validate(&html) {
+            problems.entry(message).or_default().push(page.clone());
+        }
+    }
+
+    for (message, pages) in &problems {
+        warn!("Invalid HTML: {} on {}", message, list_pages(pages));
+    }
+    match problems.len() {
+        0 => info!("Found no problems in the generated HTML"),
+        count => warn!("Found {} HTML problems", count),
+    }
+    Ok(problems
+        .into_iter()
+        .map(|(message, pages)| Problem { message, pages })
+        .collect())
+}
+
+/// The structural problems of one page, each described once
+pub fn validate(html: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut report = |problem: String| {
+        if !problems.contains(&problem) {
+            problems.push(problem);
+        }
+    };
+    if !html
+        .trim_start()
+        .get(..9)
+        .is_some_and(|start| start.eq_ignore_ascii_case("<!doctype"))
+    {
+        report("the page doesn't start with a doctype".to_string());
+    }
+
+    let mut open: Vec<String> = Vec::new();
+    let mut ids = HashSet::new();
+    let mut in_head = false;
+    let mut head_closed = false;
+    for tag in html::tags(html) {
+        let name = html::tag_name(tag);
+        if tag.starts_with('/') {
+            if name == "head" {
+                in_head = false;
+                head_closed = true;
+            }
+            match open.iter().rposition(|open| *open == name) {
+                Some(i) => {
+                    for unclosed in open.drain(i..).skip(1) {
+                        if !OPTIONAL_END.contains(&unclosed.as_str()) {
+                            report(format!("<{}> is never closed before </{}>", unclosed, name));
+                        }
+                    }
+                }
+                None => report(format!("</{}> doesn't close an open element", name)),
+            }
+            continue;
+        }
+
+        if name.is_empty() {
+            continue;
+        }
+        match name.as_str() {
+            "head" => in_head = true,
+            "body" => {
+                in_head = false;
+                head_closed = true;
+            }
+            // Whatever is inside a misplaced element is only misplaced along with it
+            _ if in_head
+                && open.last().is_some_and(|parent| parent == "head")
+                && !HEAD_CONTENT.contains(&name.as_str()) =>
+            {
+                report(format!("<{}> is inside <head>", name))
+            }
+            _ if !head_closed && !in_head && name != "html" => {
+                report(format!("<{}> comes before <head>", name))
+            }
+            _ => {}
+        }
+        if let Some(id) = html::attribute(tag, "id").filter(|id| !id.is_empty()) {
+            if !ids.insert(id.clone()) {
+                report(format!("the id \"{}\" is used more than once", id));
+            }
+        }
+
+        // Start tags that implicitly close the element before them
+        if let Some(last) = open.last() {
+            let closes_last = (last == "p" && CLOSES_PARAGRAPH.contains(&name.as_str()))
+                || (*last == name
+                    && ["li", "dt", "dd", "option", "tr", "td", "th"].contains(&last.as_str()));
+            if closes_last {
+                open.pop();
+            }
+        }
+        let self_closing = tag.trim_end().ends_with('/');
+        if VOID.contains(&name.as_str()) || self_closing {
+            continue;
+        }
+        open.push(name);
+    }
+
+    for unclosed in open {
+        if !OPTIONAL_END.contains(&unclosed.as_str()) {
+            report(format!("<{}> is never closed", unclosed));
+        }
+    }
+    problems
+}
@@ -0,0 +1,228 @@
+//! Earlier versions of each post's source, kept every time it is saved so an edit can be
+//! compared against or undone after the editor is closed
+
+use crate::{
+    atomic,
+    error::{Error, Result},
+};
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, TimeZone};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// Directory holding each post's versions, relative to the project root
+pub const VERSIONS_DIR: &str = ".equinox/versions";
+
+/// Versions kept of each post before the oldest are dropped
+const MAX_VERSIONS: usize = 50;
+
+/// How versions are named, by when they were saved
+const NAME_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// Lines compared against each other before a diff gives up on lining changes up
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// A saved version of a post's source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    /// Name the version is loaded by
+    pub name: String,
+    pub saved: DateTime<Local>,
+}
+
+/// How a line of one version differs from the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Same,
+    /// Only in the old version
+    Removed,
+    /// Only in the new version
+    Added,
+    /// Replaced by a different line in the new version
+    Changed,
+}
+
+/// A row of a side-by-side diff, with the old version's line on the left and the new one's on
+/// the right. A line missing from one side is `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRow {
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub change: Change,
+}
+
+/// Keeps `source` as the newest version of the post `id`, unless it is the same as the last one
+/// kept. The oldest versions are dropped once there are too many. Returns whether a version was
+/// added.
+pub fn snapshot(root: &Path, id: &str, source: &str) -> Result<bool> {
+    let versions = list(root, id)?;
+    if let Some(latest) = versions.first() {
+        if load(root, id, &latest.name).is_ok_and(|latest| latest == source) {
+            return Ok(false);
+        }
+    }
+
+    let dir = dir(root, id);
+    fs::create_dir_all(&dir).map_err(|e| Error::io("create", &dir, e))?;
+    // Versions saved within the same millisecond are kept apart, in order
+    let mut saved = Local::now();
+    let (name, path) = loop {
+        let name = saved.format(NAME_FORMAT).to_string();
+        let path = dir.join(format!("{}.md", name));
+        if !path.exists() {
+            break (name, path);
+        }
+        saved += TimeDelta::milliseconds(1);
+    };
+    atomic::write_synced(&path, source).map_err(|e| Error::io("write", &path, e))?;
+    debug!("Kept version {} of {}", name, id);
+
+    // The one just kept isn't listed yet
+    for old in versions.iter().skip(MAX_VERSIONS - 1) {
+        let path = dir.join(format!("{}.md", old.name));
+        fs::remove_file(&path).map_err(|e| Error::io("remove", &path, e))?;
+    }
+    Ok(true)
+}
+
+/// The kept versions of the post `id`, newest first
+pub fn list(root: &Path, id: &str) -> Result<Vec<Version>> {
+    let dir = dir(root, id);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let entries = fs::read_dir(&dir).map_err(|e| Error::io("read", &dir, e))?;
+    let mut versions: Vec<Version> = entries
+        .filter_map(|entry| {
+            let name = entry
+                .ok()?
+                .file_name()
+                .to_str()?
+                .strip_suffix(".md")?
+                .to_string();
+            let saved = NaiveDateTime::parse_from_str(&name, NAME_FORMAT).ok()?;
+            let saved = Local.from_local_datetime(&saved).earliest()?;
+            Some(Version { name, saved })
+        })
+        .collect();
+    versions.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(versions)
+}
+
+/// The source of the post `id` as it was in `version`
+pub fn load(root: &Path, id: &str, version: &str) -> Result<String> {
+    let path = dir(root, id).join(format!("{}.md", version));
+    fs::read_to_string(&path).map_err(|e| Error::io("read", &path, e))
+}
+
+/// A line-by-line diff from `old` to `new`. Removed lines directly followed by added ones are
+/// shown side by side as changed.
+pub fn diff(old: &str, new: &str) -> Vec<DiffRow> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // Only the middle, between the lines both start and end with, needs lining up
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut rows: Vec<DiffRow> = old[..prefix].iter().map(|line| same(line)).collect();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for (change, line) in line_changes(old_middle, new_middle) {
+        match change {
+            Change::Removed => removed.push(line),
+            Change::Added => added.push(line),
+            _ => {
+                pair(&mut rows, &mut removed, &mut added);
+                rows.push(same(line));
+            }
+        }
+    }
+    pair(&mut rows, &mut removed, &mut added);
+    rows.extend(old[old.len() - suffix..].iter().map(|line| same(line)));
+    rows
+}
+
+/// Each line of `old` and `new` in order, marked as kept, removed, or added, from their longest
+/// common subsequence. Too many lines to compare are all replaced.
+fn line_changes<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Change, &'a str)> {
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        return old
+            .iter()
+            .map(|line| (Change::Removed, *line))
+            .chain(new.iter().map(|line| (Change::Added, *line)))
+            .collect();
+    }
+    // Length of the longest common subsequence of `old[i..]` and `new[j..]`
+    let width = new.len() + 1;
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = match old[i] == new[j] {
+                true => lengths[(i + 1) * width + j + 1] + 1,
+                false => lengths[(i + 1) * width + j].max(lengths[i * width + j + 1]),
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            changes.push((Change::Same, old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len()
+            && (j == new.len() || lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            changes.push((Change::Removed, old[i]));
+            i += 1;
+        } else {
+            changes.push((Change::Added, new[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Adds rows for a run of removed and added lines, pairing them up as changed while both last
+fn pair(rows: &mut Vec<DiffRow>, removed: &mut Vec<&str>, added: &mut Vec<&str>) {
+    let count = removed.len().max(added.len());
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+    for _ in 0..count {
+        let (left, right) = (removed.next(), added.next());
+        let change = match (left, right) {
+            (Some(_), Some(_)) => Change::Changed,
+            (Some(_), None) => Change::Removed,
+            _ => Change::Added,
+        };
+        rows.push(DiffRow {
+            left: left.map(str::to_string),
+            right: right.map(str::to_string),
+            change,
+        });
+    }
+}
+
+fn same(line: &str) -> DiffRow {
+    DiffRow {
+        left: Some(line.to_string()),
+        right: Some(line.to_string()),
+        change: Change::Same,
+    }
+}
+
+fn dir(root: &Path, id: &str) -> PathBuf {
+    root.join(VERSIONS_DIR).join(id)
+}
@@ -0,0 +1,99 @@
+//! Keeping track of which published posts the site's webhooks have been told about, so each is
+//! announced once: when it is published, or for a scheduled post, at the first build or deploy
+//! after its date. Calling the webhooks is left to the caller.
+
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+    site::Site,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Record of the posts already announced, relative to the project root
+pub const LOG_FILE: &str = ".equinox/webhooks.toml";
+
+/// The posts announced to the webhooks so far, by ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifiedLog {
+    notified: BTreeSet<String>,
+}
+
+impl NotifiedLog {
+    /// Loads the log of the project at `root`, or `None` if no post has been announced yet
+    pub fn load(root: &Path) -> Option<NotifiedLog> {
+        fs::read_to_string(path(root))
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = path(root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        let contents = toml::to_string(self).map_err(|e| {
+            Error::Invalid(format!("Failed to serialize the announced posts: {}", e))
+        })?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    pub fn is_notified(&self, id: &str) -> bool {
+        self.notified.contains(id)
+    }
+
+    pub fn record(&mut self, id: &str) {
+        self.notified.insert(id.to_string());
+    }
+}
+
+/// Starts the log if `site` doesn't have one, recording every published post but `current` as
+/// announced so adding webhooks doesn't announce the whole archive at once
+pub fn start(site: &Site, current: Option<&str>) -> Result<()> {
+    if NotifiedLog::load(&site.root).is_some() {
+        return Ok(());
+    }
+    let mut log = NotifiedLog::default();
+    for post in unnotified(site, &log)? {
+        if Some(post.id.as_str()) != current {
+            log.record(&post.id);
+        }
+    }
+    log.save(&site.root)
+}
+
+/// Records the published ones among `posts`, just imported from elsewhere, as announced so an
+/// import doesn't announce a whole archive. Before the log is started there is nothing to do, as
+/// starting it records them.
+pub fn record_imported(site: &Site, posts: &[Post]) -> Result<()> {
+    let Some(mut log) = NotifiedLog::load(&site.root) else {
+        return Ok(());
+    };
+    for post in posts.iter().filter(|post| post.is_published()) {
+        log.record(&post.id);
+    }
+    log.save(&site.root)
+}
+
+/// Published posts of `site` that aren't in `log`, oldest first. Drafts and posts dated in the
+/// future are left for later.
+pub fn unnotified(site: &Site, log: &NotifiedLog) -> Result<Vec<Post>> {
+    let mut posts: Vec<Post> = site
+        .store()
+        .list()?
+        .into_iter()
+        .filter(|post| post.is_published() && !log.is_notified(&post.id))
+        .collect();
+    posts.sort_by_key(Post::date);
+    Ok(posts)
+}
+
+fn path(root: &Path) -> PathBuf {
+    root.join(LOG_FILE)
+}
@@ -0,0 +1,166 @@
+//! Webmentions: telling the sites a post links to that it did, so they can show the post as a
+//! response. Sending the requests is left to the caller; this finds who to notify and keeps
+//! track of who has been.
+
+use crate::{
+    atomic,
+    content::Post,
+    error::{Error, Result},
+    html, markdown,
+    site::Site,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Record of the webmentions already sent, relative to the project root
+pub const SENT_FILE: &str = ".equinox/webmentions.toml";
+
+/// The pages `post` links to on other sites, each once and without its fragment
+pub fn targets(site: &Site, post: &Post) -> Vec<String> {
+    let base_url = site.config.base_url.trim_end_matches('/');
+    let mut targets: Vec<String> = Vec::new();
+    for link in markdown::links(&post.body) {
+        let target = link.split('#').next().unwrap_or_default();
+        let external = target.starts_with("http://") || target.starts_with("https://");
+        let own = !base_url.is_empty() && target.starts_with(base_url);
+        if external && !own && !targets.iter().any(|seen| seen == target) {
+            targets.push(target.to_string());
+        }
+    }
+    targets
+}
+
+/// The webmention endpoint of the page at `url`, advertised in one of its `Link` headers or in
+/// a `<link>` or `<a>` element with `rel="webmention"` in its `html`, resolved against `url`
+pub fn discover_endpoint(url: &str, link_headers: &[&str], html: &str) -> Option<String> {
+    let from_header = link_headers
+        .iter()
+        .flat_map(|header| header.split(','))
+        .find_map(|link| {
+            let (target, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+            let is_webmention = params.split(';').any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("rel=")
+                    .is_some_and(|rel| has_webmention_rel(rel.trim_matches('"')))
+            });
+            is_webmention.then(|| target.to_string())
+        });
+    let endpoint = from_header.or_else(|| {
+        let lower = html.to_ascii_lowercase();
+        let mut rest = 0;
+        while let Some(start) = lower[rest..].find('<').map(|i| rest + i) {
+            let end = lower[start..].find('>').map_or(lower.len(), |i| start + i);
+            let tag = &html[start + 1..end];
+            rest = end;
+            let name = tag.split_whitespace().next().unwrap_or_default();
+            if !name.eq_ignore_ascii_case("link") && !name.eq_ignore_ascii_case("a") {
+                continue;
+            }
+            if html::attribute(tag, "rel").is_some_and(|rel| has_webmention_rel(&rel)) {
+                if let Some(href) = html::attribute(tag, "href") {
+                    return Some(href);
+                }
+            }
+        }
+        None
+    })?;
+    Some(resolve(url, &endpoint))
+}
+
+/// Whether the space-separated `rel` values include `webmention`
+fn has_webmention_rel(rel: &str) -> bool {
+    rel.split_whitespace()
+        .any(|value| value.eq_ignore_ascii_case("webmention"))
+}
+
+/// `href` as an absolute URL, relative to the page at `base`
+pub fn resolve(base: &str, href: &str) -> String {
+    let href = href.trim();
+    if href.contains("://") {
+        return href.to_string();
+    }
+    let (scheme, rest) = base.split_once("://").unwrap_or(("https", base));
+    let origin_end = rest.find('/').unwrap_or(rest.len());
+    let origin = format!("{}://{}", scheme, &rest[..origin_end]);
+    let path = rest[origin_end..]
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default();
+
+    if let Some(host_relative) = href.strip_prefix("//") {
+        format!("{}://{}", scheme, host_relative)
+    } else if href.starts_with('/') {
+        format!("{}{}", origin, href)
+    } else if href.is_empty() || href.starts_with('#') {
+        base.split('#').next().unwrap_or(base).to_string()
+    } else if href.starts_with('?') {
+        format!("{}{}{}", origin, path, href)
+    } else {
+        let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+        let mut parts: Vec<&str> = dir.split('/').filter(|part| !part.is_empty()).collect();
+        for part in href.split('/') {
+            match part {
+                "." => {}
+                ".." => {
+                    parts.pop();
+                }
+                part => parts.push(part),
+            }
+        }
+        format!("{}/{}", origin, parts.join("/"))
+    }
+}
+
+/// The pages each post has already told about linking to them, so every link is only
+/// mentioned once
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SentLog {
+    /// Targets notified, by the URL of the post linking to them
+    sent: BTreeMap<String, Vec<String>>,
+}
+
+impl SentLog {
+    /// Loads the log of the project at `root`, empty if nothing has been sent yet
+    pub fn load(root: &Path) -> SentLog {
+        fs::read_to_string(path(root))
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = path(root);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+        let contents = toml::to_string(self).map_err(|e| {
+            Error::Invalid(format!("Failed to serialize the sent webmentions: {}", e))
+        })?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    /// Whether `target` was already told that `source` links to it
+    pub fn is_sent(&self, source: &str, target: &str) -> bool {
+        self.sent
+            .get(source)
+            .is_some_and(|targets| targets.iter().any(|sent| sent == target))
+    }
+
+    /// Records that `target` has been dealt with for `source`, whether it was sent a webmention
+    /// or turned out not to accept any
+    pub fn record(&mut self, source: &str, target: &str) {
+        let targets = self.sent.entry(source.to_string()).or_default();
+        if !targets.iter().any(|sent| sent == target) {
+            targets.push(target.to_string());
+        }
+    }
+}
+
+fn path(root: &Path) -> PathBuf {
+    root.join(SENT_FILE)
+}
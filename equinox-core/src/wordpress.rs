@@ -0,0 +1,208 @@
+//! Importing posts from a WordPress export (WXR) file
+
+use crate::{
+    content::{Post, PostEdit},
+    error::{Error, Result},
+    front_matter::FrontMatter,
+    import::ImportReport,
+    site::Site,
+};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use roxmltree::{Document, Node};
+use std::{fs, path::Path};
+use tracing::{info, warn};
+
+/// Namespace of the `content:encoded` element holding a post's HTML
+const CONTENT_NS: &str = "http://purl.org/rss/1.0/modules/content/";
+/// Namespace of the `dc:creator` element naming a post's author
+const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+
+/// How WordPress writes dates, e.g. `2021-03-04 05:06:07`
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// A post read from an export, before it is stored
+#[derive(Debug, Clone)]
+pub struct WordPressPost {
+    pub title: String,
+    /// The post's `post_name`, used in its old URL
+    pub slug: String,
+    pub date: Option<DateTime<FixedOffset>>,
+    pub author: String,
+    /// The first category's slug; equinox posts belong to one category
+    pub category: String,
+    /// Tags followed by any further categories
+    pub tags: Vec<String>,
+    pub excerpt: String,
+    /// The post's HTML, which Markdown passes through
+    pub content: String,
+    /// Not published on the old site, e.g. a draft or private post
+    pub draft: bool,
+}
+
+/// Stores every post in the WordPress export at `path` as a post of `site`, keeping the old
+/// slugs so pages keep their names. Pages, attachments, and trashed posts are left out.
+pub fn import_export(site: &Site, path: &Path, default_author: &str) -> Result<ImportReport> {
+    let text = fs::read_to_string(path).map_err(|e| Error::io("read", path, e))?;
+    let posts = parse(&text).map_err(|e| match e {
+        Error::Parse { what, message, .. } => Error::parse(&what, Some(path), message),
+        e => e,
+    })?;
+
+    let mut import = ImportReport::default();
+    for wp in posts {
+        let name = match wp.title.is_empty() {
+            true => wp.slug.clone(),
+            false => wp.title.clone(),
+        };
+        let result = store_post(site, wp, default_author);
+        match result.and_then(|post| site.save_post(&post).map(|()| post)) {
+            Ok(post) => import.posts.push(post),
+            Err(e) => {
+                warn!("Skipped WordPress post {}: {}", name, e);
+                import.failed.push((name, e));
+            }
+        }
+    }
+    import.record_announced(site)?;
+    info!(
+        "Imported {} WordPress posts from {}",
+        import.posts.len(),
+        path.display()
+    );
+    Ok(import)
+}
+
+/// Turns an exported post into an unsaved equinox post
+fn store_post(site: &Site, wp: WordPressPost, default_author: &str) -> Result<Post> {
+    let front_matter = FrontMatter {
+        slug: (!wp.slug.is_empty()).then_some(wp.slug),
+        draft: wp.draft.then_some(true),
+        ..FrontMatter::default()
+    };
+    let title = match wp.title.is_empty() {
+        true => "Untitled".to_string(),
+        false => wp.title,
+    };
+    site.store().edit(
+        None,
+        PostEdit {
+            title,
+            category: wp.category,
+            tags: wp.tags,
+            description: wp.excerpt,
+            date: wp.date,
+            author: wp.author,
+            default_author: default_author.to_string(),
+            content: format!("{}{}", front_matter.to_block()?, wp.content.trim()),
+        },
+    )
+}
+
+/// Reads the posts out of a WordPress export
+pub fn parse(text: &str) -> Result<Vec<WordPressPost>> {
+    let doc = Document::parse(text).map_err(|e| Error::parse("WordPress export", None, e))?;
+    let channel = doc
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("channel"))
+        .ok_or_else(|| Error::parse("WordPress export", None, "no <channel> element"))?;
+
+    let posts = channel
+        .children()
+        .filter(|node| node.has_tag_name("item"))
+        .filter(|item| wp_text(*item, "post_type") == "post")
+        .filter(|item| wp_text(*item, "status") != "trash")
+        .map(read_item)
+        .collect();
+    Ok(posts)
+}
+
+fn read_item(item: Node) -> WordPressPost {
+    let mut categories = Vec::new();
+    let mut tags = Vec::new();
+    for node in item.children().filter(|node| node.has_tag_name("category")) {
+        let name = node
+            .attribute("nicename")
+            .map(str::to_string)
+            .unwrap_or_else(|| text(node));
+        match node.attribute("domain") {
+            Some("category") if name != "uncategorized" => categories.push(name),
+            Some("post_tag") => tags.push(text(node)),
+            _ => {}
+        }
+    }
+    let category = match categories.is_empty() {
+        true => String::new(),
+        false => categories.remove(0),
+    };
+    tags.extend(categories);
+
+    let status = wp_text(item, "status");
+    WordPressPost {
+        title: child_text(item, "", "title").trim().to_string(),
+        slug: wp_text(item, "post_name"),
+        date: post_date(item),
+        author: child_text(item, DC_NS, "creator"),
+        category,
+        tags,
+        excerpt: excerpt(item),
+        content: child_text(item, CONTENT_NS, "encoded"),
+        draft: !matches!(status.as_str(), "publish" | "future"),
+    }
+}
+
+/// The publish date in UTC, or in the site's time zone if the export has no UTC date, as with
+/// drafts
+fn post_date(item: Node) -> Option<DateTime<FixedOffset>> {
+    let parse = |name| NaiveDateTime::parse_from_str(&wp_text(item, name), DATE_FORMAT).ok();
+    if let Some(date) = parse("post_date_gmt") {
+        return Some(Utc.from_utc_datetime(&date).fixed_offset());
+    }
+    parse("post_date")
+        .and_then(|date| Local.from_local_datetime(&date).single())
+        .map(|date| date.fixed_offset())
+}
+
+/// The `excerpt:encoded` element, whose namespace differs between export versions
+fn excerpt(item: Node) -> String {
+    item.children()
+        .find(|node| {
+            node.tag_name().name() == "encoded"
+                && node
+                    .tag_name()
+                    .namespace()
+                    .is_some_and(|ns| ns.contains("/excerpt/"))
+        })
+        .map(text)
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Text of the `wp:` element `name`; the namespace's version differs between exports
+fn wp_text(item: Node, name: &str) -> String {
+    item.children()
+        .find(|node| {
+            node.tag_name().name() == name
+                && node.tag_name().namespace().is_some_and(|ns| {
+                    ns.contains("wordpress.org/export/") && !ns.contains("/excerpt/")
+                })
+        })
+        .map(text)
+        .unwrap_or_default()
+}
+
+/// Text of the child `name` in namespace `ns`, empty for no namespace
+fn child_text(item: Node, ns: &str, name: &str) -> String {
+    item.children()
+        .find(|node| {
+            node.tag_name().name() == name && node.tag_name().namespace().unwrap_or("") == ns
+        })
+        .map(text)
+        .unwrap_or_default()
+}
+
+/// Text and CDATA content of `node`
+fn text(node: Node) -> String {
+    node.children().filter_map(|child| child.text()).collect()
+}
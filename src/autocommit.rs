@@ -0,0 +1,126 @@
+//! Committing the project to its Git repository whenever a post or the site is published
+
+use equinox_core::{Post, Site};
+use git2::{IndexAddOption, Oid, Repository, Signature};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+/// Commits whatever the site's `[auto_commit]` settings ask for after `post` was published, or
+/// after the whole site was deployed when there is no `post`, creating a repository at the
+/// project root if it isn't in one yet. Returns the commit, or `None` if nothing changed since
+/// the last one. Whatever else was already staged is committed along with it, as `git commit`
+/// would.
+pub fn commit_published(site: &Site, post: Option<&Post>) -> Result<Option<Oid>, String> {
+    let config = &site.config.auto_commit;
+    let repo = match Repository::discover(&site.root) {
+        Ok(repo) => repo,
+        Err(_) => {
+            info!("Creating a Git repository at {}", site.root.display());
+            Repository::init(&site.root).map_err(git_error)?
+        }
+    };
+    let workdir = repo
+        .workdir()
+        .ok_or("the project's repository has no working directory")?
+        .to_path_buf();
+
+    let mut dirs: Vec<(PathBuf, bool)> = Vec::new();
+    if config.content {
+        dirs.push((site.content_dir(), false));
+        dirs.push((site.assets_dir(), false));
+    }
+    if config.output {
+        // The output directory is often ignored, but committing it was asked for. A site
+        // generated into the project root is left to the ignore rules.
+        dirs.push((site.output_dir(), site.output_dir() != site.root));
+    }
+
+    let mut index = repo.index().map_err(git_error)?;
+    for (dir, force) in &dirs {
+        if !dir.is_dir() {
+            continue;
+        }
+        let Some(pathspec) = relative(&workdir, dir) else {
+            warn!(
+                "Not committing {}, which is outside the repository at {}",
+                dir.display(),
+                workdir.display()
+            );
+            continue;
+        };
+        let flags = match force {
+            true => IndexAddOption::FORCE,
+            false => IndexAddOption::DEFAULT,
+        };
+        index.add_all([&pathspec], flags, None).map_err(git_error)?;
+        // Picks up files that were deleted
+        index.update_all([&pathspec], None).map_err(git_error)?;
+    }
+    index.write().map_err(git_error)?;
+    let tree_id = index.write_tree().map_err(git_error)?;
+    let tree = repo.find_tree(tree_id).map_err(git_error)?;
+
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+    if parent
+        .as_ref()
+        .is_some_and(|parent| parent.tree_id() == tree_id)
+    {
+        debug!("Nothing changed since the last commit");
+        return Ok(None);
+    }
+
+    let signature = repo
+        .signature()
+        .or_else(|_| Signature::now("equinox", "equinox@localhost"))
+        .map_err(git_error)?;
+    let message = message(site, post);
+    let parents: Vec<_> = parent.iter().collect();
+    let commit = repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parents,
+        )
+        .map_err(git_error)?;
+    info!("Committed {} to {}", commit, workdir.display());
+    Ok(Some(commit))
+}
+
+/// The commit message for publishing `post` or the site, listing what was committed
+fn message(site: &Site, post: Option<&Post>) -> String {
+    let config = &site.config.auto_commit;
+    let committed = match (config.content, config.output) {
+        (true, true) => "post sources and the generated site",
+        (true, false) => "post sources",
+        _ => "the generated site",
+    };
+    match post {
+        Some(post) => format!(
+            "Publish \"{}\"\n\nCommitted the {} after publishing {}.\n",
+            post.meta.title,
+            committed,
+            site.config.post_url(post)
+        ),
+        None => format!(
+            "Publish the site\n\nCommitted the {} after deploying it.\n",
+            committed
+        ),
+    }
+}
+
+/// `dir` relative to the repository's working directory, as a pathspec
+fn relative(workdir: &Path, dir: &Path) -> Option<String> {
+    let (workdir, dir) = (workdir.canonicalize().ok()?, dir.canonicalize().ok()?);
+    let relative = dir.strip_prefix(workdir).ok()?;
+    Some(match relative.as_os_str().is_empty() {
+        true => ".".to_string(),
+        false => relative.to_string_lossy().replace('\\', "/"),
+    })
+}
+
+fn git_error(e: git2::Error) -> String {
+    format!("Git error: {}", e.message())
+}
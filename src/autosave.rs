@@ -0,0 +1,71 @@
+use chrono::{DateTime, Local};
+use equinox_core::{atomic, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// Snapshot of one edited post, written periodically so unsaved work survives a crash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Autosave {
+    /// Root of the project the post belongs to
+    pub project: Option<PathBuf>,
+    /// Id of the stored post being edited, empty for a new post
+    pub post_id: String,
+    pub title: String,
+    pub category: String,
+    #[serde(default)]
+    pub author: String,
+    pub tags: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub date: String,
+    pub content: String,
+    pub saved_at: DateTime<Local>,
+}
+
+/// Layout of the autosave file, one entry per open post with unpublished changes
+#[derive(Serialize, Deserialize)]
+struct Drafts {
+    #[serde(default)]
+    draft: Vec<Autosave>,
+}
+
+impl Autosave {
+    /// Location of the autosave, e.g. `~/.local/share/equinox/drafts/autosave.toml` on Linux
+    fn path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("equinox").join("drafts").join("autosave.toml"))
+    }
+
+    /// Reads every saved draft, including a single draft saved before the editor had tabs
+    pub fn load_all() -> Vec<Autosave> {
+        let Some(contents) = Autosave::path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return Vec::new();
+        };
+        match toml::from_str::<Drafts>(&contents) {
+            Ok(drafts) if !drafts.draft.is_empty() => drafts.draft,
+            _ => toml::from_str(&contents)
+                .map(|draft| vec![draft])
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Replaces the saved drafts with `drafts`
+    pub fn save_all(drafts: Vec<Autosave>) -> Result<()> {
+        let path = Autosave::path()
+            .ok_or_else(|| Error::Invalid("Could not determine the data directory".to_string()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+
+        let contents = toml::to_string(&Drafts { draft: drafts })
+            .map_err(|e| Error::Invalid(format!("Failed to serialize autosave: {}", e)))?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+
+    /// Removes the autosave once its contents are safely stored elsewhere
+    pub fn clear() {
+        if let Some(path) = Autosave::path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
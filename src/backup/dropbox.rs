@@ -0,0 +1,163 @@
+use super::Remote;
+use equinox_core::site::DropboxBackup;
+use serde_json::{json, Value};
+use std::io::Read;
+use ureq::{http, Agent};
+
+const API: &str = "https://api.dropboxapi.com";
+const CONTENT_API: &str = "https://content.dropboxapi.com";
+
+/// A folder in Dropbox, reached with an access token
+pub struct Dropbox {
+    agent: Agent,
+    folder: String,
+    token: String,
+}
+
+impl Dropbox {
+    /// Signs in with `secret`, which is an access token, or a refresh token for the app
+    /// `target.app_key` if one is set
+    pub fn new(target: &DropboxBackup, secret: &str) -> Result<Dropbox, String> {
+        let mut dropbox = Dropbox {
+            // Dropbox explains failures in the body, so statuses are handled here
+            agent: Agent::config_builder()
+                .http_status_as_error(false)
+                .build()
+                .into(),
+            folder: format!("/{}", target.folder.trim_matches('/')),
+            token: secret.to_string(),
+        };
+        if !target.app_key.is_empty() {
+            dropbox.token = dropbox.refresh(&target.app_key, secret)?;
+        }
+        Ok(dropbox)
+    }
+
+    /// A short-lived access token for the app `app_key`, from its refresh token
+    fn refresh(&self, app_key: &str, refresh_token: &str) -> Result<String, String> {
+        let uri = format!("{}/oauth2/token", API);
+        let mut response = self
+            .agent
+            .post(&uri)
+            .send_form([
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+                ("client_id", app_key),
+            ])
+            .map_err(|e| format!("Signing in to Dropbox failed: {}", e))?;
+        let status = response.status();
+        let body: Value = response
+            .body_mut()
+            .read_to_string()
+            .ok()
+            .and_then(|body| serde_json::from_str(&body).ok())
+            .unwrap_or_default();
+        match body["access_token"].as_str() {
+            Some(token) if status.is_success() => Ok(token.to_string()),
+            _ => Err(format!(
+                "Signing in to Dropbox failed: {}",
+                body["error_description"]
+                    .as_str()
+                    .unwrap_or("no access token")
+            )),
+        }
+    }
+
+    /// Calls the endpoint `endpoint` of `host` with `arg` in the header, as Dropbox's content
+    /// endpoints take it, or as the body if there is no `contents`
+    fn call(
+        &self,
+        host: &str,
+        endpoint: &str,
+        arg: Value,
+        contents: Option<Vec<u8>>,
+    ) -> Result<(u16, Vec<u8>), String> {
+        let uri = format!("{}/2/{}", host, endpoint);
+        let request = http::Request::builder()
+            .method("POST")
+            .uri(&uri)
+            .header("authorization", format!("Bearer {}", self.token));
+        let request = match contents {
+            Some(contents) => request
+                .header("dropbox-api-arg", ascii_json(&arg))
+                .header("content-type", "application/octet-stream")
+                .body(contents),
+            None if host == CONTENT_API => request
+                .header("dropbox-api-arg", ascii_json(&arg))
+                .body(Vec::new()),
+            None => request
+                .header("content-type", "application/json")
+                .body(arg.to_string().into_bytes()),
+        }
+        .map_err(|e| format!("Invalid request to {}: {}", uri, e))?;
+
+        let mut response = self
+            .agent
+            .run(request)
+            .map_err(|e| format!("{} failed: {}", uri, e))?;
+        let mut body = Vec::new();
+        response
+            .body_mut()
+            .as_reader()
+            .read_to_end(&mut body)
+            .map_err(|e| format!("Failed to read the response from {}: {}", uri, e))?;
+        Ok((response.status().as_u16(), body))
+    }
+
+    fn path(&self, path: &str) -> String {
+        format!("{}/{}", self.folder.trim_end_matches('/'), path)
+    }
+}
+
+impl Remote for Dropbox {
+    fn put(&self, path: &str, contents: Vec<u8>) -> Result<(), String> {
+        let arg = json!({ "path": self.path(path), "mode": "overwrite", "mute": true });
+        match self.call(CONTENT_API, "files/upload", arg, Some(contents))? {
+            (200, _) => Ok(()),
+            (status, body) => Err(failure("upload", path, status, &body)),
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<Vec<u8>, String> {
+        let arg = json!({ "path": self.path(path) });
+        match self.call(CONTENT_API, "files/download", arg, None)? {
+            (200, contents) => Ok(contents),
+            (status, body) => Err(failure("download", path, status, &body)),
+        }
+    }
+
+    fn delete(&self, path: &str) -> Result<(), String> {
+        let arg = json!({ "path": self.path(path) });
+        match self.call(API, "files/delete_v2", arg, None)? {
+            (200, _) => Ok(()),
+            (409, body) if String::from_utf8_lossy(&body).contains("not_found") => Ok(()),
+            (status, body) => Err(failure("delete", path, status, &body)),
+        }
+    }
+}
+
+/// Describes a failed call, with Dropbox's summary of the error if it gave one
+fn failure(action: &str, path: &str, status: u16, body: &[u8]) -> String {
+    let summary = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|body| body["error_summary"].as_str().map(str::to_string))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).trim().to_string());
+    format!("Failed to {} {}: {} {}", action, path, status, summary)
+}
+
+/// `value` as JSON with everything outside ASCII escaped, since HTTP headers can't carry it
+fn ascii_json(value: &Value) -> String {
+    let mut encoded = String::new();
+    for c in value.to_string().chars() {
+        match c.is_ascii() {
+            true => encoded.push(c),
+            false => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    encoded.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,189 @@
+//! Backing up the project's sources to a cloud service after every change, and restoring a
+//! project from its backup on another machine
+
+mod dropbox;
+mod s3;
+mod webdav;
+
+use crate::deploy::sftp::KEYRING_SERVICE;
+use equinox_core::{
+    atomic,
+    backup::{self, Manifest, MANIFEST_NAME},
+    site::BackupTarget,
+    Site,
+};
+use std::{env, fs, path::Path};
+use tracing::{debug, info};
+
+/// Environment variable holding the backup service's secret instead of the keyring
+const SECRET_VAR: &str = "EQUINOX_BACKUP_SECRET";
+
+/// A folder on a backup service, holding files by their path relative to the project root
+trait Remote {
+    fn put(&self, path: &str, contents: Vec<u8>) -> Result<(), String>;
+    fn get(&self, path: &str) -> Result<Vec<u8>, String>;
+    /// Removes `path`, doing nothing if it is already gone
+    fn delete(&self, path: &str) -> Result<(), String>;
+}
+
+/// Result of backing up the project
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BackupReport {
+    pub uploaded: usize,
+    /// Files removed from the backup because the project no longer has them
+    pub removed: usize,
+}
+
+/// Sends the sources that changed since the last backup to the site's backup service, removing
+/// the ones that are gone, then the manifest restoring reads. With `full`, every source is sent
+/// again, for a backup that has lost files. `progress` is called with a message before each
+/// upload.
+pub fn back_up(
+    site: &Site,
+    full: bool,
+    mut progress: impl FnMut(String),
+) -> Result<BackupReport, String> {
+    let target = site
+        .config
+        .backup
+        .as_ref()
+        .ok_or("No backup service is configured")?;
+    let previous = match full {
+        true => Manifest::default(),
+        false => Manifest::load(&site.root),
+    };
+    let current = backup::scan(site).map_err(|e| e.to_string())?;
+    let changes = previous.changes(&current);
+    if changes.is_empty() {
+        debug!("Nothing changed since the last backup");
+        return Ok(BackupReport::default());
+    }
+
+    let remote = connect(target)?;
+    let mut report = BackupReport::default();
+    for (i, file) in changes.upload.iter().enumerate() {
+        progress(format!(
+            "Backing up {} ({}/{})",
+            file,
+            i + 1,
+            changes.upload.len()
+        ));
+        let path = site.root.join(file);
+        let contents =
+            fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        remote.put(file, contents)?;
+        report.uploaded += 1;
+    }
+    for file in &changes.remove {
+        progress(format!("Removing {} from the backup", file));
+        remote.delete(file)?;
+        report.removed += 1;
+    }
+
+    let manifest = current.to_toml().map_err(|e| e.to_string())?;
+    remote.put(MANIFEST_NAME, manifest.into_bytes())?;
+    current.save(&site.root).map_err(|e| e.to_string())?;
+    info!(
+        "Backed up {} files to {} ({} removed)",
+        report.uploaded,
+        describe(target),
+        report.removed
+    );
+    Ok(report)
+}
+
+/// Writes every file in the backup at `target` into the project at `root`, returning how many
+/// there were. Files the project has that the backup doesn't are left alone.
+pub fn restore(
+    root: &Path,
+    target: &BackupTarget,
+    mut progress: impl FnMut(String),
+) -> Result<usize, String> {
+    let remote = connect(target)?;
+    progress(format!("Reading the backup at {}", describe(target)));
+    let manifest = remote
+        .get(MANIFEST_NAME)
+        .map_err(|e| format!("No backup found at {}: {}", describe(target), e))?;
+    let manifest = String::from_utf8(manifest)
+        .map_err(|_| "The backup's manifest isn't valid text".to_string())
+        .and_then(|text| Manifest::parse(&text).map_err(|e| e.to_string()))?;
+
+    let files: Vec<&str> = manifest.files().collect();
+    for (i, file) in files.iter().enumerate() {
+        progress(format!("Restoring {} ({}/{})", file, i + 1, files.len()));
+        let path = backup::restored_path(root, file).map_err(|e| e.to_string())?;
+        let contents = remote.get(file)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        atomic::write_synced(&path, contents)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    // What was restored is what the backup holds, so only later changes are sent back
+    manifest.save(root).map_err(|e| e.to_string())?;
+    info!(
+        "Restored {} files from {} into {}",
+        files.len(),
+        describe(target),
+        root.display()
+    );
+    Ok(files.len())
+}
+
+/// Saves the WebDAV password or Dropbox token for `target` in the OS keyring
+pub fn store_secret(target: &BackupTarget, secret: &str) -> Result<(), String> {
+    keyring_entry(target)?
+        .set_password(secret)
+        .map_err(|e| format!("Failed to store the secret in the keyring: {}", e))
+}
+
+/// The WebDAV password or Dropbox token for `target`, from `EQUINOX_BACKUP_SECRET` if it is set
+/// so machines without a keyring can back up too, otherwise from the keyring
+fn stored_secret(target: &BackupTarget) -> Result<String, String> {
+    if let Ok(secret) = env::var(SECRET_VAR) {
+        return Ok(secret);
+    }
+    match keyring_entry(target)?.get_password() {
+        Ok(secret) => Ok(secret),
+        Err(keyring::Error::NoEntry) => Err(format!(
+            "No secret for {} in the keyring: run `equinox backup-secret` to add it, or set {}",
+            describe(target),
+            SECRET_VAR
+        )),
+        Err(e) => Err(format!("Failed to read the secret from the keyring: {}", e)),
+    }
+}
+
+fn keyring_entry(target: &BackupTarget) -> Result<keyring::Entry, String> {
+    let user = match target {
+        BackupTarget::S3(_) => {
+            return Err("S3 backups use your AWS credentials rather than the keyring".to_string())
+        }
+        BackupTarget::WebDav(target) => format!("webdav:{}@{}", target.username, target.url),
+        BackupTarget::Dropbox(target) => format!("dropbox:{}", target.folder),
+    };
+    keyring::Entry::new(KEYRING_SERVICE, &user)
+        .map_err(|e| format!("Failed to access the keyring: {}", e))
+}
+
+fn connect(target: &BackupTarget) -> Result<Box<dyn Remote>, String> {
+    Ok(match target {
+        BackupTarget::S3(config) => Box::new(s3::S3::new(config)?),
+        BackupTarget::WebDav(config) => {
+            Box::new(webdav::WebDav::new(config, &stored_secret(target)?))
+        }
+        BackupTarget::Dropbox(config) => {
+            Box::new(dropbox::Dropbox::new(config, &stored_secret(target)?)?)
+        }
+    })
+}
+
+/// Where `target` keeps the backup, for messages
+pub fn describe(target: &BackupTarget) -> String {
+    match target {
+        BackupTarget::S3(target) => format!("s3://{}/{}", target.bucket, target.prefix),
+        BackupTarget::WebDav(target) => target.url.clone(),
+        BackupTarget::Dropbox(target) => format!("Dropbox {}", target.folder),
+    }
+}
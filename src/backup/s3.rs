@@ -0,0 +1,53 @@
+use super::Remote;
+use crate::deploy::s3::{Client, Credentials, Endpoint};
+use equinox_core::site::S3Backup;
+
+/// A prefix in an S3 bucket
+pub struct S3 {
+    credentials: Credentials,
+    region: String,
+    endpoint: Endpoint,
+    prefix: String,
+}
+
+impl S3 {
+    pub fn new(target: &S3Backup) -> Result<S3, String> {
+        Ok(S3 {
+            credentials: Credentials::load(&target.profile)?,
+            region: target.region.clone(),
+            endpoint: Endpoint::for_bucket(&target.bucket, &target.region, &target.endpoint)?,
+            prefix: match target.prefix.trim_matches('/') {
+                "" => String::new(),
+                prefix => format!("{}/", prefix),
+            },
+        })
+    }
+
+    fn send(&self, method: &str, path: &str, body: Vec<u8>) -> Result<Vec<u8>, String> {
+        let s3 = Client::new(&self.credentials, &self.region, "s3");
+        let key = format!("{}{}", self.prefix, path);
+        s3.send_bytes(
+            method,
+            &self.endpoint,
+            &self.endpoint.object_path(&key),
+            &[],
+            &[],
+            body,
+        )
+    }
+}
+
+impl Remote for S3 {
+    fn put(&self, path: &str, contents: Vec<u8>) -> Result<(), String> {
+        self.send("PUT", path, contents).map(|_| ())
+    }
+
+    fn get(&self, path: &str) -> Result<Vec<u8>, String> {
+        self.send("GET", path, Vec::new())
+    }
+
+    fn delete(&self, path: &str) -> Result<(), String> {
+        // S3 answers deletes of missing keys as if they succeeded
+        self.send("DELETE", path, Vec::new()).map(|_| ())
+    }
+}
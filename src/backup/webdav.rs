@@ -0,0 +1,111 @@
+use super::Remote;
+use crate::deploy::s3::uri_encode;
+use equinox_core::{site::WebDavBackup, sri::base64};
+use std::{cell::RefCell, collections::HashSet, io::Read};
+use ureq::{http, Agent};
+
+/// A folder on a WebDAV server, signed into with a username and password
+pub struct WebDav {
+    agent: Agent,
+    url: String,
+    authorization: String,
+    /// Folders known to exist on the server, relative to `url`
+    folders: RefCell<HashSet<String>>,
+}
+
+impl WebDav {
+    pub fn new(target: &WebDavBackup, password: &str) -> WebDav {
+        WebDav {
+            // Statuses such as a missing folder are handled here, and folders are created with
+            // WebDAV's own MKCOL
+            agent: Agent::config_builder()
+                .http_status_as_error(false)
+                .allow_non_standard_methods(true)
+                .build()
+                .into(),
+            url: target.url.trim_end_matches('/').to_string(),
+            authorization: format!(
+                "Basic {}",
+                base64(format!("{}:{}", target.username, password).as_bytes())
+            ),
+            folders: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Sends a request for `path`, returning the status and the body
+    fn send(&self, method: &str, path: &str, body: Vec<u8>) -> Result<(u16, Vec<u8>), String> {
+        let uri = format!("{}/{}", self.url, uri_encode(path, false));
+        let request = http::Request::builder()
+            .method(method)
+            .uri(&uri)
+            .header("authorization", &self.authorization)
+            .body(body)
+            .map_err(|e| format!("Invalid request to {}: {}", uri, e))?;
+        let mut response = self
+            .agent
+            .run(request)
+            .map_err(|e| format!("{} {} failed: {}", method, uri, e))?;
+        let mut contents = Vec::new();
+        response
+            .body_mut()
+            .as_reader()
+            .read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read the response from {}: {}", uri, e))?;
+        let status = response.status().as_u16();
+        match status {
+            401 | 403 => Err(format!(
+                "{} {} was refused: check the username and password",
+                method, uri
+            )),
+            _ => Ok((status, contents)),
+        }
+    }
+
+    /// Creates the folders `path` is in that the server doesn't have yet
+    fn create_folders(&self, path: &str) -> Result<(), String> {
+        let Some((parent, _)) = path.rsplit_once('/') else {
+            return Ok(());
+        };
+        let mut folder = String::new();
+        for part in parent.split('/') {
+            if !folder.is_empty() {
+                folder.push('/');
+            }
+            folder.push_str(part);
+            if self.folders.borrow().contains(&folder) {
+                continue;
+            }
+            // 405 means the folder is already there
+            match self.send("MKCOL", &folder, Vec::new())? {
+                (200..=299 | 405, _) => {}
+                (status, _) => return Err(format!("Failed to create {}: {}", folder, status)),
+            }
+            self.folders.borrow_mut().insert(folder.clone());
+        }
+        Ok(())
+    }
+}
+
+impl Remote for WebDav {
+    fn put(&self, path: &str, contents: Vec<u8>) -> Result<(), String> {
+        self.create_folders(path)?;
+        match self.send("PUT", path, contents)? {
+            (200..=299, _) => Ok(()),
+            (status, _) => Err(format!("Failed to upload {}: {}", path, status)),
+        }
+    }
+
+    fn get(&self, path: &str) -> Result<Vec<u8>, String> {
+        match self.send("GET", path, Vec::new())? {
+            (200..=299, contents) => Ok(contents),
+            (status, _) => Err(format!("Failed to download {}: {}", path, status)),
+        }
+    }
+
+    fn delete(&self, path: &str) -> Result<(), String> {
+        match self.send("DELETE", path, Vec::new())? {
+            (200..=299 | 404, _) => Ok(()),
+            (status, _) => Err(format!("Failed to delete {}: {}", path, status)),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Sharing new posts to Bluesky with a link card, once they are deployed
+
+use crate::deploy::sftp::KEYRING_SERVICE;
+use equinox_core::{
+    crosspost::{self, Network, ShareReport, SharedLog},
+    site::BlueskyConfig,
+    Post, Site,
+};
+use serde_json::{json, Value};
+use std::{env, fs, path::Path, time::Duration};
+use tracing::{debug, info};
+use ureq::Agent;
+
+/// Environment variable holding the app password instead of the keyring
+const PASSWORD_VAR: &str = "BLUESKY_APP_PASSWORD";
+
+/// How long the server gets to answer each request
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Characters of the title kept as a post's text, Bluesky's limit being 300
+const MAX_TEXT_LENGTH: usize = 300;
+
+/// Characters of the summary kept as the link card's description
+const MAX_DESCRIPTION_LENGTH: usize = 300;
+
+/// Largest image Bluesky takes as a link card's thumbnail
+const MAX_THUMBNAIL_SIZE: u64 = 1_000_000;
+
+fn keyring_entry(config: &BlueskyConfig) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("bluesky:{}", config.handle))
+        .map_err(|e| format!("Failed to access the keyring: {}", e))
+}
+
+/// Saves the app password for the account `config.handle` in the OS keyring
+pub fn store_password(config: &BlueskyConfig, password: &str) -> Result<(), String> {
+    keyring_entry(config)?
+        .set_password(password)
+        .map_err(|e| format!("Failed to store the app password in the keyring: {}", e))
+}
+
+fn stored_password(config: &BlueskyConfig) -> Result<String, String> {
+    if let Ok(password) = env::var(PASSWORD_VAR) {
+        return Ok(password);
+    }
+    match keyring_entry(config)?.get_password() {
+        Ok(password) => Ok(password),
+        Err(keyring::Error::NoEntry) => Err(format!(
+            "No app password for {} in the keyring: run `equinox bluesky-password` to add it, \
+             or set {}",
+            config.handle, PASSWORD_VAR
+        )),
+        Err(e) => Err(format!(
+            "Failed to read the app password from the keyring: {}",
+            e
+        )),
+    }
+}
+
+/// A signed in account
+struct Session {
+    agent: Agent,
+    service: String,
+    did: String,
+    authorization: String,
+}
+
+impl Session {
+    fn sign_in(config: &BlueskyConfig, password: &str) -> Result<Session, String> {
+        let mut session = Session {
+            // The server explains failures in the body, so statuses are handled here
+            agent: Agent::config_builder()
+                .timeout_global(Some(TIMEOUT))
+                .http_status_as_error(false)
+                .build()
+                .into(),
+            service: config.service.trim_end_matches('/').to_string(),
+            did: String::new(),
+            authorization: String::new(),
+        };
+        let body = json!({ "identifier": config.handle, "password": password });
+        let reply = session.call(
+            "com.atproto.server.createSession",
+            "application/json",
+            body.to_string().into_bytes(),
+        )?;
+        match (reply["did"].as_str(), reply["accessJwt"].as_str()) {
+            (Some(did), Some(token)) => {
+                session.did = did.to_string();
+                session.authorization = format!("Bearer {}", token);
+                Ok(session)
+            }
+            _ => Err(format!("Bluesky didn't sign {} in", config.handle)),
+        }
+    }
+
+    /// Calls the procedure `method` with `body`, returning the server's reply
+    fn call(&self, method: &str, content_type: &str, body: Vec<u8>) -> Result<Value, String> {
+        let uri = format!("{}/xrpc/{}", self.service, method);
+        let mut request = self.agent.post(&uri).header("content-type", content_type);
+        if !self.authorization.is_empty() {
+            request = request.header("authorization", &self.authorization);
+        }
+        let mut response = request
+            .send(&body[..])
+            .map_err(|e| format!("Failed to reach {}: {}", self.service, e))?;
+        let status = response.status();
+        let reply: Value = response
+            .body_mut()
+            .read_to_string()
+            .ok()
+            .and_then(|body| serde_json::from_str(&body).ok())
+            .unwrap_or_default();
+        match status.is_success() {
+            true => Ok(reply),
+            false => Err(format!(
+                "{} failed: {} {}",
+                method,
+                status.as_u16(),
+                reply["message"]
+                    .as_str()
+                    .or(reply["error"].as_str())
+                    .unwrap_or_default()
+            )),
+        }
+    }
+
+    /// Uploads the post's image for its link card, if it has one in the output directory small
+    /// enough for Bluesky to take
+    fn upload_thumbnail(&self, site: &Site, post: &Post) -> Result<Option<Value>, String> {
+        let image = &post.meta.image;
+        if image.is_empty() || image.contains("://") {
+            return Ok(None);
+        }
+        let content_type = match Path::new(image).extension().and_then(|e| e.to_str()) {
+            Some("png") => "image/png",
+            Some("jpg" | "jpeg") => "image/jpeg",
+            Some("webp") => "image/webp",
+            Some("gif") => "image/gif",
+            _ => return Ok(None),
+        };
+        let path = site.output_dir().join(image.trim_start_matches('/'));
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.len() <= MAX_THUMBNAIL_SIZE => {}
+            _ => {
+                debug!("Leaving {} out of the link card", path.display());
+                return Ok(None);
+            }
+        }
+        let contents =
+            fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let reply = self.call("com.atproto.repo.uploadBlob", content_type, contents)?;
+        Ok(Some(reply["blob"].clone()))
+    }
+}
+
+/// Posts the title of each published post that hasn't been shared yet, with a link card for it.
+/// The first time, the posts already online are only recorded. `progress` is called with a
+/// status message as each post is shared.
+pub fn share_new(site: &Site, mut progress: impl FnMut(String)) -> Result<ShareReport, String> {
+    let config = site
+        .config
+        .bluesky
+        .as_ref()
+        .ok_or("No Bluesky account is configured")?;
+    if !site.config.base_url.contains("://") {
+        return Err("the site needs a base URL for link cards to point at".to_string());
+    }
+    let mut report = ShareReport {
+        earlier: crosspost::start(site, Network::Bluesky).map_err(|e| e.to_string())?,
+        ..ShareReport::default()
+    };
+    let mut log = SharedLog::load(&site.root, Network::Bluesky).unwrap_or_default();
+    let posts = crosspost::unshared(site, &log).map_err(|e| e.to_string())?;
+    if posts.is_empty() {
+        return Ok(report);
+    }
+
+    let session = Session::sign_in(config, &stored_password(config)?)?;
+    for post in &posts {
+        progress(format!("Sharing {} to Bluesky", post.meta.title));
+        let mut card = json!({
+            "uri": site.url_for(&site.config.post_url(post)),
+            "title": post.meta.title,
+            "description": crosspost::shorten(&crosspost::summary(post), MAX_DESCRIPTION_LENGTH),
+        });
+        if let Some(thumbnail) = session.upload_thumbnail(site, post)? {
+            card["thumb"] = thumbnail;
+        }
+        let record = json!({
+            "repo": session.did,
+            "collection": "app.bsky.feed.post",
+            "record": {
+                "$type": "app.bsky.feed.post",
+                "text": crosspost::shorten(&post.meta.title, MAX_TEXT_LENGTH),
+                "createdAt": chrono::Utc::now()
+                    .to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                "embed": { "$type": "app.bsky.embed.external", "external": card },
+            },
+        });
+        let reply = session
+            .call(
+                "com.atproto.repo.createRecord",
+                "application/json",
+                record.to_string().into_bytes(),
+            )
+            .map_err(|e| format!("Failed to share {}: {}", post.meta.title, e))?;
+
+        // Records are named at://<did>/app.bsky.feed.post/<key>, and shown under that key
+        let key = reply["uri"]
+            .as_str()
+            .and_then(|uri| uri.rsplit('/').next())
+            .unwrap_or_default();
+        let url = format!("https://bsky.app/profile/{}/post/{}", config.handle, key);
+        info!("Shared {} to Bluesky at {}", post.meta.title, url);
+        log.record(&post.id, &url);
+        // Kept after every post, so an interrupted run doesn't share it again
+        log.save(&site.root, Network::Bluesky)
+            .map_err(|e| e.to_string())?;
+        report.shared += 1;
+    }
+    Ok(report)
+}
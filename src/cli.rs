@@ -0,0 +1,566 @@
+use crate::{
+    autocommit, backup, bluesky, config::Config, deploy, links, mastodon, pdf, sri, webhook,
+    webmention,
+};
+use equinox_core::{
+    accessibility,
+    content::parse_tags,
+    crosspost::{self, Network},
+    export, front_matter, import, links as site_links, newsletter, validate, wordpress, Post,
+    PostEdit, Site,
+};
+use std::{
+    error::Error,
+    fs,
+    io::{self, IsTerminal, Read},
+    path::PathBuf,
+};
+
+/// What a backup command asks for on a site without a backup service
+const NO_BACKUP: &str = "add a [backup] section to equinox.toml naming the service with \
+                         `service = \"s3\"`, `\"webdav\"`, or `\"dropbox\"`";
+
+/// What a Bluesky command asks for on a site without an account
+const NO_BLUESKY: &str = "add a [bluesky] section to equinox.toml with the account's `handle`, \
+                          e.g. \"me.bsky.social\"";
+
+/// What a Mastodon command asks for on a site without an account
+const NO_MASTODON: &str = "add a [mastodon] section to equinox.toml with the account's \
+                           `instance`, e.g. \"https://mastodon.social\"";
+
+const USAGE: &str = "\
+Usage: equinox [--site <dir>] <command>
+
+Commands:
+  init                                    Create an equinox.toml in the site directory
+  build                                   Re-render changed posts and every listing and feed
+  rebuild                                 Re-render everything, removing pages of deleted posts
+  new-post <title> [--category <name>] [--tags <a,b>] [--description <text>] [--date <date>]
+           [--author <name>] [--from <file>]
+                                          Store a new post, reading content from <file> or stdin
+  import <dir>                            Store every Markdown or text file under <dir> as a
+                                          post, then build the site
+  import <export.xml>                     Store the posts of a WordPress export, keeping their
+                                          slugs, then build the site
+  publish <id>                            Render a stored post and regenerate the listings
+  deploy [--dry-run]                      Upload the output directory to every deploy target, or
+                                          with --dry-run, list what [deploy.rsync] would change
+  check-links [--external]                List links to missing pages and files, and with
+                                          --external, links to other sites that can't be reached
+  validate                                List generated pages with invalid HTML structure
+  audit                                   List accessibility issues in the generated pages and
+                                          the theme's colors
+  integrity [--update]                    Pin the hashes of the scripts and stylesheets pages load
+                                          from other sites, re-pinning changed ones with --update
+  webmentions                             Notify the pages deployed posts link to that haven't
+                                          been sent a webmention yet
+  mastodon                                Share the deployed posts that haven't been shared yet
+                                          to the [mastodon] account
+  mastodon-token                          Store the Mastodon access token read from stdin in the
+                                          OS keyring
+  bluesky                                 Share the deployed posts that haven't been shared yet
+                                          to the [bluesky] account
+  bluesky-password                        Store the Bluesky app password read from stdin in the
+                                          OS keyring
+  backup [--full]                         Send the sources changed since the last backup to the
+                                          [backup] service, or every source with --full
+  backup-secret                           Store the WebDAV password or Dropbox token read from
+                                          stdin in the OS keyring
+  restore [--force]                       Write every file of the [backup] service's backup into
+                                          the site, which must have no posts unless --force
+  export <archive.tar.gz>                 Render the whole site into a temporary directory and
+                                          pack it into one archive
+  export-pdf <id> <file.pdf>              Render a stored post and print its page to a PDF
+  export-newsletter <id> <file.html>      Render a stored post as email HTML with inline styles,
+                                          and as plain text next to it
+
+Without a command, the editor window is opened. The site defaults to the current directory if
+it contains an equinox.toml, otherwise to the last project opened in the editor.";
+
+/// Runs a headless command, using the same code paths as the editor
+pub fn run(args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let mut args = Args::new(args);
+    let site_dir = args.option("--site")?;
+
+    let Some(command) = args.positional() else {
+        return Err(USAGE.into());
+    };
+
+    match command.as_str() {
+        "init" => {
+            args.finish()?;
+            let root = match site_dir {
+                Some(dir) => PathBuf::from(dir),
+                None => std::env::current_dir()?,
+            };
+            if Site::exists(&root) {
+                return Err(format!("{} is already a site", root.display()).into());
+            }
+            let site = Site::create(&root)?;
+            println!("Created {}", site.root.join(Site::CONFIG_FILE).display());
+        }
+        "build" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let pages = site.build()?;
+            pin_resources(&site);
+            check_external_links(&site);
+            println!(
+                "Rendered {} changed posts into {}",
+                pages.len(),
+                site.output_dir().display()
+            );
+            // Scheduled posts that came due are announced at the first build after their date
+            notify_webhooks(&site, None);
+        }
+        "rebuild" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let (pages, removed) = site.rebuild()?;
+            pin_resources(&site);
+            check_external_links(&site);
+            for path in &removed {
+                println!("Removed {}", path.display());
+            }
+            println!(
+                "Rebuilt {} posts into {}",
+                pages.len(),
+                site.output_dir().display()
+            );
+            notify_webhooks(&site, None);
+        }
+        "new-post" => {
+            let category = args.option("--category")?.unwrap_or_default();
+            let tags = args
+                .option("--tags")?
+                .map(|t| parse_tags(&t))
+                .unwrap_or_default();
+            let description = args.option("--description")?.unwrap_or_default();
+            let date = match args.option("--date")? {
+                Some(text) => Some(
+                    front_matter::parse_date(&text)
+                        .ok_or_else(|| format!("Invalid date: {}", text))?,
+                ),
+                None => None,
+            };
+            let author = args.option("--author")?.unwrap_or_default();
+            let from = args.option("--from")?;
+            let title = args.positional().ok_or("new-post requires a title")?;
+            args.finish()?;
+
+            let content = match from {
+                Some(path) => fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path, e))?,
+                None if !io::stdin().is_terminal() => {
+                    let mut content = String::new();
+                    io::stdin().read_to_string(&mut content)?;
+                    content
+                }
+                None => String::new(),
+            };
+
+            let site = open_site(site_dir)?;
+            let post = site.store().edit(
+                None,
+                PostEdit {
+                    title,
+                    category,
+                    tags,
+                    description,
+                    date,
+                    author,
+                    default_author: Config::load().author,
+                    content,
+                },
+            )?;
+            site.save_post(&post)?;
+            back_up(&site);
+            println!("{}", post.id);
+        }
+        "import" => {
+            let source = args
+                .positional()
+                .map(PathBuf::from)
+                .ok_or("import requires a directory or a WordPress export")?;
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let author = Config::load().author;
+            let import = match source.is_dir() {
+                true => import::import_folder(&site, &source, &author)?,
+                false => wordpress::import_export(&site, &source, &author)?,
+            };
+            site.build()?;
+            back_up(&site);
+            // Files that failed were already logged as warnings
+            println!(
+                "Imported {} posts into {}, skipped {} files",
+                import.posts.len(),
+                site.output_dir().display(),
+                import.failed.len()
+            );
+        }
+        "publish" => {
+            let id = args.positional().ok_or("publish requires a post id")?;
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let post = site.store().load(&id)?;
+            let path = site.publish(&post)?;
+            back_up(&site);
+            println!("Published {}", path.display());
+            if site.config.auto_commit.is_enabled() {
+                match autocommit::commit_published(&site, Some(&post))? {
+                    Some(commit) => println!("Committed {}", commit),
+                    None => println!("Nothing new to commit"),
+                }
+            }
+            notify_webhooks(&site, Some(&post));
+        }
+        "export-pdf" => {
+            let id = args.positional().ok_or("export-pdf requires a post id")?;
+            let file = args.positional().ok_or("export-pdf requires a PDF path")?;
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let post = site.store().load(&id)?;
+            let page = site.generate(&post)?;
+            pdf::export(&page, &PathBuf::from(&file))?;
+            println!("Exported {}", file);
+        }
+        "export-newsletter" => {
+            let id = args
+                .positional()
+                .ok_or("export-newsletter requires a post id")?;
+            let file = args
+                .positional()
+                .ok_or("export-newsletter requires an HTML path")?;
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let post = site.store().load(&id)?;
+            let text = newsletter::render(&site, &post)?.save(&PathBuf::from(&file))?;
+            println!("Exported {} and {}", file, text.display());
+        }
+        "export" => {
+            let archive = args.positional().ok_or("export requires an archive path")?;
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let count = export::export_site(&site, &PathBuf::from(&archive))?;
+            println!("Packed {} files into {}", count, archive);
+        }
+        "deploy" => {
+            let dry_run = args.flag("--dry-run");
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            if dry_run {
+                let target = site
+                    .config
+                    .deploy
+                    .rsync
+                    .as_ref()
+                    .ok_or("--dry-run needs a [deploy.rsync] section in equinox.toml")?;
+                let changes = deploy::rsync::preview(&site, target)?;
+                for change in &changes {
+                    println!("Would {}", change);
+                }
+                if changes.is_empty() {
+                    println!("{} is up to date", target.host);
+                }
+                return Ok(());
+            }
+            let deployment = deploy::deploy_all(&site, |message| println!("{}", message)).map_err(
+                |e| match site.config.deploy.is_empty() {
+                    true => format!(
+                        "{}: add a [deploy.sftp], [deploy.rsync], [deploy.git], [deploy.s3], or [deploy.netlify] section to equinox.toml",
+                        e
+                    ),
+                    false => e.to_string(),
+                },
+            )?;
+            for summary in deployment.summaries {
+                println!("Deployed: {}", summary);
+            }
+            if let Some(url) = deployment.url {
+                println!("Live at {}", url);
+            }
+        }
+        "check-links" => {
+            let external = args.flag("--external");
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let broken = site_links::check_internal(&site)?;
+            for link in &broken {
+                println!(
+                    "{} doesn't exist, linked from {}",
+                    link.target,
+                    link.pages.join(", ")
+                );
+            }
+            let mut summary = format!("Found {} broken links", broken.len());
+            if external {
+                let report = links::check_external(&site, |message| println!("{}", message))?;
+                summary.push_str(&format!(
+                    ", and {} of {} links to other sites are broken",
+                    report.broken, report.checked
+                ));
+            }
+            println!("{}", summary);
+        }
+        "validate" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let problems = validate::check_output(&site)?;
+            for problem in &problems {
+                println!("{}: {}", problem.message, problem.pages.join(", "));
+            }
+            println!("Found {} HTML problems", problems.len());
+        }
+        "audit" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let issues = accessibility::audit(&site)?;
+            for issue in &issues {
+                println!("{}: {}", issue.message, issue.pages.join(", "));
+            }
+            println!("Found {} accessibility issues", issues.len());
+        }
+        "integrity" => {
+            let update = args.flag("--update");
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            if !site.config.subresource_integrity {
+                return Err("set subresource_integrity = true in equinox.toml first".into());
+            }
+            let report = sri::pin_all(&site, update, |message| println!("{}", message))?;
+            println!(
+                "Pinned {} external resources, {} changed upstream, {} failed",
+                report.pinned, report.changed, report.failed
+            );
+        }
+        "webmentions" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let report = webmention::send_all(&site, |message| println!("{}", message))?;
+            println!(
+                "Sent {} webmentions, {} linked pages don't accept them, {} failed",
+                report.sent, report.unsupported, report.failed
+            );
+        }
+        "mastodon" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let report = mastodon::share_new(&site, |message| println!("{}", message))?;
+            if report.earlier > 0 {
+                println!(
+                    "Recorded {} posts already online as shared without posting them",
+                    report.earlier
+                );
+            }
+            println!("Shared {} posts", report.shared);
+        }
+        "mastodon-token" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let config = site.config.mastodon.as_ref().ok_or(NO_MASTODON)?;
+            let mut token = String::new();
+            io::stdin().read_line(&mut token)?;
+            let token = token.trim();
+            if token.is_empty() {
+                return Err("mastodon-token reads the access token from stdin".into());
+            }
+            mastodon::store_token(config, token)?;
+            println!("Stored the token for {}", config.instance);
+            // From here on only posts published after now are shared
+            let earlier = crosspost::start(&site, Network::Mastodon)?;
+            if earlier > 0 {
+                println!("{} posts already online won't be shared", earlier);
+            }
+        }
+        "bluesky" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let report = bluesky::share_new(&site, |message| println!("{}", message))?;
+            if report.earlier > 0 {
+                println!(
+                    "Recorded {} posts already online as shared without posting them",
+                    report.earlier
+                );
+            }
+            println!("Shared {} posts", report.shared);
+        }
+        "bluesky-password" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let config = site.config.bluesky.as_ref().ok_or(NO_BLUESKY)?;
+            let mut password = String::new();
+            io::stdin().read_line(&mut password)?;
+            let password = password.trim();
+            if password.is_empty() {
+                return Err("bluesky-password reads the app password from stdin".into());
+            }
+            bluesky::store_password(config, password)?;
+            println!("Stored the app password for {}", config.handle);
+            // From here on only posts published after now are shared
+            let earlier = crosspost::start(&site, Network::Bluesky)?;
+            if earlier > 0 {
+                println!("{} posts already online won't be shared", earlier);
+            }
+        }
+        "backup" => {
+            let full = args.flag("--full");
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let report = backup::back_up(&site, full, |message| println!("{}", message))?;
+            println!(
+                "Backed up {} files, removed {} from the backup",
+                report.uploaded, report.removed
+            );
+        }
+        "backup-secret" => {
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let target = site.config.backup.as_ref().ok_or(NO_BACKUP)?;
+            let mut secret = String::new();
+            io::stdin().read_line(&mut secret)?;
+            let secret = secret.trim();
+            if secret.is_empty() {
+                return Err("backup-secret reads the password or token from stdin".into());
+            }
+            backup::store_secret(target, secret)?;
+            println!("Stored the secret for {}", backup::describe(target));
+        }
+        "restore" => {
+            let force = args.flag("--force");
+            args.finish()?;
+            let site = open_site(site_dir)?;
+            let target = site.config.backup.as_ref().ok_or(NO_BACKUP)?;
+            if !force && !site.store().list()?.is_empty() {
+                return Err(
+                    "the site already has posts, which restoring may overwrite: pass --force if \
+                     that's intended"
+                        .into(),
+                );
+            }
+            let count = backup::restore(&site.root, target, |message| println!("{}", message))?;
+            println!(
+                "Restored {} files into {}: run `equinox rebuild` to generate the site",
+                count,
+                site.root.display()
+            );
+        }
+        "help" | "--help" | "-h" => println!("{}", USAGE),
+        other => return Err(format!("Unknown command: {}\n\n{}", other, USAGE).into()),
+    }
+
+    Ok(())
+}
+
+/// Pins the external resources of a freshly built site if it uses subresource integrity. The
+/// build still succeeded if this fails, so problems are only warned about.
+fn pin_resources(site: &Site) {
+    if !site.config.subresource_integrity {
+        return;
+    }
+    match sri::pin_all(site, false, |_| {}) {
+        Ok(report) if report.changed > 0 => eprintln!(
+            "{} external resources changed upstream: run `equinox integrity --update` once \
+             they've been checked",
+            report.changed
+        ),
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to pin external resources: {}", e),
+    }
+}
+
+/// Backs up the sources a command changed if the site has a backup service. The command still
+/// succeeded if this fails, so problems are only warned about.
+fn back_up(site: &Site) {
+    if site.config.backup.is_none() {
+        return;
+    }
+    if let Err(e) = backup::back_up(site, false, |_| {}) {
+        eprintln!("Failed to back up the site: {}", e);
+    }
+}
+
+/// Tells the site's webhooks about the posts that are published but weren't announced yet, like
+/// `post` just published. The command still succeeded if this fails, so problems are only
+/// reported.
+fn notify_webhooks(site: &Site, post: Option<&Post>) {
+    match webhook::notify_new(site, post) {
+        Ok(0) => {}
+        Ok(count) => println!("Told the webhooks about {} posts", count),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+/// Checks the links a freshly built site has to other sites if it asks for that, which only
+/// warns about the broken ones
+fn check_external_links(site: &Site) {
+    if !site.config.check_external_links {
+        return;
+    }
+    if let Err(e) = links::check_external(site, |_| {}) {
+        eprintln!("Failed to check links to other sites: {}", e);
+    }
+}
+
+fn open_site(dir: Option<String>) -> equinox_core::Result<Site> {
+    let root = match dir {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let cwd = std::env::current_dir()
+                .map_err(|e| equinox_core::Error::io("read", "the current directory", e))?;
+            if Site::exists(&cwd) {
+                cwd
+            } else {
+                Config::load().last_project.ok_or_else(|| {
+                    equinox_core::Error::Invalid(
+                        "No site found: pass --site <dir> or run inside a project".to_string(),
+                    )
+                })?
+            }
+        }
+    };
+    Site::open(&root)
+}
+
+/// Minimal argument parser: `--name value` options and `--name` flags anywhere, everything else
+/// positional
+struct Args {
+    args: Vec<String>,
+}
+
+impl Args {
+    fn new(args: Vec<String>) -> Args {
+        Args { args }
+    }
+
+    fn option(&mut self, name: &str) -> Result<Option<String>, String> {
+        let Some(i) = self.args.iter().position(|a| a == name) else {
+            return Ok(None);
+        };
+        if i + 1 >= self.args.len() {
+            return Err(format!("{} requires a value", name));
+        }
+        self.args.remove(i);
+        Ok(Some(self.args.remove(i)))
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        let Some(i) = self.args.iter().position(|a| a == name) else {
+            return false;
+        };
+        self.args.remove(i);
+        true
+    }
+
+    fn positional(&mut self) -> Option<String> {
+        let i = self.args.iter().position(|a| !a.starts_with("--"))?;
+        Some(self.args.remove(i))
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.args.first() {
+            Some(arg) => Err(format!("Unexpected argument: {}", arg)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use equinox_core::{atomic, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+use tracing::warn;
+
+/// Persisted application settings, stored as TOML in the platform config directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Root of the site project opened on startup
+    pub last_project: Option<PathBuf>,
+    /// Author recorded on posts whose front matter doesn't name one
+    pub author: String,
+    /// Font size of the content editor and preview, in pixels
+    pub editor_font_size: u32,
+    /// Color scheme of the editor window
+    pub appearance: Appearance,
+    /// Hunspell dictionary the content is spell checked against, e.g. `en_US`; empty to turn
+    /// spell checking off
+    pub spelling_language: String,
+    /// Posts opened or generated lately, most recent first
+    pub recent_posts: Vec<RecentPost>,
+}
+
+/// A post offered in the Recent menu
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentPost {
+    /// Root of the site the post belongs to
+    pub project: PathBuf,
+    pub id: String,
+    pub title: String,
+}
+
+/// Light or dark scheme for the editor's own UI
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Appearance {
+    /// Follow the desktop's setting
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    /// In the order offered in the preferences
+    pub const ALL: [Appearance; 3] = [Appearance::System, Appearance::Light, Appearance::Dark];
+}
+
+impl Config {
+    /// Smallest and largest editor font sizes accepted from the preferences
+    pub const FONT_SIZES: std::ops::RangeInclusive<u32> = 8..=48;
+
+    /// Number of posts kept in `recent_posts`
+    pub const MAX_RECENT: usize = 10;
+
+    /// Location of the config file, e.g. `~/.config/equinox/config.toml` on Linux
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("equinox").join("config.toml"))
+    }
+
+    /// Loads the config file, falling back to defaults if it is missing or unreadable
+    pub fn load() -> Config {
+        let Some(path) = Config::path() else {
+            return Config::default();
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring invalid config {}: {}", path.display(), e);
+                Config::default()
+            }),
+            Err(_) => Config::default(),
+        }
+    }
+
+    /// Moves `post` to the front of the recent posts
+    pub fn remember_post(&mut self, post: RecentPost) {
+        self.recent_posts
+            .retain(|recent| recent.project != post.project || recent.id != post.id);
+        self.recent_posts.insert(0, post);
+        self.recent_posts.truncate(Config::MAX_RECENT);
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Config::path().ok_or_else(|| {
+            Error::Invalid("Could not determine the config directory".to_string())
+        })?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io("create", parent, e))?;
+        }
+
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| Error::Invalid(format!("Failed to serialize config: {}", e)))?;
+        atomic::write_synced(&path, contents).map_err(|e| Error::io("write", &path, e))
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            last_project: None,
+            author: String::new(),
+            editor_font_size: 14,
+            appearance: Appearance::System,
+            spelling_language: "en_US".to_string(),
+            recent_posts: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::DeployReport;
+use chrono::Local;
+use equinox_core::{site::GitTarget, Site};
+use git2::{
+    Cred, CredentialType, FetchOptions, Index, IndexEntry, IndexTime, Oid, PushOptions, Remote,
+    RemoteCallbacks, Repository, Signature,
+};
+use std::{cell::Cell, fs, path::PathBuf};
+use tracing::info;
+
+/// Repository used when the project itself is not under version control
+const PRIVATE_REPO: &str = ".equinox/deploy.git";
+
+/// How many times a remote may ask for credentials before giving up, so a rejected
+/// key doesn't loop forever
+const MAX_CREDENTIAL_ATTEMPTS: usize = 3;
+
+/// Commits the site's output directory to `target`'s branch and pushes it. The commit is built
+/// straight from the output files, so no working tree or checked-out branch is touched.
+pub fn deploy(
+    site: &Site,
+    target: &GitTarget,
+    mut progress: impl FnMut(String),
+) -> Result<DeployReport, String> {
+    let repo = open_repository(site)?;
+    let mut remote = find_remote(&repo, &target.remote)?;
+    let branch = format!("refs/heads/{}", target.branch);
+    let tracking = format!("refs/remotes/equinox-deploy/{}", target.branch);
+
+    // Build on top of what the remote already serves so the push is a fast-forward
+    progress(format!(
+        "Fetching {} from {}...",
+        target.branch, target.remote
+    ));
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(credential_callbacks());
+    remote
+        .fetch(
+            &[format!("+{}:{}", branch, tracking)],
+            Some(&mut fetch_options),
+            None,
+        )
+        .map_err(|e| format!("Failed to fetch from {}: {}", target.remote, e))?;
+
+    let parent = repo
+        .find_reference(&tracking)
+        .or_else(|_| repo.find_reference(&branch))
+        .and_then(|r| r.peel_to_commit())
+        .ok();
+
+    progress(format!("Committing the site to {}...", target.branch));
+    let files = site.output_files().map_err(|e| e.to_string())?;
+    let tree_id = write_tree(&repo, site, &files)?;
+    let tree = repo.find_tree(tree_id).map_err(git_error)?;
+
+    let changed = match &parent {
+        Some(parent) => {
+            let parent_tree = parent.tree().map_err(git_error)?;
+            repo.diff_tree_to_tree(Some(&parent_tree), Some(&tree), None)
+                .and_then(|diff| diff.stats())
+                .map_err(git_error)?
+                .files_changed()
+        }
+        None => files.len(),
+    };
+
+    let commit = match &parent {
+        Some(parent) if changed == 0 => parent.id(),
+        _ => {
+            let signature = repo
+                .signature()
+                .or_else(|_| Signature::now("equinox", "equinox@localhost"))
+                .map_err(git_error)?;
+            let message = format!("Publish site {}", Local::now().format("%Y-%m-%d %H:%M"));
+            let parents: Vec<_> = parent.iter().collect();
+            repo.commit(None, &signature, &signature, &message, &tree, &parents)
+                .map_err(git_error)?
+        }
+    };
+    repo.reference(&branch, commit, true, "equinox: publish site")
+        .map_err(git_error)?;
+
+    push(&mut remote, target, &branch, &mut progress)?;
+    info!(
+        "Pushed commit {} with {} changed files to {} on {}",
+        commit, changed, target.branch, target.remote
+    );
+
+    Ok(DeployReport {
+        uploaded: changed,
+        unchanged: files.len().saturating_sub(changed),
+        ..DeployReport::default()
+    })
+}
+
+fn open_repository(site: &Site) -> Result<Repository, String> {
+    if let Ok(repo) = Repository::discover(&site.root) {
+        return Ok(repo);
+    }
+    let path = site.root.join(PRIVATE_REPO);
+    Repository::open_bare(&path)
+        .or_else(|_| Repository::init_bare(&path))
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))
+}
+
+fn find_remote<'r>(repo: &'r Repository, remote: &str) -> Result<Remote<'r>, String> {
+    repo.find_remote(remote)
+        .or_else(|_| repo.remote_anonymous(remote))
+        .map_err(|e| format!("Invalid remote {}: {}", remote, e))
+}
+
+/// Writes every output file as a blob and returns the tree holding them
+fn write_tree(repo: &Repository, site: &Site, files: &[PathBuf]) -> Result<Oid, String> {
+    let output_dir = site.output_dir();
+    let mut index = Index::new().map_err(git_error)?;
+
+    let mut add = |path: String, contents: &[u8]| -> Result<(), String> {
+        let id = repo.blob(contents).map_err(git_error)?;
+        let entry = IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: contents.len() as u32,
+            id,
+            flags: 0,
+            flags_extended: 0,
+            path: path.into_bytes(),
+        };
+        index.add(&entry).map_err(git_error)
+    };
+
+    for file in files {
+        let local = output_dir.join(file);
+        let contents =
+            fs::read(&local).map_err(|e| format!("Failed to read {}: {}", local.display(), e))?;
+        let path: Vec<_> = file
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        add(path.join("/"), &contents)?;
+    }
+    // Stops GitHub Pages from running the site through Jekyll, which drops `_`-prefixed files
+    add(".nojekyll".to_string(), b"")?;
+
+    index.write_tree_to(repo).map_err(git_error)
+}
+
+fn push(
+    remote: &mut Remote,
+    target: &GitTarget,
+    branch: &str,
+    progress: &mut impl FnMut(String),
+) -> Result<(), String> {
+    let rejection = Cell::new(None);
+    {
+        // libgit2 reports every object, so only pass on every tenth of the way
+        let reported = Cell::new(None);
+        let mut callbacks = credential_callbacks();
+        callbacks.push_transfer_progress(|current, total, _| {
+            let step = (current * 10).checked_div(total);
+            if step.is_some() && step != reported.get() {
+                reported.set(step);
+                progress(format!(
+                    "Pushing to {}: {}/{} objects",
+                    target.remote, current, total
+                ));
+            }
+        });
+        callbacks.push_update_reference(|_, status| {
+            rejection.set(status.map(|s| s.to_string()));
+            Ok(())
+        });
+
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        remote
+            .push(&[format!("{}:{}", branch, branch)], Some(&mut options))
+            .map_err(|e| format!("Failed to push to {}: {}", target.remote, e))?;
+    }
+
+    match rejection.take() {
+        Some(reason) => Err(format!(
+            "{} rejected the push to {}: {}",
+            target.remote, target.branch, reason
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Authenticates with the SSH agent for SSH remotes and git's credential helpers for HTTPS
+fn credential_callbacks<'a>() -> RemoteCallbacks<'a> {
+    let attempts = Cell::new(0);
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        attempts.set(attempts.get() + 1);
+        if attempts.get() > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str(
+                "authentication failed: check the SSH agent or git credential helper",
+            ));
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            let config = git2::Config::open_default()?;
+            Cred::credential_helper(&config, url, username)
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks
+}
+
+fn git_error(e: git2::Error) -> String {
+    format!("Git error: {}", e.message())
+}
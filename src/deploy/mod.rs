@@ -0,0 +1,165 @@
+//! Uploading the generated site to where it is hosted
+
+pub mod git;
+pub mod netlify;
+pub mod rsync;
+pub mod s3;
+pub mod sftp;
+
+use crate::{autocommit, bluesky, mastodon, sri, webhook, webmention};
+use equinox_core::{accessibility, Error, Result, Site};
+use tracing::{info, warn};
+
+/// Result of a deployment
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeployReport {
+    pub uploaded: usize,
+    pub unchanged: usize,
+    /// Files deleted from the target because they are no longer in the output
+    pub removed: usize,
+}
+
+/// What deploying the site to all its targets did
+#[derive(Debug, Clone, Default)]
+pub struct Deployment {
+    /// A summary of each step, for showing to the user
+    pub summaries: Vec<String>,
+    /// Where the new deploy can be seen, if a target gives it its own address
+    pub url: Option<String>,
+}
+
+/// Deploys the site to every configured target in turn, summarising each, then sends
+/// webmentions for new links, tells the webhooks about posts that came due, shares new posts
+/// to Mastodon and Bluesky, and commits the project to Git if the site asks for them. The
+/// site's accessibility is audited first, and external resources pinned when the site uses
+/// subresource integrity. `progress` is called with a status message as each step starts.
+pub fn deploy_all(site: &Site, mut progress: impl FnMut(String)) -> Result<Deployment> {
+    let mut progress = |message: String| {
+        info!("{}", message);
+        progress(message);
+    };
+    let config = &site.config.deploy;
+    if config.is_empty() {
+        return Err(Error::Invalid("No deploy target is configured".to_string()));
+    }
+
+    let mut summaries = Vec::new();
+    // Shown before anything goes up, though they don't stop the deploy
+    if site.config.audit_accessibility {
+        progress("Checking the site's accessibility".to_string());
+        let issues = accessibility::audit(site)?;
+        if !issues.is_empty() {
+            summaries.push(format!(
+                "found {} accessibility issues, see the log",
+                issues.len()
+            ));
+        }
+    }
+    // Pages still work without their hashes, so a failure here doesn't stop the deploy
+    if site.config.subresource_integrity {
+        match sri::pin_all(site, false, &mut progress) {
+            Ok(report) if report.changed > 0 => summaries.push(format!(
+                "{} external resources changed upstream and won't load",
+                report.changed
+            )),
+            Ok(_) => {}
+            Err(e) => warn!("Failed to pin external resources: {}", e),
+        }
+    }
+    if let Some(target) = &config.sftp {
+        let report =
+            sftp::deploy(site, target, &mut progress).map_err(|e| Error::deploy("SFTP", e))?;
+        summaries.push(format!(
+            "uploaded {} files to {} ({} unchanged)",
+            report.uploaded, target.host, report.unchanged
+        ));
+    }
+    if let Some(target) = &config.rsync {
+        let report =
+            rsync::deploy(site, target, &mut progress).map_err(|e| Error::deploy("rsync", e))?;
+        let mut summary = format!(
+            "synced {} changed files to {} ({} unchanged",
+            report.uploaded, target.host, report.unchanged
+        );
+        if report.removed > 0 {
+            summary.push_str(&format!(", {} removed", report.removed));
+        }
+        summary.push(')');
+        summaries.push(summary);
+    }
+    if let Some(target) = &config.git {
+        let report =
+            git::deploy(site, target, &mut progress).map_err(|e| Error::deploy("Git", e))?;
+        summaries.push(format!(
+            "pushed {} changed files to {} ({})",
+            report.uploaded, target.branch, target.remote
+        ));
+    }
+    if let Some(target) = &config.s3 {
+        let report = s3::deploy(site, target, &mut progress).map_err(|e| Error::deploy("S3", e))?;
+        let mut summary = format!(
+            "uploaded {} files to s3://{} ({} unchanged",
+            report.uploaded, target.bucket, report.unchanged
+        );
+        if report.removed > 0 {
+            summary.push_str(&format!(", {} removed", report.removed));
+        }
+        summary.push(')');
+        summaries.push(summary);
+    }
+    let mut url = None;
+    if let Some(target) = &config.netlify {
+        let (report, deploy_url) = netlify::deploy(site, target, &mut progress)
+            .map_err(|e| Error::deploy("Netlify", e))?;
+        summaries.push(format!(
+            "uploaded {} files to Netlify site {}",
+            report.uploaded, target.site_id
+        ));
+        url = (!deploy_url.is_empty()).then_some(deploy_url);
+    }
+    for summary in &summaries {
+        info!("Deployed: {}", summary);
+    }
+
+    // The site is up either way, so a failure here doesn't fail the deploy
+    if site.config.send_webmentions {
+        match webmention::send_all(site, &mut progress) {
+            Ok(report) => summaries.push(format!(
+                "sent {} webmentions ({} failed)",
+                report.sent, report.failed
+            )),
+            Err(e) => warn!("Failed to send webmentions: {}", e),
+        }
+    }
+    match webhook::notify_new(site, None) {
+        Ok(0) => {}
+        Ok(count) => summaries.push(format!("told the webhooks about {} posts", count)),
+        Err(e) => warn!("Failed to notify webhooks: {}", e),
+    }
+    if site.config.mastodon.is_some() {
+        match mastodon::share_new(site, &mut progress) {
+            Ok(report) if report.shared > 0 => {
+                summaries.push(format!("shared {} posts to Mastodon", report.shared))
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to share new posts to Mastodon: {}", e),
+        }
+    }
+    if site.config.bluesky.is_some() {
+        match bluesky::share_new(site, &mut progress) {
+            Ok(report) if report.shared > 0 => {
+                summaries.push(format!("shared {} posts to Bluesky", report.shared))
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed to share new posts to Bluesky: {}", e),
+        }
+    }
+    if site.config.auto_commit.is_enabled() {
+        match autocommit::commit_published(site, None) {
+            Ok(Some(commit)) => summaries.push(format!("committed {}", commit)),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to commit the site: {}", e),
+        }
+    }
+    Ok(Deployment { summaries, url })
+}
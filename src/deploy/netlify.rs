@@ -0,0 +1,139 @@
+use super::{sftp::KEYRING_SERVICE, DeployReport};
+use equinox_core::{export, site::NetlifyTarget, Site};
+use serde_json::Value;
+use std::{
+    env, thread,
+    time::{Duration, Instant},
+};
+use tracing::info;
+use ureq::Agent;
+
+const API: &str = "https://api.netlify.com/api/v1";
+
+/// Environment variable holding the access token instead of the keyring, as Netlify's own
+/// tools read it
+const TOKEN_VAR: &str = "NETLIFY_AUTH_TOKEN";
+
+/// How long Netlify gets to process an upload before the deploy is given up on
+const PROCESSING_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often a deploy being processed is checked on
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn keyring_entry(target: &NetlifyTarget) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, &format!("netlify:{}", target.site_id))
+        .map_err(|e| format!("Failed to access the keyring: {}", e))
+}
+
+/// Saves the access token for `target` in the OS keyring
+pub fn store_token(target: &NetlifyTarget, token: &str) -> Result<(), String> {
+    keyring_entry(target)?
+        .set_password(token)
+        .map_err(|e| format!("Failed to store the token in the keyring: {}", e))
+}
+
+fn stored_token(target: &NetlifyTarget) -> Result<String, String> {
+    if let Ok(token) = env::var(TOKEN_VAR) {
+        return Ok(token);
+    }
+    match keyring_entry(target)?.get_password() {
+        Ok(token) => Ok(token),
+        Err(keyring::Error::NoEntry) => Err(format!(
+            "No access token for {}: add one in the publish settings or set {}",
+            target.site_id, TOKEN_VAR
+        )),
+        Err(e) => Err(format!("Failed to read the token from the keyring: {}", e)),
+    }
+}
+
+/// Uploads the site's output directory to `target` as a zip archive and waits for Netlify to
+/// publish it, returning the report and the URL of the new deploy
+pub fn deploy(
+    site: &Site,
+    target: &NetlifyTarget,
+    mut progress: impl FnMut(String),
+) -> Result<(DeployReport, String), String> {
+    let token = stored_token(target)?;
+    let files = site.output_files().map_err(|e| e.to_string())?;
+    progress(format!("Zipping {} files...", files.len()));
+    let zip = export::zip_output(site).map_err(|e| e.to_string())?;
+
+    // Netlify explains failures in the body, so statuses are handled here
+    let agent: Agent = Agent::config_builder()
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let authorization = format!("Bearer {}", token);
+    progress(format!("Uploading to Netlify site {}...", target.site_id));
+    let mut response = agent
+        .post(format!("{}/sites/{}/deploys", API, target.site_id))
+        .header("authorization", &authorization)
+        .header("content-type", "application/zip")
+        .send(&zip[..])
+        .map_err(|e| format!("Uploading to Netlify failed: {}", e))?;
+    let mut deploy = read_deploy(&mut response)?;
+    let id = deploy["id"]
+        .as_str()
+        .ok_or("Netlify didn't say which deploy it made")?
+        .to_string();
+
+    let started = Instant::now();
+    loop {
+        match deploy["state"].as_str().unwrap_or_default() {
+            "ready" => break,
+            "error" => {
+                return Err(format!(
+                    "Netlify failed to publish the deploy: {}",
+                    deploy["error_message"]
+                        .as_str()
+                        .unwrap_or("no reason given")
+                ))
+            }
+            state => {
+                if started.elapsed() > PROCESSING_TIMEOUT {
+                    return Err(format!("Netlify was still {} the deploy, giving up", state));
+                }
+                progress(format!("Netlify is processing the deploy ({})...", state));
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+        let mut response = agent
+            .get(format!("{}/deploys/{}", API, id))
+            .header("authorization", &authorization)
+            .call()
+            .map_err(|e| format!("Checking the Netlify deploy failed: {}", e))?;
+        deploy = read_deploy(&mut response)?;
+    }
+
+    let url = ["deploy_ssl_url", "deploy_url", "ssl_url", "url"]
+        .iter()
+        .find_map(|field| deploy[field].as_str())
+        .unwrap_or_default()
+        .to_string();
+    info!("Netlify published deploy {} at {}", id, url);
+    let report = DeployReport {
+        uploaded: files.len(),
+        ..DeployReport::default()
+    };
+    Ok((report, url))
+}
+
+/// The deploy Netlify describes in `response`, or what went wrong
+fn read_deploy(response: &mut ureq::http::Response<ureq::Body>) -> Result<Value, String> {
+    let status = response.status();
+    let body = response
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| format!("Failed to read Netlify's response: {}", e))?;
+    let deploy: Value = serde_json::from_str(&body).unwrap_or_default();
+    match status.as_u16() {
+        200..=299 => Ok(deploy),
+        401 | 403 => Err("Netlify refused the access token".to_string()),
+        404 => Err("Netlify has no such site, or the token can't reach it".to_string()),
+        status => Err(format!(
+            "Netlify answered {}: {}",
+            status,
+            deploy["message"].as_str().unwrap_or(body.trim())
+        )),
+    }
+}
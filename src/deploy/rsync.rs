@@ -0,0 +1,158 @@
+use super::DeployReport;
+use equinox_core::{site::RsyncTarget, Site};
+use std::{
+    fmt,
+    io::{BufRead, BufReader, ErrorKind, Read},
+    process::{Command, Stdio},
+    thread,
+};
+use tracing::{debug, info};
+
+/// A file rsync sent to the server or deleted from it, or would in a dry run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Upload(String),
+    Delete(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Upload(path) => write!(f, "upload {}", path),
+            Change::Delete(path) => write!(f, "delete {}", path),
+        }
+    }
+}
+
+/// Syncs the site's output directory into `target`, sending only files whose contents changed.
+/// `progress` is called with a message for each file sent or deleted.
+pub fn deploy(
+    site: &Site,
+    target: &RsyncTarget,
+    mut progress: impl FnMut(String),
+) -> Result<DeployReport, String> {
+    let files = site.output_files().map_err(|e| e.to_string())?;
+    progress(format!("Syncing with {}...", target.host));
+    let mut report = DeployReport::default();
+    run(site, target, false, |change| {
+        progress(match change {
+            Change::Upload(path) => {
+                report.uploaded += 1;
+                format!("Uploading {}", path)
+            }
+            Change::Delete(path) => {
+                report.removed += 1;
+                format!("Deleting {}", path)
+            }
+        })
+    })?;
+    report.unchanged = files.len().saturating_sub(report.uploaded);
+    info!(
+        "Synced {} changed files to {}:{} ({} removed)",
+        report.uploaded, target.host, target.remote_dir, report.removed
+    );
+    Ok(report)
+}
+
+/// What deploying to `target` would change on the server, without changing anything
+pub fn preview(site: &Site, target: &RsyncTarget) -> Result<Vec<Change>, String> {
+    let mut changes = Vec::new();
+    run(site, target, true, |change| changes.push(change))?;
+    Ok(changes)
+}
+
+/// Runs rsync, calling `on_change` as it reports each file it sends or deletes
+fn run(
+    site: &Site,
+    target: &RsyncTarget,
+    dry_run: bool,
+    mut on_change: impl FnMut(Change),
+) -> Result<(), String> {
+    let mut command = Command::new("rsync");
+    // Every build rewrites the output, so files are compared by contents rather than by time.
+    // SSH runs in batch mode as there is nowhere to type a password.
+    command
+        .arg("--recursive")
+        .arg("--times")
+        .arg("--checksum")
+        .arg("--compress")
+        .arg("--itemize-changes")
+        .arg("-e")
+        .arg(format!("ssh -p {} -o BatchMode=yes", target.port));
+    if target.delete_removed {
+        command.arg("--delete");
+    }
+    if dry_run {
+        command.arg("--dry-run");
+    }
+    let destination = match target.username.as_str() {
+        "" => format!(
+            "{}:{}/",
+            target.host,
+            target.remote_dir.trim_end_matches('/')
+        ),
+        username => format!(
+            "{}@{}:{}/",
+            username,
+            target.host,
+            target.remote_dir.trim_end_matches('/')
+        ),
+    };
+    // The trailing slash syncs what's in the directory rather than the directory itself
+    command
+        .arg(format!("{}/", site.output_dir().display()))
+        .arg(&destination)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    debug!("Running {:?}", command);
+
+    let mut child = command.spawn().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => {
+            "rsync isn't installed: install it, or deploy over SFTP instead".to_string()
+        }
+        _ => format!("Failed to run rsync: {}", e),
+    })?;
+    // Read alongside stdout so a chatty rsync can't fill the pipe and stall
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let errors = thread::spawn(move || {
+        let mut errors = String::new();
+        let _ = stderr.read_to_string(&mut errors);
+        errors
+    });
+    let stdout = child.stdout.take().expect("stdout is piped");
+    for line in BufReader::new(stdout).lines() {
+        let line = line.map_err(|e| format!("Failed to read rsync's output: {}", e))?;
+        if let Some(change) = parse_change(&line) {
+            on_change(change);
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to run rsync: {}", e))?;
+    let errors = errors.join().unwrap_or_default();
+    match status.code() {
+        Some(0) => Ok(()),
+        // rsync passes on SSH's own exit status when it can't connect
+        Some(255) => Err(format!(
+            "Couldn't connect to {}, check that the server accepts your SSH key: {}",
+            target.host,
+            errors.trim()
+        )),
+        _ => Err(format!("rsync failed ({}): {}", status, errors.trim())),
+    }
+}
+
+/// The change a line of `--itemize-changes` output describes, if it sends or deletes a file.
+/// Each line is an 11-character summary, such as `<f.st......` for a file sent because its
+/// size and time changed or `*deleting` padded out, then a space and the path.
+fn parse_change(line: &str) -> Option<Change> {
+    let (summary, path) = (line.get(..11)?, line.get(12..)?);
+    if summary.starts_with("*deleting") {
+        Some(Change::Delete(path.to_string()))
+    } else if summary.starts_with("<f") {
+        Some(Change::Upload(path.to_string()))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,537 @@
+use super::DeployReport;
+use chrono::{DateTime, Utc};
+use equinox_core::{assets::content_type, fingerprint, html, site::S3Target, Site};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    io::Read,
+    path::PathBuf,
+};
+use tracing::{debug, info};
+use ureq::{http, Agent};
+
+/// Caching allowed for fingerprinted assets
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Above this many changed paths a single wildcard invalidation is cheaper than listing them
+const MAX_INVALIDATION_PATHS: usize = 15;
+
+/// Uploads the site's output directory to `target`'s bucket, skipping objects whose contents
+/// already match, then invalidates the changed paths in CloudFront if a distribution is set
+pub fn deploy(
+    site: &Site,
+    target: &S3Target,
+    mut progress: impl FnMut(String),
+) -> Result<DeployReport, String> {
+    let credentials = Credentials::load(&target.profile)?;
+    let s3 = Client::new(&credentials, &target.region, "s3");
+    let endpoint = Endpoint::for_bucket(&target.bucket, &target.region, &target.endpoint)?;
+    let prefix = match target.prefix.trim_matches('/') {
+        "" => String::new(),
+        prefix => format!("{}/", prefix),
+    };
+
+    progress(format!("Listing s3://{}/{}...", target.bucket, prefix));
+    let remote = list_objects(&s3, &endpoint, &prefix)?;
+
+    let output_dir = site.output_dir();
+    let files = site.output_files().map_err(|e| e.to_string())?;
+    let mut report = DeployReport::default();
+    let mut changed = Vec::new();
+    let mut local_keys = HashSet::new();
+
+    for (i, file) in files.iter().enumerate() {
+        let path: Vec<_> = file
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        let key = format!("{}{}", prefix, path.join("/"));
+        local_keys.insert(key.clone());
+
+        let local = output_dir.join(file);
+        let contents =
+            fs::read(&local).map_err(|e| format!("Failed to read {}: {}", local.display(), e))?;
+        if remote.get(&key) == Some(&hex(&Md5::digest(&contents))) {
+            debug!("Skipped unchanged {}", key);
+            report.unchanged += 1;
+            continue;
+        }
+
+        progress(format!(
+            "Uploading {} ({}/{})",
+            file.display(),
+            i + 1,
+            files.len()
+        ));
+        let mut headers = vec![("content-type", content_type(file))];
+        // Fingerprinted copies get a new name whenever they change, so they can be kept forever
+        if fingerprint::is_fingerprinted(&local) {
+            headers.push(("cache-control", IMMUTABLE));
+        }
+        s3.send(
+            "PUT",
+            &endpoint,
+            &endpoint.object_path(&key),
+            &[],
+            &headers,
+            contents,
+        )?;
+        info!(
+            "Uploaded {} to s3://{}/{}",
+            file.display(),
+            target.bucket,
+            key
+        );
+        report.uploaded += 1;
+        changed.push(key);
+    }
+
+    if target.delete_removed {
+        for key in remote.keys().filter(|key| !local_keys.contains(*key)) {
+            progress(format!("Deleting {}", key));
+            s3.send(
+                "DELETE",
+                &endpoint,
+                &endpoint.object_path(key),
+                &[],
+                &[],
+                Vec::new(),
+            )?;
+            info!("Deleted s3://{}/{}", target.bucket, key);
+            report.removed += 1;
+            changed.push(key.clone());
+        }
+    }
+
+    if !target.cloudfront_distribution.is_empty() && !changed.is_empty() {
+        progress(format!(
+            "Invalidating {} paths in CloudFront...",
+            changed.len()
+        ));
+        invalidate(&credentials, &target.cloudfront_distribution, &changed)?;
+    }
+
+    Ok(report)
+}
+
+/// Keys under `prefix` in the bucket mapped to their ETags, which are the MD5 of the contents
+/// for objects uploaded in a single part
+fn list_objects(
+    s3: &Client,
+    endpoint: &Endpoint,
+    prefix: &str,
+) -> Result<HashMap<String, String>, String> {
+    let mut objects = HashMap::new();
+    let mut continuation = None;
+
+    loop {
+        let mut query = vec![
+            ("list-type", "2".to_string()),
+            ("prefix", prefix.to_string()),
+        ];
+        if let Some(token) = continuation.take() {
+            query.push(("continuation-token", token));
+        }
+        let xml = s3.send(
+            "GET",
+            endpoint,
+            &endpoint.object_path(""),
+            &query,
+            &[],
+            Vec::new(),
+        )?;
+
+        for object in xml.split("<Contents>").skip(1) {
+            if let (Some(key), Some(etag)) = (xml_value(object, "Key"), xml_value(object, "ETag")) {
+                objects.insert(key, etag.trim_matches('"').to_string());
+            }
+        }
+
+        match xml_value(&xml, "NextContinuationToken") {
+            Some(token) if xml_value(&xml, "IsTruncated").as_deref() == Some("true") => {
+                continuation = Some(token)
+            }
+            _ => return Ok(objects),
+        }
+    }
+}
+
+fn invalidate(
+    credentials: &Credentials,
+    distribution: &str,
+    keys: &[String],
+) -> Result<(), String> {
+    let mut paths: Vec<String> = Vec::new();
+    for key in keys {
+        paths.push(format!("/{}", key));
+        // Directory URLs are cached separately from the index page they serve
+        if let Some(dir) = key.strip_suffix("index.html") {
+            paths.push(format!("/{}", dir));
+        }
+    }
+    if paths.len() > MAX_INVALIDATION_PATHS {
+        paths = vec!["/*".to_string()];
+    }
+
+    let items: String = paths
+        .iter()
+        .map(|path| format!("<Path>{}</Path>", html::escape(path)))
+        .collect();
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <InvalidationBatch xmlns=\"http://cloudfront.amazonaws.com/doc/2020-05-31/\">\
+         <Paths><Quantity>{}</Quantity><Items>{}</Items></Paths>\
+         <CallerReference>equinox-{}</CallerReference>\
+         </InvalidationBatch>",
+        paths.len(),
+        items,
+        Utc::now().timestamp_millis()
+    );
+
+    // CloudFront is a global service signed as if it lived in us-east-1
+    let cloudfront = Client::new(credentials, "us-east-1", "cloudfront");
+    let endpoint = Endpoint {
+        base: "https://cloudfront.amazonaws.com".to_string(),
+        host: "cloudfront.amazonaws.com".to_string(),
+        path_prefix: String::new(),
+    };
+    cloudfront.send(
+        "POST",
+        &endpoint,
+        &format!(
+            "/2020-05-31/distribution/{}/invalidation",
+            uri_encode(distribution, true)
+        ),
+        &[],
+        &[("content-type", "text/xml")],
+        body.into_bytes(),
+    )?;
+    Ok(())
+}
+
+/// AWS access keys
+pub(crate) struct Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl Credentials {
+    /// Reads credentials from `AWS_ACCESS_KEY_ID`/`AWS_SECRET_ACCESS_KEY`, falling back to
+    /// `profile` (or `AWS_PROFILE`, or `default`) in the shared credentials file
+    pub(crate) fn load(profile: &str) -> Result<Credentials, String> {
+        if let (Ok(access_key), Ok(secret_key)) = (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Ok(Credentials {
+                access_key,
+                secret_key,
+                session_token: env::var("AWS_SESSION_TOKEN").ok(),
+            });
+        }
+
+        let profile = match profile {
+            "" => env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string()),
+            profile => profile.to_string(),
+        };
+        let path = env::var_os("AWS_SHARED_CREDENTIALS_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| {
+                dirs::home_dir()
+                    .unwrap_or_default()
+                    .join(".aws")
+                    .join("credentials")
+            });
+        let contents = fs::read_to_string(&path).map_err(|e| {
+            format!(
+                "No AWS credentials: set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY or create {} ({})",
+                path.display(),
+                e
+            )
+        })?;
+
+        let mut values = HashMap::new();
+        let mut in_profile = false;
+        for line in contents.lines().map(str::trim) {
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                in_profile = section.trim() == profile;
+            } else if let (true, Some((key, value))) = (in_profile, line.split_once('=')) {
+                values.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+
+        match (
+            values.remove("aws_access_key_id"),
+            values.remove("aws_secret_access_key"),
+        ) {
+            (Some(access_key), Some(secret_key)) => Ok(Credentials {
+                access_key,
+                secret_key,
+                session_token: values.remove("aws_session_token"),
+            }),
+            _ => Err(format!(
+                "No credentials for profile '{}' in {}",
+                profile,
+                path.display()
+            )),
+        }
+    }
+}
+
+/// Where requests for a bucket are sent
+pub(crate) struct Endpoint {
+    /// Scheme and authority, e.g. `https://bucket.s3.eu-west-1.amazonaws.com`
+    base: String,
+    host: String,
+    /// Path every object key is under: empty for AWS's virtual-hosted buckets, `/bucket` for
+    /// path-style S3-compatible services
+    path_prefix: String,
+}
+
+impl Endpoint {
+    /// The endpoint for `bucket` in `region` on AWS, or on the S3-compatible service at
+    /// `endpoint` if one is given
+    pub(crate) fn for_bucket(
+        bucket: &str,
+        region: &str,
+        endpoint: &str,
+    ) -> Result<Endpoint, String> {
+        if endpoint.is_empty() {
+            let host = format!("{}.s3.{}.amazonaws.com", bucket, region);
+            return Ok(Endpoint {
+                base: format!("https://{}", host),
+                host,
+                path_prefix: String::new(),
+            });
+        }
+
+        let base = endpoint.trim_end_matches('/');
+        let host = base
+            .split_once("://")
+            .map(|(_, host)| host)
+            .filter(|host| !host.is_empty() && !host.contains('/'))
+            .ok_or_else(|| format!("Invalid S3 endpoint: {}", endpoint))?;
+        Ok(Endpoint {
+            base: base.to_string(),
+            host: host.to_string(),
+            path_prefix: format!("/{}", uri_encode(bucket, true)),
+        })
+    }
+
+    pub(crate) fn object_path(&self, key: &str) -> String {
+        format!("{}/{}", self.path_prefix, uri_encode(key, false))
+    }
+}
+
+/// Sends requests signed with AWS Signature Version 4
+pub(crate) struct Client<'a> {
+    agent: Agent,
+    credentials: &'a Credentials,
+    region: &'a str,
+    service: &'a str,
+}
+
+impl<'a> Client<'a> {
+    pub(crate) fn new(
+        credentials: &'a Credentials,
+        region: &'a str,
+        service: &'a str,
+    ) -> Client<'a> {
+        // Error responses carry AWS's explanation in the body, so they're handled here
+        let agent = Agent::config_builder()
+            .http_status_as_error(false)
+            .build()
+            .into();
+        Client {
+            agent,
+            credentials,
+            region,
+            service,
+        }
+    }
+
+    /// Headers to send for a request, and its `Authorization` header
+    fn sign(
+        &self,
+        method: &str,
+        host: &str,
+        path: &str,
+        query: &str,
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> (Vec<(&'static str, String)>, String) {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let mut signed = vec![
+            ("host", host.to_string()),
+            ("x-amz-content-sha256", payload_hash.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            signed.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = signed
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = signed
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, path, query, canonical_headers, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [date.as_str(), self.region, self.service, "aws4_request"]
+            .iter()
+            .fold(
+                format!("AWS4{}", self.credentials.secret_key).into_bytes(),
+                |key, part| hmac(&key, part.as_bytes()),
+            );
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.credentials.access_key, scope, signed_headers, signature
+        );
+        (signed, authorization)
+    }
+
+    /// Sends a request to `path` (already URI-encoded) and returns the response body
+    pub(crate) fn send(
+        &self,
+        method: &str,
+        endpoint: &Endpoint,
+        path: &str,
+        query: &[(&str, String)],
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<String, String> {
+        let body = self.send_bytes(method, endpoint, path, query, headers, body)?;
+        String::from_utf8(body).map_err(|_| format!("{} {} returned invalid text", method, path))
+    }
+
+    /// Like [`Client::send`], for responses that aren't text
+    pub(crate) fn send_bytes(
+        &self,
+        method: &str,
+        endpoint: &Endpoint,
+        path: &str,
+        query: &[(&str, String)],
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Vec<u8>, String> {
+        let payload_hash = hex(&Sha256::digest(&body));
+        let mut query: Vec<String> = query
+            .iter()
+            .map(|(key, value)| format!("{}={}", uri_encode(key, true), uri_encode(value, true)))
+            .collect();
+        query.sort();
+        let query = query.join("&");
+
+        let (signed, authorization) = self.sign(
+            method,
+            &endpoint.host,
+            path,
+            &query,
+            &payload_hash,
+            Utc::now(),
+        );
+
+        let uri = match query.is_empty() {
+            true => format!("{}{}", endpoint.base, path),
+            false => format!("{}{}?{}", endpoint.base, path, query),
+        };
+        let mut request = http::Request::builder()
+            .method(method)
+            .uri(&uri)
+            .header("authorization", authorization);
+        for (name, value) in signed.iter().filter(|(name, _)| *name != "host") {
+            request = request.header(*name, value);
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let request = request
+            .body(body)
+            .map_err(|e| format!("Invalid request to {}: {}", uri, e))?;
+
+        let mut response = self
+            .agent
+            .run(request)
+            .map_err(|e| format!("{} {} failed: {}", method, uri, e))?;
+        let mut contents = Vec::new();
+        response
+            .body_mut()
+            .as_reader()
+            .read_to_end(&mut contents)
+            .map_err(|e| format!("Failed to read the response from {}: {}", uri, e))?;
+
+        if !response.status().is_success() {
+            let text = String::from_utf8_lossy(&contents);
+            let message = xml_value(&text, "Message").unwrap_or_else(|| text.trim().to_string());
+            return Err(format!(
+                "{} {} failed: {} {}",
+                method,
+                uri,
+                response.status(),
+                message
+            ));
+        }
+        Ok(contents)
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encodes everything but unreserved characters, and `/` unless `encode_slash` is set
+pub(crate) fn uri_encode(text: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Text of the first `<tag>` element in `xml`
+fn xml_value(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml_unescape(&xml[start..end]))
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
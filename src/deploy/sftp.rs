@@ -0,0 +1,213 @@
+use super::DeployReport;
+use equinox_core::{site::SftpTarget, Site};
+use ssh2::{CheckResult, FileStat, KnownHostFileKind, Session, Sftp};
+use std::{
+    collections::HashSet,
+    fs,
+    io::Write,
+    net::TcpStream,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tracing::{debug, info};
+
+/// Service name passwords are stored under in the OS keyring
+pub(crate) const KEYRING_SERVICE: &str = "equinox";
+
+fn keyring_entry(target: &SftpTarget) -> Result<keyring::Entry, String> {
+    let user = format!("sftp:{}@{}:{}", target.username, target.host, target.port);
+    keyring::Entry::new(KEYRING_SERVICE, &user)
+        .map_err(|e| format!("Failed to access the keyring: {}", e))
+}
+
+/// Saves the password for `target` in the OS keyring
+pub fn store_password(target: &SftpTarget, password: &str) -> Result<(), String> {
+    keyring_entry(target)?
+        .set_password(password)
+        .map_err(|e| format!("Failed to store password in the keyring: {}", e))
+}
+
+fn stored_password(target: &SftpTarget) -> Result<Option<String>, String> {
+    match keyring_entry(target)?.get_password() {
+        Ok(password) => Ok(Some(password)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read password from the keyring: {}", e)),
+    }
+}
+
+/// Uploads the site's output directory to `target`, skipping files whose size and modification
+/// time already match the server's copy. `progress` is called with a message before each upload.
+pub fn deploy(
+    site: &Site,
+    target: &SftpTarget,
+    mut progress: impl FnMut(String),
+) -> Result<DeployReport, String> {
+    let files = site.output_files().map_err(|e| e.to_string())?;
+    progress(format!("Connecting to {}...", target.host));
+    let session = connect(target)?;
+    let sftp = session
+        .sftp()
+        .map_err(|e| format!("Failed to start SFTP on {}: {}", target.host, e))?;
+
+    let output_dir = site.output_dir();
+    let remote_root = PathBuf::from(&target.remote_dir);
+    let mut created_dirs = HashSet::new();
+    let mut report = DeployReport::default();
+
+    for (i, file) in files.iter().enumerate() {
+        let local = output_dir.join(file);
+        let remote = remote_root.join(file);
+        let metadata = fs::metadata(&local)
+            .map_err(|e| format!("Failed to read {}: {}", local.display(), e))?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+
+        if let Ok(stat) = sftp.stat(&remote) {
+            if stat.size == Some(metadata.len()) && stat.mtime.is_some_and(|t| t >= mtime) {
+                debug!("Skipped unchanged {}", remote.display());
+                report.unchanged += 1;
+                continue;
+            }
+        }
+
+        progress(format!(
+            "Uploading {} ({}/{})",
+            file.display(),
+            i + 1,
+            files.len()
+        ));
+        if let Some(parent) = remote.parent() {
+            create_remote_dir(&sftp, parent, &mut created_dirs)?;
+        }
+        upload(&sftp, &local, &remote, mtime)?;
+        info!(
+            "Uploaded {} to {}:{}",
+            file.display(),
+            target.host,
+            remote.display()
+        );
+        report.uploaded += 1;
+    }
+
+    Ok(report)
+}
+
+fn connect(target: &SftpTarget) -> Result<Session, String> {
+    let address = format!("{}:{}", target.host, target.port);
+    let tcp = TcpStream::connect(&address)
+        .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+
+    let mut session = Session::new().map_err(|e| format!("Failed to start SSH session: {}", e))?;
+    session.set_tcp_stream(tcp);
+    session
+        .handshake()
+        .map_err(|e| format!("SSH handshake with {} failed: {}", address, e))?;
+    verify_host_key(&session, target)?;
+
+    // Prefer a stored password, then whatever the SSH agent offers, then the usual key files
+    if let Some(password) = stored_password(target)? {
+        session
+            .userauth_password(&target.username, &password)
+            .map_err(|e| format!("Password login to {} failed: {}", address, e))?;
+        return Ok(session);
+    }
+
+    if session.userauth_agent(&target.username).is_ok() {
+        return Ok(session);
+    }
+
+    let ssh_dir = dirs::home_dir().unwrap_or_default().join(".ssh");
+    for key in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+        let key = ssh_dir.join(key);
+        if key.is_file()
+            && session
+                .userauth_pubkey_file(&target.username, None, &key, None)
+                .is_ok()
+        {
+            return Ok(session);
+        }
+    }
+
+    Err(format!(
+        "Could not log in to {} as {}: store a password in the deploy settings or add a key to the SSH agent",
+        address, target.username
+    ))
+}
+
+/// Refuses to send credentials to a server whose key isn't already trusted in `known_hosts`
+fn verify_host_key(session: &Session, target: &SftpTarget) -> Result<(), String> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| format!("{} did not present a host key", target.host))?;
+
+    let mut known_hosts = session
+        .known_hosts()
+        .map_err(|e| format!("Failed to read known hosts: {}", e))?;
+    let path = dirs::home_dir()
+        .unwrap_or_default()
+        .join(".ssh")
+        .join("known_hosts");
+    if path.is_file() {
+        known_hosts
+            .read_file(&path, KnownHostFileKind::OpenSSH)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    }
+
+    match known_hosts.check_port(&target.host, target.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(format!(
+            "The host key for {} does not match {}; refusing to connect",
+            target.host,
+            path.display()
+        )),
+        CheckResult::NotFound => Err(format!(
+            "{} is not a known host: connect once with `ssh` to trust its key",
+            target.host
+        )),
+        CheckResult::Failure => Err(format!("Failed to check the host key for {}", target.host)),
+    }
+}
+
+fn create_remote_dir(
+    sftp: &Sftp,
+    dir: &Path,
+    created: &mut HashSet<PathBuf>,
+) -> Result<(), String> {
+    if dir.as_os_str().is_empty() || created.contains(dir) {
+        return Ok(());
+    }
+    if let Some(parent) = dir.parent() {
+        create_remote_dir(sftp, parent, created)?;
+    }
+    if sftp.stat(dir).is_err() {
+        sftp.mkdir(dir, 0o755)
+            .map_err(|e| format!("Failed to create {} on the server: {}", dir.display(), e))?;
+    }
+    created.insert(dir.to_path_buf());
+    Ok(())
+}
+
+fn upload(sftp: &Sftp, local: &Path, remote: &Path, mtime: u64) -> Result<(), String> {
+    let contents =
+        fs::read(local).map_err(|e| format!("Failed to read {}: {}", local.display(), e))?;
+    let mut file = sftp
+        .create(remote)
+        .map_err(|e| format!("Failed to create {} on the server: {}", remote.display(), e))?;
+    file.write_all(&contents)
+        .map_err(|e| format!("Failed to upload {}: {}", remote.display(), e))?;
+
+    // Match the local modification time so the next deploy can tell the file is unchanged
+    let stat = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: None,
+        atime: Some(mtime),
+        mtime: Some(mtime),
+    };
+    sftp.setstat(remote, stat)
+        .map_err(|e| format!("Failed to set times on {}: {}", remote.display(), e))
+}
@@ -0,0 +1,65 @@
+//! Checking the links the site's pages have to other sites
+
+use equinox_core::{links, Site};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use ureq::Agent;
+
+/// How long a site gets to answer each request
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Result of checking the links to other sites
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkReport {
+    pub checked: usize,
+    /// Links that couldn't be reached or led to an error, each logged as a warning
+    pub broken: usize,
+}
+
+/// Requests every page on another site that the site's generated pages link to, warning about
+/// each one that can't be reached along with the pages linking to it. `progress` is called with
+/// a status message as each link is checked.
+pub fn check_external(site: &Site, mut progress: impl FnMut(String)) -> Result<LinkReport, String> {
+    let external = links::external_links(site).map_err(|e| e.to_string())?;
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let mut report = LinkReport::default();
+    for (url, pages) in &external {
+        progress(format!("Checking {}", url));
+        report.checked += 1;
+        match check(&agent, url) {
+            Ok(()) => debug!("{} is reachable", url),
+            Err(e) => {
+                warn!(
+                    "{}, linked from {}, is broken: {}",
+                    url,
+                    pages.join(", "),
+                    e
+                );
+                report.broken += 1;
+            }
+        }
+    }
+    match report.broken {
+        0 => info!("All {} links to other sites work", report.checked),
+        broken => warn!(
+            "Found {} broken links to other sites out of {}",
+            broken, report.checked
+        ),
+    }
+    Ok(report)
+}
+
+/// Whether `url` can be fetched, asking for only its headers where the host allows it
+fn check(agent: &Agent, url: &str) -> Result<(), String> {
+    match agent.head(url).call() {
+        Ok(_) => Ok(()),
+        // Some hosts don't answer `HEAD` requests, or not the same way
+        Err(ureq::Error::StatusCode(status)) if status == 405 || status == 403 => {
+            agent.get(url).call().map(|_| ()).map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,155 @@
+//! Log output to stderr, a rotating log file, and the editor's log panel
+
+use chrono::Local;
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{
+    filter::{LevelFilter, Targets},
+    layer::{Context, SubscriberExt},
+    util::SubscriberInitExt,
+    Layer,
+};
+
+/// Number of lines the log panel keeps
+pub const PANEL_LINES: usize = 500;
+
+/// Number of daily log files kept before the oldest is deleted
+const LOG_FILES: usize = 7;
+
+/// Environment variable overriding the log level, e.g. `EQUINOX_LOG=debug`
+const LEVEL_VAR: &str = "EQUINOX_LOG";
+
+/// One logged event, as shown in the log panel
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub time: String,
+    pub level: Level,
+    pub message: String,
+}
+
+type Sink = Box<dyn Fn(&Line) + Send + Sync>;
+
+/// Recent lines for the log panel, and where to send new ones once the panel exists
+#[derive(Default)]
+struct Panel {
+    lines: VecDeque<Line>,
+    sink: Option<Sink>,
+}
+
+/// Handle to the lines collected for the log panel
+#[derive(Clone, Default)]
+pub struct PanelLog {
+    panel: Arc<Mutex<Panel>>,
+}
+
+impl PanelLog {
+    /// Returns the lines logged so far, oldest first, and calls `sink` with every line logged
+    /// from now on, from whichever thread logs it
+    pub fn attach(&self, sink: impl Fn(&Line) + Send + Sync + 'static) -> Vec<Line> {
+        let mut panel = self.panel.lock().unwrap();
+        panel.sink = Some(Box::new(sink));
+        panel.lines.iter().cloned().collect()
+    }
+
+    fn push(&self, line: Line) {
+        let mut panel = self.panel.lock().unwrap();
+        if let Some(sink) = &panel.sink {
+            sink(&line);
+        }
+        if panel.lines.len() == PANEL_LINES {
+            panel.lines.pop_front();
+        }
+        panel.lines.push_back(line);
+    }
+}
+
+impl<S: Subscriber> Layer<S> for PanelLog {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut message = Message::default();
+        event.record(&mut message);
+        self.push(Line {
+            time: Local::now().format("%H:%M:%S").to_string(),
+            level: *event.metadata().level(),
+            message: message.0,
+        });
+    }
+}
+
+/// Collects an event's message followed by any other fields
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        use fmt::Write;
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        let _ = match field.name() {
+            "message" => write!(self.0, "{:?}", value),
+            name => write!(self.0, "{}={:?}", name, value),
+        };
+    }
+}
+
+/// Directory of the log files, e.g. `~/.local/share/equinox/logs` on Linux
+pub fn dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("equinox").join("logs"))
+}
+
+/// Starts logging equinox's own events. Headless commands print their results themselves, so
+/// only warnings reach stderr while `quiet`; everything is also written to a log file rotated
+/// daily. Returns the log collected for the editor's log panel.
+pub fn init(quiet: bool) -> PanelLog {
+    let level = std::env::var(LEVEL_VAR)
+        .ok()
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::INFO);
+    let targets = |level: LevelFilter| {
+        Targets::new()
+            .with_target("equinox", level)
+            .with_target("equinox_core", level)
+    };
+
+    let file = dir().and_then(|dir| {
+        // The appender complains about a missing directory before creating it
+        let _ = fs::create_dir_all(&dir);
+        RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix("equinox")
+            .filename_suffix("log")
+            .max_log_files(LOG_FILES)
+            .build(dir)
+            .map_err(|e| eprintln!("Not writing a log file: {}", e))
+            .ok()
+    });
+
+    let panel = PanelLog::default();
+    let stderr_level = if quiet { LevelFilter::WARN } else { level };
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .without_time()
+                .with_target(false)
+                .with_filter(targets(stderr_level)),
+        )
+        .with(file.map(|file| {
+            tracing_subscriber::fmt::layer()
+                .with_writer(file)
+                .with_ansi(false)
+                .with_filter(targets(level))
+        }))
+        .with(panel.clone().with_filter(targets(level)))
+        .init();
+    panel
+}
@@ -1,17 +1,74 @@
 // Prevent console window in addition to Slint window in Windows release builds when, e.g., starting the app via file manager. Ignored on other platforms.
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use maud::{html, DOCTYPE};
+mod autocommit;
+mod autosave;
+mod backup;
+mod bluesky;
+mod cli;
+mod config;
+mod deploy;
+mod links;
+mod logging;
+mod mastodon;
+mod pdf;
+mod sri;
+mod tabs;
+mod webhook;
+mod webmention;
+
+use autosave::Autosave;
+use chrono::Local;
+use config::{Config, RecentPost};
+use equinox_core::{
+    content::{parse_tags, Collision},
+    export, find,
+    formatting::{self, Format},
+    front_matter::{self, FrontMatter},
+    history::{self, History},
+    import, index, markdown, newsletter, permalink,
+    preview::{self, BlockKind},
+    serve::PreviewServer,
+    site::{
+        AnalyticsConfig, AnalyticsProvider, CommentsConfig, CommentsProvider, GitTarget,
+        NetlifyTarget, SftpTarget,
+    },
+    spelling::{self, SpellChecker},
+    themes,
+    versions::{self, Change, Version},
+    wordpress, Error, NavLink, Post, PostEdit, Result, Site,
+};
 use rfd::FileDialog;
-use slint::{ModelRc, SharedString, VecModel};
+use slint::{
+    winit_030::{winit::event::WindowEvent, EventResult, WinitWindowAccessor},
+    CloseRequestResponse, Model, ModelRc, SharedString, Timer, TimerMode, VecModel,
+};
 use std::{
-    error::Error,
+    cell::{Cell, RefCell},
     fmt, fs,
     path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
 };
+use tabs::{Tab, Tabs};
+use tracing::{error, info, warn};
 
 slint::include_modules!();
 
+/// How publish dates are shown in the editor's date field
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// How saved versions are listed in the history panel
+const VERSION_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Most misspelled words underlined at once, since placing each one lays out the whole text
+const MAX_UNDERLINES: usize = 100;
+
+/// Spell checker for the content, `None` until its dictionary has loaded
+type Speller = Arc<Mutex<Option<SpellChecker>>>;
+
 /// Represents a category containing indexed folder names from a directory
 #[derive(Debug, Clone)]
 pub struct SiteStructure {
@@ -47,12 +104,15 @@ impl fmt::Display for SiteStructure {
     }
 }
 
-fn parse_site_structure(path: &Path) -> Result<SiteStructure, String> {
+fn parse_site_structure(path: &Path) -> Result<SiteStructure> {
     if !path.is_dir() {
-        return Err(format!("{} is not a directory", path.display()));
+        return Err(Error::Invalid(format!(
+            "{} is not a directory",
+            path.display()
+        )));
     }
 
-    let entries = fs::read_dir(path).map_err(|e| format!("Failed to read directory: {}", e))?;
+    let entries = fs::read_dir(path).map_err(|e| Error::io("read", path, e))?;
 
     let mut index_path: Option<PathBuf> = None;
     let mut assets_path: Option<PathBuf> = None;
@@ -69,8 +129,8 @@ fn parse_site_structure(path: &Path) -> Result<SiteStructure, String> {
             if name_str == "assets" {
                 assets_path = Some(entry_path);
             } else {
-                // Skip hidden directories (starting with .)
-                if !name_str.starts_with('.') {
+                // Skip hidden directories (starting with .) and equinox's own project directories
+                if !name_str.starts_with('.') && !Site::RESERVED_DIRS.contains(&name_str.as_ref()) {
                     categories.push(name_str.into_owned());
                 }
             }
@@ -85,57 +145,860 @@ fn parse_site_structure(path: &Path) -> Result<SiteStructure, String> {
     })
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Any arguments select a headless command instead of the editor window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let log = logging::init(!args.is_empty());
+    if !args.is_empty() {
+        if let Err(e) = cli::run(args) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let ui = AppWindow::new()?;
+    show_log(&ui, &log);
+    let config = Rc::new(RefCell::new(Config::load()));
+    apply_preferences(&ui, &config.borrow());
+    show_recent_posts(&ui, &config.borrow());
+    let site: Rc<RefCell<Option<Site>>> = Rc::new(RefCell::new(None));
 
-    ui.on_parse_directory({
+    // Reopen the last project so the app starts where it left off
+    let last_project = config.borrow().last_project.clone();
+    if let Some(root) = last_project.filter(|root| Site::exists(root)) {
+        match Site::open(&root) {
+            Ok(opened) => {
+                show_project(&ui, &opened);
+                *site.borrow_mut() = Some(opened);
+            }
+            Err(e) => show_error(&ui, &e),
+        }
+    }
+
+    // Set whenever the editor changes, cleared once the changes are autosaved
+    let dirty = Rc::new(Cell::new(false));
+    // Undo history of the content field, for the active tab
+    let history = Rc::new(RefCell::new(History::new("")));
+    let tabs = Rc::new(RefCell::new(Tabs::default()));
+    // Tab waiting for the user to confirm it may be closed with unpublished changes
+    let closing_tab = Rc::new(Cell::new(None::<usize>));
+    // Post waiting to be generated until the user says what to do with the post whose page it
+    // would overwrite
+    let colliding: Rc<RefCell<Option<(Post, Post)>>> = Rc::new(RefCell::new(None));
+    refresh_tab_bar(&ui, &tabs.borrow());
+    let speller: Speller = Arc::new(Mutex::new(None));
+    load_spell_checker(&ui, &speller, &config.borrow().spelling_language);
+
+    let drafts = Rc::new(RefCell::new(Autosave::load_all()));
+    if let Some(draft) = drafts.borrow().first() {
+        let title = if draft.title.is_empty() {
+            "Untitled"
+        } else {
+            &draft.title
+        };
+        let message = match drafts.borrow().len() {
+            1 => format!(
+                "Found an unsaved draft \"{}\" from {}.",
+                title,
+                draft.saved_at.format("%Y-%m-%d %H:%M")
+            ),
+            count => format!(
+                "Found {} unsaved drafts, including \"{}\" from {}.",
+                count,
+                title,
+                draft.saved_at.format("%Y-%m-%d %H:%M")
+            ),
+        };
+        ui.set_restore_prompt_message(message.into());
+        ui.set_show_restore_prompt(true);
+    }
+
+    ui.on_restore_draft({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let drafts = drafts.clone();
+        let dirty = dirty.clone();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            ui.set_show_restore_prompt(false);
+            let drafts = std::mem::take(&mut *drafts.borrow_mut());
+            let Some(project) = drafts.first().map(|draft| draft.project.clone()) else {
+                return;
+            };
+
+            // The drafts may belong to a different project than the one reopened on startup
+            let current_root = site.borrow().as_ref().map(|site| site.root.clone());
+            if let Some(root) = project.filter(|root| Some(root) != current_root.as_ref()) {
+                match Site::open(&root) {
+                    Ok(opened) => {
+                        show_project(&ui, &opened);
+                        *site.borrow_mut() = Some(opened);
+                    }
+                    Err(e) => {
+                        if let Some(ui) = ui_handle.upgrade() {
+                            show_error(&ui, &e);
+                        }
+                    }
+                }
+            }
+
+            let mut tabs = tabs.borrow_mut();
+            *tabs = Tabs {
+                tabs: drafts.into_iter().map(Tab::from_draft).collect(),
+                active: 0,
+            };
+            show_tab(&ui, &mut tabs, &history);
+            dirty.set(true);
+        }
+    });
+
+    ui.on_discard_draft({
+        let ui_handle = ui.as_weak();
+        let drafts = drafts.clone();
+        move || {
+            drafts.borrow_mut().clear();
+            Autosave::clear();
+            if let Some(ui) = ui_handle.upgrade() {
+                ui.set_show_restore_prompt(false);
+            }
+        }
+    });
+
+    let autosave_timer = Timer::default();
+    autosave_timer.start(TimerMode::Repeated, Duration::from_secs(30), {
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let dirty = dirty.clone();
+        let tabs = tabs.clone();
+        move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                let site = site.borrow();
+                let project = site.as_ref().map(|site| site.root.as_path());
+                autosave(&ui, project, &tabs.borrow(), &dirty);
+            }
+        }
+    });
+
+    // Rebuilds the site when a scheduled post comes due while the editor is open, so it
+    // appears in the listings without waiting for the next manual build
+    let schedule_timer = Timer::default();
+    let last_schedule_check = Cell::new(Local::now());
+    schedule_timer.start(TimerMode::Repeated, Duration::from_secs(60), {
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        move || {
+            let site = site.borrow();
+            let (Some(ui), Some(site)) = (ui_handle.upgrade(), site.as_ref()) else {
+                return;
+            };
+            let now = Local::now();
+            let since = last_schedule_check.replace(now);
+            let due = site.store().list().is_ok_and(|posts| {
+                posts
+                    .iter()
+                    .any(|post| !post.meta.draft && post.date() > since && post.date() <= now)
+            });
+            if !due || ui.get_building() {
+                return;
+            }
+            build_in_background(&ui, site, build_site, |ui, site, _| {
+                refresh_posts(ui, site);
+                "Published scheduled posts".to_string()
+            });
+        }
+    });
+
+    ui.window().on_close_requested({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let dirty = dirty.clone();
+        let tabs = tabs.clone();
+        move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                let site = site.borrow();
+                let project = site.as_ref().map(|site| site.root.as_path());
+                autosave(&ui, project, &tabs.borrow(), &dirty);
+            }
+            CloseRequestResponse::HideWindow
+        }
+    });
+
+    ui.on_pick_output_dir({
         let ui_handle = ui.as_weak();
+        let site = site.clone();
         move || {
-            let dialog = FileDialog::new().set_title("Select a directory");
+            let mut site = site.borrow_mut();
+            let Some(site) = site.as_mut() else {
+                warn!("Open a project before choosing an output directory");
+                return;
+            };
+
+            let dialog = FileDialog::new()
+                .set_title("Select an output directory")
+                .set_directory(&site.root);
 
             if let Some(path) = dialog.pick_folder() {
-                match parse_site_structure(&path) {
-                    Ok(structure) => {
-                        println!("{}", structure);
-
-                        let project_name = structure
-                            .root_path
-                            .file_name()
-                            .and_then(|name| name.to_str())
-                            .map(|s| s.to_string())
-                            .unwrap_or_else(|| structure.root_path.display().to_string());
-
-                        // Convert categories to Slint model
-                        let categories: Vec<SharedString> = structure
-                            .categories
-                            .iter()
-                            .map(|s| SharedString::from(s.as_str()))
-                            .collect();
-                        let categories_model = ModelRc::new(VecModel::from(categories));
+                site.set_output_dir(&path);
+                if let Err(e) = save_site(site) {
+                    error!("{}", e);
+                }
 
-                        if let Some(ui) = ui_handle.upgrade() {
-                            ui.set_selected_project(project_name.into());
-                            ui.set_show_selected_project(true);
-                            ui.set_categories(categories_model);
+                if let Some(ui) = ui_handle.upgrade() {
+                    show_project(&ui, site);
+                }
+            }
+        }
+    });
+
+    ui.on_rebuild_site({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+
+            if ui.get_building() {
+                return;
+            }
+            build_in_background(
+                &ui,
+                site,
+                |site| {
+                    let rebuilt = site.rebuild()?;
+                    announce_due(site);
+                    // Broken links are reported in the log, so a failed check isn't a failed build
+                    if site.config.check_external_links {
+                        if let Err(e) = links::check_external(site, |_| {}) {
+                            warn!("Failed to check links to other sites: {}", e);
                         }
+                    }
+                    Ok(rebuilt)
+                },
+                |ui, site, (pages, removed)| {
+                    refresh_posts(ui, site);
+                    format!(
+                        "Rebuilt {} posts, removed {} stale pages",
+                        pages.len(),
+                        removed.len()
+                    )
+                },
+            );
+        }
+    });
+
+    ui.on_export_site({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+            if ui.get_building() {
+                return;
+            }
+            let Some(mut archive) = FileDialog::new()
+                .set_title("Export the site")
+                .set_file_name(format!("{}.{}", site.name(), export::EXTENSION))
+                .add_filter("Gzipped tarball", &["gz"])
+                .save_file()
+            else {
+                return;
+            };
+            if !archive.to_string_lossy().ends_with(export::EXTENSION) {
+                archive = archive.with_extension(export::EXTENSION);
+            }
+
+            build_in_background(
+                &ui,
+                site,
+                move |site| {
+                    let count = export::export_site(site, &archive)?;
+                    Ok((count, archive))
+                },
+                |_, _, (count, archive)| {
+                    format!("Exported {} files to {}", count, archive.display())
+                },
+            );
+        }
+    });
+
+    ui.on_theme_selected({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        move |name| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let mut site = site.borrow_mut();
+            let Some(site) = site.as_mut() else {
+                return;
+            };
+            if site.config.theme == name.as_str() {
+                return;
+            }
+
+            // Every page embeds the theme's templates, so the whole site is rebuilt
+            site.config.theme = name.to_string();
+            if let Err(e) = save_site(site) {
+                show_error(&ui, &e);
+                return;
+            }
+            build_in_background(&ui, site, build_site, move |_, _, pages| {
+                format!("Rebuilt {} posts with the {} theme", pages.len(), name)
+            });
+        }
+    });
+
+    ui.on_edit_preferences({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let config = config.borrow();
+            ui.set_pref_author(config.author.clone().into());
+            ui.set_pref_font_size(config.editor_font_size.to_string().into());
+            ui.set_pref_appearance_index(
+                config::Appearance::ALL
+                    .iter()
+                    .position(|a| *a == config.appearance)
+                    .map_or(0, |i| i as i32),
+            );
+            if let Some(site) = site.borrow().as_ref() {
+                ui.set_pref_output_dir(site.output_dir().display().to_string().into());
+                ui.set_pref_base_url(site.config.base_url.clone().into());
+                ui.set_pref_theme_index(ui.get_theme_index());
+                ui.set_pref_heading_offset(i32::from(site.config.heading_offset));
+                let analytics = &site.config.analytics;
+                ui.set_pref_analytics_index(
+                    AnalyticsProvider::ALL
+                        .iter()
+                        .position(|p| *p == analytics.provider)
+                        .map_or(0, |i| i as i32),
+                );
+                ui.set_pref_analytics_code(analytics.code.clone().into());
+                let comments = &site.config.comments;
+                ui.set_pref_comments_index(
+                    CommentsProvider::ALL
+                        .iter()
+                        .position(|p| *p == comments.provider)
+                        .map_or(0, |i| i as i32),
+                );
+                ui.set_pref_comments_repo(comments.repo.clone().into());
+                ui.set_pref_comments_category(comments.category.clone().into());
+                ui.set_pref_comments_repo_id(comments.repo_id.clone().into());
+                ui.set_pref_comments_category_id(comments.category_id.clone().into());
+            }
+            ui.set_show_preferences(true);
+        }
+    });
+
+    ui.on_pick_preferences_output_dir({
+        let ui_handle = ui.as_weak();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let dialog = FileDialog::new()
+                .set_title("Select an output directory")
+                .set_directory(ui.get_pref_output_dir().as_str());
+            if let Some(path) = dialog.pick_folder() {
+                ui.set_pref_output_dir(path.display().to_string().into());
+            }
+        }
+    });
+
+    ui.on_save_preferences({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let font_size = match ui.get_pref_font_size().trim().parse() {
+                Ok(size) if Config::FONT_SIZES.contains(&size) => size,
+                _ => {
+                    ui.set_status_message(
+                        format!(
+                            "Error: the font size must be between {} and {}",
+                            Config::FONT_SIZES.start(),
+                            Config::FONT_SIZES.end()
+                        )
+                        .into(),
+                    );
+                    return;
+                }
+            };
+
+            let mut config = config.borrow_mut();
+            config.author = ui.get_pref_author().trim().to_string();
+            config.editor_font_size = font_size;
+            config.appearance = usize::try_from(ui.get_pref_appearance_index())
+                .ok()
+                .and_then(|i| config::Appearance::ALL.get(i).copied())
+                .unwrap_or_default();
+            if let Err(e) = config.save() {
+                show_error(&ui, &e);
+                return;
+            }
+            apply_preferences(&ui, &config);
+            ui.set_show_preferences(false);
+            ui.set_status_message("Preferences saved".into());
+
+            let mut site = site.borrow_mut();
+            let Some(site) = site.as_mut() else {
+                return;
+            };
+            let output_dir = PathBuf::from(ui.get_pref_output_dir().trim());
+            let base_url = ui.get_pref_base_url().trim().to_string();
+            let theme = usize::try_from(ui.get_pref_theme_index())
+                .ok()
+                .and_then(|i| ui.get_themes().row_data(i))
+                .map_or_else(|| site.config.theme.clone(), |name| name.to_string());
+            let heading_offset = u8::try_from(ui.get_pref_heading_offset())
+                .map_or(site.config.heading_offset, |offset| offset.min(5));
+            let analytics = AnalyticsConfig {
+                provider: usize::try_from(ui.get_pref_analytics_index())
+                    .ok()
+                    .and_then(|i| AnalyticsProvider::ALL.get(i).copied())
+                    .unwrap_or_default(),
+                code: ui.get_pref_analytics_code().trim().to_string(),
+            };
+            let comments = CommentsConfig {
+                provider: usize::try_from(ui.get_pref_comments_index())
+                    .ok()
+                    .and_then(|i| CommentsProvider::ALL.get(i).copied())
+                    .unwrap_or_default(),
+                repo: ui.get_pref_comments_repo().trim().to_string(),
+                category: ui.get_pref_comments_category().trim().to_string(),
+                repo_id: ui.get_pref_comments_repo_id().trim().to_string(),
+                category_id: ui.get_pref_comments_category_id().trim().to_string(),
+            };
+            if output_dir == site.output_dir()
+                && base_url == site.config.base_url
+                && theme == site.config.theme
+                && heading_offset == site.config.heading_offset
+                && analytics == site.config.analytics
+                && comments == site.config.comments
+            {
+                return;
+            }
+
+            // Every page links to the base URL and embeds the theme and analytics, and every post
+            // the comments and headings, so the whole site is rebuilt
+            if !output_dir.as_os_str().is_empty() {
+                site.set_output_dir(&site.root.join(output_dir));
+            }
+            site.config.base_url = base_url;
+            site.config.theme = theme;
+            site.config.heading_offset = heading_offset;
+            site.config.analytics = analytics;
+            site.config.comments = comments;
+            if let Err(e) = save_site(site) {
+                show_error(&ui, &e);
+                return;
+            }
+            show_project(&ui, site);
+            build_in_background(&ui, site, build_site, |_, _, pages| {
+                format!("Applied the site settings to {} posts", pages.len())
+            });
+        }
+    });
+
+    // Rows of the navbar editor, copied from and back to the site config
+    let nav_links = Rc::new(VecModel::<NavEntry>::default());
+    ui.set_nav_links(ModelRc::from(nav_links.clone()));
+
+    ui.on_edit_navbar({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let nav_links = nav_links.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+
+            let entries: Vec<NavEntry> = site
+                .config
+                .nav
+                .iter()
+                .map(|link| NavEntry {
+                    label: link.label.clone().into(),
+                    url: link.url.clone().into(),
+                    right: link.right,
+                })
+                .collect();
+            nav_links.set_vec(entries);
+            ui.set_show_navbar_editor(true);
+        }
+    });
+
+    ui.on_nav_link_edited({
+        let nav_links = nav_links.clone();
+        move |index, entry| nav_links.set_row_data(index as usize, entry)
+    });
+
+    ui.on_add_nav_link({
+        let nav_links = nav_links.clone();
+        move || nav_links.push(NavEntry::default())
+    });
+
+    ui.on_remove_nav_link({
+        let nav_links = nav_links.clone();
+        move |index| {
+            let index = index as usize;
+            if index < nav_links.row_count() {
+                nav_links.remove(index);
+            }
+        }
+    });
+
+    ui.on_move_nav_link_up({
+        let nav_links = nav_links.clone();
+        move |index| {
+            let index = index as usize;
+            if index > 0 && index < nav_links.row_count() {
+                let entry = nav_links.remove(index);
+                nav_links.insert(index - 1, entry);
+            }
+        }
+    });
+
+    ui.on_save_navbar({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let nav_links = nav_links.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let mut site = site.borrow_mut();
+            let Some(site) = site.as_mut() else {
+                return;
+            };
+
+            site.config.nav = nav_links
+                .iter()
+                .map(|entry| NavLink::new(entry.label.trim(), entry.url.trim(), entry.right))
+                .filter(|link| !link.label.is_empty() && !link.url.is_empty())
+                .collect();
+            ui.set_show_navbar_editor(false);
+
+            // The navbar is part of every page, so the whole site is rebuilt
+            if let Err(e) = save_site(site) {
+                show_error(&ui, &e);
+                return;
+            }
+            show_sections(&ui, site);
+            show_authors(&ui, site);
+            build_in_background(&ui, site, build_site, |_, _, pages| {
+                format!("Updated the navigation bar on {} posts", pages.len())
+            });
+        }
+    });
+
+    ui.on_edit_deploy_settings({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+
+            let target = site.config.deploy.sftp.as_ref();
+            ui.set_deploy_host(target.map(|t| t.host.clone()).unwrap_or_default().into());
+            ui.set_deploy_port(
+                target
+                    .map_or(SftpTarget::default_port(), |t| t.port)
+                    .to_string()
+                    .into(),
+            );
+            ui.set_deploy_username(
+                target
+                    .map(|t| t.username.clone())
+                    .unwrap_or_default()
+                    .into(),
+            );
+            ui.set_deploy_remote_dir(
+                target
+                    .map(|t| t.remote_dir.clone())
+                    .unwrap_or_default()
+                    .into(),
+            );
+            ui.set_deploy_password(SharedString::new());
+
+            let git = site.config.deploy.git.as_ref();
+            ui.set_deploy_git_remote(git.map(|t| t.remote.clone()).unwrap_or_default().into());
+            ui.set_deploy_git_branch(
+                git.map_or_else(GitTarget::default_branch, |t| t.branch.clone())
+                    .into(),
+            );
+            ui.set_deploy_netlify_site(
+                site.config
+                    .deploy
+                    .netlify
+                    .as_ref()
+                    .map(|t| t.site_id.clone())
+                    .unwrap_or_default()
+                    .into(),
+            );
+            ui.set_deploy_netlify_token(SharedString::new());
+            ui.set_show_deploy_settings(true);
+        }
+    });
+
+    ui.on_save_deploy_settings({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let mut site = site.borrow_mut();
+            let Some(site) = site.as_mut() else {
+                return;
+            };
+
+            // Each target is optional: leaving its first field empty removes it
+            let host = ui.get_deploy_host().trim().to_string();
+            let sftp = if host.is_empty() {
+                None
+            } else {
+                let username = ui.get_deploy_username().trim().to_string();
+                let Ok(port) = ui.get_deploy_port().trim().parse() else {
+                    ui.set_status_message("Error: the port must be a number".into());
+                    return;
+                };
+                if username.is_empty() {
+                    ui.set_status_message("Error: SFTP needs a username".into());
+                    return;
+                }
+                Some(SftpTarget {
+                    host,
+                    port,
+                    username,
+                    remote_dir: ui.get_deploy_remote_dir().trim().to_string(),
+                })
+            };
+
+            let remote = ui.get_deploy_git_remote().trim().to_string();
+            let git = (!remote.is_empty()).then(|| GitTarget {
+                remote,
+                branch: match ui.get_deploy_git_branch().trim() {
+                    "" => GitTarget::default_branch(),
+                    branch => branch.to_string(),
+                },
+            });
+
+            let site_id = ui.get_deploy_netlify_site().trim().to_string();
+            let netlify = (!site_id.is_empty()).then_some(NetlifyTarget { site_id });
+
+            let password = ui.get_deploy_password();
+            let token = ui.get_deploy_netlify_token();
+            site.config.deploy.sftp = sftp.clone();
+            site.config.deploy.git = git;
+            site.config.deploy.netlify = netlify.clone();
+
+            let result = save_site(site)
+                .and_then(|_| match &sftp {
+                    Some(target) if !password.is_empty() => {
+                        deploy::sftp::store_password(target, &password)
+                            .map_err(|e| Error::deploy("SFTP", e))
+                    }
+                    _ => Ok(()),
+                })
+                .and_then(|_| match &netlify {
+                    Some(target) if !token.trim().is_empty() => {
+                        deploy::netlify::store_token(target, token.trim())
+                            .map_err(|e| Error::deploy("Netlify", e))
+                    }
+                    _ => Ok(()),
+                });
+            ui.set_deploy_password(SharedString::new());
+            ui.set_deploy_netlify_token(SharedString::new());
+            match result {
+                Ok(()) => {
+                    ui.set_show_deploy_settings(false);
+                    ui.set_status_message("Publish settings saved".into());
+                }
+                Err(e) => show_error(&ui, &e),
+            }
+        }
+    });
+
+    ui.on_deploy_site({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let Some(site) = site.borrow().clone() else {
+                return;
+            };
+            if site.config.deploy.is_empty() {
+                ui.invoke_edit_deploy_settings();
+                return;
+            }
+            if ui.get_building() {
+                ui.set_status_message("Wait for the build to finish before publishing".into());
+                return;
+            }
+
+            // Uploading can take a while, so it runs off the UI thread and reports back. The
+            // editor counts as busy meanwhile, so no build rewrites the pages being uploaded.
+            ui.set_building(true);
+            ui.set_deploying(true);
+            let ui_handle = ui_handle.clone();
+            thread::spawn(move || {
+                let result = deploy::deploy_all(&site, |message| {
+                    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                        ui.set_status_message(message.into());
+                    });
+                });
 
-                        // Check if index.html is missing and prompt to create
-                        if structure.index_path.is_none() {
-                            if let Some(ui) = ui_handle.upgrade() {
-                                ui.set_show_create_index_prompt(true);
+                let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                    ui.set_building(false);
+                    ui.set_deploying(false);
+                    match result {
+                        Ok(deployment) => {
+                            ui.set_status_message(
+                                format!("Published: {}", deployment.summaries.join("; ")).into(),
+                            );
+                            if let Some(url) = deployment.url {
+                                ui.set_deployed_url(url.into());
+                                ui.set_show_deployed_prompt(true);
                             }
                         }
+                        Err(e) => show_error(&ui, &e),
+                    }
+                });
+            });
+        }
+    });
+
+    ui.on_open_deployed_site({
+        let ui_handle = ui.as_weak();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            ui.set_show_deployed_prompt(false);
+            if let Err(e) = webbrowser::open(&ui.get_deployed_url()) {
+                warn!("Failed to open browser: {}", e);
+            }
+        }
+    });
+
+    ui.on_parse_directory({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        move || {
+            let dialog = FileDialog::new().set_title("Select a project directory");
+
+            if let Some(path) = dialog.pick_folder() {
+                match Site::open_or_create(&path) {
+                    Ok(opened) => {
+                        if let Some(ui) = ui_handle.upgrade() {
+                            show_project(&ui, &opened);
+                        }
+
+                        let mut config = config.borrow_mut();
+                        config.last_project = Some(opened.root.clone());
+                        if let Err(e) = config.save() {
+                            error!("{}", e);
+                        }
+
+                        *site.borrow_mut() = Some(opened);
                     }
-                    Err(e) => eprintln!("Error: {}", e),
+                    Err(e) => error!("{}", e),
                 }
             }
         }
     });
 
+    ui.on_import_folder({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        move || {
+            let Some(dir) = FileDialog::new()
+                .set_title("Select a folder of Markdown posts")
+                .pick_folder()
+            else {
+                return;
+            };
+            if let Some(ui) = ui_handle.upgrade() {
+                let author = config.borrow().author.clone();
+                import_in_background(&ui, &site.borrow(), move |site| {
+                    import::import_folder(site, &dir, &author)
+                });
+            }
+        }
+    });
+
+    ui.on_import_wordpress({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        move || {
+            let Some(path) = FileDialog::new()
+                .set_title("Select a WordPress export")
+                .add_filter("WordPress export", &["xml"])
+                .pick_file()
+            else {
+                return;
+            };
+            if let Some(ui) = ui_handle.upgrade() {
+                let author = config.borrow().author.clone();
+                import_in_background(&ui, &site.borrow(), move |site| {
+                    wordpress::import_export(site, &path, &author)
+                });
+            }
+        }
+    });
+
     ui.on_create_index_file({
         let ui_handle = ui.as_weak();
+        let site = site.clone();
         move || {
-            println!("Creating index.html...");
+            if let Some(site) = site.borrow().as_ref() {
+                let result = site.store().list().and_then(|posts| {
+                    let posts: Vec<_> = posts.into_iter().filter(|p| p.is_published()).collect();
+                    index::write_index(site, &site.renderer()?, &posts)
+                });
+                match result {
+                    Ok(path) => info!("Created {}", path.display()),
+                    Err(e) => error!("{}", e),
+                }
+            }
+
             if let Some(ui) = ui_handle.upgrade() {
                 ui.set_show_create_index_prompt(false);
             }
@@ -153,42 +1016,1753 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     ui.on_generate_page({
         let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        let dirty = dirty.clone();
+        let tabs = tabs.clone();
+        let colliding = colliding.clone();
         move || {
             if let Some(ui) = ui_handle.upgrade() {
-                let title = ui.get_blog_title().to_string();
-                let content = ui.get_blog_content().to_string();
-                match blog_to_html(title, content) {
-                    Ok(result) => println!("Generated: {}", result),
-                    Err(e) => eprintln!("Error: {}", e),
+                let site = site.borrow();
+                let Some(site) = site.as_ref() else {
+                    warn!("No project open");
+                    return;
+                };
+                if ui.get_building() {
+                    return;
+                }
+                let post = match edited_post(&ui, site, &config.borrow().author) {
+                    Ok(post) => post,
+                    Err(e) => return show_error(&ui, &e),
+                };
+                match site.store().colliding_post(&post) {
+                    Ok(Some(other)) => {
+                        ui.set_collision_prompt_message(
+                            format!(
+                                "\"{}\" already has the page {}.",
+                                other.meta.title,
+                                site.config.post_url(&other)
+                            )
+                            .into(),
+                        );
+                        ui.set_show_collision_prompt(true);
+                        *colliding.borrow_mut() = Some((post, other));
+                    }
+                    Ok(None) => generate_post(&ui, site, &config, &tabs, &dirty, post),
+                    Err(e) => show_error(&ui, &e),
                 }
             }
         }
     });
 
-    ui.on_create_category(|| {
-        // TODO: pop up textbox to create category
-        println!("Create category clicked");
+    ui.on_resolve_collision({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        let dirty = dirty.clone();
+        let tabs = tabs.clone();
+        let history = history.clone();
+        let colliding = colliding.clone();
+        move |overwrite| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            ui.set_show_collision_prompt(false);
+            let Some((post, other)) = colliding.take() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+            let collision = match overwrite {
+                true => Collision::Overwrite,
+                false => Collision::KeepBoth,
+            };
+            let slug = post.meta.slug.clone();
+            let post = match site.store().resolve_collision(post, &other, collision) {
+                Ok(post) => post,
+                Err(e) => return show_error(&ui, &e),
+            };
+            if post.meta.slug != slug {
+                // Keep the editor's front matter in step, or the next generate collides again
+                match FrontMatter::from_meta(&post.meta).to_block() {
+                    Ok(front_matter) => {
+                        let content = format!("{}{}", front_matter, post.body);
+                        history.borrow_mut().checkpoint(&content, 0);
+                        ui.set_blog_content(content.into());
+                        ui.invoke_content_edited();
+                    }
+                    Err(e) => return show_error(&ui, &e),
+                }
+            }
+            if collision == Collision::Overwrite {
+                // A tab still editing the replaced post would save it back
+                let mut tabs = tabs.borrow_mut();
+                let active = tabs.active;
+                if let Some(index) = tabs.find(&other.id).filter(|&index| index != active) {
+                    tabs.tabs[index].post_id.clear();
+                    tabs.tabs[index].modified = true;
+                }
+            }
+            generate_post(&ui, site, &config, &tabs, &dirty, post);
+        }
     });
 
-    ui.run()?;
+    ui.on_cancel_collision({
+        let ui_handle = ui.as_weak();
+        let colliding = colliding.clone();
+        move || {
+            colliding.take();
+            if let Some(ui) = ui_handle.upgrade() {
+                ui.set_show_collision_prompt(false);
+            }
+        }
+    });
 
-    Ok(())
-}
+    ui.on_export_pdf({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        let dirty = dirty.clone();
+        let tabs = tabs.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                warn!("No project open");
+                return;
+            };
+            if ui.get_building() {
+                return;
+            }
+            let name = match ui.get_blog_title().trim() {
+                "" => "post".to_string(),
+                title => title.to_string(),
+            };
+            let Some(pdf) = FileDialog::new()
+                .set_title("Export the post as a PDF")
+                .set_file_name(format!("{}.pdf", name))
+                .add_filter("PDF", &["pdf"])
+                .save_file()
+            else {
+                return;
+            };
+
+            match save_post(&ui, site, &config.borrow().author) {
+                Ok(post) => {
+                    remember_post(&ui, &config, site, &post.id, &post.meta.title);
+                    let mut tabs = tabs.borrow_mut();
+                    tabs.active_mut().modified = false;
+                    refresh_tab_bar(&ui, &tabs);
+                    dirty.set(true);
+                    autosave(&ui, Some(&site.root), &tabs, &dirty);
+                    build_in_background(
+                        &ui,
+                        site,
+                        move |site| {
+                            let page = site.generate(&post)?;
+                            pdf::export(&page, &pdf)?;
+                            Ok(pdf)
+                        },
+                        |_, _, pdf| format!("Exported {}", pdf.display()),
+                    );
+                }
+                Err(e) => show_error(&ui, &e),
+            }
+        }
+    });
+
+    ui.on_export_newsletter({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        let dirty = dirty.clone();
+        let tabs = tabs.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                warn!("No project open");
+                return;
+            };
+            if ui.get_building() {
+                return;
+            }
+            let name = match ui.get_blog_title().trim() {
+                "" => "post".to_string(),
+                title => title.to_string(),
+            };
+            let Some(file) = FileDialog::new()
+                .set_title("Export the post as a newsletter")
+                .set_file_name(format!("{}.html", name))
+                .add_filter("HTML", &["html"])
+                .save_file()
+            else {
+                return;
+            };
 
-fn blog_to_html(title: String, content: String) -> Result<String, String> {
-    let htmldoc = html! {
-        (DOCTYPE)
-        html lang="en" {
-            head {
-                meta charset="UTF-8";
-                title { (format!("jjp | {}", title)) }
+            match save_post(&ui, site, &config.borrow().author) {
+                Ok(post) => {
+                    remember_post(&ui, &config, site, &post.id, &post.meta.title);
+                    let mut tabs = tabs.borrow_mut();
+                    tabs.active_mut().modified = false;
+                    refresh_tab_bar(&ui, &tabs);
+                    dirty.set(true);
+                    autosave(&ui, Some(&site.root), &tabs, &dirty);
+                    build_in_background(
+                        &ui,
+                        site,
+                        move |site| {
+                            newsletter::render(site, &post)?.save(&file)?;
+                            Ok(file)
+                        },
+                        |_, _, file| format!("Exported the newsletter to {}", file.display()),
+                    );
+                }
+                Err(e) => show_error(&ui, &e),
             }
-            body {
-                h1 { (title) }
-                div { (content) }
+        }
+    });
+
+    ui.on_copy_html({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+            if ui.get_building() {
+                return;
             }
+            let post = match edited_post(&ui, site, &config.borrow().author) {
+                Ok(post) => post,
+                Err(e) => return show_error(&ui, &e),
+            };
+            build_in_background(
+                &ui,
+                site,
+                move |site| site.entry_html(&post),
+                |ui, _, html| {
+                    ui.invoke_copy_to_clipboard(html.into());
+                    "Copied the post's HTML".to_string()
+                },
+            );
         }
-    };
-    println!("{}", htmldoc.into_string());
-    Ok("Generated HTML".to_string())
+    });
+
+    ui.on_open_post({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let config = config.clone();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        move |id| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+
+            let mut tabs = tabs.borrow_mut();
+            stash_tab(&ui, &mut tabs, &history);
+            if let Some(index) = tabs.find(&id) {
+                tabs.active = index;
+                show_tab(&ui, &mut tabs, &history);
+                return;
+            }
+
+            let post = match site.store().load(&id) {
+                Ok(post) => post,
+                Err(e) => return show_error(&ui, &e),
+            };
+            remember_post(&ui, &config, site, &post.id, &post.meta.title);
+            let tab = match tab_for_post(post) {
+                Ok(tab) => tab,
+                Err(e) => return show_error(&ui, &e),
+            };
+            // An untouched new post makes way for the opened one
+            if tabs.active().is_blank() {
+                *tabs.active_mut() = tab;
+            } else {
+                tabs.tabs.push(tab);
+                tabs.active = tabs.tabs.len() - 1;
+            }
+            show_tab(&ui, &mut tabs, &history);
+        }
+    });
+
+    // Versions listed in the history panel, newest first
+    let shown_versions = Rc::new(RefCell::new(Vec::<Version>::new()));
+
+    ui.on_show_history({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let shown_versions = shown_versions.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+
+            let id = ui.get_current_post_id();
+            let (versions, current) = match versions::list(&site.root, &id)
+                .and_then(|versions| Ok((versions, site.store().source(&id)?)))
+            {
+                Ok(listed) => listed,
+                Err(e) => return show_error(&ui, &e),
+            };
+            if versions.is_empty() {
+                ui.set_status_message("No earlier versions of this post are kept yet".into());
+                return;
+            }
+            let sources: Vec<Option<String>> = versions
+                .iter()
+                .map(|version| versions::load(&site.root, &id, &version.name).ok())
+                .collect();
+            let entries: Vec<VersionEntry> = versions
+                .iter()
+                .zip(&sources)
+                .map(|(version, source)| VersionEntry {
+                    label: version.saved.format(VERSION_DATE_FORMAT).to_string().into(),
+                    current: source.as_deref() == Some(current.as_str()),
+                })
+                .collect();
+            // The newest version is usually the post as it is now, so the last change is shown
+            let selected = entries.iter().position(|entry| !entry.current).unwrap_or(0);
+            ui.set_versions(ModelRc::new(VecModel::from(entries)));
+            *shown_versions.borrow_mut() = versions;
+            show_version_diff(&ui, site, &shown_versions.borrow(), selected);
+            ui.set_show_version_history(true);
+        }
+    });
+
+    ui.on_select_version({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let shown_versions = shown_versions.clone();
+        move |index| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+            show_version_diff(&ui, site, &shown_versions.borrow(), index as usize);
+        }
+    });
+
+    ui.on_restore_version({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        let shown_versions = shown_versions.clone();
+        move |index| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+            let shown_versions = shown_versions.borrow();
+            let Some(version) = shown_versions.get(index as usize) else {
+                return;
+            };
+
+            let id = ui.get_current_post_id();
+            let tab = match site
+                .restore_version(&id, &version.name)
+                .and_then(tab_for_post)
+            {
+                Ok(tab) => tab,
+                Err(e) => return show_error(&ui, &e),
+            };
+            let mut tabs = tabs.borrow_mut();
+            if let Some(index) = tabs.find(&id) {
+                tabs.tabs[index] = tab;
+                if index == tabs.active {
+                    show_tab(&ui, &mut tabs, &history);
+                }
+            }
+            refresh_posts(&ui, site);
+            back_up_in_background(site);
+            ui.set_show_version_history(false);
+            ui.set_status_message(
+                format!(
+                    "Restored the version from {}; generate the post to update its page",
+                    version.saved.format(VERSION_DATE_FORMAT)
+                )
+                .into(),
+            );
+        }
+    });
+
+    ui.on_open_recent({
+        let ui_handle = ui.as_weak();
+        let config = config.clone();
+        let site = site.clone();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        move |index| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let Some(recent) = config.borrow().recent_posts.get(index as usize).cloned() else {
+                return;
+            };
+
+            let current_root = site.borrow().as_ref().map(|site| site.root.clone());
+            if current_root.as_ref() != Some(&recent.project) {
+                // Open tabs refer to posts of the current site
+                if tabs.borrow().tabs.iter().any(|tab| tab.modified) {
+                    return show_error(
+                        &ui,
+                        &Error::Invalid(
+                            "Generate or close the posts with changes before opening a post \
+                             from another project"
+                                .to_string(),
+                        ),
+                    );
+                }
+                match Site::open(&recent.project) {
+                    Ok(opened) => {
+                        show_project(&ui, &opened);
+                        let mut config = config.borrow_mut();
+                        config.last_project = Some(opened.root.clone());
+                        if let Err(e) = config.save() {
+                            error!("{}", e);
+                        }
+                        *site.borrow_mut() = Some(opened);
+                        let mut tabs = tabs.borrow_mut();
+                        *tabs = Tabs::default();
+                        show_tab(&ui, &mut tabs, &history);
+                    }
+                    Err(e) => {
+                        forget_post(&ui, &config, &recent);
+                        return show_error(&ui, &e);
+                    }
+                }
+            }
+
+            let missing = site
+                .borrow()
+                .as_ref()
+                .map(|site| site.store().load(&recent.id))
+                .and_then(|loaded| loaded.err());
+            if let Some(e) = missing {
+                forget_post(&ui, &config, &recent);
+                return show_error(&ui, &e);
+            }
+            ui.invoke_open_post(recent.id.into());
+        }
+    });
+
+    ui.on_new_post({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                let mut tabs = tabs.borrow_mut();
+                stash_tab(&ui, &mut tabs, &history);
+                tabs.tabs.push(Tab::default());
+                tabs.active = tabs.tabs.len() - 1;
+                show_tab(&ui, &mut tabs, &history);
+            }
+        }
+    });
+
+    ui.on_select_tab({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        move |index| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let mut tabs = tabs.borrow_mut();
+            let index = index as usize;
+            if index == tabs.active || index >= tabs.tabs.len() {
+                return;
+            }
+            stash_tab(&ui, &mut tabs, &history);
+            tabs.active = index;
+            show_tab(&ui, &mut tabs, &history);
+        }
+    });
+
+    ui.on_close_tab({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        let closing_tab = closing_tab.clone();
+        move |index| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let mut tabs = tabs.borrow_mut();
+            let index = index as usize;
+            let Some(tab) = tabs.tabs.get(index) else {
+                return;
+            };
+            if tab.modified {
+                let title = match index == tabs.active {
+                    true => ui.get_blog_title().to_string(),
+                    false => tab.title.clone(),
+                };
+                let title = if title.is_empty() { "Untitled" } else { &title };
+                closing_tab.set(Some(index));
+                ui.set_close_tab_prompt_message(
+                    format!("\"{}\" has changes that haven't been generated.", title).into(),
+                );
+                ui.set_show_close_tab_prompt(true);
+                return;
+            }
+            stash_tab(&ui, &mut tabs, &history);
+            tabs.close(index);
+            show_tab(&ui, &mut tabs, &history);
+        }
+    });
+
+    ui.on_confirm_close_tab({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        let dirty = dirty.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            ui.set_show_close_tab_prompt(false);
+            let Some(index) = closing_tab.take() else {
+                return;
+            };
+            let mut tabs = tabs.borrow_mut();
+            if index < tabs.tabs.len() {
+                stash_tab(&ui, &mut tabs, &history);
+                tabs.close(index);
+                show_tab(&ui, &mut tabs, &history);
+                // Drop the closed tab's draft at the next autosave
+                dirty.set(true);
+            }
+        }
+    });
+
+    ui.on_insert_image({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let history = history.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+
+            let dialog = FileDialog::new().set_title("Select an image").add_filter(
+                "Images",
+                &["png", "jpg", "jpeg", "gif", "webp", "avif", "svg"],
+            );
+            let Some(path) = dialog.pick_file() else {
+                return;
+            };
+
+            match insert_image(&ui, site, &path) {
+                Ok(()) => {
+                    history
+                        .borrow_mut()
+                        .checkpoint(&ui.get_blog_content(), ui.get_content_cursor() as usize);
+                    ui.invoke_content_edited();
+                }
+                Err(e) => show_error(&ui, &e),
+            }
+        }
+    });
+
+    ui.on_attach_audio({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let history = history.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            if let Some(site) = site.borrow().as_ref() {
+                attach_file(
+                    &ui,
+                    site,
+                    &history,
+                    ("Select an audio file", "Audio"),
+                    &[
+                        "mp3", "m4a", "ogg", "oga", "opus", "wav", "flac", "aac", "webm",
+                    ],
+                    Site::import_audio,
+                    |front_matter, audio| front_matter.audio = Some(audio),
+                );
+            }
+        }
+    });
+
+    ui.on_set_cover({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let history = history.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            if let Some(site) = site.borrow().as_ref() {
+                attach_file(
+                    &ui,
+                    site,
+                    &history,
+                    ("Select a cover image", "Images"),
+                    &["png", "jpg", "jpeg", "gif", "webp", "avif", "svg"],
+                    Site::import_image,
+                    |front_matter, image| front_matter.image = Some(image),
+                );
+            }
+        }
+    });
+
+    ui.on_format_selection({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        move |name| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let Some(format) = Format::from_name(&name) else {
+                return;
+            };
+            let content = ui.get_blog_content().to_string();
+            let formatted = formatting::apply(
+                &content,
+                clamp_to_char_boundary(&content, ui.get_content_anchor()),
+                clamp_to_char_boundary(&content, ui.get_content_cursor()),
+                format,
+            );
+            history
+                .borrow_mut()
+                .checkpoint(&formatted.text, formatted.selection_end);
+            ui.set_blog_content(formatted.text.into());
+            ui.invoke_set_content_selection(
+                formatted.selection_start as i32,
+                formatted.selection_end as i32,
+            );
+            ui.invoke_content_edited();
+        }
+    });
+
+    ui.on_find_edited({
+        let ui_handle = ui.as_weak();
+        move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                // Stay on the current match while the query is being typed
+                find_match(&ui, |found, (start, _)| {
+                    found.iter().position(|m| m.0 >= start).or(Some(0))
+                });
+            }
+        }
+    });
+
+    ui.on_find_next({
+        let ui_handle = ui.as_weak();
+        move |forward| {
+            if let Some(ui) = ui_handle.upgrade() {
+                find_match(&ui, |found, (start, end)| match forward {
+                    true => found.iter().position(|m| m.0 >= end).or(Some(0)),
+                    false => found
+                        .iter()
+                        .rposition(|m| m.1 <= start)
+                        .or(found.len().checked_sub(1)),
+                });
+            }
+        }
+    });
+
+    ui.on_replace_match({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let content = ui.get_blog_content().to_string();
+            let selection = content_selection(&ui, &content);
+            let found = find::matches(&content, &ui.get_find_query(), ui.get_find_case_sensitive());
+            if found.contains(&selection) {
+                let replacement = ui.get_find_replacement();
+                let content = format!(
+                    "{}{}{}",
+                    &content[..selection.0],
+                    replacement,
+                    &content[selection.1..]
+                );
+                let after = selection.0 + replacement.len();
+                history.borrow_mut().checkpoint(&content, after);
+                ui.set_blog_content(content.into());
+                let after = after as i32;
+                ui.invoke_highlight_content(after, after);
+                ui.invoke_content_edited();
+            }
+            ui.invoke_find_next(true);
+        }
+    });
+
+    ui.on_replace_all_matches({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let (content, count) = find::replace_all(
+                &ui.get_blog_content(),
+                &ui.get_find_query(),
+                &ui.get_find_replacement(),
+                ui.get_find_case_sensitive(),
+            );
+            if count > 0 {
+                history
+                    .borrow_mut()
+                    .checkpoint(&content, ui.get_content_cursor() as usize);
+                ui.set_blog_content(content.into());
+                ui.invoke_content_edited();
+            }
+            ui.set_find_status(format!("Replaced {}", count).into());
+        }
+    });
+
+    let preview_server: Rc<RefCell<Option<PreviewServer>>> = Rc::new(RefCell::new(None));
+    ui.on_preview_site({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let preview_server = preview_server.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let site = site.borrow();
+            let Some(site) = site.as_ref() else {
+                return;
+            };
+
+            // Keep one server running, restarting it if the output directory changed
+            let output_dir = site.output_dir();
+            let mut server = preview_server.borrow_mut();
+            if server.as_ref().is_none_or(|s| s.root() != output_dir) {
+                *server = None;
+                match PreviewServer::start(&output_dir) {
+                    Ok(started) => *server = Some(started),
+                    Err(e) => {
+                        show_error(&ui, &e);
+                        return;
+                    }
+                }
+            }
+
+            if let Some(server) = server.as_ref() {
+                ui.set_status_message(format!("Previewing at {}", server.url()).into());
+                if let Err(e) = webbrowser::open(server.url()) {
+                    warn!("Failed to open browser: {}", e);
+                }
+            }
+        }
+    });
+
+    ui.on_undo_content({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        move || step_history(&ui_handle, &history, History::undo)
+    });
+
+    ui.on_redo_content({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        move || step_history(&ui_handle, &history, History::redo)
+    });
+
+    ui.on_check_spelling({
+        let ui_handle = ui.as_weak();
+        let site = site.clone();
+        let speller = speller.clone();
+        move || {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let mut speller = speller.lock().unwrap();
+            let Some(checker) = speller.as_mut() else {
+                return;
+            };
+            checker.use_site(site.borrow().as_ref().map(|site| site.root.as_path()));
+
+            let content = ui.get_blog_content();
+            let misspellings: Vec<Misspelling> = checker
+                .misspellings(&content)
+                .into_iter()
+                .take(MAX_UNDERLINES)
+                .filter_map(|range| {
+                    let start = ui.invoke_content_text_position(range.start as i32);
+                    let end = ui.invoke_content_text_position(range.end as i32);
+                    // A word broken across lines isn't underlined
+                    (start.y == end.y).then(|| Misspelling {
+                        word: content[range.clone()].into(),
+                        start: range.start as i32,
+                        end: range.end as i32,
+                        x: start.x,
+                        y: start.y,
+                        width: end.x - start.x,
+                    })
+                })
+                .collect();
+            ui.set_content_misspellings(ModelRc::new(VecModel::from(misspellings)));
+        }
+    });
+
+    ui.on_spelling_suggestions({
+        let speller = speller.clone();
+        move |word| {
+            let suggestions: Vec<SharedString> = speller
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|checker| checker.suggest(&word))
+                .unwrap_or_default()
+                .into_iter()
+                .map(SharedString::from)
+                .collect();
+            ModelRc::new(VecModel::from(suggestions))
+        }
+    });
+
+    ui.on_replace_content_range({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        move |start, end, replacement| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let content = ui.get_blog_content();
+            let (start, end) = (start as usize, end as usize);
+            if content.get(start..end).is_none() {
+                return;
+            }
+            let content = format!("{}{}{}", &content[..start], replacement, &content[end..]);
+            let after = start + replacement.len();
+            history.borrow_mut().checkpoint(&content, after);
+            ui.set_blog_content(content.into());
+            ui.invoke_set_content_cursor(after as i32);
+            ui.invoke_content_edited();
+        }
+    });
+
+    ui.on_add_to_dictionary({
+        let ui_handle = ui.as_weak();
+        let speller = speller.clone();
+        move |word| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return;
+            };
+            let added = match speller.lock().unwrap().as_mut() {
+                Some(checker) => checker.add_to_site(&word),
+                None => return,
+            };
+            match added {
+                Ok(()) => {
+                    ui.set_status_message(
+                        format!("Added \"{}\" to the site dictionary", word).into(),
+                    );
+                    ui.invoke_check_spelling();
+                }
+                Err(e) => show_error(&ui, &e),
+            }
+        }
+    });
+
+    let preview_timer = Rc::new(Timer::default());
+    ui.on_content_edited({
+        let ui_handle = ui.as_weak();
+        let preview_timer = preview_timer.clone();
+        let dirty = dirty.clone();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        move || {
+            dirty.set(true);
+            if let Some(ui) = ui_handle.upgrade() {
+                let mut history = history.borrow_mut();
+                history.record(&ui.get_blog_content(), ui.get_content_cursor() as usize);
+                ui.set_can_undo(history.can_undo());
+                ui.set_can_redo(history.can_redo());
+
+                let mut tabs = tabs.borrow_mut();
+                tabs.active_mut().modified = true;
+                refresh_tab_bar(&ui, &tabs);
+            }
+
+            // Restarting the timer on every keystroke debounces the preview refresh
+            let ui_handle = ui_handle.clone();
+            preview_timer.start(
+                TimerMode::SingleShot,
+                Duration::from_millis(250),
+                move || {
+                    if let Some(ui) = ui_handle.upgrade() {
+                        refresh_preview(&ui);
+                    }
+                },
+            );
+        }
+    });
+
+    // Markdown and text files dropped on the window open as new posts
+    ui.window().on_winit_window_event({
+        let ui_handle = ui.as_weak();
+        let history = history.clone();
+        let tabs = tabs.clone();
+        let dirty = dirty.clone();
+        move |_, event| {
+            let Some(ui) = ui_handle.upgrade() else {
+                return EventResult::Propagate;
+            };
+            match event {
+                WindowEvent::HoveredFile(_) => ui.set_show_drop_hint(true),
+                WindowEvent::HoveredFileCancelled => ui.set_show_drop_hint(false),
+                WindowEvent::DroppedFile(path) => {
+                    ui.set_show_drop_hint(false);
+                    match import::read(path) {
+                        Ok(edit) => {
+                            let mut tabs = tabs.borrow_mut();
+                            stash_tab(&ui, &mut tabs, &history);
+                            let tab = Tab {
+                                title: edit.title,
+                                category: edit.category,
+                                author: edit.author,
+                                tags: edit.tags.join(", "),
+                                description: edit.description,
+                                date: edit
+                                    .date
+                                    .map(|date| date.format(DATE_FORMAT).to_string())
+                                    .unwrap_or_default(),
+                                history: History::new(&edit.content),
+                                content: edit.content,
+                                modified: true,
+                                ..Tab::default()
+                            };
+                            if tabs.active().is_blank() {
+                                *tabs.active_mut() = tab;
+                            } else {
+                                tabs.tabs.push(tab);
+                                tabs.active = tabs.tabs.len() - 1;
+                            }
+                            show_tab(&ui, &mut tabs, &history);
+                            dirty.set(true);
+                            ui.set_status_message(format!("Imported {}", path.display()).into());
+                        }
+                        Err(e) => show_error(&ui, &e),
+                    }
+                }
+                _ => {}
+            }
+            EventResult::Propagate
+        }
+    });
+
+    ui.on_create_category(|| {
+        // TODO: pop up textbox to create category
+        info!("Create category clicked");
+    });
+
+    ui.run()?;
+
+    Ok(())
+}
+
+/// Puts a post at the top of the Recent menu
+fn remember_post(ui: &AppWindow, config: &RefCell<Config>, site: &Site, id: &str, title: &str) {
+    let mut config = config.borrow_mut();
+    config.remember_post(RecentPost {
+        project: site.root.clone(),
+        id: id.to_string(),
+        title: title.to_string(),
+    });
+    if let Err(e) = config.save() {
+        error!("{}", e);
+    }
+    show_recent_posts(ui, &config);
+}
+
+/// Drops a post that can no longer be opened from the Recent menu
+fn forget_post(ui: &AppWindow, config: &RefCell<Config>, post: &RecentPost) {
+    let mut config = config.borrow_mut();
+    config
+        .recent_posts
+        .retain(|recent| recent.project != post.project || recent.id != post.id);
+    if let Err(e) = config.save() {
+        error!("{}", e);
+    }
+    show_recent_posts(ui, &config);
+}
+
+fn show_recent_posts(ui: &AppWindow, config: &Config) {
+    let entries: Vec<RecentEntry> = config
+        .recent_posts
+        .iter()
+        .map(|recent| RecentEntry {
+            title: recent.title.as_str().into(),
+            project: recent
+                .project
+                .file_name()
+                .unwrap_or(recent.project.as_os_str())
+                .to_string_lossy()
+                .as_ref()
+                .into(),
+        })
+        .collect();
+    ui.set_recent_posts(ModelRc::new(VecModel::from(entries)));
+}
+
+/// Loads the dictionary for `language` on a worker thread, then checks the content against it.
+/// Dictionaries are looked for in equinox's data directory before the system's.
+fn load_spell_checker(ui: &AppWindow, speller: &Speller, language: &str) {
+    if language.is_empty() {
+        return;
+    }
+
+    let mut dirs = Vec::new();
+    if let Some(dir) = dirs::data_local_dir() {
+        dirs.push(dir.join("equinox").join("dictionaries"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        dirs.push(home.join("Library").join("Spelling"));
+    }
+    dirs.extend(spelling::SYSTEM_DICTIONARY_DIRS.iter().map(PathBuf::from));
+
+    let ui_handle = ui.as_weak();
+    let speller = speller.clone();
+    let language = language.to_string();
+    thread::spawn(move || match SpellChecker::load(&language, &dirs) {
+        Ok(checker) => {
+            *speller.lock().unwrap() = Some(checker);
+            let _ = ui_handle.upgrade_in_event_loop(|ui| ui.invoke_check_spelling());
+        }
+        Err(e) => warn!("Spell checking is off: {}", e),
+    });
+}
+
+/// Applies the editor settings in `config` to the window
+fn apply_preferences(ui: &AppWindow, config: &Config) {
+    ui.set_editor_font_size(config.editor_font_size as i32);
+    ui.set_appearance(match config.appearance {
+        config::Appearance::System => Appearance::System,
+        config::Appearance::Light => Appearance::Light,
+        config::Appearance::Dark => Appearance::Dark,
+    });
+    // Underlines move with the font size
+    ui.invoke_check_spelling();
+}
+
+/// Updates the project header, output directory, and categories to reflect `site`
+fn show_project(ui: &AppWindow, site: &Site) {
+    let output_dir = site.output_dir();
+    ui.set_selected_project(site.name().into());
+    ui.set_show_selected_project(true);
+    ui.set_output_dir(output_dir.display().to_string().into());
+
+    let themes = themes::available(&site.root);
+    let theme_index = themes.iter().position(|name| *name == site.config.theme);
+    let themes: Vec<SharedString> = themes.into_iter().map(SharedString::from).collect();
+    ui.set_themes(ModelRc::new(VecModel::from(themes)));
+    ui.set_theme_index(theme_index.map_or(-1, |i| i as i32));
+
+    refresh_posts(ui, site);
+    show_sections(ui, site);
+    show_authors(ui, site);
+
+    match parse_site_structure(&output_dir) {
+        Ok(structure) => {
+            info!("{}", structure);
+
+            // Check if index.html is missing and prompt to create
+            if structure.index_path.is_none() {
+                ui.set_show_create_index_prompt(true);
+            }
+        }
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// Fills the category dropdown with the site's sections, then any other category a post uses
+fn show_sections(ui: &AppWindow, site: &Site) {
+    let mut names = vec![SharedString::from("No section")];
+    let mut values = vec![SharedString::new()];
+    for section in site.config.sections() {
+        names.push(section.label.into());
+        values.push(section.category.into());
+    }
+    let posts = site.store().list().unwrap_or_default();
+    for post in posts {
+        let category = post.meta.category.as_str();
+        if !category.is_empty() && !values.iter().any(|value| value == category) {
+            names.push(category.into());
+            values.push(category.into());
+        }
+    }
+    ui.set_categories(ModelRc::new(VecModel::from(names)));
+    ui.set_category_values(ModelRc::new(VecModel::from(values)));
+    show_category(ui, &ui.get_blog_category());
+}
+
+/// Selects `category` in the category dropdown, adding it if it isn't listed yet
+fn show_category(ui: &AppWindow, category: &str) {
+    let values = ui.get_category_values();
+    let index = match values.iter().position(|value| value == category) {
+        Some(index) => index,
+        None => {
+            let mut names: Vec<SharedString> = ui.get_categories().iter().collect();
+            let mut values: Vec<SharedString> = values.iter().collect();
+            names.push(category.into());
+            values.push(category.into());
+            let index = values.len() - 1;
+            ui.set_categories(ModelRc::new(VecModel::from(names)));
+            ui.set_category_values(ModelRc::new(VecModel::from(values)));
+            index
+        }
+    };
+    ui.set_category_index(index as i32);
+    ui.set_blog_category(category.into());
+}
+
+/// Fills the author dropdown with the site's authors table, then any other author a post names
+fn show_authors(ui: &AppWindow, site: &Site) {
+    let mut names = vec![SharedString::from("Default author")];
+    let mut values = vec![SharedString::new()];
+    for author in &site.config.authors {
+        names.push(author.name.as_str().into());
+        values.push(author.name.as_str().into());
+    }
+    let posts = site.store().list().unwrap_or_default();
+    for post in posts {
+        let author = post.meta.author.as_str();
+        if !author.is_empty() && !values.iter().any(|value| value == author) {
+            names.push(author.into());
+            values.push(author.into());
+        }
+    }
+    ui.set_authors(ModelRc::new(VecModel::from(names)));
+    ui.set_author_values(ModelRc::new(VecModel::from(values)));
+    show_author(ui, &ui.get_blog_author());
+}
+
+/// Selects `author` in the author dropdown, adding them if they aren't listed yet
+fn show_author(ui: &AppWindow, author: &str) {
+    let values = ui.get_author_values();
+    let index = match values.iter().position(|value| value == author) {
+        Some(index) => index,
+        None => {
+            let mut names: Vec<SharedString> = ui.get_authors().iter().collect();
+            let mut values: Vec<SharedString> = values.iter().collect();
+            names.push(author.into());
+            values.push(author.into());
+            let index = values.len() - 1;
+            ui.set_authors(ModelRc::new(VecModel::from(names)));
+            ui.set_author_values(ModelRc::new(VecModel::from(values)));
+            index
+        }
+    };
+    ui.set_author_index(index as i32);
+    ui.set_blog_author(author.into());
+}
+
+/// Reloads the post sidebar from the site's content store
+fn refresh_posts(ui: &AppWindow, site: &Site) {
+    match site.store().list() {
+        Ok(posts) => {
+            let summaries: Vec<PostSummary> = posts
+                .into_iter()
+                .map(|post| PostSummary {
+                    date: format!(
+                        "{}{}",
+                        post.date().format("%Y-%m-%d"),
+                        if post.meta.draft {
+                            " · draft"
+                        } else if post.is_scheduled() {
+                            " · scheduled"
+                        } else {
+                            ""
+                        }
+                    )
+                    .into(),
+                    id: post.id.into(),
+                    title: post.meta.title.into(),
+                })
+                .collect();
+            ui.set_posts(ModelRc::new(VecModel::from(summaries)));
+        }
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// Stores `post`, then renders its page and the listings in the background
+fn generate_post(
+    ui: &AppWindow,
+    site: &Site,
+    config: &RefCell<Config>,
+    tabs: &RefCell<Tabs>,
+    dirty: &Cell<bool>,
+    post: Post,
+) {
+    if let Err(e) = store_post(ui, site, &post) {
+        return show_error(ui, &e);
+    }
+    remember_post(ui, config, site, &post.id, &post.meta.title);
+    // Only this tab's draft is no longer needed
+    let mut tabs = tabs.borrow_mut();
+    tabs.active_mut().modified = false;
+    refresh_tab_bar(ui, &tabs);
+    dirty.set(true);
+    autosave(ui, Some(&site.root), &tabs, dirty);
+    build_in_background(
+        ui,
+        site,
+        move |site| {
+            let path = site.generate(&post)?;
+            // The post is out either way, so a failed commit or webhook is only warned about
+            if site.config.auto_commit.is_enabled() {
+                if let Err(e) = autocommit::commit_published(site, Some(&post)) {
+                    warn!("Failed to commit {}: {}", post.meta.title, e);
+                }
+            }
+            if let Err(e) = webhook::notify_new(site, Some(&post)) {
+                warn!("{}", e);
+            }
+            Ok(path)
+        },
+        |_, _, path| format!("Generated {}", path.display()),
+    );
+}
+
+/// Stores the post open in the editor, creating it if it is new, and returns it ready to be
+/// generated. Refuses if its page belongs to another post, which generating lets the user
+/// settle.
+fn save_post(ui: &AppWindow, site: &Site, author: &str) -> Result<Post> {
+    let post = edited_post(ui, site, author)?;
+    if let Some(other) = site.store().colliding_post(&post)? {
+        return Err(Error::Invalid(format!(
+            "\"{}\" already has the page {}; generate the post to overwrite it or keep both",
+            other.meta.title,
+            site.config.post_url(&other)
+        )));
+    }
+    store_post(ui, site, &post)?;
+    Ok(post)
+}
+
+/// Stores `post` as the one open in the editor, keeping its source as a new version
+fn store_post(ui: &AppWindow, site: &Site, post: &Post) -> Result<()> {
+    site.save_post(post)?;
+    back_up_in_background(site);
+
+    ui.set_current_post_id(post.id.clone().into());
+    refresh_posts(ui, site);
+    Ok(())
+}
+
+/// The post open in the editor as it would be stored, without storing it
+fn edited_post(ui: &AppWindow, site: &Site, author: &str) -> Result<Post> {
+    let date = match ui.get_blog_date().trim() {
+        "" => None,
+        text => Some(
+            front_matter::parse_date(text)
+                .ok_or_else(|| Error::Invalid(format!("Invalid publish date: {}", text)))?,
+        ),
+    };
+    let edit = PostEdit {
+        title: ui.get_blog_title().to_string(),
+        category: ui.get_blog_category().to_string(),
+        author: ui.get_blog_author().to_string(),
+        tags: parse_tags(&ui.get_blog_tags()),
+        description: ui.get_blog_description().trim().to_string(),
+        date,
+        default_author: author.to_string(),
+        content: ui.get_blog_content().to_string(),
+    };
+
+    let current_id = ui.get_current_post_id();
+    let current_id = (!current_id.is_empty()).then_some(current_id.as_str());
+    site.store().edit(current_id, edit)
+}
+
+/// Runs `build` on a worker thread so the window stays responsive, marking the editor busy
+/// until it finishes. On success, `done` runs back on the UI thread and returns the status
+/// message to show.
+///
+/// Nothing runs while another build or a deploy is underway, since it would rewrite the pages
+/// they are working on and mark the editor idle before they finish.
+fn build_in_background<T: Send + 'static>(
+    ui: &AppWindow,
+    site: &Site,
+    build: impl FnOnce(&Site) -> Result<T> + Send + 'static,
+    done: impl FnOnce(&AppWindow, &Site, T) -> String + Send + 'static,
+) {
+    if ui.get_building() || ui.get_deploying() {
+        return;
+    }
+    ui.set_building(true);
+    ui.set_status_message("Building...".into());
+    let ui_handle = ui.as_weak();
+    let site = site.clone();
+    thread::spawn(move || {
+        let result = build(&site);
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            ui.set_building(false);
+            match result {
+                Ok(value) => {
+                    let message = done(&ui, &site, value);
+                    ui.set_status_message(message.into());
+                }
+                Err(e) => show_error(&ui, &e),
+            }
+        });
+    });
+}
+
+/// Builds the site, then tells its webhooks about any scheduled post that came due
+fn build_site(site: &Site) -> Result<Vec<PathBuf>> {
+    let pages = site.build()?;
+    announce_due(site);
+    Ok(pages)
+}
+
+/// Tells the site's webhooks about the published posts they haven't heard of, such as scheduled
+/// posts that came due. The build still succeeded if this fails, so it is only warned about.
+fn announce_due(site: &Site) {
+    if let Err(e) = webhook::notify_new(site, None) {
+        warn!("{}", e);
+    }
+}
+
+/// Saves the site's settings, backing them up if the site has a backup service
+fn save_site(site: &Site) -> Result<()> {
+    site.save()?;
+    back_up_in_background(site);
+    Ok(())
+}
+
+/// Sends the sources changed since the last backup to the site's backup service on a worker
+/// thread, if it has one. Only one backup runs at a time; the next picks up whatever changed
+/// in the meantime.
+fn back_up_in_background(site: &Site) {
+    static RUNNING: Mutex<()> = Mutex::new(());
+    if site.config.backup.is_none() {
+        return;
+    }
+    let site = site.clone();
+    thread::spawn(move || {
+        let _running = RUNNING.lock();
+        if let Err(e) = backup::back_up(&site, false, |_| {}) {
+            warn!("Failed to back up the site: {}", e);
+        }
+    });
+}
+
+/// Runs `import` on a worker thread, then rebuilds the site to show the imported posts
+fn import_in_background(
+    ui: &AppWindow,
+    site: &Option<Site>,
+    import: impl FnOnce(&Site) -> Result<import::ImportReport> + Send + 'static,
+) {
+    let Some(site) = site.as_ref() else {
+        return;
+    };
+    if ui.get_building() {
+        return;
+    }
+    build_in_background(
+        ui,
+        site,
+        move |site| {
+            let report = import(site)?;
+            build_site(site)?;
+            Ok(report)
+        },
+        |ui, site, report| {
+            refresh_posts(ui, site);
+            match report.failed.len() {
+                0 => format!("Imported {} posts", report.posts.len()),
+                failed => format!(
+                    "Imported {} posts; {} failed, see the log",
+                    report.posts.len(),
+                    failed
+                ),
+            }
+        },
+    );
+}
+
+/// Fills the log panel with the lines logged so far and keeps it up to date
+fn show_log(ui: &AppWindow, log: &logging::PanelLog) {
+    let ui_handle = ui.as_weak();
+    let lines = log.attach(move |line| {
+        let line = log_line(line);
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            let lines = ui.get_log_lines();
+            if let Some(lines) = lines.as_any().downcast_ref::<VecModel<LogLine>>() {
+                lines.insert(0, line);
+                if lines.row_count() > logging::PANEL_LINES {
+                    lines.remove(logging::PANEL_LINES);
+                }
+            }
+        });
+    });
+    let lines: Vec<LogLine> = lines.iter().rev().map(log_line).collect();
+    ui.set_log_lines(ModelRc::new(VecModel::from(lines)));
+    ui.set_log_dir(
+        logging::dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    ui.on_clear_log({
+        let ui_handle = ui.as_weak();
+        move || {
+            if let Some(ui) = ui_handle.upgrade() {
+                ui.set_log_lines(ModelRc::new(VecModel::<LogLine>::default()));
+            }
+        }
+    });
+}
+
+fn log_line(line: &logging::Line) -> LogLine {
+    LogLine {
+        time: line.time.clone().into(),
+        level: line.level.to_string().into(),
+        message: line.message.clone().into(),
+    }
+}
+
+/// Reports `error` in the status bar and explains it in the error dialog
+fn show_error(ui: &AppWindow, error: &Error) {
+    error!("{}", error);
+    ui.set_status_message(format!("Error: {}", error).into());
+    ui.set_error_kind(error.kind().into());
+    ui.set_error_message(error.to_string().into());
+    ui.set_error_path(
+        error
+            .path()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    ui.set_error_suggestion(error.suggestion().unwrap_or_default().into());
+    ui.set_show_error_dialog(true);
+}
+
+/// Copies the image at `path` into the site and inserts a Markdown reference at the cursor
+fn insert_image(ui: &AppWindow, site: &Site, path: &Path) -> Result<()> {
+    let image = site.import_image(path)?;
+
+    let alt = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let reference = format!("![{}]({})", alt, permalink::content_link(&image));
+
+    let content = ui.get_blog_content().to_string();
+    let cursor = clamp_to_char_boundary(&content, ui.get_content_cursor());
+    let content = format!("{}{}{}", &content[..cursor], reference, &content[cursor..]);
+    ui.set_blog_content(content.into());
+    ui.invoke_set_content_cursor((cursor + reference.len()) as i32);
+    Ok(())
+}
+
+/// Asks for a file with one of `extensions` in a dialog titled `title` and a filter named
+/// `filter`, copies it into the site with `import`, and stores where it went in the post's front
+/// matter with `set`, replacing whatever was set before
+fn attach_file(
+    ui: &AppWindow,
+    site: &Site,
+    history: &RefCell<History>,
+    (title, filter): (&str, &str),
+    extensions: &[&str],
+    import: impl FnOnce(&Site, &Path) -> Result<String>,
+    set: impl FnOnce(&mut FrontMatter, String),
+) {
+    let dialog = FileDialog::new()
+        .set_title(title)
+        .add_filter(filter, extensions);
+    let Some(path) = dialog.pick_file() else {
+        return;
+    };
+
+    let result = import(site, &path).and_then(|file| {
+        let content = ui.get_blog_content().to_string();
+        let (front_matter, body) = FrontMatter::parse(&content)?;
+        let mut front_matter = front_matter.unwrap_or_default();
+        set(&mut front_matter, file);
+        Ok(format!("{}{}", front_matter.to_block()?, body))
+    });
+    match result {
+        Ok(content) => {
+            ui.set_blog_content(content.into());
+            history
+                .borrow_mut()
+                .checkpoint(&ui.get_blog_content(), ui.get_content_cursor() as usize);
+            ui.invoke_content_edited();
+        }
+        Err(e) => show_error(ui, &e),
+    }
+}
+
+/// Shows the content `step` moves the history to, if it can move
+fn step_history(
+    ui_handle: &slint::Weak<AppWindow>,
+    history: &RefCell<History>,
+    step: impl FnOnce(&mut History) -> Option<&history::Snapshot>,
+) {
+    let Some(ui) = ui_handle.upgrade() else {
+        return;
+    };
+    let mut history = history.borrow_mut();
+    if let Some(snapshot) = step(&mut history) {
+        let cursor = snapshot.cursor as i32;
+        ui.set_blog_content(snapshot.text.clone().into());
+        ui.invoke_set_content_cursor(cursor);
+    }
+    ui.set_can_undo(history.can_undo());
+    ui.set_can_redo(history.can_redo());
+    drop(history);
+    ui.invoke_content_edited();
+}
+
+/// Byte range selected in the content editor, start first
+fn content_selection(ui: &AppWindow, content: &str) -> (usize, usize) {
+    let cursor = clamp_to_char_boundary(content, ui.get_content_cursor());
+    let anchor = clamp_to_char_boundary(content, ui.get_content_anchor());
+    (cursor.min(anchor), cursor.max(anchor))
+}
+
+/// Highlights the match of the find bar's query chosen by `pick` from every match and the
+/// current selection, and shows its position in the find bar
+fn find_match(ui: &AppWindow, pick: impl Fn(&[(usize, usize)], (usize, usize)) -> Option<usize>) {
+    let content = ui.get_blog_content();
+    let query = ui.get_find_query();
+    let found = find::matches(&content, &query, ui.get_find_case_sensitive());
+    match pick(&found, content_selection(ui, &content)).and_then(|i| Some((i, found.get(i)?))) {
+        Some((i, (start, end))) => {
+            ui.invoke_highlight_content(*start as i32, *end as i32);
+            ui.set_find_status(format!("{} of {}", i + 1, found.len()).into());
+        }
+        None if query.is_empty() => ui.set_find_status(SharedString::new()),
+        None => ui.set_find_status("No matches".into()),
+    }
+}
+
+/// Converts a byte offset reported by the editor into a valid index into `text`
+fn clamp_to_char_boundary(text: &str, offset: i32) -> usize {
+    let mut offset = (offset.max(0) as usize).min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// Writes every tab with changes that haven't been generated to the autosave, if anything
+/// changed since the last save
+fn autosave(ui: &AppWindow, project: Option<&Path>, tabs: &Tabs, dirty: &Cell<bool>) {
+    if !dirty.get() {
+        return;
+    }
+
+    let project = project.map(Path::to_path_buf);
+    let drafts: Vec<Autosave> = tabs
+        .tabs
+        .iter()
+        .enumerate()
+        .filter(|(_, tab)| tab.modified)
+        .map(|(index, tab)| match index == tabs.active {
+            true => tab_from_window(ui).to_draft(project.clone()),
+            false => tab.to_draft(project.clone()),
+        })
+        .collect();
+    if drafts.is_empty() {
+        Autosave::clear();
+        dirty.set(false);
+        return;
+    }
+    match Autosave::save_all(drafts) {
+        Ok(()) => dirty.set(false),
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// A tab editing the stored `post`, with its metadata block at the top of the content
+fn tab_for_post(post: Post) -> Result<Tab> {
+    let front_matter = FrontMatter::from_meta(&post.meta).to_block()?;
+    let content = format!("{}{}", front_matter, post.body);
+    Ok(Tab {
+        post_id: post.id,
+        title: post.meta.title,
+        category: post.meta.category,
+        author: post.meta.author,
+        tags: post.meta.tags.join(", "),
+        description: post.meta.description,
+        date: post
+            .meta
+            .date
+            .map(|date| date.format(DATE_FORMAT).to_string())
+            .unwrap_or_default(),
+        history: History::new(&content),
+        content,
+        ..Tab::default()
+    })
+}
+
+/// Shows in the history panel how the version at `index` differs from the post as it is stored
+fn show_version_diff(ui: &AppWindow, site: &Site, shown: &[Version], index: usize) {
+    let Some(version) = shown.get(index) else {
+        return;
+    };
+    let id = ui.get_current_post_id();
+    let diff = match versions::load(&site.root, &id, &version.name)
+        .and_then(|old| Ok(versions::diff(&old, &site.store().source(&id)?)))
+    {
+        Ok(diff) => diff,
+        Err(e) => return show_error(ui, &e),
+    };
+    let lines: Vec<DiffLine> = diff
+        .into_iter()
+        .map(|row| DiffLine {
+            left: row.left.unwrap_or_default().into(),
+            right: row.right.unwrap_or_default().into(),
+            change: match row.change {
+                Change::Same => "same",
+                Change::Removed => "removed",
+                Change::Added => "added",
+                Change::Changed => "changed",
+            }
+            .into(),
+        })
+        .collect();
+    ui.set_version_diff(ModelRc::new(VecModel::from(lines)));
+    ui.set_selected_version(index as i32);
+}
+
+/// The editor fields of the active tab as shown in the window
+fn tab_from_window(ui: &AppWindow) -> Tab {
+    Tab {
+        post_id: ui.get_current_post_id().into(),
+        title: ui.get_blog_title().into(),
+        category: ui.get_blog_category().into(),
+        author: ui.get_blog_author().into(),
+        tags: ui.get_blog_tags().into(),
+        description: ui.get_blog_description().into(),
+        date: ui.get_blog_date().into(),
+        content: ui.get_blog_content().into(),
+        cursor: ui.get_content_cursor() as usize,
+        ..Tab::default()
+    }
+}
+
+/// Copies the window's editor fields and undo history back into the active tab before another
+/// tab is shown
+fn stash_tab(ui: &AppWindow, tabs: &mut Tabs, history: &RefCell<History>) {
+    let tab = tabs.active_mut();
+    *tab = Tab {
+        history: history.take(),
+        modified: tab.modified,
+        ..tab_from_window(ui)
+    };
+}
+
+/// Shows the active tab in the window's editor fields
+fn show_tab(ui: &AppWindow, tabs: &mut Tabs, history: &RefCell<History>) {
+    let tab = tabs.active_mut();
+    show_category(ui, &tab.category);
+    show_author(ui, &tab.author);
+    ui.set_current_post_id(tab.post_id.as_str().into());
+    ui.set_blog_title(tab.title.as_str().into());
+    ui.set_blog_tags(tab.tags.as_str().into());
+    ui.set_blog_description(tab.description.as_str().into());
+    ui.set_blog_date(tab.date.as_str().into());
+    ui.set_blog_content(tab.content.as_str().into());
+    *history.borrow_mut() = std::mem::take(&mut tab.history);
+    ui.set_can_undo(history.borrow().can_undo());
+    ui.set_can_redo(history.borrow().can_redo());
+    ui.invoke_set_content_cursor(tab.cursor as i32);
+    refresh_preview(ui);
+    refresh_tab_bar(ui, tabs);
+}
+
+/// Lists the open tabs, naming the active one by the title being edited
+fn refresh_tab_bar(ui: &AppWindow, tabs: &Tabs) {
+    let infos: Vec<TabInfo> = tabs
+        .tabs
+        .iter()
+        .enumerate()
+        .map(|(index, tab)| TabInfo {
+            title: match index == tabs.active {
+                true => ui.get_blog_title(),
+                false => tab.title.as_str().into(),
+            },
+            modified: tab.modified,
+        })
+        .collect();
+    ui.set_tabs(ModelRc::new(VecModel::from(infos)));
+    ui.set_active_tab(tabs.active as i32);
+}
+
+/// Re-renders the preview pane and the word count in the status bar from the content box
+fn refresh_preview(ui: &AppWindow) {
+    let content = ui.get_blog_content();
+    let body = front_matter::split(&content).map_or(content.as_str(), |(_, body)| body);
+    let blocks: Vec<PreviewBlock> = preview::blocks(body)
+        .into_iter()
+        .map(|block| {
+            let (kind, level) = match block.kind {
+                BlockKind::Heading(level) => ("heading", level as i32),
+                BlockKind::Paragraph => ("paragraph", 0),
+                BlockKind::ListItem => ("item", 0),
+                BlockKind::Quote => ("quote", 0),
+                BlockKind::Code => ("code", 0),
+                BlockKind::Rule => ("rule", 0),
+            };
+            PreviewBlock {
+                kind: kind.into(),
+                level,
+                text: block.text.into(),
+            }
+        })
+        .collect();
+    ui.set_preview_blocks(ModelRc::new(VecModel::from(blocks)));
+    ui.invoke_check_spelling();
+
+    let stats = markdown::text_stats(body);
+    ui.set_text_stats(
+        format!(
+            "{} words · {} characters · {} min read",
+            stats.words,
+            stats.characters,
+            stats.reading_minutes()
+        )
+        .into(),
+    );
 }
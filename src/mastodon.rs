@@ -0,0 +1,124 @@
+//! Sharing new posts to Mastodon, once they are deployed
+
+use crate::deploy::sftp::KEYRING_SERVICE;
+use equinox_core::{
+    crosspost::{self, Network, ShareReport, SharedLog},
+    site::MastodonConfig,
+    Site,
+};
+use serde_json::Value;
+use std::{env, time::Duration};
+use tracing::info;
+use ureq::Agent;
+
+/// Environment variable holding the access token instead of the keyring
+const TOKEN_VAR: &str = "MASTODON_ACCESS_TOKEN";
+
+/// How long the server gets to answer each request
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Characters of the title kept in a status, leaving room in Mastodon's 500 for the link
+const MAX_TITLE_LENGTH: usize = 400;
+
+fn keyring_entry(config: &MastodonConfig) -> Result<keyring::Entry, String> {
+    let instance = config.instance.trim_end_matches('/');
+    keyring::Entry::new(KEYRING_SERVICE, &format!("mastodon:{}", instance))
+        .map_err(|e| format!("Failed to access the keyring: {}", e))
+}
+
+/// Saves the access token for the account on `config.instance` in the OS keyring
+pub fn store_token(config: &MastodonConfig, token: &str) -> Result<(), String> {
+    keyring_entry(config)?
+        .set_password(token)
+        .map_err(|e| format!("Failed to store the token in the keyring: {}", e))
+}
+
+fn stored_token(config: &MastodonConfig) -> Result<String, String> {
+    if let Ok(token) = env::var(TOKEN_VAR) {
+        return Ok(token);
+    }
+    match keyring_entry(config)?.get_password() {
+        Ok(token) => Ok(token),
+        Err(keyring::Error::NoEntry) => Err(format!(
+            "No access token for {} in the keyring: run `equinox mastodon-token` to add it, or \
+             set {}",
+            config.instance, TOKEN_VAR
+        )),
+        Err(e) => Err(format!("Failed to read the token from the keyring: {}", e)),
+    }
+}
+
+/// Posts a status with the title of and a link to each published post that hasn't been shared
+/// yet. The posts must already be deployed, since the server fetches a preview of them. The first
+/// time, the posts already online are only recorded. `progress` is called with a status message
+/// as each post is shared.
+pub fn share_new(site: &Site, mut progress: impl FnMut(String)) -> Result<ShareReport, String> {
+    let config = site
+        .config
+        .mastodon
+        .as_ref()
+        .ok_or("No Mastodon account is configured")?;
+    if !site.config.base_url.contains("://") {
+        return Err("the site needs a base URL for statuses to link to its posts".to_string());
+    }
+    let mut report = ShareReport {
+        earlier: crosspost::start(site, Network::Mastodon).map_err(|e| e.to_string())?,
+        ..ShareReport::default()
+    };
+    let mut log = SharedLog::load(&site.root, Network::Mastodon).unwrap_or_default();
+    let posts = crosspost::unshared(site, &log).map_err(|e| e.to_string())?;
+    if posts.is_empty() {
+        return Ok(report);
+    }
+
+    let token = stored_token(config)?;
+    // The server explains failures in the body, so statuses are handled here
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let uri = format!("{}/api/v1/statuses", config.instance.trim_end_matches('/'));
+    for post in &posts {
+        progress(format!("Sharing {} to Mastodon", post.meta.title));
+        let status = format!(
+            "{}\n\n{}",
+            crosspost::shorten(&post.meta.title, MAX_TITLE_LENGTH),
+            site.url_for(&site.config.post_url(post))
+        );
+        let mut response = agent
+            .post(&uri)
+            .header("authorization", format!("Bearer {}", token))
+            // Makes the server ignore a repeat if the log couldn't be saved after the first
+            .header("idempotency-key", format!("equinox-{}", post.id))
+            .send_form([
+                ("status", status.as_str()),
+                ("visibility", config.visibility.name()),
+            ])
+            .map_err(|e| format!("Failed to reach {}: {}", config.instance, e))?;
+        let code = response.status();
+        let body: Value = response
+            .body_mut()
+            .read_to_string()
+            .ok()
+            .and_then(|body| serde_json::from_str(&body).ok())
+            .unwrap_or_default();
+        if !code.is_success() {
+            return Err(format!(
+                "{} turned down the status for {}: {} {}",
+                config.instance,
+                post.meta.title,
+                code.as_u16(),
+                body["error"].as_str().unwrap_or_default()
+            ));
+        }
+        let url = body["url"].as_str().unwrap_or_default();
+        info!("Shared {} to Mastodon at {}", post.meta.title, url);
+        log.record(&post.id, url);
+        // Kept after every post, so an interrupted run doesn't share it again
+        log.save(&site.root, Network::Mastodon)
+            .map_err(|e| e.to_string())?;
+        report.shared += 1;
+    }
+    Ok(report)
+}
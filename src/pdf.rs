@@ -0,0 +1,95 @@
+//! Printing generated pages to PDF with a headless browser
+
+use equinox_core::{Error, Result};
+use std::{
+    io::ErrorKind,
+    path::Path,
+    process::{Command, Output},
+};
+use tracing::{debug, info};
+
+/// Chromium-based browsers that can print to PDF headlessly, tried in order
+const BROWSERS: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+    "microsoft-edge",
+    "/Applications/Google Chrome.app/Contents/MacOS/Google Chrome",
+    "/Applications/Chromium.app/Contents/MacOS/Chromium",
+    "/Applications/Microsoft Edge.app/Contents/MacOS/Microsoft Edge",
+    r"C:\Program Files\Google\Chrome\Application\chrome.exe",
+    r"C:\Program Files (x86)\Microsoft\Edge\Application\msedge.exe",
+];
+
+/// Prints the generated `page` to `pdf`, with the stylesheets it links to. Uses the first
+/// installed Chromium-based browser, falling back to `wkhtmltopdf`.
+pub fn export(page: &Path, pdf: &Path) -> Result<()> {
+    let page = std::path::absolute(page).map_err(|e| Error::io("find", page, e))?;
+    let url = file_url(&page);
+    for browser in BROWSERS {
+        let output = Command::new(browser)
+            .arg("--headless")
+            .arg("--disable-gpu")
+            .arg("--no-pdf-header-footer")
+            .arg(format!("--print-to-pdf={}", pdf.display()))
+            .arg(&url)
+            .output();
+        if let Some(result) = finish(browser, output) {
+            return result.map(|()| info!("Printed {} to {}", page.display(), pdf.display()));
+        }
+    }
+
+    let output = Command::new("wkhtmltopdf")
+        .arg("--enable-local-file-access")
+        .arg(&url)
+        .arg(pdf)
+        .output();
+    match finish("wkhtmltopdf", output) {
+        Some(result) => result.map(|()| info!("Printed {} to {}", page.display(), pdf.display())),
+        None => Err(Error::Invalid(
+            "Exporting a PDF needs Chromium, Google Chrome, Microsoft Edge, or wkhtmltopdf \
+             installed"
+                .to_string(),
+        )),
+    }
+}
+
+/// How running `program` went, or `None` if it isn't installed
+fn finish(program: &str, output: std::io::Result<Output>) -> Option<Result<()>> {
+    let output = match output {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            debug!("{} is not installed", program);
+            return None;
+        }
+        Err(e) => {
+            return Some(Err(Error::Invalid(format!(
+                "Failed to run {}: {}",
+                program, e
+            ))))
+        }
+    };
+    if output.status.success() {
+        return Some(Ok(()));
+    }
+    Some(Err(Error::Invalid(format!(
+        "{} failed to print the PDF: {}",
+        program,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))))
+}
+
+/// A `file://` URL for the absolute `path`
+fn file_url(path: &Path) -> String {
+    let path = path
+        .to_string_lossy()
+        .replace('\\', "/")
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('#', "%23");
+    match path.starts_with('/') {
+        true => format!("file://{}", path),
+        false => format!("file:///{}", path),
+    }
+}
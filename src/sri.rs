@@ -0,0 +1,96 @@
+//! Fetching the scripts and stylesheets pages load from other sites to pin their integrity
+//! hashes
+
+use equinox_core::{
+    sri::{self, Pins},
+    Site,
+};
+use std::{io::Read, time::Duration};
+use tracing::{info, warn};
+use ureq::Agent;
+
+/// Most of a resource read while hashing it
+const MAX_RESOURCE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// How long a site gets to answer each request
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Result of pinning the site's external resources
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IntegrityReport {
+    /// Resources pinned for the first time, or re-pinned after changing
+    pub pinned: usize,
+    /// Pinned resources whose content has changed upstream
+    pub changed: usize,
+    /// Resources that couldn't be fetched, left as they were
+    pub failed: usize,
+}
+
+/// Fetches every external resource the site's generated pages load that hasn't been pinned yet,
+/// pins its hash, and adds the hashes to the pages. Resources already pinned are fetched again to
+/// check them: one that changed upstream is reported with a warning, since browsers will refuse
+/// it, and only re-pinned when `update` is set. `progress` is called with a status message as
+/// each resource is fetched.
+pub fn pin_all(
+    site: &Site,
+    update: bool,
+    mut progress: impl FnMut(String),
+) -> Result<IntegrityReport, String> {
+    let resources = sri::resources(site).map_err(|e| e.to_string())?;
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let mut pins = Pins::load(&site.root);
+    let mut report = IntegrityReport::default();
+
+    for url in &resources {
+        progress(format!("Checking {}", url));
+        let integrity = match fetch(&agent, url) {
+            Ok(contents) => sri::integrity(&contents),
+            Err(e) => {
+                warn!("Failed to fetch {}: {}", url, e);
+                report.failed += 1;
+                continue;
+            }
+        };
+        match pins.get(url) {
+            Some(pinned) if pinned == integrity => {}
+            Some(_) => {
+                report.changed += 1;
+                match update {
+                    true => {
+                        info!("Re-pinned {}, which changed upstream", url);
+                        pins.set(url, integrity);
+                        report.pinned += 1;
+                    }
+                    false => warn!(
+                        "{} has changed upstream since it was pinned, so browsers will refuse it: \
+                         check the new version and run `equinox integrity --update` to pin it",
+                        url
+                    ),
+                }
+            }
+            None => {
+                info!("Pinned {}", url);
+                pins.set(url, integrity);
+                report.pinned += 1;
+            }
+        }
+    }
+    pins.save(&site.root).map_err(|e| e.to_string())?;
+    sri::write_integrity(site).map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+fn fetch(agent: &Agent, url: &str) -> Result<Vec<u8>, String> {
+    let mut response = agent.get(url).call().map_err(|e| e.to_string())?;
+    let mut contents = Vec::new();
+    response
+        .body_mut()
+        .as_reader()
+        .take(MAX_RESOURCE_SIZE)
+        .read_to_end(&mut contents)
+        .map_err(|e| e.to_string())?;
+    Ok(contents)
+}
@@ -0,0 +1,108 @@
+use crate::autosave::Autosave;
+use chrono::Local;
+use equinox_core::history::History;
+use std::path::PathBuf;
+
+/// A post open in the editor. The active tab's fields are shown in the window and only copied
+/// back here when another tab is selected.
+#[derive(Debug, Clone, Default)]
+pub struct Tab {
+    /// Id of the stored post, empty for a new post
+    pub post_id: String,
+    pub title: String,
+    pub category: String,
+    /// Author picked for the post, empty for the default author
+    pub author: String,
+    pub tags: String,
+    pub description: String,
+    pub date: String,
+    pub content: String,
+    /// Byte offset of the cursor in `content`
+    pub cursor: usize,
+    pub history: History,
+    /// Edited since the post was last generated
+    pub modified: bool,
+}
+
+impl Tab {
+    /// A new post that hasn't been touched yet, which opening a post may replace
+    pub fn is_blank(&self) -> bool {
+        self.post_id.is_empty()
+            && !self.modified
+            && self.title.is_empty()
+            && self.content.is_empty()
+    }
+
+    pub fn from_draft(draft: Autosave) -> Tab {
+        Tab {
+            history: History::new(&draft.content),
+            post_id: draft.post_id,
+            title: draft.title,
+            category: draft.category,
+            author: draft.author,
+            tags: draft.tags,
+            description: draft.description,
+            date: draft.date,
+            content: draft.content,
+            cursor: 0,
+            modified: true,
+        }
+    }
+
+    pub fn to_draft(&self, project: Option<PathBuf>) -> Autosave {
+        Autosave {
+            project,
+            post_id: self.post_id.clone(),
+            title: self.title.clone(),
+            category: self.category.clone(),
+            author: self.author.clone(),
+            tags: self.tags.clone(),
+            description: self.description.clone(),
+            date: self.date.clone(),
+            content: self.content.clone(),
+            saved_at: Local::now(),
+        }
+    }
+}
+
+/// The posts open in the editor, always at least one
+#[derive(Debug, Clone)]
+pub struct Tabs {
+    pub tabs: Vec<Tab>,
+    pub active: usize,
+}
+
+impl Default for Tabs {
+    fn default() -> Self {
+        Tabs {
+            tabs: vec![Tab::default()],
+            active: 0,
+        }
+    }
+}
+
+impl Tabs {
+    pub fn active(&self) -> &Tab {
+        &self.tabs[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.active]
+    }
+
+    /// Index of the tab editing the stored post `id`
+    pub fn find(&self, id: &str) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.post_id == id)
+    }
+
+    /// Removes the tab at `index`, leaving a blank one if it was the last
+    pub fn close(&mut self, index: usize) {
+        self.tabs.remove(index);
+        if self.tabs.is_empty() {
+            self.tabs.push(Tab::default());
+        }
+        if self.active > index || self.active == self.tabs.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Telling other services about each published post with a JSON POST to the site's webhooks
+
+use equinox_core::{
+    crosspost,
+    webhooks::{self, NotifiedLog},
+    Error, Post, Result, Site,
+};
+use serde_json::json;
+use std::time::Duration;
+use tracing::{info, warn};
+use ureq::Agent;
+
+/// How long each webhook gets to answer
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Tells every webhook the site has about each published post they haven't heard of yet: `post`
+/// just generated, if it is published, and any scheduled post that came due. Drafts and posts
+/// dated in the future wait until they are published. The first time, the posts already
+/// published before `post` are only recorded. Returns how many posts were announced.
+pub fn notify_new(site: &Site, post: Option<&Post>) -> Result<usize> {
+    if site.config.webhooks.is_empty() {
+        return Ok(0);
+    }
+    webhooks::start(site, post.map(|post| post.id.as_str()))?;
+    let mut log = NotifiedLog::load(&site.root).unwrap_or_default();
+    let posts = webhooks::unnotified(site, &log)?;
+
+    let mut errors = Vec::new();
+    for post in &posts {
+        if let Err(e) = notify_published(site, post) {
+            errors.push(e.to_string());
+        }
+        // Recorded even if a webhook failed, as the others would hear of it twice otherwise
+        log.record(&post.id);
+        log.save(&site.root)?;
+    }
+    match errors.is_empty() {
+        true => Ok(posts.len()),
+        false => Err(Error::Invalid(errors.join("; "))),
+    }
+}
+
+/// Posts the title, URL, and summary of `post` to every webhook the site has. A webhook that
+/// fails doesn't stop the rest being called, but is reported after.
+fn notify_published(site: &Site, post: &Post) -> Result<()> {
+    let hooks = &site.config.webhooks;
+    let payload = json!({
+        "event": "published",
+        "id": post.id,
+        "title": post.meta.title,
+        "url": site.url_for(&site.config.post_url(post)),
+        "summary": crosspost::summary(post),
+        "date": post.date().to_rfc3339(),
+        "tags": post.meta.tags,
+        "category": post.meta.category,
+    })
+    .to_string();
+
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let mut failed = Vec::new();
+    for hook in hooks {
+        let result = agent
+            .post(hook)
+            .header("content-type", "application/json")
+            .send(&payload);
+        match result {
+            Ok(_) => info!("Told {} about {}", hook, post.meta.title),
+            Err(e) => {
+                warn!("Webhook {} failed: {}", hook, e);
+                failed.push(hook.as_str());
+            }
+        }
+    }
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Invalid(format!(
+            "{} of {} webhooks failed: {}",
+            failed.len(),
+            hooks.len(),
+            failed.join(", ")
+        ))),
+    }
+}
@@ -0,0 +1,120 @@
+//! Sending webmentions to the pages posts link to, once the posts are live
+
+use equinox_core::{
+    webmention::{self, SentLog},
+    Site,
+};
+use std::{io::Read, time::Duration};
+use tracing::{debug, info, warn};
+use ureq::{Agent, ResponseExt};
+
+/// Most of a linked page read while looking for its endpoint
+const MAX_PAGE_SIZE: u64 = 2 * 1024 * 1024;
+
+/// How long a site gets to answer each request
+const TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Result of notifying linked pages
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebmentionReport {
+    pub sent: usize,
+    /// Linked pages that don't accept webmentions
+    pub unsupported: usize,
+    /// Linked pages that couldn't be reached or turned the webmention down, tried again next time
+    pub failed: usize,
+}
+
+/// Sends a webmention for every link in the site's published posts that hasn't been mentioned
+/// yet. The posts must already be deployed, since receivers check that they link to them.
+/// `progress` is called with a status message as each link is handled.
+pub fn send_all(site: &Site, mut progress: impl FnMut(String)) -> Result<WebmentionReport, String> {
+    if !site.config.base_url.contains("://") {
+        return Err("the site needs a base URL for other sites to find its posts at".to_string());
+    }
+    let posts = site.store().list().map_err(|e| e.to_string())?;
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .into();
+    let mut log = SentLog::load(&site.root);
+    let mut report = WebmentionReport::default();
+
+    for post in posts.iter().filter(|post| post.is_published()) {
+        let source = site.url_for(&site.config.post_url(post));
+        let targets: Vec<String> = webmention::targets(site, post)
+            .into_iter()
+            .filter(|target| !log.is_sent(&source, target))
+            .collect();
+        for target in targets {
+            progress(format!("Looking for a webmention endpoint at {}", target));
+            let endpoint = match find_endpoint(&agent, &target) {
+                Ok(Some(endpoint)) => endpoint,
+                Ok(None) => {
+                    debug!("{} doesn't accept webmentions", target);
+                    log.record(&source, &target);
+                    report.unsupported += 1;
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to fetch {}: {}", target, e);
+                    report.failed += 1;
+                    continue;
+                }
+            };
+
+            progress(format!("Sending a webmention to {}", target));
+            match agent
+                .post(&endpoint)
+                .send_form([("source", source.as_str()), ("target", target.as_str())])
+            {
+                Ok(_) => {
+                    info!("Sent a webmention for {} to {}", source, target);
+                    log.record(&source, &target);
+                    report.sent += 1;
+                }
+                Err(e) => {
+                    warn!(
+                        "{} turned down the webmention for {}: {}",
+                        endpoint, target, e
+                    );
+                    report.failed += 1;
+                }
+            }
+        }
+        // Kept after every post, so an interrupted run doesn't mention its links again
+        log.save(&site.root).map_err(|e| e.to_string())?;
+    }
+    Ok(report)
+}
+
+/// The webmention endpoint of the page at `url`, or `None` if it doesn't advertise one
+fn find_endpoint(agent: &Agent, url: &str) -> Result<Option<String>, String> {
+    let mut response = agent.get(url).call().map_err(|e| e.to_string())?;
+    // Relative endpoints are relative to wherever redirects ended up
+    let url = response.get_uri().to_string();
+    let link_headers: Vec<String> = response
+        .headers()
+        .get_all("link")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::to_string)
+        .collect();
+    let is_html = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("html"));
+    let mut html = Vec::new();
+    if is_html {
+        // Endpoints are found in the head, so a page cut short still has one
+        response
+            .body_mut()
+            .as_reader()
+            .take(MAX_PAGE_SIZE)
+            .read_to_end(&mut html)
+            .map_err(|e| e.to_string())?;
+    }
+    let html = String::from_utf8_lossy(&html);
+    let link_headers: Vec<&str> = link_headers.iter().map(String::as_str).collect();
+    Ok(webmention::discover_endpoint(&url, &link_headers, &html))
+}